@@ -21,3 +21,257 @@ function! gnvim#enable_ext_popupmenu(enable)
                 \ 'EnableExtPopupmenu',
                 \ a:enable)
 endfunction
+
+function! gnvim#enable_kinetic_scrolling(enable)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'EnableKineticScrolling',
+                \ a:enable == 1)
+endfunction
+
+function! gnvim#enable_ligatures(enable)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'EnableLigatures',
+                \ a:enable == 1)
+endfunction
+
+function! gnvim#set_font_features(features)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'FontFeatures',
+                \ a:features)
+endfunction
+
+function! gnvim#load_css(path)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'UserCss',
+                \ join(readfile(expand(a:path)), "\n"))
+endfunction
+
+function! gnvim#set_background_opacity(opacity)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'BackgroundOpacity',
+                \ a:opacity * 1.0)
+endfunction
+
+function! gnvim#set_inactive_dim(dim)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'InactiveDim',
+                \ a:dim * 1.0)
+endfunction
+
+function! gnvim#set_max_fps(fps)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'MaxFps',
+                \ a:fps)
+endfunction
+
+" Accepts one (all sides), two (vertical, horizontal) or four (top, right,
+" bottom, left) values, like CSS.
+function! gnvim#set_padding(...)
+    if a:0 == 1
+        let l:padding = [a:1, a:1, a:1, a:1]
+    elseif a:0 == 2
+        let l:padding = [a:1, a:2, a:1, a:2]
+    elseif a:0 == 4
+        let l:padding = a:000
+    else
+        throw 'gnvim#set_padding: expected 1, 2 or 4 arguments'
+    endif
+
+    return call('rpcnotify',
+                \ [g:gnvim_channel_id, 'Gnvim', 'Padding'] + l:padding)
+endfunction
+
+function! gnvim#set_size(columns, lines)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'Size',
+                \ a:columns,
+                \ a:lines)
+endfunction
+
+function! gnvim#set_cursor_overlay(line, column)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'CursorOverlay',
+                \ a:line,
+                \ a:column)
+endfunction
+
+function! gnvim#enable_synthetic_styles(enable)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'EnableSyntheticStyles',
+                \ a:enable == 1)
+endfunction
+
+function! gnvim#bold_is_bright(enable)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'BoldIsBright',
+                \ a:enable == 1)
+endfunction
+
+function! gnvim#enable_visual_bell(enable)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'EnableVisualBell',
+                \ a:enable == 1)
+endfunction
+
+function! gnvim#enable_busy_pointer(enable)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'EnableBusyPointer',
+                \ a:enable == 1)
+endfunction
+
+function! gnvim#find()
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'OpenFind')
+endfunction
+
+function! gnvim#enable_find_key(enable)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'EnableFindKey',
+                \ a:enable == 1)
+endfunction
+
+function! gnvim#enable_background_blur(enable)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'BackgroundBlur',
+                \ a:enable == 1)
+endfunction
+
+function! gnvim#toggle_fullscreen()
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'ToggleFullscreen')
+endfunction
+
+function! gnvim#enable_fullscreen_key(enable)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'EnableFullscreenKey',
+                \ a:enable == 1)
+endfunction
+
+function! gnvim#enable_hover(enable)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'EnableHover',
+                \ a:enable == 1)
+endfunction
+
+function! gnvim#auto_background(enable)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'AutoBackground',
+                \ a:enable == 1)
+endfunction
+
+function! gnvim#enable_external_resize(enable)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'EnableExternalResize',
+                \ a:enable == 1)
+endfunction
+
+function! gnvim#enable_external_focus(enable)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'EnableExternalFocus',
+                \ a:enable == 1)
+endfunction
+
+function! gnvim#enable_float_animation(enable)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'EnableFloatAnimation',
+                \ a:enable == 1)
+endfunction
+
+function! gnvim#enable_float_border(enable)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'EnableFloatBorder',
+                \ a:enable == 1)
+endfunction
+
+function! gnvim#alt_is_meta(enable)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'AltIsMeta',
+                \ a:enable == 1)
+endfunction
+
+function! gnvim#set_key_passthrough(keys)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'KeyPassthrough',
+                \ a:keys)
+endfunction
+
+function! gnvim#enable_context_menu(enable)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'EnableContextMenu',
+                \ a:enable == 1)
+endfunction
+
+function! gnvim#set_mode_color(mode, color)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'ModeColor',
+                \ a:mode,
+                \ a:color)
+endfunction
+
+function! gnvim#set_mouse_scroll_lines(lines)
+    return rpcnotify(g:gnvim_channel_id, 'Gnvim', 'MouseScrollLines', a:lines)
+endfunction
+
+function! gnvim#set_mouse_scroll_shift_multiplier(multiplier)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'MouseScrollShiftMultiplier',
+                \ a:multiplier * 1.0)
+endfunction
@@ -0,0 +1,24 @@
+" The contents are sent as the lines and the register type, so that e.g.
+" linewise yanks are pasted linewise.
+
+function! gnvim#clipboard#copy(register, lines, regtype)
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'ClipboardSet', a:register, a:lines, a:regtype)
+endfunction
+
+function! gnvim#clipboard#paste(register)
+    return rpcrequest(g:gnvim_channel_id, 'Gnvim', 'ClipboardGet', a:register)
+endfunction
+
+function! gnvim#clipboard#provider()
+    return {
+                \ 'name': 'gnvim',
+                \ 'copy': {
+                \   '+': {lines, regtype -> gnvim#clipboard#copy('+', lines, regtype)},
+                \   '*': {lines, regtype -> gnvim#clipboard#copy('*', lines, regtype)},
+                \ },
+                \ 'paste': {
+                \   '+': {-> gnvim#clipboard#paste('+')},
+                \   '*': {-> gnvim#clipboard#paste('*')},
+                \ },
+                \ }
+endfunction
@@ -5,3 +5,28 @@ function! gnvim#cursor#enable_animations(enable)
                 \ 'EnableCursorAnimations',
                 \ a:enable == 1)
 endfunction
+
+function! gnvim#cursor#set_animation_duration(duration)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'CursorAnimationDuration',
+                \ a:duration)
+endfunction
+
+function! gnvim#cursor#set_color(color)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'CursorColor',
+                \ a:color)
+endfunction
+
+function! gnvim#cursor#set_mode_color(mode, color)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'ModeCursorColor',
+                \ a:mode,
+                \ a:color)
+endfunction
@@ -34,6 +34,10 @@ function! gnvim#cursor_tooltip#hide()
     call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'CursorTooltipHide')
 endfunction
 
+function! gnvim#cursor_tooltip#scroll(lines)
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'CursorTooltipScroll', a:lines)
+endfunction
+
 function! gnvim#cursor_tooltip#load_style(path)
     call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'CursorTooltipLoadStyle', a:path)
 endfunction
@@ -0,0 +1,7 @@
+" Scrolls the current window a page forward (or backward), like <C-f> (or
+" <C-b>), for the find bar. Returns 1 if the window scrolled.
+function! gnvim#find#scroll(forward)
+    let l:topline = line('w0')
+    execute 'normal! ' . (a:forward ? "\<C-f>" : "\<C-b>")
+    return line('w0') != l:topline
+endfunction
@@ -0,0 +1,80 @@
+" Returns the lines to show in the hover tooltip for cell `row`, `col` (both
+" zero based) of window `winid`. The lines come from the function named in
+" `g:gnvim_hover_function`, which is called in the window with the (one
+" based) line and byte column of the text in the cell.
+function! gnvim#hover#get(winid, row, col)
+    let l:func = get(g:, 'gnvim_hover_function', '')
+    let l:info = getwininfo(a:winid)
+    if empty(l:func) || empty(l:info)
+        return []
+    endif
+    let l:info = l:info[0]
+
+    let l:pos = []
+    call win_execute(a:winid, 'let l:pos = s:cell_position(l:info, a:row, a:col)')
+    if empty(l:pos)
+        return []
+    endif
+
+    let l:lines = []
+    call win_execute(a:winid, 'let l:lines = call(l:func, l:pos)')
+
+    return type(l:lines) == v:t_list ? l:lines : []
+endfunction
+
+" Returns the line and byte column of the text at cell `row`, `col` of the
+" current window, or [] if there's no text there. `info` is the window's
+" getwininfo(). The text is looked up with screenpos(), so that folds,
+" wrapped lines, tabs and wide characters are taken into account.
+function! s:cell_position(info, row, col)
+    let l:target = [a:info.winrow + a:row, a:info.wincol + a:col]
+    if a:col < a:info.textoff
+        return []
+    endif
+
+    " Find the line that's shown on the cell's screen line.
+    let l:lnum = a:info.topline
+    while l:lnum <= a:info.botline
+        " Closed folds are shown on one screen line.
+        let l:next = max([l:lnum, foldclosedend(l:lnum)]) + 1
+        let l:next_row = l:next > a:info.botline
+                    \ ? a:info.winrow + a:info.height
+                    \ : screenpos(a:info.winid, l:next, 1).row
+        if l:target[0] < l:next_row
+            break
+        endif
+        let l:lnum = l:next
+    endwhile
+    if l:lnum > a:info.botline || foldclosed(l:lnum) != -1
+        return []
+    endif
+
+    let l:text = getline(l:lnum)
+    let l:chars = strchars(l:text)
+    if l:chars == 0
+        return []
+    endif
+
+    " Find the last character that starts at or before the cell.
+    let l:lo = 0
+    let l:hi = l:chars - 1
+    while l:lo < l:hi
+        let l:mid = (l:lo + l:hi + 1) / 2
+        let l:pos = screenpos(a:info.winid, l:lnum, byteidx(l:text, l:mid) + 1)
+        if l:pos.row < l:target[0]
+                    \ || (l:pos.row == l:target[0] && l:pos.col <= l:target[1])
+            let l:lo = l:mid
+        else
+            let l:hi = l:mid - 1
+        endif
+    endwhile
+
+    let l:col = byteidx(l:text, l:lo) + 1
+    let l:pos = screenpos(a:info.winid, l:lnum, l:col)
+    if l:pos.row != l:target[0]
+                \ || l:target[1] < l:pos.col || l:target[1] > l:pos.endcol
+        return []
+    endif
+
+    return [l:lnum, l:col]
+endfunction
@@ -6,6 +6,10 @@ function! gnvim#popupmenu#set_width_details(width)
     call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'PopupmenuSetWidthDetails', a:width)
 endfunction
 
+function! gnvim#popupmenu#set_max_height(height)
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'PopupmenuSetMaxHeight', a:height)
+endfunction
+
 function! gnvim#popupmenu#toggle_details()
     call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'CompletionMenuToggleInfo')
     return ''
@@ -15,3 +19,8 @@ function! gnvim#popupmenu#show_menu_on_all_items(bool)
     call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'PopupmenuShowMenuOnAllItems', a:bool)
     return ''
 endfunction
+
+function! gnvim#popupmenu#highlight_matches(bool)
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'PopupmenuHighlightMatches', a:bool)
+    return ''
+endfunction
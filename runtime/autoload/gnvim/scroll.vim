@@ -0,0 +1,15 @@
+function! gnvim#scroll#enable_animation(enable)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'EnableScrollAnimation',
+                \ a:enable == 1)
+endfunction
+
+function! gnvim#scroll#set_animation_duration(duration)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'ScrollAnimationDuration',
+                \ a:duration)
+endfunction
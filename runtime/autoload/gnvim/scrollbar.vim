@@ -0,0 +1,61 @@
+function! gnvim#scrollbar#click_to_jump(enable)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'ScrollbarClickToJump',
+                \ a:enable == 1)
+endfunction
+
+function! gnvim#scrollbar#autohide(enable)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'ScrollbarAutohide',
+                \ a:enable == 1)
+endfunction
+
+function! gnvim#scrollbar#set_width(width)
+    return rpcnotify(g:gnvim_channel_id, 'Gnvim', 'ScrollbarWidth', a:width)
+endfunction
+
+function! gnvim#scrollbar#set_opacity(opacity)
+    " Make sure we always send a float, even if called with e.g. `1`.
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'ScrollbarOpacity',
+                \ a:opacity * 1.0)
+endfunction
+
+function! gnvim#scrollbar#set_position(position)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'ScrollbarPosition',
+                \ a:position)
+endfunction
+
+" Returns information needed for the scrollbars of window `winid`:
+" [w:gnvim_scrollbar, &wrap, leftcol, text area width, widest visible line].
+function! gnvim#scrollbar#info(winid)
+    let l:info = getwininfo(a:winid)
+    if empty(l:info)
+        return [1, 1, 0, 0, 0]
+    endif
+    let l:info = l:info[0]
+
+    let l:leftcol = 0
+    call win_execute(a:winid, 'let l:leftcol = winsaveview().leftcol')
+
+    let l:width = l:info.width - l:info.textoff
+    let l:lines = getbufline(l:info.bufnr, l:info.topline, l:info.botline)
+    let l:maxwidth = max(map(l:lines, 'strdisplaywidth(v:val)'))
+
+    return [
+                \ !!getwinvar(a:winid, 'gnvim_scrollbar', 1),
+                \ getwinvar(a:winid, '&wrap'),
+                \ l:leftcol,
+                \ l:width,
+                \ l:maxwidth,
+                \ ]
+endfunction
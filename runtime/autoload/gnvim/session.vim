@@ -0,0 +1,24 @@
+" Sets the session to be saved to `file` when nvim exits. Called by gnvim for
+" the file given with `--session`.
+function! gnvim#session#setup(file)
+    let s:file = a:file
+    augroup gnvim_session
+        autocmd!
+        autocmd VimLeavePre * call gnvim#session#save()
+    augroup END
+endfunction
+
+function! gnvim#session#save()
+    " Gnvim restores the window's size and position by itself.
+    let l:sessionoptions = &sessionoptions
+    set sessionoptions-=resize sessionoptions-=winpos
+    execute 'mksession!' fnameescape(s:file)
+    let &sessionoptions = l:sessionoptions
+endfunction
+
+" Restores the saved session, if it's enabled with g:gnvim_restore_session.
+function! gnvim#session#restore()
+    if get(g:, 'gnvim_restore_session', 0) && filereadable(s:file)
+        execute 'source' fnameescape(s:file)
+    endif
+endfunction
@@ -0,0 +1,3 @@
+function! gnvim#wildmenu#horizontal(bool)
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'WildmenuHorizontal', a:bool)
+endfunction
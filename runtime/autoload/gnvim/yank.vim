@@ -0,0 +1,82 @@
+" Flashes the text that was just yanked, called on TextYankPost. The yanked
+" text is sent to gnvim as the cells of the current window's grid that it
+" covers.
+function! gnvim#yank#flash()
+    if v:event.operator !=# 'y'
+        return
+    endif
+
+    let [l:start, l:end] = [getpos("'["), getpos("']")]
+    let l:block = v:event.regtype[0] ==# "\<C-v>"
+    let l:charwise = v:event.regtype ==# 'v'
+
+    " screenpos() and win_screenpos() are relative to the screen, but the
+    " cells are relative to the window's grid.
+    let [l:winrow, l:wincol] = win_screenpos(0)
+    let l:info = getwininfo(win_getid())[0]
+    let l:first = l:wincol + l:info.textoff
+    let l:last = l:wincol + l:info.width - 1
+
+    " Only the lines on the screen can be flashed, which also keeps big
+    " yanks (e.g. ggyG) cheap.
+    let l:top = max([l:start[1], line('w0')])
+    let l:bottom = min([l:end[1], line('w$')])
+    if l:top > l:bottom
+        return
+    endif
+
+    if l:block
+        " The block's edges from its first and last lines on the screen.
+        let l:bstart = screenpos(0, l:top, l:start[2]).col
+        let l:bend = screenpos(0, l:bottom, l:end[2]).endcol
+    endif
+
+    let l:ranges = []
+    for l:lnum in range(l:top, l:bottom)
+        let l:scol = l:charwise && l:lnum == l:start[1] ? l:start[2] : 1
+        let l:ecol = l:charwise && l:lnum == l:end[1]
+                    \ ? l:end[2]
+                    \ : max([col([l:lnum, '$']) - 1, 1])
+        let l:s = screenpos(0, l:lnum, l:scol)
+        let l:e = screenpos(0, l:lnum, l:ecol)
+        if l:s.row == 0
+            continue
+        endif
+        if l:e.row == 0
+            " The end is wrapped below the window.
+            let l:e = {'row': l:info.winrow + l:info.height - 1, 'endcol': l:last}
+        endif
+
+        if l:block
+            call add(l:ranges,
+                        \ [l:s.row - l:winrow, l:bstart - l:wincol, l:bend - l:wincol + 1])
+            continue
+        endif
+
+        " Wrapped lines span many rows.
+        for l:row in range(l:s.row, l:e.row)
+            let l:from = l:row == l:s.row ? l:s.col : l:first
+            let l:to = l:row == l:e.row ? l:e.endcol : l:last
+            call add(l:ranges,
+                        \ [l:row - l:winrow, l:from - l:wincol, l:to - l:wincol + 1])
+        endfor
+    endfor
+
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'YankFlash', l:ranges)
+endfunction
+
+function! gnvim#yank#set_flash_color(color)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'YankFlashColor',
+                \ a:color)
+endfunction
+
+function! gnvim#yank#set_flash_duration(duration)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'YankFlashDuration',
+                \ a:duration)
+endfunction
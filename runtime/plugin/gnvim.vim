@@ -6,5 +6,35 @@ let g:gnvim_runtime_loaded = 1
 
 inoremap <expr> <C-s> gnvim#popupmenu#toggle_details()
 
+augroup gnvim
+    autocmd!
+    " Nvim doesn't tell UIs about 'scrolloff', so we'll have to do it
+    " ourselves. Gnvim needs it for its scrollbars.
+    autocmd OptionSet scrolloff
+                \ call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'ScrollOff', &scrolloff)
+    " Same for 'mouse', which tells if the mouse input is sent to nvim.
+    autocmd UIEnter * call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'Mouse', &mouse)
+    " The user's config has been run (e.g. 'guifont' and gnvim#set_size()).
+    autocmd UIEnter * call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'UIEnter')
+    autocmd OptionSet mouse
+                \ call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'Mouse', &mouse)
+    autocmd TextYankPost *
+                \ if get(g:, 'gnvim_yank_flash', 0) | call gnvim#yank#flash() | endif
+    " External windows are titled by their buffers.
+    autocmd BufEnter,BufFilePost *
+                \ call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'BufEnter')
+augroup END
+
 command! -nargs=1 GnvimCursorEnableAnimations
             \ call gnvim#cursor#enable_animations(<q-args>)
+command! -nargs=0 GnvimFind call gnvim#find()
+command! -nargs=0 GnvimToggleFullscreen call gnvim#toggle_fullscreen()
+command! -nargs=1 -complete=file GnvimLoadCss
+            \ call gnvim#load_css(expand(<q-args>))
+command! -nargs=1 GnvimBackgroundOpacity
+            \ call gnvim#set_background_opacity(str2float(<q-args>))
+command! -nargs=1 GnvimFontFeatures call gnvim#set_font_features(<q-args>)
+command! -nargs=+ GnvimPadding
+            \ call call('gnvim#set_padding', map([<f-args>], 'str2nr(v:val)'))
+command! -nargs=0 GnvimPopupmenuToggleDetails
+            \ call gnvim#popupmenu#toggle_details()
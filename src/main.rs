@@ -23,8 +23,13 @@ extern crate pangocairo;
 #[cfg(feature = "libwebkit2gtk")]
 extern crate webkit2gtk;
 
+use std::cell::RefCell;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
 use gio::prelude::*;
-use gtk::SettingsExt;
+use gtk::{GtkApplicationExt, GtkWindowExt, SettingsExt};
 
 use log::error;
 
@@ -51,6 +56,46 @@ fn parse_geometry(input: &str) -> Result<(i32, i32), String> {
     }
 }
 
+/// Parses `--size`'s `COLSxLINES`. Both have to be at least 1.
+fn parse_size(input: &str) -> Result<(u64, u64), String> {
+    let parse = |value: &str, name: &str| match value.parse::<u64>() {
+        Ok(v) if v >= 1 => Ok(v),
+        _ => Err(format!("{} must be a whole number of at least 1", name)),
+    };
+
+    let mut parts = input.split('x');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(cols), Some(lines), None) => {
+            Ok((parse(cols, "COLS")?, parse(lines, "LINES")?))
+        }
+        _ => Err(String::from("must be of form 'COLS'x'LINES'")),
+    }
+}
+
+/// Returns the path to executable `bin`. If `bin` is just a name (e.g.
+/// `nvim`), it's looked up from `$PATH`.
+fn find_executable(bin: &str) -> Option<PathBuf> {
+    let is_executable = |path: &Path| {
+        path.metadata()
+            .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+    };
+
+    let path = Path::new(bin);
+    if path.components().count() > 1 {
+        return if is_executable(path) {
+            Some(path.to_path_buf())
+        } else {
+            None
+        };
+    }
+
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(bin))
+        .find(|path| is_executable(path))
+}
+
 /// Gnvim is a graphical UI for neovim.
 #[derive(StructOpt, Debug)]
 #[structopt(
@@ -63,7 +108,8 @@ struct Options {
     #[structopt(long = "print-nvim-cmd")]
     print_nvim_cmd: bool,
 
-    /// Path to neovim binary.
+    /// Path to neovim binary. If it's just a name, it's looked up from
+    /// $PATH.
     #[structopt(long = "nvim", name = "BIN", default_value = "nvim")]
     nvim_path: String,
 
@@ -79,10 +125,20 @@ struct Options {
     #[structopt(value_name = "FILES")]
     open_files: Vec<String>,
 
-    /// Arguments that are passed to nvim.
+    /// Arguments that are passed to nvim, e.g. `gnvim -- -u NONE`.
     #[structopt(value_name = "ARGS", last = true)]
     nvim_args: Vec<String>,
 
+    /// Connect to an already running nvim (started with --listen) instead
+    /// of starting a new one. Either a path to a unix socket, or a tcp
+    /// address in host:port form
+    #[structopt(
+        long = "server",
+        value_name = "ADDRESS",
+        conflicts_with = "nvim_args"
+    )]
+    server: Option<String>,
+
     /// Disables externalized popup menu
     #[structopt(long = "disable-ext-popupmenu")]
     disable_ext_popupmenu: bool,
@@ -99,9 +155,48 @@ struct Options {
     #[structopt(long = "gtk-prefer-dark-theme")]
     prefer_dark_theme: bool,
 
-    /// Geometry of the window in widthxheight form
-    #[structopt(long = "geometry", parse(try_from_str = parse_geometry), default_value = "1280x720")]
-    geometry: (i32, i32),
+    /// Open the files in an already running gnvim (started with this flag
+    /// too), if there is one
+    #[structopt(long = "single-instance")]
+    single_instance: bool,
+
+    /// Let any program on the session bus evaluate vimscript and lua in
+    /// this gnvim, e.g. with --remote-eval. That includes running shell
+    /// commands. See :h gnvim-dbus
+    #[structopt(long = "enable-remote-eval", requires = "single_instance")]
+    enable_remote_eval: bool,
+
+    /// Geometry of the window in widthxheight form [default: the size on
+    /// the previous exit, or 1280x720]
+    #[structopt(long = "geometry", parse(try_from_str = parse_geometry))]
+    geometry: Option<(i32, i32)>,
+
+    /// Size of the editor in COLSxLINES form, e.g. 100x40. The window is
+    /// sized to fit it, instead of using --geometry (the window's size in
+    /// pixels) or the restored size. Wins over gnvim#set_size() in the
+    /// config
+    #[structopt(
+        long = "size",
+        value_name = "COLSxLINES",
+        parse(try_from_str = parse_size)
+    )]
+    size: Option<(u64, u64)>,
+
+    /// Save the session (see :h :mksession) to FILE on exit. See
+    /// :h gnvim-sessions for restoring it
+    #[structopt(long = "session", value_name = "FILE", parse(from_os_str))]
+    session: Option<PathBuf>,
+
+    /// Don't restore the window's geometry from the previous exit, nor
+    /// save it on exit
+    #[structopt(long = "no-restore-geometry")]
+    no_restore_geometry: bool,
+
+    /// Evaluate the vimscript expression EXPR in the gnvim running with
+    /// --single-instance and --enable-remote-eval, print the result and
+    /// exit. See :h gnvim-dbus
+    #[structopt(long = "remote-eval", value_name = "EXPR")]
+    remote_eval: Option<String>,
 }
 
 enum Error {
@@ -112,7 +207,15 @@ enum Error {
 impl std::fmt::Display for Error {
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            Error::Start(e) => write!(fmt, "Failed to start nvim: {}", e),
+            Error::Start(e @ nvim_gio::Error::Connect(_)) => {
+                write!(fmt, "{}", e)
+            }
+            Error::Start(e) => write!(
+                fmt,
+                "Failed to start nvim: {} (see --nvim for using another \
+                 binary)",
+                e
+            ),
             Error::Call(e) => write!(fmt, "Call to nvim failed: {}", e),
         }
     }
@@ -130,21 +233,137 @@ impl From<Box<nvim_rs::error::CallError>> for Error {
     }
 }
 
-async fn build(app: &gtk::Application, opts: &Options) -> Result<(), Error> {
+async fn build(
+    app: &gtk::Application,
+    opts: &Options,
+) -> Result<nvim_gio::GioNeovim, Error> {
     let (tx, rx) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
     let bridge = nvim_bridge::NvimBridge::new(tx.clone());
 
     let rtp = format!("let &rtp.=',{}'", opts.gnvim_rtp);
-    let mut args: Vec<&str> = vec![
-        &opts.nvim_path,
-        "--embed",
-        "--cmd",
-        "let g:gnvim=1",
-        "--cmd",
-        "set termguicolors",
-        "--cmd",
-        &rtp,
-    ];
+    let mut nvim = match opts.server {
+        Some(ref address) => nvim_gio::new_connection(bridge, address, tx)?,
+        None => spawn_nvim(opts, &rtp, bridge, tx)?,
+    };
+
+    nvim.subscribe("Gnvim").await.map_err(Error::from)?;
+
+    let api_info = nvim.get_api_info().await.map_err(Error::from)?;
+    nvim.set_var("gnvim_channel_id", api_info[0].clone())
+        .await
+        .map_err(Error::from)?;
+
+    if opts.server.is_some() {
+        setup_remote(&nvim, opts, &rtp).await?;
+    }
+
+    // Restored before attaching, so that the window gets its size before
+    // nvim's first redraw.
+    let geometry = window_geometry(opts);
+
+    let mut ui_opts = nvim_rs::UiAttachOptions::new();
+    ui_opts.set_rgb(true);
+    ui_opts.set_linegrid_external(true);
+    ui_opts.set_multigrid_external(true);
+    ui_opts.set_popupmenu_external(!opts.disable_ext_popupmenu);
+    ui_opts.set_tabline_external(!opts.disable_ext_tabline);
+    ui_opts.set_cmdline_external(!opts.disable_ext_cmdline);
+
+    let (cols, rows) = opts.size.unwrap_or((80, 30));
+    nvim.ui_attach(cols as i64, rows as i64, &ui_opts)
+        .await
+        .map_err(Error::from)?;
+
+    if let Some(ref session) = opts.session {
+        let file = session.to_string_lossy();
+        nvim.call_function("gnvim#session#setup", vec![file.as_ref().into()])
+            .await
+            .map_err(Error::from)?;
+    }
+
+    let ui = ui::UI::init(
+        app,
+        rx,
+        geometry,
+        !opts.no_restore_geometry,
+        opts.size,
+        // Files given to us win over the ones in the session.
+        opts.session.is_some() && opts.open_files.is_empty(),
+        opts.enable_remote_eval,
+        nvim.clone(),
+    );
+    ui.start();
+
+    Ok(nvim)
+}
+
+/// Does for a nvim that we connected to (with `--server`) what the startup
+/// arguments do for the nvim we start ourselves: loads our runtime files,
+/// and opens the files given to us.
+async fn setup_remote(
+    nvim: &nvim_gio::GioNeovim,
+    opts: &Options,
+    rtp: &str,
+) -> Result<(), Error> {
+    for var in gnvim_vars(opts) {
+        nvim.command(&var).await.map_err(Error::from)?;
+    }
+    for cmd in &["set termguicolors", rtp] {
+        nvim.command(cmd).await.map_err(Error::from)?;
+    }
+    nvim.command("runtime! plugin/gnvim.vim")
+        .await
+        .map_err(Error::from)?;
+
+    if !opts.open_files.is_empty() {
+        // The remote nvim might be running in some other directory.
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let paths: Vec<PathBuf> =
+            opts.open_files.iter().map(|file| cwd.join(file)).collect();
+        nvim.command(&ui::tabedit_command(&paths))
+            .await
+            .map_err(Error::from)?;
+    }
+
+    Ok(())
+}
+
+/// Returns the commands that set the global variables telling the scripts
+/// about us: `g:gnvim`, `g:gnvim_version` and `g:gnvim_features`. Set with
+/// `--cmd`, so that they're available to init.vim too.
+fn gnvim_vars(opts: &Options) -> Vec<String> {
+    let features = [
+        ("cursor_tooltip", cfg!(feature = "libwebkit2gtk")),
+        ("popupmenu", !opts.disable_ext_popupmenu),
+        ("cmdline", !opts.disable_ext_cmdline),
+        ("tabline", !opts.disable_ext_tabline),
+    ]
+    .iter()
+    .map(|(name, enabled)| format!("'{}': {}", name, *enabled as u8))
+    .collect::<Vec<_>>()
+    .join(", ");
+
+    vec![
+        String::from("let g:gnvim=1"),
+        format!("let g:gnvim_version='{}'", VERSION),
+        format!("let g:gnvim_features={{{}}}", features),
+    ]
+}
+
+/// Starts a new (embedded) nvim.
+fn spawn_nvim(
+    opts: &Options,
+    rtp: &str,
+    bridge: nvim_bridge::NvimBridge,
+    tx: glib::Sender<nvim_bridge::Message>,
+) -> Result<nvim_gio::GioNeovim, Error> {
+    let vars = gnvim_vars(opts);
+    let mut args: Vec<&str> = vec![&opts.nvim_path, "--embed"];
+    for var in vars.iter() {
+        args.push("--cmd");
+        args.push(var);
+    }
+    args.extend(&["--cmd", "set termguicolors", "--cmd", rtp]);
 
     // Pass arguments from cli to nvim.
     for arg in opts.nvim_args.iter() {
@@ -161,36 +380,89 @@ async fn build(app: &gtk::Application, opts: &Options) -> Result<(), Error> {
         println!("nvim cmd: {:?}", args);
     }
 
-    let mut nvim = nvim_gio::new_child(
+    let nvim = nvim_gio::new_child(
         bridge,
         args.iter().map(|a| std::ffi::OsStr::new(a)).collect(),
         tx,
-    )
-    .map_err(Error::from)?;
+    )?;
 
-    nvim.subscribe("Gnvim").await.map_err(Error::from)?;
+    Ok(nvim)
+}
 
-    let api_info = nvim.get_api_info().await.map_err(Error::from)?;
-    nvim.set_var("gnvim_channel_id", api_info[0].clone())
-        .await
-        .map_err(Error::from)?;
+/// Returns the initial geometry of the main window. An explicit `--geometry`
+/// wins over the one restored from the previous exit. With `--size`, the
+/// window isn't maximized, since it's resized to fit the requested size.
+fn window_geometry(opts: &Options) -> ui::WindowGeometry {
+    let restored = if opts.no_restore_geometry {
+        None
+    } else {
+        ui::WindowGeometry::load()
+    };
+
+    match (opts.geometry, restored) {
+        (Some((width, height)), _) => ui::WindowGeometry {
+            width,
+            height,
+            maximized: false,
+        },
+        (None, Some(restored)) => ui::WindowGeometry {
+            maximized: restored.maximized && opts.size.is_none(),
+            ..restored
+        },
+        (None, None) => ui::WindowGeometry {
+            width: 1280,
+            height: 720,
+            maximized: false,
+        },
+    }
+}
 
-    let mut ui_opts = nvim_rs::UiAttachOptions::new();
-    ui_opts.set_rgb(true);
-    ui_opts.set_linegrid_external(true);
-    ui_opts.set_multigrid_external(true);
-    ui_opts.set_popupmenu_external(!opts.disable_ext_popupmenu);
-    ui_opts.set_tabline_external(!opts.disable_ext_tabline);
-    ui_opts.set_cmdline_external(!opts.disable_ext_cmdline);
+/// Builds the UI, unless it's already running (with `--single-instance`),
+/// in which case its window is brought to the front.
+fn activate(
+    app: &gtk::Application,
+    opts: &Options,
+    running: &RefCell<Option<nvim_gio::GioNeovim>>,
+) {
+    if running.borrow().is_some() {
+        if let Some(win) = app.get_active_window() {
+            win.present();
+        }
+        return;
+    }
 
-    nvim.ui_attach(80, 30, &ui_opts)
-        .await
-        .map_err(Error::from)?;
+    let c = glib::MainContext::default();
+    c.block_on(async move {
+        match build(app, opts).await {
+            Ok(nvim) => {
+                running.replace(Some(nvim));
+            }
+            Err(err) => error!("Failed to build UI: {}", err),
+        }
+    });
+}
 
-    let ui = ui::UI::init(app, rx, opts.geometry, nvim);
-    ui.start();
+/// Opens `files` (from another gnvim, started with `--single-instance`) in
+/// new tabs.
+fn open(
+    app: &gtk::Application,
+    files: &[gio::File],
+    nvim: nvim_gio::GioNeovim,
+) {
+    let paths: Vec<PathBuf> =
+        files.iter().filter_map(|f| f.get_path()).collect();
+    if !paths.is_empty() {
+        let cmd = ui::tabedit_command(&paths);
+        glib::MainContext::default().spawn_local(async move {
+            if let Err(err) = nvim.command(&cmd).await {
+                error!("Failed to open files: {}", err);
+            }
+        });
+    }
 
-    Ok(())
+    if let Some(win) = app.get_active_window() {
+        win.present();
+    }
 }
 
 fn main() {
@@ -218,8 +490,33 @@ fn main() {
         },
     ));
 
+    if let Some(ref expr) = opts.remote_eval {
+        match ui::remote_eval(expr) {
+            Ok(res) => println!("{}", res),
+            Err(err) => {
+                eprintln!("Failed to evaluate in gnvim: {}", err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if opts.server.is_none() && find_executable(&opts.nvim_path).is_none() {
+        clap::Error::with_description(
+            &format!(
+                "Can't find the nvim binary '{}'. Make sure it's installed, \
+                 or give its path with --nvim",
+                opts.nvim_path
+            ),
+            clap::ErrorKind::InvalidValue,
+        )
+        .exit();
+    }
+
     let mut flags = gio::ApplicationFlags::empty();
-    flags.insert(gio::ApplicationFlags::NON_UNIQUE);
+    if !opts.single_instance {
+        flags.insert(gio::ApplicationFlags::NON_UNIQUE);
+    }
     flags.insert(gio::ApplicationFlags::HANDLES_OPEN);
     let app = gtk::Application::new(Some("com.github.vhakulinen.gnvim"), flags)
         .unwrap();
@@ -234,15 +531,94 @@ fn main() {
         }
     }
 
-    app.connect_activate(move |app| {
-        let opts = &opts;
-        let c = glib::MainContext::default();
-        c.block_on(async move {
-            if let Err(err) = build(app, opts).await {
-                error!("Failed to build UI: {}", err);
+    let opts = Rc::new(opts);
+    // Nvim of the UI, once it's built.
+    let running = Rc::new(RefCell::new(None));
+
+    {
+        let opts = opts.clone();
+        let running = running.clone();
+        app.connect_activate(move |app| activate(app, &opts, &running));
+    }
+
+    {
+        let opts = opts.clone();
+        let running = running.clone();
+        let restart = gio::SimpleAction::new(ui::RESTART_ACTION, None);
+        app.add_action(&restart);
+
+        let app = app.clone();
+        restart.connect_activate(move |_, _| {
+            // The previous nvim is gone, so start from scratch.
+            running.replace(None);
+            activate(&app, &opts, &running);
+        });
+    }
+
+    {
+        let opts = opts.clone();
+        let running = running.clone();
+        app.connect_open(move |app, files, _| {
+            let nvim = running.borrow().clone();
+            match nvim {
+                Some(nvim) => open(app, files, nvim),
+                // We're the first instance, and the files are already
+                // passed to nvim.
+                None => activate(app, &opts, &running),
             }
         });
-    });
+    }
+
+    if opts.single_instance {
+        // The files are passed through the application, so that they end
+        // up in the running instance (if there is one).
+        let mut args = vec![String::from("gnvim")];
+        args.extend(opts.open_files.iter().cloned());
+        app.run(&args);
+    } else {
+        app.run(&[]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gnvim_vars() {
+        let opts = Options::from_iter(&["gnvim", "--disable-ext-tabline"]);
+        let vars = gnvim_vars(&opts);
+
+        assert_eq!("let g:gnvim=1", vars[0]);
+        assert_eq!(format!("let g:gnvim_version='{}'", VERSION), vars[1]);
+        assert!(vars[2].starts_with("let g:gnvim_features={'cursor_tooltip': "));
+        assert!(
+            vars[2].ends_with(", 'popupmenu': 1, 'cmdline': 1, 'tabline': 0}")
+        );
+    }
 
-    app.run(&[]);
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(Ok((100, 40)), parse_size("100x40"));
+        assert_eq!(Ok((1, 1)), parse_size("1x1"));
+
+        assert!(parse_size("0x0").is_err());
+        assert!(parse_size("80x0").is_err());
+        assert!(parse_size("-80x30").is_err());
+        assert!(parse_size("80x-30").is_err());
+        assert!(parse_size("80x30x2").is_err());
+        assert!(parse_size("80").is_err());
+        assert!(parse_size("80xfoo").is_err());
+    }
+
+    #[test]
+    fn test_find_executable() {
+        assert_eq!(Some(PathBuf::from("/bin/sh")), find_executable("/bin/sh"));
+        assert!(find_executable("sh").is_some());
+
+        assert_eq!(None, find_executable("/bin/gnvim-no-such-binary"));
+        assert_eq!(None, find_executable("gnvim-no-such-binary"));
+        // Not executable.
+        assert_eq!(None, find_executable("/etc/passwd"));
+    }
 }
@@ -2,10 +2,10 @@ use log::{debug, error};
 
 use std::collections::HashMap;
 use std::fmt;
-use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use futures::channel::oneshot;
 use futures::future::Future;
 use nvim_rs::{create::Spawner, neovim::Neovim, Handler};
 use rmpv::Value;
@@ -73,6 +73,20 @@ macro_rules! try_u64 {
     };
 }
 
+macro_rules! try_i64 {
+    ($val:expr, $msg:expr) => {
+        $val.as_i64()
+            .ok_or(format!("Value is not an i64: {}", $msg))?
+    };
+}
+
+macro_rules! try_f64 {
+    ($val:expr, $msg:expr) => {
+        $val.as_f64()
+            .ok_or(format!("Value is not an f64: {}", $msg))?
+    };
+}
+
 impl Highlight {
     fn from_map_val(map: &[(Value, Value)]) -> Self {
         let mut hl = Highlight::default();
@@ -120,11 +134,33 @@ impl Highlight {
             "undercurl" => {
                 self.undercurl = unwrap_bool!(val);
             }
+            "strikethrough" => {
+                self.strikethrough = unwrap_bool!(val);
+            }
+            // Older nvim versions call these underlineline, underdot and
+            // underdash.
+            "underdouble" | "underlineline" => {
+                self.underdouble = unwrap_bool!(val);
+            }
+            "underdotted" | "underdot" => {
+                self.underdotted = unwrap_bool!(val);
+            }
+            "underdashed" | "underdash" => {
+                self.underdashed = unwrap_bool!(val);
+            }
             "blend" => {
                 self.blend = unwrap_f64!(val) / 100.0;
             }
             "cterm_fg" => {}
             "cterm_bg" => {}
+            _ if prop.starts_with("under") => {
+                // Fall back to plain underline with underline styles we
+                // don't know about.
+                debug!("Unknown underline style: {}", prop);
+                if val.as_bool().unwrap_or(false) {
+                    self.underline = true;
+                }
+            }
             _ => {
                 debug!("Unknown highligh property: {}", prop);
             }
@@ -142,7 +178,7 @@ pub enum Notify {
     GnvimEvent(Result<GnvimEvent, String>),
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum CursorShape {
     Block,
     Horizontal,
@@ -170,11 +206,18 @@ impl Default for CursorShape {
 
 #[derive(Default, Clone, Debug, PartialEq)]
 pub struct ModeInfo {
-    /// The cursor blinking period (in ms)
+    /// Time (in ms) the cursor is shown when blinking.
     pub blink_on: u64,
+    /// Time (in ms) the cursor is hidden when blinking.
+    pub blink_off: u64,
+    /// Delay (in ms) before the cursor starts blinking.
+    pub blink_wait: u64,
     pub cursor_shape: CursorShape,
     /// The cursor's width (in percentages, from 0..1).
     pub cell_percentage: f64,
+    /// Highlight id of the cursor's colors. 0 means that the cell under
+    /// the cursor is drawn inverted.
+    pub attr_id: u64,
     // TODO(ville): Implement the rest.
 }
 
@@ -184,6 +227,12 @@ impl ModeInfo {
             "blinkon" => {
                 self.blink_on = unwrap_u64!(val);
             }
+            "blinkoff" => {
+                self.blink_off = unwrap_u64!(val);
+            }
+            "blinkwait" => {
+                self.blink_wait = unwrap_u64!(val);
+            }
             "cursor_shape" => {
                 self.cursor_shape = CursorShape::from_string(unwrap_str!(val))
             }
@@ -196,6 +245,9 @@ impl ModeInfo {
                 }
                 self.cell_percentage = val as f64 / 100.0;
             }
+            "attr_id" => {
+                self.attr_id = unwrap_u64!(val);
+            }
             _ => {}
         }
     }
@@ -213,6 +265,8 @@ pub struct Cell {
 pub enum OptionSet {
     /// Font name.
     GuiFont(String),
+    /// Font name for double width characters.
+    GuiFontWide(String),
     /// Space between lines.
     LineSpace(i64),
     ExtTabline(bool),
@@ -231,6 +285,10 @@ impl From<Value> for OptionSet {
                 let val = unwrap_str!(args[1]);
                 OptionSet::GuiFont(String::from(val))
             }
+            "guifontwide" => {
+                let val = unwrap_str!(args[1]);
+                OptionSet::GuiFontWide(String::from(val))
+            }
             "linespace" => {
                 let val = unwrap_i64!(args[1]);
                 OptionSet::LineSpace(val)
@@ -282,34 +340,38 @@ impl From<&str> for CompletionItemKind {
     //   https://github.com/neoclide/coc.nvim/blob/909710fddb04d383e5546b0f869c44f395a80d02/src/languages.ts#L143-L167
     // Pascal cased kinds are LanguageClient-neovim
     //   https://github.com/autozimu/LanguageClient-neovim/blob/0ac444affdff8db699684aa4cf04c2cb0daf0286/rplugin/python3/denite/lsp/protocol.py#L48-L55
+    // Pascal cased kinds without spaces are nvim's builtin lsp client
+    //   https://github.com/neovim/neovim/blob/v0.5.0/runtime/lua/vim/lsp/protocol.lua#L200-L226
     fn from(from: &str) -> Self {
         match from {
             "class" | "C" | "Class" => CompletionItemKind::Class,
-            "color" => CompletionItemKind::Color,
+            "color" | "Color" => CompletionItemKind::Color,
             "constant" | "Constant" => CompletionItemKind::Constant,
             "constructor" | "Constructor" => CompletionItemKind::Constructor,
             "enum" | "Enum" => CompletionItemKind::Enum,
-            "enum member" | "Enum Member" => CompletionItemKind::EnumMember,
+            "enum member" | "Enum Member" | "EnumMember" => {
+                CompletionItemKind::EnumMember
+            }
             "event" | "E" | "Event" => CompletionItemKind::Event,
             "file" | "F" | "File" => CompletionItemKind::File,
             "field" | "m" | "Field" => CompletionItemKind::Field,
             "folder" | "Folder" => CompletionItemKind::Folder,
             "function" | "Function" => CompletionItemKind::Function,
             "interface" | "I" | "Interface" => CompletionItemKind::Interface,
-            "keyword" | "k" | "Key" => CompletionItemKind::Keyword,
+            "keyword" | "k" | "Key" | "Keyword" => CompletionItemKind::Keyword,
             "method" | "f" | "Method" => CompletionItemKind::Method,
             "module" | "M" | "Module" => CompletionItemKind::Module,
             "operator" | "O" | "Operator" => CompletionItemKind::Operator,
             "property" | "Property" => CompletionItemKind::Property,
-            "reference" | "r" => CompletionItemKind::Reference,
-            "snippet" => CompletionItemKind::Snippet,
+            "reference" | "r" | "Reference" => CompletionItemKind::Reference,
+            "snippet" | "Snippet" => CompletionItemKind::Snippet,
             "struct" | "S" | "Struct" => CompletionItemKind::Struct,
-            "text" => CompletionItemKind::Text,
-            "type parameter" | "T" | "Type Parameter" => {
+            "text" | "Text" => CompletionItemKind::Text,
+            "type parameter" | "T" | "Type Parameter" | "TypeParameter" => {
                 CompletionItemKind::TypeParameter
             }
-            "unit" | "U" => CompletionItemKind::Unit,
-            "value" => CompletionItemKind::Value,
+            "unit" | "U" | "Unit" => CompletionItemKind::Unit,
+            "value" | "Value" => CompletionItemKind::Value,
             "variable" | "v" | "Variable" => CompletionItemKind::Variable,
             _ => CompletionItemKind::Unknown,
         }
@@ -572,7 +634,16 @@ impl From<Value> for HlAttrDefine {
         let id = unwrap_u64!(args[0]);
         let map = unwrap_map!(args[1]);
 
-        let hl = Highlight::from_map_val(map);
+        let mut hl = Highlight::from_map_val(map);
+
+        // The cterm attributes tell if the foreground comes from the
+        // palette.
+        if let Some(Value::Map(cterm)) = args.get(2) {
+            hl.cterm_fg = cterm
+                .iter()
+                .find(|(k, _)| k.as_str() == Some("foreground"))
+                .and_then(|(_, v)| v.as_u64());
+        }
 
         HlAttrDefine { id, hl }
     }
@@ -729,10 +800,11 @@ pub struct CmdlineBlockShow {
 
 impl From<Value> for CmdlineBlockShow {
     fn from(args: Value) -> Self {
-        let lines = unwrap_array!(args)
+        let args = unwrap_array!(args);
+        let lines = unwrap_array!(args[0])
             .iter()
             .map(|line| {
-                unwrap_array!(line[0])
+                unwrap_array!(line)
                     .iter()
                     .map(|v| {
                         let hl_id = unwrap_u64!(v[0]);
@@ -852,6 +924,36 @@ impl From<Value> for WindowExternalPos {
 }
 
 #[derive(Debug, PartialEq)]
+pub struct WindowViewport {
+    pub grid: i64,
+    pub win: Value,
+    /// First line (zero based) in the window.
+    pub topline: u64,
+    /// Line after the last line (zero based) in the window.
+    pub botline: u64,
+    pub curline: u64,
+    pub curcol: u64,
+    /// Number of lines in the window's buffer. Not sent by older nvim
+    /// versions.
+    pub line_count: Option<u64>,
+}
+
+impl From<Value> for WindowViewport {
+    fn from(args: Value) -> Self {
+        let args = unwrap_array!(args);
+        Self {
+            grid: unwrap_i64!(args[0]),
+            win: args[1].clone(),
+            topline: unwrap_u64!(args[2]),
+            botline: unwrap_u64!(args[3]),
+            curline: unwrap_u64!(args[4]),
+            curcol: unwrap_u64!(args[5]),
+            line_count: args.get(6).and_then(|v| v.as_u64()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub struct MsgSetPos {
     pub grid: i64,
     pub row: u64,
@@ -874,6 +976,7 @@ impl From<Value> for MsgSetPos {
 #[derive(Debug, PartialEq)]
 pub enum RedrawEvent {
     SetTitle(Vec<String>),
+    SetIcon(Vec<String>),
 
     GridLine(Vec<GridLineSegment>),
     GridResize(Vec<GridResize>),
@@ -889,6 +992,8 @@ pub enum RedrawEvent {
     ModeInfoSet(Vec<ModeInfoSet>),
     ModeChange(Vec<ModeChange>),
     SetBusy(bool),
+    Bell(),
+    VisualBell(),
 
     Flush(),
 
@@ -910,6 +1015,7 @@ pub enum RedrawEvent {
     WindowExternalPos(Vec<WindowExternalPos>),
     WindowHide(Vec<i64>),
     WindowClose(Vec<i64>),
+    WindowViewport(Vec<WindowViewport>),
     MsgSetPos(Vec<MsgSetPos>),
 
     Ignored(String),
@@ -920,6 +1026,7 @@ impl fmt::Display for RedrawEvent {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         match self {
             RedrawEvent::SetTitle(..) => write!(fmt, "SetTitle"),
+            RedrawEvent::SetIcon(..) => write!(fmt, "SetIcon"),
             RedrawEvent::GridLine(..) => write!(fmt, "GridLine"),
             RedrawEvent::GridResize(..) => write!(fmt, "GridResize"),
             RedrawEvent::GridCursorGoto(..) => write!(fmt, "GridCursorGoto"),
@@ -935,6 +1042,8 @@ impl fmt::Display for RedrawEvent {
             RedrawEvent::ModeInfoSet(..) => write!(fmt, "ModeInfoSet"),
             RedrawEvent::ModeChange(..) => write!(fmt, "ModeChange"),
             RedrawEvent::SetBusy(..) => write!(fmt, "SetBusy"),
+            RedrawEvent::Bell(..) => write!(fmt, "Bell"),
+            RedrawEvent::VisualBell(..) => write!(fmt, "VisualBell"),
             RedrawEvent::Flush(..) => write!(fmt, "Flush"),
             RedrawEvent::PopupmenuShow(..) => write!(fmt, "PopupmenuShow"),
             RedrawEvent::PopupmenuHide(..) => write!(fmt, "PopupmenuHide"),
@@ -963,6 +1072,7 @@ impl fmt::Display for RedrawEvent {
             }
             RedrawEvent::WindowHide(..) => write!(fmt, "WindowHide"),
             RedrawEvent::WindowClose(..) => write!(fmt, "WindowClose"),
+            RedrawEvent::WindowViewport(..) => write!(fmt, "WindowViewport"),
             RedrawEvent::MsgSetPos(..) => write!(fmt, "MsgSetPos"),
 
             RedrawEvent::Ignored(..) => write!(fmt, "Ignored"),
@@ -971,6 +1081,32 @@ impl fmt::Display for RedrawEvent {
     }
 }
 
+/// Where the vertical scrollbars are placed in the windows.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScrollbarPosition {
+    Left,
+    Right,
+    /// No vertical scrollbars at all.
+    None,
+}
+
+impl ScrollbarPosition {
+    fn from_string(name: &str) -> Result<Self, String> {
+        match name {
+            "left" => Ok(ScrollbarPosition::Left),
+            "right" => Ok(ScrollbarPosition::Right),
+            "none" => Ok(ScrollbarPosition::None),
+            _ => Err(format!("Invalid scrollbar position: {}", name)),
+        }
+    }
+}
+
+impl Default for ScrollbarPosition {
+    fn default() -> Self {
+        ScrollbarPosition::Right
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum GnvimEvent {
     CompletionMenuToggleInfo,
@@ -978,56 +1114,144 @@ pub enum GnvimEvent {
     CursorTooltipLoadStyle(String),
     CursorTooltipShow(String, u64, u64),
     CursorTooltipHide,
+    /// Scrolls the cursor tooltip's content by the number of lines (negative
+    /// scrolls up).
+    CursorTooltipScroll(i64),
     CursorTooltipSetStyle(String),
 
     PopupmenuWidth(u64),
     PopupmenuWidthDetails(u64),
+    /// Maximum height of the popupmenu, in rows.
+    PopupmenuMaxHeight(u64),
     PopupmenuShowMenuOnAllItems(bool),
+    PopupmenuHighlightMatches(bool),
+
+    WildmenuHorizontal(bool),
 
     EnableCursorAnimations(bool),
+    /// Duration (in ms) of the cursor's movement animation.
+    CursorAnimationDuration(u64),
+    /// Fixed color for the cursor. `None` uses the cursor's highlight.
+    CursorColor(Option<Color>),
+    /// Yanked text in the current window, as row, and start and end
+    /// (exclusive) columns of the yanked cells on each row.
+    YankFlash(Vec<(u64, u64, u64)>),
+    YankFlashColor(Option<Color>),
+    /// Duration (in ms) of the yank flash.
+    YankFlashDuration(u64),
+    /// Fixed color for the cursor in a mode (see `mode_change`), overriding
+    /// `CursorColor`. `None` removes the mode's color.
+    ModeCursorColor(String, Option<Color>),
+    EnableScrollAnimation(bool),
+    /// Duration (in ms) of the scroll animation.
+    ScrollAnimationDuration(u64),
+    EnableLigatures(bool),
+    FontFeatures(String),
+    /// User CSS for styling the UI, applied on top of our own styles.
+    UserCss(String),
+    /// Accent color for a mode (see `mode_change`). `None` removes the
+    /// mode's color.
+    ModeColor(String, Option<Color>),
+    EnableSyntheticStyles(bool),
+    BoldIsBright(bool),
+    EnableVisualBell(bool),
+    EnableBusyPointer(bool),
+    EnableFindKey(bool),
+    BackgroundBlur(bool),
+    EnableFullscreenKey(bool),
+    EnableHover(bool),
+    AutoBackground(bool),
+    EnableExternalResize(bool),
+    EnableExternalFocus(bool),
+    EnableFloatAnimation(bool),
+    EnableFloatBorder(bool),
+    AltIsMeta(bool),
+    /// Keys (in nvim's notation, `*` matching any key) that aren't sent to
+    /// nvim.
+    KeyPassthrough(Vec<String>),
+    EnableContextMenu(bool),
+    MouseScrollLines(u64),
+    MouseScrollShiftMultiplier(f64),
+    EnableKineticScrolling(bool),
+
+    /// Value of the 'scrolloff' option (sent by our runtime files when the
+    /// option changes).
+    ScrollOff(u64),
+    /// Value of the 'mouse' option (sent by our runtime files, like
+    /// `ScrollOff`).
+    Mouse(String),
+    ScrollbarClickToJump(bool),
+    ScrollbarAutohide(bool),
+    /// Width of the scrollbars in pixels, 0 for GTK's default.
+    ScrollbarWidth(u64),
+    ScrollbarOpacity(f64),
+    ScrollbarPosition(ScrollbarPosition),
+    /// Opacity of the default background, 0.0-1.0.
+    BackgroundOpacity(f64),
+    /// How much (0.0-1.0) the grids are dimmed when the window isn't
+    /// focused, 0.0 to disable.
+    InactiveDim(f64),
+    /// Maximum frame rate for drawing the grids, or 0 for no limit.
+    MaxFps(u64),
+    /// Space (in pixels) between the grids and the window's edges: top,
+    /// right, bottom and left.
+    Padding(u64, u64, u64, u64),
+    /// Size (columns, lines) to open the main window at.
+    Size(u64, u64),
+    /// If we draw the cursor line and the cursor column.
+    CursorOverlay(bool, bool),
 
     EnableExtTabline(bool),
     EnableExtCmdline(bool),
     EnableExtPopupmenu(bool),
 
+    /// Sets the clipboard register's (`+` or `*`) contents: the lines and
+    /// the register type (see `:h setreg()`).
+    ClipboardSet(String, Vec<String>, String),
+
+    /// A buffer was entered (or renamed), so the external windows' titles
+    /// might be outdated.
+    BufEnter,
+    /// Nvim has started, and the user's config has been run.
+    UIEnter,
+    ToggleFullscreen,
+    OpenFind,
+
     Unknown(String),
 }
 
+#[derive(Debug, PartialEq)]
 pub enum Request {
     CursorTooltipStyles,
+    /// Gets the clipboard register's (`+` or `*`) contents, as the lines and
+    /// the register type.
+    ClipboardGet(String),
 }
 
 /// Message type that we are sending to the UI.
 pub enum Message {
     /// RPC notify (see `:h rpcnotify()`).
     Notify(Notify),
-    /// RPC Request (see `: rpcrequest()`).
-    Request(Sender<Result<Value, Value>>, Request),
-    /// Nvim went away or reading from the rcp connection failed.
+    /// RPC Request (see `: rpcrequest()`). The UI should send the response
+    /// to the channel, but it doesn't need to do that right away.
+    Request(oneshot::Sender<Result<Value, Value>>, Request),
+    /// Nvim exited normally.
     Close,
+    /// Nvim exited unexpectedly (e.g. crashed), or the rpc connection to it
+    /// broke. Contains a description of what happened.
+    Crash(String),
 }
 
 #[derive(Clone)]
 pub struct NvimBridge {
     /// Channel to send messages to the ui.
     tx: Arc<ThreadGuard<glib::Sender<Message>>>,
-
-    /// Channel to pass to the UI when we receive a request from nvim.
-    /// The UI should send values to this channel when ever it gets a message
-    /// Message::Request on its receiving end of `tx`.
-    request_tx: Arc<ThreadGuard<Sender<Result<Value, Value>>>>,
-    /// Receiving end of `request_tx`.
-    request_rx: Arc<ThreadGuard<Receiver<Result<Value, Value>>>>,
 }
 
 impl NvimBridge {
     pub fn new(tx: glib::Sender<Message>) -> Self {
-        let (request_tx, request_rx) = channel();
-
         NvimBridge {
             tx: Arc::new(ThreadGuard::new(tx)),
-            request_tx: Arc::new(ThreadGuard::new(request_tx)),
-            request_rx: Arc::new(ThreadGuard::new(request_rx)),
         }
     }
 }
@@ -1045,14 +1269,17 @@ impl Handler for NvimBridge {
         match name.as_str() {
             "Gnvim" => match parse_request(args) {
                 Ok(msg) => {
-                    let tx = self.tx.borrow_mut();
-                    tx.send(Message::Request(
-                        self.request_tx.borrow_mut().clone(),
-                        msg,
-                    ))
-                    .unwrap();
-                    let rx = self.request_rx.borrow_mut();
-                    rx.recv().unwrap()
+                    // The UI runs on the same main context as we do, so we
+                    // can't block while waiting for the response.
+                    let (tx, rx) = oneshot::channel();
+                    let res =
+                        self.tx.borrow_mut().send(Message::Request(tx, msg));
+                    if let Err(err) = res {
+                        error!("Failed to send request to the gui: {}", err);
+                        return Err("UI is gone".into());
+                    }
+
+                    rx.await.unwrap_or_else(|_| Err("UI is gone".into()))
                 }
                 Err(_) => Err("Failed to parse request".into()),
             },
@@ -1071,7 +1298,9 @@ impl Handler for NvimBridge {
     ) {
         if let Some(notify) = parse_notify(&name, args) {
             let tx = self.tx.borrow_mut();
-            tx.send(Message::Notify(notify)).unwrap();
+            if let Err(err) = tx.send(Message::Notify(notify)) {
+                error!("Failed to send notify to the gui: {}", err);
+            }
         } else {
             error!("Unknown notify: {}", name);
         }
@@ -1096,6 +1325,10 @@ fn parse_request(args: Vec<Value>) -> Result<Request, ()> {
 
     match cmd {
         "CursorTooltipGetStyles" => Ok(Request::CursorTooltipStyles),
+        "ClipboardGet" => {
+            let register = args.get(1).and_then(Value::as_str).ok_or(())?;
+            Ok(Request::ClipboardGet(register.to_string()))
+        }
         _ => Err(()),
     }
 }
@@ -1115,6 +1348,11 @@ fn parse_single_redraw_event(cmd: &str, args: Vec<Value>) -> RedrawEvent {
                 .map(|v| unwrap_str!(v[0]).to_string())
                 .collect(),
         ),
+        "set_icon" => RedrawEvent::SetIcon(
+            args.into_iter()
+                .map(|v| unwrap_str!(v[0]).to_string())
+                .collect(),
+        ),
         "grid_resize" => RedrawEvent::GridResize(
             args.into_iter().map(GridResize::from).collect(),
         ),
@@ -1153,6 +1391,8 @@ fn parse_single_redraw_event(cmd: &str, args: Vec<Value>) -> RedrawEvent {
         ),
         "busy_start" => RedrawEvent::SetBusy(true),
         "busy_stop" => RedrawEvent::SetBusy(false),
+        "bell" => RedrawEvent::Bell(),
+        "visual_bell" => RedrawEvent::VisualBell(),
         "flush" => RedrawEvent::Flush(),
         "popupmenu_show" => RedrawEvent::PopupmenuShow(
             args.into_iter().map(PopupmenuShow::from).collect(),
@@ -1206,6 +1446,9 @@ fn parse_single_redraw_event(cmd: &str, args: Vec<Value>) -> RedrawEvent {
                 })
                 .collect(),
         ),
+        "win_viewport" => RedrawEvent::WindowViewport(
+            args.into_iter().map(WindowViewport::from).collect(),
+        ),
         "msg_set_pos" => RedrawEvent::MsgSetPos(
             args.into_iter().map(MsgSetPos::from).collect(),
         ),
@@ -1248,6 +1491,10 @@ pub(crate) fn parse_gnvim_event(
             GnvimEvent::CursorTooltipShow(content.to_string(), row, col)
         }
         "CursorTooltipHide" => GnvimEvent::CursorTooltipHide,
+        "CursorTooltipScroll" => GnvimEvent::CursorTooltipScroll(try_i64!(
+            args.get(1).ok_or("lines missing")?,
+            "tooltip scroll lines"
+        )),
         "CursorTooltipSetStyle" => {
             let style = try_str!(
                 args.get(1).ok_or("path missing")?,
@@ -1265,6 +1512,13 @@ pub(crate) fn parse_gnvim_event(
                 try_u64!(args.get(1).ok_or("width missing")?, "pmenu width");
             GnvimEvent::PopupmenuWidthDetails(w)
         }
+        "PopupmenuSetMaxHeight" => {
+            let h = try_u64!(
+                args.get(1).ok_or("height missing")?,
+                "pmenu max height"
+            );
+            GnvimEvent::PopupmenuMaxHeight(h)
+        }
         "PopupmenuShowMenuOnAllItems" => {
             let b = try_u64!(
                 args.get(1).ok_or("bool missing")?,
@@ -1273,12 +1527,345 @@ pub(crate) fn parse_gnvim_event(
 
             GnvimEvent::PopupmenuShowMenuOnAllItems(b != 0)
         }
+        "PopupmenuHighlightMatches" => {
+            let b = try_u64!(
+                args.get(1).ok_or("bool missing")?,
+                "pmenu highlight matches"
+            );
+
+            GnvimEvent::PopupmenuHighlightMatches(b != 0)
+        }
+        "WildmenuHorizontal" => {
+            let b = try_u64!(
+                args.get(1).ok_or("bool missing")?,
+                "wildmenu horizontal"
+            );
+
+            GnvimEvent::WildmenuHorizontal(b != 0)
+        }
         "EnableCursorAnimations" => GnvimEvent::EnableCursorAnimations(
             try_u64!(
                 args.get(1).ok_or("argument missing")?,
                 "failed to parse enable cursor animations argument"
             ) == 1,
         ),
+        "CursorAnimationDuration" => {
+            GnvimEvent::CursorAnimationDuration(try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse cursor animation duration argument"
+            ))
+        }
+        "CursorColor" => {
+            let color =
+                try_str!(args.get(1).ok_or("color missing")?, "cursor color");
+            let color = if color.is_empty() {
+                None
+            } else {
+                Some(Color::from_hex_string(color.to_string())?)
+            };
+            GnvimEvent::CursorColor(color)
+        }
+        "YankFlash" => GnvimEvent::YankFlash(parse_yank_ranges(
+            args.get(1).ok_or("ranges missing")?,
+        )?),
+        "YankFlashColor" => {
+            let color = try_str!(
+                args.get(1).ok_or("color missing")?,
+                "yank flash color"
+            );
+            let color = if color.is_empty() {
+                None
+            } else {
+                Some(Color::from_hex_string(color.to_string())?)
+            };
+            GnvimEvent::YankFlashColor(color)
+        }
+        "YankFlashDuration" => GnvimEvent::YankFlashDuration(try_u64!(
+            args.get(1).ok_or("argument missing")?,
+            "failed to parse yank flash duration argument"
+        )),
+        "ModeCursorColor" => {
+            let mode = try_str!(args.get(1).ok_or("mode missing")?, "mode");
+            let color = try_str!(
+                args.get(2).ok_or("color missing")?,
+                "mode cursor color"
+            );
+            let color = if color.is_empty() {
+                None
+            } else {
+                Some(Color::from_hex_string(color.to_string())?)
+            };
+            GnvimEvent::ModeCursorColor(mode.to_string(), color)
+        }
+        "EnableScrollAnimation" => GnvimEvent::EnableScrollAnimation(
+            try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse enable scroll animation argument"
+            ) == 1,
+        ),
+        "ScrollAnimationDuration" => {
+            GnvimEvent::ScrollAnimationDuration(try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse scroll animation duration argument"
+            ))
+        }
+        "EnableLigatures" => GnvimEvent::EnableLigatures(
+            try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse enable ligatures argument"
+            ) == 1,
+        ),
+        "EnableSyntheticStyles" => GnvimEvent::EnableSyntheticStyles(
+            try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse enable synthetic styles argument"
+            ) == 1,
+        ),
+        "ClipboardSet" => {
+            let register = try_str!(
+                args.get(1).ok_or("register missing")?,
+                "clipboard register"
+            );
+            let lines = args
+                .get(2)
+                .ok_or("lines missing")?
+                .as_array()
+                .ok_or("lines is not an array")?;
+            let lines = lines
+                .iter()
+                .map(|line| Ok(try_str!(line, "clipboard line").to_string()))
+                .collect::<Result<Vec<_>, String>>()?;
+            let regtype = try_str!(
+                args.get(3).ok_or("register type missing")?,
+                "clipboard register type"
+            );
+            GnvimEvent::ClipboardSet(
+                register.to_string(),
+                lines,
+                regtype.to_string(),
+            )
+        }
+        "MouseScrollLines" => GnvimEvent::MouseScrollLines(try_u64!(
+            args.get(1).ok_or("argument missing")?,
+            "failed to parse mouse scroll lines argument"
+        )),
+        "MouseScrollShiftMultiplier" => {
+            GnvimEvent::MouseScrollShiftMultiplier(try_f64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse mouse scroll shift multiplier argument"
+            ))
+        }
+        "EnableContextMenu" => GnvimEvent::EnableContextMenu(
+            try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse enable context menu argument"
+            ) == 1,
+        ),
+        "KeyPassthrough" => {
+            let keys = args
+                .get(1)
+                .ok_or("keys missing")?
+                .as_array()
+                .ok_or("keys is not an array")?;
+            let keys = keys
+                .iter()
+                .map(|key| Ok(try_str!(key, "key").to_string()))
+                .collect::<Result<Vec<_>, String>>()?;
+            GnvimEvent::KeyPassthrough(keys)
+        }
+        "AltIsMeta" => GnvimEvent::AltIsMeta(
+            try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse alt is meta argument"
+            ) == 1,
+        ),
+        "EnableFloatBorder" => GnvimEvent::EnableFloatBorder(
+            try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse enable float border argument"
+            ) == 1,
+        ),
+        "EnableFloatAnimation" => GnvimEvent::EnableFloatAnimation(
+            try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse float animation argument"
+            ) == 1,
+        ),
+        "EnableExternalFocus" => GnvimEvent::EnableExternalFocus(
+            try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse external focus argument"
+            ) == 1,
+        ),
+        "EnableExternalResize" => GnvimEvent::EnableExternalResize(
+            try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse external resize argument"
+            ) == 1,
+        ),
+        "AutoBackground" => GnvimEvent::AutoBackground(
+            try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse auto background argument"
+            ) == 1,
+        ),
+        "EnableHover" => GnvimEvent::EnableHover(
+            try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse enable hover argument"
+            ) == 1,
+        ),
+        "EnableFullscreenKey" => GnvimEvent::EnableFullscreenKey(
+            try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse enable fullscreen key argument"
+            ) == 1,
+        ),
+        "BackgroundBlur" => GnvimEvent::BackgroundBlur(
+            try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse background blur argument"
+            ) == 1,
+        ),
+        "EnableFindKey" => GnvimEvent::EnableFindKey(
+            try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse enable find key argument"
+            ) == 1,
+        ),
+        "EnableBusyPointer" => GnvimEvent::EnableBusyPointer(
+            try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse enable busy pointer argument"
+            ) == 1,
+        ),
+        "EnableVisualBell" => GnvimEvent::EnableVisualBell(
+            try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse enable visual bell argument"
+            ) == 1,
+        ),
+        "BoldIsBright" => GnvimEvent::BoldIsBright(
+            try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse bold is bright argument"
+            ) == 1,
+        ),
+        "FontFeatures" => {
+            let features = try_str!(
+                args.get(1).ok_or("features missing")?,
+                "font features"
+            );
+            GnvimEvent::FontFeatures(features.to_string())
+        }
+        "ModeColor" => {
+            let mode = try_str!(args.get(1).ok_or("mode missing")?, "mode");
+            // The mode's name goes into a CSS selector.
+            if mode.is_empty()
+                || !mode.chars().all(|c| c.is_ascii_lowercase() || c == '_')
+            {
+                return Err(format!("invalid mode name: {}", mode));
+            }
+            let color =
+                try_str!(args.get(2).ok_or("color missing")?, "mode color");
+            let color = if color.is_empty() {
+                None
+            } else {
+                Some(Color::from_hex_string(color.to_string())?)
+            };
+            GnvimEvent::ModeColor(mode.to_string(), color)
+        }
+        "UserCss" => {
+            let css = try_str!(args.get(1).ok_or("css missing")?, "user css");
+            GnvimEvent::UserCss(css.to_string())
+        }
+        "EnableKineticScrolling" => GnvimEvent::EnableKineticScrolling(
+            try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse enable kinetic scrolling argument"
+            ) == 1,
+        ),
+        "ScrollOff" => GnvimEvent::ScrollOff(try_u64!(
+            args.get(1).ok_or("argument missing")?,
+            "failed to parse scrolloff argument"
+        )),
+        "Mouse" => GnvimEvent::Mouse(
+            try_str!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse mouse argument"
+            )
+            .to_string(),
+        ),
+        "ScrollbarClickToJump" => GnvimEvent::ScrollbarClickToJump(
+            try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse scrollbar click to jump argument"
+            ) == 1,
+        ),
+        "ScrollbarAutohide" => GnvimEvent::ScrollbarAutohide(
+            try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse scrollbar autohide argument"
+            ) == 1,
+        ),
+        "ScrollbarWidth" => GnvimEvent::ScrollbarWidth(try_u64!(
+            args.get(1).ok_or("argument missing")?,
+            "failed to parse scrollbar width argument"
+        )),
+        "ScrollbarOpacity" => GnvimEvent::ScrollbarOpacity(try_f64!(
+            args.get(1).ok_or("argument missing")?,
+            "failed to parse scrollbar opacity argument"
+        )),
+        "ScrollbarPosition" => GnvimEvent::ScrollbarPosition(
+            ScrollbarPosition::from_string(try_str!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse scrollbar position argument"
+            ))?,
+        ),
+        "BackgroundOpacity" => GnvimEvent::BackgroundOpacity(try_f64!(
+            args.get(1).ok_or("argument missing")?,
+            "failed to parse background opacity argument"
+        )),
+        "InactiveDim" => GnvimEvent::InactiveDim(try_f64!(
+            args.get(1).ok_or("argument missing")?,
+            "failed to parse inactive dim argument"
+        )),
+        "MaxFps" => GnvimEvent::MaxFps(try_u64!(
+            args.get(1).ok_or("argument missing")?,
+            "failed to parse max fps argument"
+        )),
+        "Padding" => {
+            let padding = |i: usize| -> Result<u64, String> {
+                Ok(try_u64!(
+                    args.get(i).ok_or("argument missing")?,
+                    "failed to parse padding argument"
+                ))
+            };
+            GnvimEvent::Padding(
+                padding(1)?,
+                padding(2)?,
+                padding(3)?,
+                padding(4)?,
+            )
+        }
+        "Size" => {
+            let size = |i: usize| -> Result<u64, String> {
+                Ok(try_u64!(
+                    args.get(i).ok_or("argument missing")?,
+                    "failed to parse size argument"
+                ))
+            };
+            GnvimEvent::Size(size(1)?, size(2)?)
+        }
+        "CursorOverlay" => {
+            let enable = |i: usize| -> Result<bool, String> {
+                Ok(try_u64!(
+                    args.get(i).ok_or("argument missing")?,
+                    "failed to parse cursor overlay argument"
+                ) == 1)
+            };
+            GnvimEvent::CursorOverlay(enable(1)?, enable(2)?)
+        }
         "EnableExtTabline" => GnvimEvent::EnableExtTabline(
             try_u64!(
                 args.get(1).ok_or("argument missing")?,
@@ -1297,12 +1884,37 @@ pub(crate) fn parse_gnvim_event(
                 "failed to parse enable ext popupmenu argument"
             ) == 1,
         ),
+        "BufEnter" => GnvimEvent::BufEnter,
+        "UIEnter" => GnvimEvent::UIEnter,
+        "ToggleFullscreen" => GnvimEvent::ToggleFullscreen,
+        "OpenFind" => GnvimEvent::OpenFind,
         _ => GnvimEvent::Unknown(String::from(cmd)),
     };
 
     Ok(res)
 }
 
+/// Parses the `[row, start, end]` ranges of `YankFlash`.
+fn parse_yank_ranges(val: &Value) -> Result<Vec<(u64, u64, u64)>, String> {
+    let ranges = val.as_array().ok_or("ranges is not an array")?;
+
+    let mut res = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        let range = range.as_array().ok_or("range is not an array")?;
+        if range.len() != 3 {
+            return Err(format!("invalid range: {:?}", range));
+        }
+
+        res.push((
+            try_u64!(range[0], "range row"),
+            try_u64!(range[1], "range start"),
+            try_u64!(range[2], "range end"),
+        ));
+    }
+
+    Ok(res)
+}
+
 fn map_to_hash<'a>(val: &'a Value) -> HashMap<&'a str, &'a Value> {
     let mut h = HashMap::new();
     for (prop, val) in unwrap_map!(val) {
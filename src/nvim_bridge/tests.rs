@@ -16,11 +16,11 @@ mod parse_redraw_event_tests {
 
     use crate::nvim_bridge;
     use crate::nvim_bridge::{
-        Cell, CmdlineBlockAppend, CmdlinePos, CmdlineShow, CmdlineSpecialChar,
-        CompletionItem, CompletionItemKind, CursorShape, DefaultColorsSet,
-        GridCursorGoto, GridLineSegment, GridResize, GridScroll, HlAttrDefine,
-        ModeChange, ModeInfo, ModeInfoSet, OptionSet, PopupmenuShow,
-        RedrawEvent, TablineUpdate,
+        Cell, CmdlineBlockAppend, CmdlineBlockShow, CmdlinePos, CmdlineShow,
+        CmdlineSpecialChar, CompletionItem, CompletionItemKind, CursorShape,
+        DefaultColorsSet, GridCursorGoto, GridLineSegment, GridResize,
+        GridScroll, HlAttrDefine, ModeChange, ModeInfo, ModeInfoSet, OptionSet,
+        PopupmenuShow, RedrawEvent, TablineUpdate, WindowViewport,
     };
     use crate::ui::color::{Color, Highlight};
     use rmpv::Value;
@@ -38,6 +38,18 @@ mod parse_redraw_event_tests {
         assert_eq!(expected, res);
     }
 
+    #[test]
+    fn set_icon() {
+        let expected = vec![RedrawEvent::SetIcon(vec!["my icon".to_string()])];
+
+        let res = nvim_bridge::parse_redraw_event(args!(
+            String::from("set_icon").into(),
+            Value::Array(vec!(String::from("my icon").into(),))
+        ));
+
+        assert_eq!(expected, res);
+    }
+
     #[test]
     fn grid_line() {
         let expected = vec![RedrawEvent::GridLine(vec![
@@ -243,7 +255,12 @@ mod parse_redraw_event_tests {
                     bold: true,
                     underline: true,
                     undercurl: false,
+                    underdouble: false,
+                    underdotted: false,
+                    underdashed: false,
+                    strikethrough: false,
                     blend: 0.0,
+                    cterm_fg: None,
                 },
             },
             HlAttrDefine {
@@ -257,7 +274,12 @@ mod parse_redraw_event_tests {
                     bold: true,
                     underline: false,
                     undercurl: true,
+                    underdouble: false,
+                    underdotted: false,
+                    underdashed: false,
+                    strikethrough: false,
                     blend: 0.0,
+                    cterm_fg: None,
                 },
             },
             HlAttrDefine {
@@ -271,7 +293,12 @@ mod parse_redraw_event_tests {
                     bold: true,
                     underline: false,
                     undercurl: true,
+                    underdouble: false,
+                    underdotted: false,
+                    underdashed: false,
+                    strikethrough: false,
                     blend: 0.3,
+                    cterm_fg: None,
                 },
             },
             HlAttrDefine {
@@ -285,7 +312,31 @@ mod parse_redraw_event_tests {
                     bold: false,
                     underline: false,
                     undercurl: false,
+                    underdouble: false,
+                    underdotted: false,
+                    underdashed: false,
+                    strikethrough: false,
                     blend: 0.0,
+                    cterm_fg: None,
+                },
+            },
+            HlAttrDefine {
+                id: 4,
+                hl: Highlight {
+                    underline: true,
+                    underdouble: true,
+                    underdotted: true,
+                    underdashed: true,
+                    strikethrough: true,
+                    ..Highlight::default()
+                },
+            },
+            HlAttrDefine {
+                id: 5,
+                hl: Highlight {
+                    bold: true,
+                    cterm_fg: Some(3),
+                    ..Highlight::default()
                 },
             },
         ])];
@@ -327,7 +378,27 @@ mod parse_redraw_event_tests {
                     ("blend".into(), 30.into()),
                 )),
             )),
-            Value::Array(vec!(3.into(), Value::Map(vec!()),))
+            Value::Array(vec!(3.into(), Value::Map(vec!()),)),
+            Value::Array(vec!(
+                4.into(),
+                Value::Map(vec!(
+                    ("underdouble".into(), true.into()),
+                    ("underdotted".into(), true.into()),
+                    ("underdashed".into(), true.into()),
+                    ("strikethrough".into(), true.into()),
+                    // Unknown styles fall back to underline.
+                    ("underwavy".into(), true.into()),
+                )),
+            )),
+            Value::Array(vec!(
+                5.into(),
+                Value::Map(vec!(("bold".into(), true.into()),)),
+                Value::Map(vec!(
+                    ("bold".into(), true.into()),
+                    ("foreground".into(), 3.into()),
+                )),
+                Value::Array(vec!()),
+            ))
         ));
 
         assert_eq!(expected, res);
@@ -337,12 +408,14 @@ mod parse_redraw_event_tests {
     fn option_set() {
         let expected = vec![RedrawEvent::OptionSet(vec![
             OptionSet::GuiFont("my awesome font:h32".into()),
+            OptionSet::GuiFontWide("my wide font:h32".into()),
             OptionSet::LineSpace(32),
         ])];
 
         let res = nvim_bridge::parse_redraw_event(args!(
             "option_set".into(),
             Value::Array(vec!("guifont".into(), "my awesome font:h32".into(),)),
+            Value::Array(vec!("guifontwide".into(), "my wide font:h32".into())),
             Value::Array(vec!("linespace".into(), 32.into()))
         ));
 
@@ -356,13 +429,19 @@ mod parse_redraw_event_tests {
             mode_info: vec![
                 ModeInfo {
                     blink_on: 32,
+                    blink_off: 64,
+                    blink_wait: 128,
                     cursor_shape: CursorShape::Horizontal,
                     cell_percentage: 0.32,
+                    attr_id: 5,
                 },
                 ModeInfo {
                     blink_on: 1,
+                    blink_off: 0,
+                    blink_wait: 0,
                     cursor_shape: CursorShape::Block,
                     cell_percentage: 1.0,
+                    attr_id: 0,
                 },
             ],
         }])];
@@ -374,8 +453,11 @@ mod parse_redraw_event_tests {
                 Value::Array(vec!(
                     Value::Map(vec!(
                         ("blinkon".into(), 32.into()),
+                        ("blinkoff".into(), 64.into()),
+                        ("blinkwait".into(), 128.into()),
                         ("cursor_shape".into(), "horizontal".into()),
                         ("cell_percentage".into(), 32.into()),
+                        ("attr_id".into(), 5.into()),
                     )),
                     Value::Map(vec!(
                         ("blinkon".into(), 1.into()),
@@ -422,6 +504,24 @@ mod parse_redraw_event_tests {
         assert_eq!(expected, res);
     }
 
+    #[test]
+    fn bell() {
+        let expected = vec![RedrawEvent::Bell()];
+
+        let res = nvim_bridge::parse_redraw_event(args!("bell".into()));
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn visual_bell() {
+        let expected = vec![RedrawEvent::VisualBell()];
+
+        let res = nvim_bridge::parse_redraw_event(args!("visual_bell".into()));
+
+        assert_eq!(expected, res);
+    }
+
     #[test]
     fn flush() {
         let expected = vec![RedrawEvent::Flush()];
@@ -431,6 +531,27 @@ mod parse_redraw_event_tests {
         assert_eq!(expected, res);
     }
 
+    #[test]
+    fn completion_item_kind() {
+        let data = vec![
+            (CompletionItemKind::Class, "class"),
+            (CompletionItemKind::Class, "C"),
+            (CompletionItemKind::Class, "Class"),
+            (CompletionItemKind::EnumMember, "enum member"),
+            (CompletionItemKind::EnumMember, "EnumMember"),
+            (CompletionItemKind::Keyword, "Key"),
+            (CompletionItemKind::Keyword, "Keyword"),
+            (CompletionItemKind::Snippet, "Snippet"),
+            (CompletionItemKind::TypeParameter, "TypeParameter"),
+            (CompletionItemKind::Unknown, ""),
+            (CompletionItemKind::Unknown, "foo"),
+        ];
+
+        for (expected, kind) in data {
+            assert_eq!(expected, CompletionItemKind::from(kind));
+        }
+    }
+
     #[test]
     fn popupmenu_show() {
         let expected = vec![RedrawEvent::PopupmenuShow(vec![PopupmenuShow {
@@ -605,6 +726,32 @@ mod parse_redraw_event_tests {
         assert_eq!(expected, res);
     }
 
+    #[test]
+    fn cmdline_block_show() {
+        let expected =
+            vec![RedrawEvent::CmdlineBlockShow(vec![CmdlineBlockShow {
+                lines: vec![
+                    vec![(2, "foo".to_string()), (1, "bar".to_string())],
+                    vec![(3, "baz".to_string())],
+                ],
+            }])];
+
+        let res = nvim_bridge::parse_redraw_event(args!(
+            "cmdline_block_show".into(),
+            Value::Array(vec!(Value::Array(vec!(
+                Value::Array(vec!(
+                    Value::Array(vec!(2.into(), "foo".into(),)),
+                    Value::Array(vec!(1.into(), "bar".into(),)),
+                )),
+                Value::Array(
+                    vec!(Value::Array(vec!(3.into(), "baz".into(),)),)
+                ),
+            )),))
+        ));
+
+        assert_eq!(expected, res);
+    }
+
     #[test]
     fn cmdline_block_append() {
         let expected =
@@ -633,6 +780,53 @@ mod parse_redraw_event_tests {
         assert_eq!(expected, res);
     }
 
+    #[test]
+    fn win_viewport() {
+        let expected = vec![RedrawEvent::WindowViewport(vec![
+            WindowViewport {
+                grid: 2,
+                win: Value::from(1000),
+                topline: 10,
+                botline: 50,
+                curline: 12,
+                curcol: 4,
+                line_count: Some(200),
+            },
+            WindowViewport {
+                grid: 3,
+                win: Value::from(1001),
+                topline: 0,
+                botline: 20,
+                curline: 1,
+                curcol: 0,
+                line_count: None,
+            },
+        ])];
+
+        let res = nvim_bridge::parse_redraw_event(args!(
+            "win_viewport".into(),
+            Value::Array(vec!(
+                2.into(),
+                1000.into(),
+                10.into(),
+                50.into(),
+                12.into(),
+                4.into(),
+                200.into(),
+            )),
+            Value::Array(vec!(
+                3.into(),
+                1001.into(),
+                0.into(),
+                20.into(),
+                1.into(),
+                0.into(),
+            ))
+        ));
+
+        assert_eq!(expected, res);
+    }
+
     #[test]
     fn mouse_on() {
         let expected = vec![RedrawEvent::Ignored("mouse_on".to_owned())];
@@ -655,7 +849,7 @@ mod parse_redraw_event_tests {
 mod parse_gnvim_event_tests {
 
     use crate::nvim_bridge;
-    use crate::nvim_bridge::GnvimEvent;
+    use crate::nvim_bridge::{GnvimEvent, Request, ScrollbarPosition};
     use rmpv::Value;
 
     #[test]
@@ -709,6 +903,30 @@ mod parse_gnvim_event_tests {
         assert_eq!(expected, res);
     }
 
+    #[test]
+    fn cursor_tooltip_scroll() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::CursorTooltipScroll(3)),
+                vec!["CursorTooltipScroll".into(), 3.into()],
+            ),
+            (
+                Ok(GnvimEvent::CursorTooltipScroll(-3)),
+                vec!["CursorTooltipScroll".into(), (-3).into()],
+            ),
+            (
+                Err(String::from("Value is not an i64: tooltip scroll lines")),
+                vec!["CursorTooltipScroll".into(), "foo".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
     #[test]
     fn popupmenu_set_width() {
         let expected: Result<GnvimEvent, String> =
@@ -735,6 +953,19 @@ mod parse_gnvim_event_tests {
         assert_eq!(expected, res);
     }
 
+    #[test]
+    fn popupmenu_set_max_height() {
+        let expected: Result<GnvimEvent, String> =
+            Ok(GnvimEvent::PopupmenuMaxHeight(15));
+
+        let res = nvim_bridge::parse_gnvim_event(vec![
+            "PopupmenuSetMaxHeight".into(),
+            15.into(),
+        ]);
+
+        assert_eq!(expected, res);
+    }
+
     #[test]
     fn popupmenu_menu_on_all_items() {
         let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
@@ -754,4 +985,964 @@ mod parse_gnvim_event_tests {
             assert_eq!(expected, res);
         }
     }
+
+    #[test]
+    fn popupmenu_highlight_matches() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::PopupmenuHighlightMatches(true)),
+                vec!["PopupmenuHighlightMatches".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::PopupmenuHighlightMatches(false)),
+                vec!["PopupmenuHighlightMatches".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn wildmenu_horizontal() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::WildmenuHorizontal(true)),
+                vec!["WildmenuHorizontal".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::WildmenuHorizontal(false)),
+                vec!["WildmenuHorizontal".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn scrolloff() {
+        let expected: Result<GnvimEvent, String> = Ok(GnvimEvent::ScrollOff(5));
+
+        let res =
+            nvim_bridge::parse_gnvim_event(vec!["ScrollOff".into(), 5.into()]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn mouse() {
+        let expected: Result<GnvimEvent, String> =
+            Ok(GnvimEvent::Mouse("a".into()));
+
+        let res =
+            nvim_bridge::parse_gnvim_event(vec!["Mouse".into(), "a".into()]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn buf_enter() {
+        let expected: Result<GnvimEvent, String> = Ok(GnvimEvent::BufEnter);
+
+        let res = nvim_bridge::parse_gnvim_event(vec!["BufEnter".into()]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn ui_enter() {
+        let expected: Result<GnvimEvent, String> = Ok(GnvimEvent::UIEnter);
+
+        let res = nvim_bridge::parse_gnvim_event(vec!["UIEnter".into()]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn gnvim_event_open_find() {
+        let expected: Result<GnvimEvent, String> = Ok(GnvimEvent::OpenFind);
+
+        let res = nvim_bridge::parse_gnvim_event(vec!["OpenFind".into()]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn gnvim_event_toggle_fullscreen() {
+        let expected: Result<GnvimEvent, String> =
+            Ok(GnvimEvent::ToggleFullscreen);
+
+        let res =
+            nvim_bridge::parse_gnvim_event(vec!["ToggleFullscreen".into()]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn scrollbar_click_to_jump() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::ScrollbarClickToJump(true)),
+                vec!["ScrollbarClickToJump".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::ScrollbarClickToJump(false)),
+                vec!["ScrollbarClickToJump".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn scrollbar_autohide() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::ScrollbarAutohide(true)),
+                vec!["ScrollbarAutohide".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::ScrollbarAutohide(false)),
+                vec!["ScrollbarAutohide".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn scrollbar_width() {
+        let expected: Result<GnvimEvent, String> =
+            Ok(GnvimEvent::ScrollbarWidth(12));
+
+        let res = nvim_bridge::parse_gnvim_event(vec![
+            "ScrollbarWidth".into(),
+            12.into(),
+        ]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn background_opacity() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::BackgroundOpacity(0.8)),
+                vec!["BackgroundOpacity".into(), 0.8.into()],
+            ),
+            (
+                Err(String::from(
+                    "Value is not an f64: \
+                     failed to parse background opacity argument",
+                )),
+                vec!["BackgroundOpacity".into(), "foo".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn inactive_dim() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::InactiveDim(0.3)),
+                vec!["InactiveDim".into(), 0.3.into()],
+            ),
+            (
+                Err(String::from(
+                    "Value is not an f64: failed to parse inactive dim argument",
+                )),
+                vec!["InactiveDim".into(), "foo".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn padding() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::Padding(1, 2, 3, 4)),
+                vec!["Padding".into(), 1.into(), 2.into(), 3.into(), 4.into()],
+            ),
+            (
+                Err(String::from("argument missing")),
+                vec!["Padding".into(), 1.into(), 2.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn size() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::Size(100, 40)),
+                vec!["Size".into(), 100.into(), 40.into()],
+            ),
+            (
+                Err(String::from("argument missing")),
+                vec!["Size".into(), 100.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn cursor_overlay() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::CursorOverlay(true, false)),
+                vec!["CursorOverlay".into(), 1.into(), 0.into()],
+            ),
+            (
+                Err(String::from("argument missing")),
+                vec!["CursorOverlay".into(), 1.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn max_fps() {
+        let expected: Result<GnvimEvent, String> = Ok(GnvimEvent::MaxFps(30));
+
+        let res =
+            nvim_bridge::parse_gnvim_event(vec!["MaxFps".into(), 30.into()]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn scrollbar_opacity() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::ScrollbarOpacity(0.5)),
+                vec!["ScrollbarOpacity".into(), 0.5.into()],
+            ),
+            (
+                Ok(GnvimEvent::ScrollbarOpacity(1.0)),
+                vec!["ScrollbarOpacity".into(), 1.0.into()],
+            ),
+            (
+                Err(String::from(
+                    "Value is not an f64: \
+                     failed to parse scrollbar opacity argument",
+                )),
+                vec!["ScrollbarOpacity".into(), "foo".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn scrollbar_position() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::ScrollbarPosition(ScrollbarPosition::Left)),
+                vec!["ScrollbarPosition".into(), "left".into()],
+            ),
+            (
+                Ok(GnvimEvent::ScrollbarPosition(ScrollbarPosition::Right)),
+                vec!["ScrollbarPosition".into(), "right".into()],
+            ),
+            (
+                Ok(GnvimEvent::ScrollbarPosition(ScrollbarPosition::None)),
+                vec!["ScrollbarPosition".into(), "none".into()],
+            ),
+            (
+                Err(String::from("Invalid scrollbar position: top")),
+                vec!["ScrollbarPosition".into(), "top".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn enable_kinetic_scrolling() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::EnableKineticScrolling(true)),
+                vec!["EnableKineticScrolling".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::EnableKineticScrolling(false)),
+                vec!["EnableKineticScrolling".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn enable_ligatures() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::EnableLigatures(true)),
+                vec!["EnableLigatures".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::EnableLigatures(false)),
+                vec!["EnableLigatures".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn enable_synthetic_styles() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::EnableSyntheticStyles(true)),
+                vec!["EnableSyntheticStyles".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::EnableSyntheticStyles(false)),
+                vec!["EnableSyntheticStyles".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn mouse_scroll_lines() {
+        let expected: Result<GnvimEvent, String> =
+            Ok(GnvimEvent::MouseScrollLines(5));
+
+        let res = nvim_bridge::parse_gnvim_event(vec![
+            "MouseScrollLines".into(),
+            5.into(),
+        ]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn mouse_scroll_shift_multiplier() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::MouseScrollShiftMultiplier(2.5)),
+                vec!["MouseScrollShiftMultiplier".into(), 2.5.into()],
+            ),
+            (
+                Err(String::from(
+                    "Value is not an f64: \
+                     failed to parse mouse scroll shift multiplier argument",
+                )),
+                vec!["MouseScrollShiftMultiplier".into(), "foo".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn enable_context_menu() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::EnableContextMenu(true)),
+                vec!["EnableContextMenu".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::EnableContextMenu(false)),
+                vec!["EnableContextMenu".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn key_passthrough() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::KeyPassthrough(vec![
+                    String::from("<D-*>"),
+                    String::from("<C-A-Left>"),
+                ])),
+                vec![
+                    "KeyPassthrough".into(),
+                    Value::Array(vec!["<D-*>".into(), "<C-A-Left>".into()]),
+                ],
+            ),
+            (
+                Err(String::from("Value is not an str: key")),
+                vec!["KeyPassthrough".into(), Value::Array(vec![1.into()])],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn alt_is_meta() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::AltIsMeta(true)),
+                vec!["AltIsMeta".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::AltIsMeta(false)),
+                vec!["AltIsMeta".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn enable_float_border() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::EnableFloatBorder(true)),
+                vec!["EnableFloatBorder".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::EnableFloatBorder(false)),
+                vec!["EnableFloatBorder".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn enable_float_animation() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::EnableFloatAnimation(true)),
+                vec!["EnableFloatAnimation".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::EnableFloatAnimation(false)),
+                vec!["EnableFloatAnimation".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn enable_external_focus() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::EnableExternalFocus(true)),
+                vec!["EnableExternalFocus".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::EnableExternalFocus(false)),
+                vec!["EnableExternalFocus".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn enable_external_resize() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::EnableExternalResize(true)),
+                vec!["EnableExternalResize".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::EnableExternalResize(false)),
+                vec!["EnableExternalResize".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn auto_background() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::AutoBackground(true)),
+                vec!["AutoBackground".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::AutoBackground(false)),
+                vec!["AutoBackground".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn enable_hover() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::EnableHover(true)),
+                vec!["EnableHover".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::EnableHover(false)),
+                vec!["EnableHover".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn enable_fullscreen_key() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::EnableFullscreenKey(true)),
+                vec!["EnableFullscreenKey".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::EnableFullscreenKey(false)),
+                vec!["EnableFullscreenKey".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn background_blur() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::BackgroundBlur(true)),
+                vec!["BackgroundBlur".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::BackgroundBlur(false)),
+                vec!["BackgroundBlur".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn enable_find_key() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::EnableFindKey(true)),
+                vec!["EnableFindKey".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::EnableFindKey(false)),
+                vec!["EnableFindKey".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn enable_busy_pointer() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::EnableBusyPointer(true)),
+                vec!["EnableBusyPointer".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::EnableBusyPointer(false)),
+                vec!["EnableBusyPointer".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn enable_visual_bell() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::EnableVisualBell(true)),
+                vec!["EnableVisualBell".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::EnableVisualBell(false)),
+                vec!["EnableVisualBell".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn bold_is_bright() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::BoldIsBright(true)),
+                vec!["BoldIsBright".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::BoldIsBright(false)),
+                vec!["BoldIsBright".into(), 0.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn user_css() {
+        let expected: Result<GnvimEvent, String> =
+            Ok(GnvimEvent::UserCss(String::from("frame { border: none; }")));
+
+        let res = nvim_bridge::parse_gnvim_event(vec![
+            "UserCss".into(),
+            "frame { border: none; }".into(),
+        ]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn mode_color() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::ModeColor(
+                    String::from("insert"),
+                    Some(Color::from_u64(0x00ff00)),
+                )),
+                vec!["ModeColor".into(), "insert".into(), "#00ff00".into()],
+            ),
+            (
+                Ok(GnvimEvent::ModeColor(String::from("insert"), None)),
+                vec!["ModeColor".into(), "insert".into(), "".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+
+        assert!(nvim_bridge::parse_gnvim_event(vec![
+            "ModeColor".into(),
+            "insert".into(),
+            "green".into(),
+        ])
+        .is_err());
+
+        assert_eq!(
+            Err(String::from("invalid mode name: foo {")),
+            nvim_bridge::parse_gnvim_event(vec![
+                "ModeColor".into(),
+                "foo {".into(),
+                "#00ff00".into(),
+            ])
+        );
+        assert_eq!(
+            Ok(GnvimEvent::ModeColor(String::from("cmdline_normal"), None)),
+            nvim_bridge::parse_gnvim_event(vec![
+                "ModeColor".into(),
+                "cmdline_normal".into(),
+                "".into(),
+            ])
+        );
+    }
+
+    #[test]
+    fn font_features() {
+        let expected: Result<GnvimEvent, String> = Ok(
+            GnvimEvent::FontFeatures(String::from("\"cv01\" 1, \"zero\" 1")),
+        );
+
+        let res = nvim_bridge::parse_gnvim_event(vec![
+            "FontFeatures".into(),
+            "\"cv01\" 1, \"zero\" 1".into(),
+        ]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn clipboard_set() {
+        let expected: Result<GnvimEvent, String> =
+            Ok(GnvimEvent::ClipboardSet(
+                String::from("+"),
+                vec![String::from("foo"), String::from("bar")],
+                String::from("V"),
+            ));
+
+        let res = nvim_bridge::parse_gnvim_event(vec![
+            "ClipboardSet".into(),
+            "+".into(),
+            Value::Array(vec!["foo".into(), "bar".into()]),
+            "V".into(),
+        ]);
+
+        assert_eq!(expected, res);
+
+        let res = nvim_bridge::parse_gnvim_event(vec![
+            "ClipboardSet".into(),
+            "+".into(),
+            "foo".into(),
+            "v".into(),
+        ]);
+
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn clipboard_get() {
+        let res =
+            nvim_bridge::parse_request(vec!["ClipboardGet".into(), "*".into()]);
+
+        assert_eq!(Ok(Request::ClipboardGet(String::from("*"))), res);
+
+        let res = nvim_bridge::parse_request(vec!["ClipboardGet".into()]);
+
+        assert_eq!(Err(()), res);
+    }
+
+    #[test]
+    fn cursor_animation_duration() {
+        let expected: Result<GnvimEvent, String> =
+            Ok(GnvimEvent::CursorAnimationDuration(150));
+
+        let res = nvim_bridge::parse_gnvim_event(vec![
+            "CursorAnimationDuration".into(),
+            150.into(),
+        ]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn cursor_color() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::CursorColor(Some(Color::from_u64(0xff0000)))),
+                vec!["CursorColor".into(), "#ff0000".into()],
+            ),
+            (
+                Ok(GnvimEvent::CursorColor(None)),
+                vec!["CursorColor".into(), "".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn yank_flash() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::YankFlash(vec![(1, 4, 10), (2, 0, 3)])),
+                vec![
+                    "YankFlash".into(),
+                    Value::Array(vec![
+                        Value::Array(vec![1.into(), 4.into(), 10.into()]),
+                        Value::Array(vec![2.into(), 0.into(), 3.into()]),
+                    ]),
+                ],
+            ),
+            (
+                Ok(GnvimEvent::YankFlash(vec![])),
+                vec!["YankFlash".into(), Value::Array(vec![])],
+            ),
+            (
+                Ok(GnvimEvent::YankFlashDuration(200)),
+                vec!["YankFlashDuration".into(), 200.into()],
+            ),
+            (
+                Ok(GnvimEvent::YankFlashColor(Some(Color::from_u64(0xffcc00)))),
+                vec!["YankFlashColor".into(), "#ffcc00".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+            assert_eq!(expected, res);
+        }
+
+        let res = nvim_bridge::parse_gnvim_event(vec![
+            "YankFlash".into(),
+            Value::Array(vec![Value::Array(vec![1.into(), 4.into()])]),
+        ]);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn mode_cursor_color() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::ModeCursorColor(
+                    String::from("insert"),
+                    Some(Color::from_u64(0x00ff00)),
+                )),
+                vec![
+                    "ModeCursorColor".into(),
+                    "insert".into(),
+                    "#00ff00".into(),
+                ],
+            ),
+            (
+                Ok(GnvimEvent::ModeCursorColor(String::from("insert"), None)),
+                vec!["ModeCursorColor".into(), "insert".into(), "".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn enable_scroll_animation() {
+        let expected: Result<GnvimEvent, String> =
+            Ok(GnvimEvent::EnableScrollAnimation(true));
+
+        let res = nvim_bridge::parse_gnvim_event(vec![
+            "EnableScrollAnimation".into(),
+            1.into(),
+        ]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn scroll_animation_duration() {
+        let expected: Result<GnvimEvent, String> =
+            Ok(GnvimEvent::ScrollAnimationDuration(200));
+
+        let res = nvim_bridge::parse_gnvim_event(vec![
+            "ScrollAnimationDuration".into(),
+            200.into(),
+        ]);
+
+        assert_eq!(expected, res);
+    }
 }
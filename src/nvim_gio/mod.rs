@@ -1,6 +1,7 @@
+use futures::future::Future;
 use gio::prelude::*;
 
-use log::error;
+use log::{debug, error};
 
 use nvim_rs::{create::Spawner, neovim::Neovim, Handler};
 
@@ -8,6 +9,10 @@ use crate::nvim_bridge;
 
 pub mod compat;
 
+/// Time (in ms) nvim has to exit after its rpc connection is closed, before
+/// it's killed.
+const EXIT_GRACE_PERIOD_MS: u32 = 1000;
+
 pub type GioWriter =
     Compat<gio::OutputStreamAsyncWrite<gio::PollableOutputStream>>;
 pub type GioNeovim = Neovim<GioWriter>;
@@ -18,12 +23,13 @@ pub enum Error {
     ToPollaple,
     ToAsync,
     GlibError(glib::Error),
+    Connect(glib::Error),
 }
 
 impl std::fmt::Display for Error {
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            Error::Pipe => write!(fmt, "Failed to open pipe to subprocess"),
+            Error::Pipe => write!(fmt, "Failed to open pipe to nvim"),
             Error::ToPollaple => {
                 write!(fmt, "Failed to turn pipe into pollable stream")
             }
@@ -33,6 +39,9 @@ impl std::fmt::Display for Error {
             Error::GlibError(e) => {
                 write!(fmt, "Failed to open nvim subprocess: {}", e)
             }
+            Error::Connect(e) => {
+                write!(fmt, "Failed to connect to nvim: {}", e)
+            }
         }
     }
 }
@@ -60,34 +69,151 @@ where
 
     let p = gio::Subprocess::newv(&args, flags).map_err(Error::from)?;
 
-    let input = p
-        .get_stdin_pipe()
+    let (neovim, io) =
+        new_neovim(handler, p.get_stdin_pipe(), p.get_stdout_pipe())?;
+
+    let c = glib::MainContext::default();
+
+    {
+        let p = p.clone();
+        c.spawn_local(async move {
+            if let Err(err) = io.await {
+                debug!("Nvim io loop ended: {}", err);
+            }
+
+            // Normally nvim exits right after closing its stdout, but if the
+            // connection broke for some other reason, nvim might be still
+            // running. Make sure it's gone, so we get to know about it below.
+            glib::timeout_add_local(EXIT_GRACE_PERIOD_MS, move || {
+                p.force_exit();
+                glib::Continue(false)
+            });
+        });
+    }
+
+    c.spawn_local(async move {
+        if let Err(err) = p.wait_async_future().await {
+            error!("Failed to wait for nvim to exit: {}", err);
+        }
+
+        let msg = match exit_error(&p) {
+            Some(err) => nvim_bridge::Message::Crash(err),
+            None => nvim_bridge::Message::Close,
+        };
+        if let Err(err) = tx.send(msg) {
+            error!("Failed to send close message to the gui: {}", err)
+        }
+    });
+
+    Ok(neovim)
+}
+
+/// Connects to an already running nvim (see `:h --listen`). `address` is
+/// either a path to a unix socket, or a tcp address in `host:port` form.
+///
+/// The connected nvim isn't ours, so it's left running when we go away.
+pub fn new_connection<H>(
+    handler: H,
+    address: &str,
+    tx: glib::Sender<nvim_bridge::Message>,
+) -> Result<GioNeovim, Error>
+where
+    H: Spawner + Handler<Writer = GioWriter>,
+{
+    let client = gio::SocketClient::new();
+    let conn = if is_tcp_address(address) {
+        client.connect_to_host(address, 0, None::<&gio::Cancellable>)
+    } else {
+        let addr = gio::UnixSocketAddress::new(std::path::Path::new(address));
+        client.connect(&addr, None::<&gio::Cancellable>)
+    }
+    .map_err(Error::Connect)?;
+
+    let (neovim, io) =
+        new_neovim(handler, conn.get_output_stream(), conn.get_input_stream())?;
+
+    let c = glib::MainContext::default();
+    c.spawn_local(async move {
+        if let Err(err) = io.await {
+            debug!("Nvim io loop ended: {}", err);
+        }
+
+        // Closing the connection detaches us from nvim.
+        if let Err(err) = conn.close(None::<&gio::Cancellable>) {
+            debug!("Failed to close the connection to nvim: {}", err);
+        }
+
+        if let Err(err) = tx.send(nvim_bridge::Message::Close) {
+            error!("Failed to send close message to the gui: {}", err)
+        }
+    });
+
+    Ok(neovim)
+}
+
+/// Creates a neovim instance that talks through `input` (i.e. nvim's stdin)
+/// and `output`. Returns the instance, and its io loop that needs to be
+/// spawned.
+fn new_neovim<H>(
+    handler: H,
+    input: Option<gio::OutputStream>,
+    output: Option<gio::InputStream>,
+) -> Result<
+    (
+        GioNeovim,
+        impl Future<Output = Result<(), Box<nvim_rs::error::LoopError>>>,
+    ),
+    Error,
+>
+where
+    H: Spawner + Handler<Writer = GioWriter>,
+{
+    let input = input
         .ok_or(Error::Pipe)?
         .dynamic_cast::<gio::PollableOutputStream>()
         .map_err(|_| Error::ToPollaple)?;
     let write =
         Compat::new(input.into_async_write().map_err(|_| Error::ToAsync)?);
 
-    let output = p
-        .get_stdout_pipe()
+    let output = output
         .ok_or(Error::Pipe)?
         .dynamic_cast::<gio::PollableInputStream>()
         .map_err(|_| Error::ToPollaple)?;
     let read =
         Compat::new(output.into_async_read().map_err(|_| Error::ToAsync)?);
 
-    let (neovim, io) = Neovim::<
-        Compat<gio::OutputStreamAsyncWrite<gio::PollableOutputStream>>,
-    >::new(read, write, handler);
+    Ok(Neovim::<GioWriter>::new(read, write, handler))
+}
 
-    let c = glib::MainContext::default();
+/// If `address` is a tcp address, as opposed to a path to a unix socket.
+/// Same as what nvim itself does for `--listen`.
+fn is_tcp_address(address: &str) -> bool {
+    address.contains(':') && !address.contains('/')
+}
 
-    c.spawn(async move {
-        let _ = io.await;
-        if let Err(err) = tx.send(nvim_bridge::Message::Close) {
-            error!("Failed to send close message to the gui: {}", err)
-        }
-    });
+/// Returns a description of why the (exited) nvim process `p` went away, if
+/// it didn't exit by itself. Non-zero exit statuses are fine, since those
+/// are also used intentionally (`:cquit`).
+fn exit_error(p: &gio::Subprocess) -> Option<String> {
+    if p.get_if_exited() {
+        None
+    } else if p.get_if_signaled() {
+        Some(format!("Nvim was killed by signal {}", p.get_term_sig()))
+    } else {
+        Some(String::from("Nvim exited unexpectedly"))
+    }
+}
 
-    Ok(neovim)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_tcp_address() {
+        assert!(is_tcp_address("localhost:6666"));
+        assert!(is_tcp_address("127.0.0.1:6666"));
+        assert!(!is_tcp_address("/tmp/nvim.sock"));
+        assert!(!is_tcp_address("/tmp/foo:bar/nvim.sock"));
+        assert!(!is_tcp_address("nvim.sock"));
+    }
 }
@@ -0,0 +1,37 @@
+//! Blurring the content behind our (translucent) window.
+//!
+//! There's no common way to ask for the blur. KWin reads it from the
+//! `_KDE_NET_WM_BLUR_BEHIND_REGION` property on X11 windows (including
+//! XWayland ones), which is what we set. Native Wayland compositors need
+//! their own protocols, which GTK doesn't expose, so on those (and on X11
+//! window managers that don't know the property) this does nothing.
+
+use glib::prelude::*;
+
+const BLUR_ATOM: &str = "_KDE_NET_WM_BLUR_BEHIND_REGION";
+
+/// Asks the window manager to blur (or not) the content behind `win`.
+pub fn set_blur(win: &gdk::Window, enable: bool) {
+    if !is_x11(win) {
+        return;
+    }
+
+    let atom = gdk::Atom::intern(BLUR_ATOM);
+    if enable {
+        // An empty region blurs the whole window.
+        gdk::property_change(
+            win,
+            &atom,
+            &gdk::Atom::intern("CARDINAL"),
+            32,
+            gdk::PropMode::Replace,
+            gdk::ChangeData::ULongs(&[]),
+        );
+    } else {
+        gdk::property_delete(win, &atom);
+    }
+}
+
+fn is_x11(win: &gdk::Window) -> bool {
+    win.get_display().get_type().name() == "GdkX11Display"
+}
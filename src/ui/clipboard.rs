@@ -0,0 +1,260 @@
+//! Clipboard integration. Nvim sends and receives the clipboard's contents
+//! as lines and a register type, like its clipboard providers do.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use gtk::prelude::*;
+
+use log::error;
+
+use crate::nvim_gio::GioNeovim;
+use crate::ui::common::{report_error, spawn_local};
+
+thread_local! {
+    /// The text and register type nvim last set for each selection. The
+    /// selections only hold text, so this is how a blockwise register type
+    /// survives a copy and paste.
+    static REGTYPES: RefCell<HashMap<String, (String, String)>> =
+        RefCell::new(HashMap::new());
+}
+
+/// Maximum size (in bytes) of the chunks that pasted text is sent to nvim
+/// in, so that large pastes don't block nvim for long.
+const PASTE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Returns the selection (e.g. clipboard) for nvim's register. Accepts both
+/// the register names (`+` and `*`) and OSC 52's selection parameters (`c`
+/// and `p`).
+fn selection_atom(register: &str) -> Option<gdk::Atom> {
+    match register {
+        "+" | "c" => Some(gdk::SELECTION_CLIPBOARD),
+        "*" | "p" | "s" => Some(gdk::SELECTION_PRIMARY),
+        _ => None,
+    }
+}
+
+fn get_atom(register: &str) -> Result<gdk::Atom, String> {
+    selection_atom(register)
+        .ok_or_else(|| format!("unknown clipboard register: {}", register))
+}
+
+fn get_clipboard(register: &str) -> Result<gtk::Clipboard, String> {
+    Ok(gtk::Clipboard::get(&get_atom(register)?))
+}
+
+/// Joins nvim's register `lines` into text. Linewise (`V`) registers end
+/// with a newline.
+fn lines_to_text(lines: &[String], regtype: &str) -> String {
+    let mut text = lines.join("\n");
+    if regtype.starts_with('V') {
+        text.push('\n');
+    }
+    text
+}
+
+/// Returns the register type for `text` that nvim didn't set: text that
+/// ends with a newline is linewise, other text charwise.
+fn text_regtype(text: &str) -> &'static str {
+    if text.ends_with('\n') {
+        "V"
+    } else {
+        "v"
+    }
+}
+
+/// Splits `text` into lines for nvim's register of type `regtype`.
+fn text_to_lines(text: &str, regtype: &str) -> Vec<String> {
+    let text = if regtype.starts_with('V') && text.ends_with('\n') {
+        &text[..text.len() - 1]
+    } else {
+        text
+    };
+
+    text.split('\n').map(String::from).collect()
+}
+
+/// Sets the contents of `register`'s selection to nvim's register `lines`
+/// of type `regtype`.
+pub fn set(
+    register: &str,
+    lines: &[String],
+    regtype: &str,
+) -> Result<(), String> {
+    let atom = get_atom(register)?;
+    let text = lines_to_text(lines, regtype);
+    gtk::Clipboard::get(&atom).set_text(&text);
+
+    REGTYPES.with(|regtypes| {
+        regtypes
+            .borrow_mut()
+            .insert(atom.name().to_string(), (text, regtype.to_string()))
+    });
+    Ok(())
+}
+
+/// Sets the contents of `register`'s selection to `text`.
+pub fn set_text(register: &str, text: &str) -> Result<(), String> {
+    get_clipboard(register)?.set_text(text);
+    Ok(())
+}
+
+/// Requests the contents of `register`'s selection. Calls `f` with the
+/// contents as nvim's register lines and type once they're available, or
+/// with an error right away if `register` doesn't have a selection.
+pub fn get<F>(register: &str, f: F)
+where
+    F: FnOnce(Result<(Vec<String>, String), String>) + 'static,
+{
+    let atom = match get_atom(register) {
+        Ok(atom) => atom,
+        Err(err) => return f(Err(err)),
+    };
+
+    gtk::Clipboard::get(&atom).request_text(move |_, text| {
+        let text = text.unwrap_or("");
+
+        // If the text is what nvim set, it knows the type better.
+        let regtype = REGTYPES.with(|regtypes| {
+            match regtypes.borrow().get(atom.name().as_str()) {
+                Some((set, regtype)) if set == text => regtype.clone(),
+                _ => text_regtype(text).to_string(),
+            }
+        });
+
+        f(Ok((text_to_lines(text, &regtype), regtype)));
+    });
+}
+
+/// Requests the contents of `register`'s selection. Calls `f` with the
+/// contents once they're available.
+pub fn get_text<F>(register: &str, f: F) -> Result<(), String>
+where
+    F: FnOnce(String) + 'static,
+{
+    get_clipboard(register)?.request_text(move |_, text| {
+        f(text.unwrap_or("").to_string());
+    });
+
+    Ok(())
+}
+
+/// Pastes the contents of `register`'s selection at nvim's cursor. Uses
+/// `nvim_paste` instead of `nvim_input`, so that the text isn't interpreted
+/// as keys (or commands, in normal mode).
+pub fn paste(nvim: GioNeovim, register: &str) {
+    let res = get_text(register, move |text| {
+        if text.is_empty() {
+            return;
+        }
+
+        let nvim = nvim.clone();
+        spawn_local(async move {
+            for (chunk, phase) in paste_chunks(&text, PASTE_CHUNK_SIZE) {
+                match nvim.paste(chunk, true, phase).await {
+                    Ok(true) => {}
+                    // The user cancelled the paste.
+                    Ok(false) => return,
+                    Err(err) => {
+                        report_error("Failed to paste", err);
+                        return;
+                    }
+                }
+            }
+        });
+    });
+
+    if let Err(err) = res {
+        error!("Failed to get the clipboard: {}", err);
+    }
+}
+
+/// Splits `text` into chunks of at most `size` bytes (but at least one
+/// character), each paired with its `nvim_paste` phase: -1 if there's just
+/// one chunk, otherwise 1 for the first, 3 for the last, and 2 for the rest.
+fn paste_chunks(text: &str, size: usize) -> Vec<(&str, i64)> {
+    let mut chunks = vec![];
+    let mut rest = text;
+    while !rest.is_empty() {
+        let mut end = size.min(rest.len());
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end == 0 {
+            end = rest.chars().next().map(char::len_utf8).unwrap_or(0);
+        }
+
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+
+    let last = chunks.len().saturating_sub(1);
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let phase = match i {
+                _ if last == 0 => -1,
+                0 => 1,
+                i if i == last => 3,
+                _ => 2,
+            };
+            (chunk, phase)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selection_atom() {
+        assert_eq!(Some(gdk::SELECTION_CLIPBOARD), selection_atom("+"));
+        assert_eq!(Some(gdk::SELECTION_CLIPBOARD), selection_atom("c"));
+        assert_eq!(Some(gdk::SELECTION_PRIMARY), selection_atom("*"));
+        assert_eq!(Some(gdk::SELECTION_PRIMARY), selection_atom("p"));
+        assert_eq!(None, selection_atom("a"));
+    }
+
+    #[test]
+    fn test_lines_to_text() {
+        let lines = vec![String::from("foo"), String::from("bar")];
+        assert_eq!("foo\nbar", lines_to_text(&lines, "v"));
+        assert_eq!("foo\nbar\n", lines_to_text(&lines, "V"));
+        assert_eq!("foo\nbar", lines_to_text(&lines, "\u{16}3"));
+        assert_eq!("", lines_to_text(&[], "v"));
+    }
+
+    #[test]
+    fn test_text_regtype() {
+        assert_eq!("v", text_regtype("foo\nbar"));
+        assert_eq!("V", text_regtype("foo\nbar\n"));
+        assert_eq!("v", text_regtype(""));
+    }
+
+    #[test]
+    fn test_text_to_lines() {
+        let lines = vec![String::from("foo"), String::from("bar")];
+        assert_eq!(lines, text_to_lines("foo\nbar", "v"));
+        assert_eq!(lines, text_to_lines("foo\nbar\n", "V"));
+        assert_eq!(vec![String::new()], text_to_lines("", "v"));
+
+        // Only linewise registers end with a newline.
+        let lines = vec![String::from("foo"), String::new()];
+        assert_eq!(lines, text_to_lines("foo\n", "\u{16}3"));
+    }
+
+    #[test]
+    fn test_paste_chunks() {
+        assert_eq!(vec![("foo", -1)], paste_chunks("foo", 3));
+        assert_eq!(vec![("fo", 1), ("o", 3)], paste_chunks("foo", 2));
+        assert_eq!(vec![("a", 1), ("b", 2), ("c", 3)], paste_chunks("abc", 1));
+        assert!(paste_chunks("", 2).is_empty());
+
+        // Multibyte characters aren't split.
+        assert_eq!(vec![("a", 1), ("ä", 2), ("b", 3)], paste_chunks("aäb", 2));
+        assert_eq!(vec![("ä", 1), ("ö", 3)], paste_chunks("äö", 1));
+    }
+}
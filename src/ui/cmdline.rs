@@ -87,6 +87,9 @@ impl CmdlineBlock {
     fn show(&mut self, show: &nvim_bridge::CmdlineBlockShow, hl_defs: &HlDefs) {
         self.frame.show();
         let buffer = self.textview.get_buffer().unwrap();
+        // The whole block might be sent again (e.g. on redraw), so start
+        // from scratch.
+        buffer.set_text("");
         let mut iter = buffer.get_iter_at_offset(0);
 
         for (i, line) in show.lines.iter().enumerate() {
@@ -306,13 +309,28 @@ impl CmdlineInput {
         self.set_cursor(content.pos as usize, content.level);
     }
 
-    fn show_special_char(&mut self, ch: String, _shift: bool, _level: u64) {
-        // TODO(ville): What to do with `_shift` and `_level`?
+    fn show_special_char(&mut self, ch: String, shift: bool, level: u64) {
+        if level != self.current_level {
+            return;
+        }
+
         let buffer = self.textview.get_buffer().unwrap();
         let mark_insert = buffer.get_insert().unwrap();
         let mut iter = buffer.get_iter_at_mark(&mark_insert);
+
+        // When not shifting, the special char overwrites the char under
+        // the cursor.
+        if !shift {
+            let mut end = iter.clone();
+            end.forward_char();
+            buffer.delete(&mut iter, &mut end);
+        }
+
         buffer.insert(&mut iter, &ch);
-        iter.backward_char();
+
+        // Keep the cursor on the special char.
+        iter.backward_chars(ch.chars().count() as i32);
+        buffer.place_cursor(&iter);
     }
 
     fn set_colors(&self, colors: &CmdlineColors, hl_defs: &HlDefs) {
@@ -643,6 +661,10 @@ impl Cmdline {
         self.wildmenu.select(item_num);
     }
 
+    pub fn wildmenu_set_horizontal(&mut self, horizontal: bool) {
+        self.wildmenu.set_horizontal(horizontal);
+    }
+
     pub fn wildmenu_set_colors(&self, hl_defs: &HlDefs) {
         self.wildmenu.set_colors(hl_defs);
     }
@@ -16,6 +16,13 @@ pub enum HlGroup {
     WildmenuSel,
 
     MsgSeparator,
+
+    FloatBorder,
+
+    Search,
+
+    CursorLine,
+    CursorColumn,
 }
 
 #[derive(Default)]
@@ -55,6 +62,14 @@ impl HlDefs {
     }
 }
 
+/// The default (xterm) 16 color ANSI palette. The first 8 are the base
+/// colors, and the rest their bright variants.
+const ANSI_PALETTE: [u64; 16] = [
+    0x000000, 0xcd0000, 0x00cd00, 0xcdcd00, 0x0000ee, 0xcd00cd, 0x00cdcd,
+    0xe5e5e5, 0x7f7f7f, 0xff0000, 0x00ff00, 0xffff00, 0x5c5cff, 0xff00ff,
+    0x00ffff, 0xffffff,
+];
+
 #[derive(Debug, Clone, Copy, Default, PartialEq)]
 pub struct Highlight {
     pub foreground: Option<Color>,
@@ -66,9 +81,17 @@ pub struct Highlight {
     pub bold: bool,
     pub underline: bool,
     pub undercurl: bool,
+    pub underdouble: bool,
+    pub underdotted: bool,
+    pub underdashed: bool,
+    pub strikethrough: bool,
 
     /// The blend value in range of 0..1.
     pub blend: f64,
+
+    /// Foreground's index in the 256 color palette, from the highlight's
+    /// cterm attributes.
+    pub cterm_fg: Option<u64>,
 }
 
 impl Highlight {
@@ -86,13 +109,16 @@ impl Highlight {
         let weight = if self.bold { "bold" } else { "normal" };
         let underline = if self.undercurl {
             "error"
-        } else if self.underline {
+        } else if self.underdouble {
+            "double"
+        } else if self.underline || self.underdotted || self.underdashed {
             "underline"
         } else {
             "none"
         };
 
         let fontstyle = if self.italic { "italic" } else { "normal" };
+        let strikethrough = if self.strikethrough { "true" } else { "false" };
 
         format!(
             "<span
@@ -101,17 +127,36 @@ impl Highlight {
             underline_color=\"#{sp}\"
             weight=\"{weight}\"
             font_style=\"{fontstyle}\"
+            strikethrough=\"{strikethrough}\"
             underline=\"{underline}\">{text}</span>",
             fg = fg.to_hex(),
             bg = bg.to_hex(),
             sp = sp.to_hex(),
             weight = weight,
             fontstyle = fontstyle,
+            strikethrough = strikethrough,
             underline = underline,
             text = glib::markup_escape_text(text)
         )
     }
 
+    /// Returns the bright variant of the foreground, if the highlight is bold
+    /// and its foreground is one of the 8 base ANSI colors. Only applies to
+    /// colors that come from the palette, e.g. not to a `guifg` that just
+    /// has the same palette index in `ctermfg`.
+    pub fn bright_foreground(&self) -> Option<Color> {
+        if !self.bold {
+            return None;
+        }
+
+        let index = self.cterm_fg.filter(|i| *i < 8)? as usize;
+        let base = Color::from_u64(ANSI_PALETTE[index]);
+        match self.foreground {
+            Some(fg) if fg != base => None,
+            _ => Some(Color::from_u64(ANSI_PALETTE[index + 8])),
+        }
+    }
+
     /// Apply the highlight's blend value to color. Returns the color
     /// in `rgba()` format.
     pub fn apply_blend(&self, color: &Color) -> String {
@@ -193,4 +238,33 @@ mod tests {
 
         assert_eq!(c.to_rgba(0.4), "rgba(255, 0, 255, 0.6)");
     }
+
+    #[test]
+    fn test_bright_foreground() {
+        let mut hl = Highlight {
+            bold: true,
+            cterm_fg: Some(1),
+            foreground: Some(Color::from_u64(0xcd0000)),
+            ..Highlight::default()
+        };
+        assert_eq!(Some(Color::from_u64(0xff0000)), hl.bright_foreground());
+
+        // Foreground only from the cterm attributes.
+        hl.foreground = None;
+        assert_eq!(Some(Color::from_u64(0xff0000)), hl.bright_foreground());
+
+        // Truecolor foreground.
+        hl.foreground = Some(Color::from_u64(0xcd0001));
+        assert_eq!(None, hl.bright_foreground());
+
+        // Not a base color.
+        hl.foreground = None;
+        hl.cterm_fg = Some(9);
+        assert_eq!(None, hl.bright_foreground());
+
+        // Not bold.
+        hl.cterm_fg = Some(1);
+        hl.bold = false;
+        assert_eq!(None, hl.bright_foreground());
+    }
 }
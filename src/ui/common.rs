@@ -1,10 +1,39 @@
+use std::cell::RefCell;
+use std::fmt::Display;
+
 use futures::future::Future;
+use gtk::prelude::*;
+
+use log::error;
+
+thread_local! {
+    /// Where the errors reported with `report_error` are shown to the user.
+    static ERROR_SINK: RefCell<Option<Box<dyn Fn(&str)>>> = RefCell::new(None);
+}
 
 pub fn spawn_local<F: Future<Output = ()> + 'static>(f: F) {
     let c = glib::MainContext::default();
     c.spawn_local(f);
 }
 
+/// Sets `f` to show the errors reported with `report_error`.
+pub fn set_error_sink<F: Fn(&str) + 'static>(f: F) {
+    ERROR_SINK.with(|sink| sink.replace(Some(Box::new(f))));
+}
+
+/// Logs a recoverable error (e.g. a failed call to nvim), and shows it to
+/// the user. `what` tells what failed, e.g. "Failed to send input".
+pub fn report_error<E: Display>(what: &str, err: E) {
+    let msg = format!("{}: {}", what, err);
+    error!("{}", msg);
+
+    ERROR_SINK.with(|sink| {
+        if let Some(f) = sink.borrow().as_ref() {
+            f(&msg);
+        }
+    });
+}
+
 pub fn calc_line_space(space: i64) -> (i32, i32) {
     let half = space as f64 / 2.0;
     if half as f64 % 2.0 != 0.0 {
@@ -14,6 +43,28 @@ pub fn calc_line_space(space: i64) -> (i32, i32) {
     }
 }
 
+/// Toggles `window` between fullscreen and its previous state. GTK restores
+/// the window's earlier size (and e.g. maximization) when leaving
+/// fullscreen.
+pub fn toggle_fullscreen(window: &gtk::ApplicationWindow) {
+    let fullscreen = window
+        .get_window()
+        .map(|win| win.get_state().contains(gdk::WindowState::FULLSCREEN))
+        .unwrap_or(false);
+    if fullscreen {
+        window.unfullscreen();
+    } else {
+        window.fullscreen();
+    }
+}
+
+/// From clutter-easing.c, based on Robert Penner's
+/// infamous easing equations, MIT license.
+pub fn ease_out_cubic(t: f64) -> f64 {
+    let p = t - 1f64;
+    p * p * p + 1f64
+}
+
 /// Calculate the preferred width and x-position.
 pub fn get_preferred_horizontal_position(
     area: &gdk::Rectangle,
@@ -67,6 +118,25 @@ mod test {
 
     use super::*;
 
+    #[test]
+    fn test_report_error() {
+        use std::rc::Rc;
+
+        let reported = Rc::new(RefCell::new(vec![]));
+        {
+            let reported = reported.clone();
+            set_error_sink(move |msg| {
+                reported.borrow_mut().push(msg.to_string())
+            });
+        }
+
+        report_error("Failed to foo", "bar");
+        assert_eq!(
+            vec![String::from("Failed to foo: bar")],
+            *reported.borrow()
+        );
+    }
+
     #[test]
     fn test_calc_line_space() {
         assert_eq!((1, 0), calc_line_space(1));
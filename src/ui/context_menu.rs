@@ -0,0 +1,111 @@
+//! Context menu that is shown when right clicking a grid.
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use gtk::prelude::*;
+
+use crate::nvim_gio::GioNeovim;
+use crate::ui::clipboard;
+use crate::ui::common::{report_error, spawn_local};
+
+/// Right click menu with Cut, Copy, Paste and Select All. Cut and copy go
+/// through the `+` register, and are only available in visual mode.
+///
+/// Cloning returns a handle to the same menu, so a single `ContextMenu`
+/// can be shared between grids.
+#[derive(Clone)]
+pub struct ContextMenu {
+    menu: gtk::Menu,
+    cut: gtk::MenuItem,
+    copy: gtk::MenuItem,
+
+    enabled: Rc<Cell<bool>>,
+    /// If nvim is in visual mode, e.g. there is something to cut or copy.
+    visual: Rc<Cell<bool>>,
+}
+
+impl ContextMenu {
+    pub fn new(nvim: GioNeovim) -> Self {
+        let menu = gtk::Menu::new();
+
+        let cut = gtk::MenuItem::with_label("Cut");
+        cut.connect_activate(clone!(nvim => move |_| {
+            input(nvim.clone(), "\"+d");
+        }));
+
+        let copy = gtk::MenuItem::with_label("Copy");
+        copy.connect_activate(clone!(nvim => move |_| {
+            input(nvim.clone(), "\"+y");
+        }));
+
+        let paste = gtk::MenuItem::with_label("Paste");
+        paste.connect_activate(clone!(nvim => move |_| {
+            clipboard::paste(nvim.clone(), "+");
+        }));
+
+        let select_all = gtk::MenuItem::with_label("Select All");
+        select_all.connect_activate(clone!(nvim => move |_| {
+            input(nvim.clone(), "<Esc>ggVG");
+        }));
+
+        menu.append(&cut);
+        menu.append(&copy);
+        menu.append(&paste);
+        menu.append(&gtk::SeparatorMenuItem::new());
+        menu.append(&select_all);
+        menu.show_all();
+
+        ContextMenu {
+            menu,
+            cut,
+            copy,
+            enabled: Rc::new(Cell::new(true)),
+            visual: Rc::new(Cell::new(false)),
+        }
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.set(enabled);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.get()
+    }
+
+    /// If the menu is shown.
+    pub fn is_shown(&self) -> bool {
+        self.menu.is_visible()
+    }
+
+    /// Tells the menu nvim's current mode (e.g. `visual`).
+    pub fn set_mode(&self, mode: &str) {
+        self.visual.set(mode.starts_with("visual"));
+    }
+
+    /// Shows the menu at the pointer, over `widget` (e.g. the clicked grid).
+    pub fn popup(&self, widget: &gtk::Widget) {
+        let visual = self.visual.get();
+        self.cut.set_sensitive(visual);
+        self.copy.set_sensitive(visual);
+
+        // The menu gets its screen and transient parent from the widget
+        // it's attached to, and it can only be attached to one at a time.
+        if self.menu.get_attach_widget().as_ref() != Some(widget) {
+            if self.menu.get_attach_widget().is_some() {
+                self.menu.detach();
+            }
+            self.menu.attach_to_widget(widget, None);
+        }
+
+        self.menu.popup_at_pointer(None);
+    }
+}
+
+fn input(nvim: GioNeovim, keys: &'static str) {
+    spawn_local(async move {
+        if let Err(err) = nvim.input(keys).await {
+            report_error("Failed to send input", err);
+        }
+    });
+}
@@ -19,6 +19,7 @@ use crate::thread_guard::ThreadGuard;
 use crate::ui::color::Color;
 use crate::ui::common::{
     get_preferred_horizontal_position, get_preferred_vertical_position,
+    report_error,
 };
 use crate::ui::font::{Font, FontUnit};
 
@@ -206,6 +207,37 @@ impl CursorTooltip {
         self.frame.hide();
     }
 
+    /// Scrolls the content by `lines` lines of text. Negative values scroll
+    /// up.
+    pub fn scroll(&self, lines: i64) {
+        if !self.is_visible() {
+            return;
+        }
+
+        // The script is run in the page's global scope, so its variables are
+        // kept in a function of their own, for the next scroll to declare
+        // them again.
+        let js = format!(
+            "(() => {{
+                let content = document.getElementById('content');
+                let style = getComputedStyle(content);
+                let lineHeight = parseFloat(style.lineHeight)
+                    || parseFloat(style.fontSize) * 1.2;
+                window.scrollBy(0, {} * lineHeight);
+            }})()",
+            lines
+        );
+        self.webview.run_javascript(
+            &js,
+            None::<&gio::Cancellable>,
+            |res: Result<webkit::JavascriptResult, webkit::Error>| {
+                if let Err(err) = res {
+                    report_error("Failed to scroll the cursor tooltip", err);
+                }
+            },
+        );
+    }
+
     pub fn is_visible(&self) -> bool {
         self.frame.is_visible()
     }
@@ -0,0 +1,313 @@
+//! DBus interface for controlling a running gnvim, e.g. from scripts or a
+//! file manager's "open in gnvim" action. The interface is made of the
+//! application's actions, which `GApplication` exports over DBus (through
+//! `org.gtk.Actions`) when gnvim runs with `--single-instance`:
+//!
+//! ```sh
+//! gdbus call --session --dest com.github.vhakulinen.gnvim \
+//!     --object-path /com/github/vhakulinen/gnvim \
+//!     --method org.gtk.Actions.Activate open-file "[<'/tmp/foo.txt'>]" {}
+//! ```
+//!
+//! Actions can't return anything, so the evaluating actions send their
+//! results to the actions of whoever owns `REPLY_BUS_NAME`. That's how
+//! `gnvim --remote-eval EXPR` gets the result of `EXPR`.
+//!
+//! The actions aren't authenticated: any program on the session bus can
+//! use them, and whoever owns `REPLY_BUS_NAME` gets the results. Since the
+//! evaluating actions can run anything (including shell commands), they're
+//! only added with `--enable-remote-eval`.
+
+use std::cell::RefCell;
+use std::future::Future;
+use std::path::Path;
+use std::rc::Rc;
+
+use gio::prelude::*;
+use gtk::prelude::*;
+
+use log::error;
+use rmpv::Value;
+
+use crate::nvim_gio::GioNeovim;
+use crate::ui::common::{report_error, spawn_local, toggle_fullscreen};
+use crate::ui::file_drop::escape_path;
+
+const BUS_NAME: &str = "com.github.vhakulinen.gnvim";
+const OBJECT_PATH: &str = "/com/github/vhakulinen/gnvim";
+
+/// Bus name and object path of the caller of the evaluating actions.
+const REPLY_BUS_NAME: &str = "com.github.vhakulinen.gnvim.Reply";
+const REPLY_OBJECT_PATH: &str = "/com/github/vhakulinen/gnvim/Reply";
+/// The caller's action that gets the result of an evaluation.
+const RESULT_ACTION: &str = "result";
+/// The caller's action that gets nvim's error from an evaluation.
+const ERROR_ACTION: &str = "error";
+/// Time (in ms) `remote_eval` waits for the result.
+const REMOTE_EVAL_TIMEOUT_MS: u32 = 10_000;
+
+/// Opens the file in a new tab.
+const OPEN_FILE_ACTION: &str = "open-file";
+/// Evaluates the vimscript expression and sends its result.
+const EVAL_ACTION: &str = "eval";
+/// Runs the lua code and sends what it returns.
+const EVAL_LUA_ACTION: &str = "eval-lua";
+/// Executes the vimscript and sends its output.
+const EXEC_ACTION: &str = "exec";
+/// Brings the window to the front.
+const FOCUS_ACTION: &str = "focus";
+const TOGGLE_FULLSCREEN_ACTION: &str = "toggle-fullscreen";
+
+/// Adds the actions of our DBus interface to `window`'s application, for
+/// controlling `window` and `nvim`. The evaluating actions are only added
+/// if `eval` is true. When nvim is restarted, the new nvim's actions
+/// replace the previous ones.
+pub fn register(window: &gtk::ApplicationWindow, nvim: &GioNeovim, eval: bool) {
+    let app = match window.get_application() {
+        Some(app) => app,
+        None => return,
+    };
+
+    let open_file = gio::SimpleAction::new(
+        OPEN_FILE_ACTION,
+        glib::VariantTy::new("s").ok(),
+    );
+    open_file.connect_activate(clone!(nvim => move |_, param| {
+        let path = match string_param(param) {
+            Some(path) => path,
+            None => return,
+        };
+
+        let nvim = nvim.clone();
+        let cmd = open_command(Path::new(&path));
+        spawn_local(async move {
+            if let Err(err) = nvim.command(&cmd).await {
+                report_error("Failed to open file", err);
+            }
+        });
+    }));
+    app.add_action(&open_file);
+
+    if eval {
+        add_eval_actions(&app, nvim);
+    }
+
+    let window_weak = window.downgrade();
+    let focus = gio::SimpleAction::new(FOCUS_ACTION, None);
+    focus.connect_activate(move |_, _| {
+        let window = upgrade_weak!(window_weak);
+        window.present();
+    });
+    app.add_action(&focus);
+
+    let window_weak = window.downgrade();
+    let fullscreen = gio::SimpleAction::new(TOGGLE_FULLSCREEN_ACTION, None);
+    fullscreen.connect_activate(move |_, _| {
+        let window = upgrade_weak!(window_weak);
+        toggle_fullscreen(&window);
+    });
+    app.add_action(&fullscreen);
+}
+
+/// Adds the evaluating actions to `app`.
+fn add_eval_actions(app: &gtk::Application, nvim: &GioNeovim) {
+    add_eval_action(app, EVAL_ACTION, nvim, |nvim, expr| async move {
+        nvim.eval(&expr)
+            .await
+            .map(|val| value_to_string(&val))
+            .map_err(|err| err.to_string())
+    });
+    add_eval_action(app, EVAL_LUA_ACTION, nvim, |nvim, code| async move {
+        nvim.exec_lua(&code, vec![])
+            .await
+            .map(|val| value_to_string(&val))
+            .map_err(|err| err.to_string())
+    });
+    add_eval_action(app, EXEC_ACTION, nvim, |nvim, src| async move {
+        nvim.exec(&src, true).await.map_err(|err| err.to_string())
+    });
+}
+
+/// Adds the action `name` to `app`, which evaluates its parameter with `f`
+/// and sends the result to the caller (see `reply`).
+fn add_eval_action<F, Fut>(
+    app: &gtk::Application,
+    name: &str,
+    nvim: &GioNeovim,
+    f: F,
+) where
+    F: Fn(GioNeovim, String) -> Fut + 'static,
+    Fut: Future<Output = Result<String, String>> + 'static,
+{
+    let action = gio::SimpleAction::new(name, glib::VariantTy::new("s").ok());
+    action.connect_activate(clone!(nvim => move |_, param| {
+        if let Some(input) = string_param(param) {
+            let res = f(nvim.clone(), input);
+            spawn_local(async move {
+                reply(res.await);
+            });
+        }
+    }));
+    app.add_action(&action);
+}
+
+/// Sends the result of an evaluating action to the owner of
+/// `REPLY_BUS_NAME`, if there's one.
+fn reply(res: Result<String, String>) {
+    let conn = match gio::bus_get_sync(
+        gio::BusType::Session,
+        None::<&gio::Cancellable>,
+    ) {
+        Ok(conn) => conn,
+        Err(err) => {
+            error!("Failed to get the DBus connection: {}", err);
+            return;
+        }
+    };
+
+    let caller = gio::DBusActionGroup::get(
+        &conn,
+        Some(REPLY_BUS_NAME),
+        REPLY_OBJECT_PATH,
+    );
+    match res {
+        Ok(res) => {
+            caller.activate_action(RESULT_ACTION, Some(&res.to_variant()))
+        }
+        Err(err) => {
+            caller.activate_action(ERROR_ACTION, Some(&err.to_variant()))
+        }
+    }
+}
+
+/// Evaluates `expr` in the gnvim that runs with `--single-instance` and
+/// `--enable-remote-eval`, and returns the result. Errors from nvim are
+/// returned as the error.
+pub fn remote_eval(expr: &str) -> Result<String, String> {
+    let conn =
+        gio::bus_get_sync(gio::BusType::Session, None::<&gio::Cancellable>)
+            .map_err(|err| err.to_string())?;
+
+    let res = Rc::new(RefCell::new(None));
+    let main_loop = glib::MainLoop::new(None, false);
+    // Only the first outcome counts.
+    let finish: Rc<dyn Fn(Result<String, String>)> = {
+        let res = res.clone();
+        let main_loop = main_loop.clone();
+        Rc::new(move |outcome| {
+            if res.borrow().is_none() {
+                res.replace(Some(outcome));
+            }
+            main_loop.quit();
+        })
+    };
+
+    let replies = gio::SimpleActionGroup::new();
+    for &(name, is_ok) in &[(RESULT_ACTION, true), (ERROR_ACTION, false)] {
+        let action =
+            gio::SimpleAction::new(name, glib::VariantTy::new("s").ok());
+        let finish = finish.clone();
+        action.connect_activate(move |_, param| {
+            let msg = string_param(param).unwrap_or_default();
+            finish(if is_ok { Ok(msg) } else { Err(msg) });
+        });
+        replies.add_action(&action);
+    }
+    conn.export_action_group(REPLY_OBJECT_PATH, &replies)
+        .map_err(|err| err.to_string())?;
+
+    // Once we can get the reply, ask gnvim for it.
+    let expr = expr.to_string();
+    gio::bus_own_name(
+        gio::BusType::Session,
+        REPLY_BUS_NAME,
+        gio::BusNameOwnerFlags::DO_NOT_QUEUE,
+        |_, _| {},
+        move |conn, _| {
+            let gnvim =
+                gio::DBusActionGroup::get(&conn, Some(BUS_NAME), OBJECT_PATH);
+            gnvim.activate_action(EVAL_ACTION, Some(&expr.to_variant()));
+        },
+        clone!(finish => move |_, _| {
+            finish(Err(String::from(
+                "Another remote evaluation is in progress",
+            )))
+        }),
+    );
+
+    gio::bus_watch_name(
+        gio::BusType::Session,
+        BUS_NAME,
+        gio::BusNameWatcherFlags::NONE,
+        |_, _, _| {},
+        clone!(finish => move |_, _| {
+            finish(Err(String::from(
+                "No gnvim is running with --single-instance",
+            )))
+        }),
+    );
+
+    glib::timeout_add_local(REMOTE_EVAL_TIMEOUT_MS, move || {
+        finish(Err(String::from(
+            "No reply from gnvim, is it running with --enable-remote-eval?",
+        )));
+        glib::Continue(false)
+    });
+
+    main_loop.run();
+
+    let res = res.borrow_mut().take();
+    res.unwrap_or_else(|| Err(String::from("No reply from gnvim")))
+}
+
+/// Returns the string parameter of an action.
+fn string_param(param: Option<&glib::Variant>) -> Option<String> {
+    param.and_then(|param| param.get::<String>())
+}
+
+/// Returns the nvim command for opening `path` in a new tab.
+fn open_command(path: &Path) -> String {
+    format!("execute 'tabedit ' . {}", escape_path(path))
+}
+
+/// Returns `val` as a string for DBus. Strings are returned as is, other
+/// values in their msgpack representation.
+fn value_to_string(val: &Value) -> String {
+    match val.as_str() {
+        Some(s) => s.to_string(),
+        None => val.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_command() {
+        assert_eq!(
+            "execute 'tabedit ' . fnameescape('/tmp/it''s')",
+            open_command(Path::new("/tmp/it's"))
+        );
+    }
+
+    #[test]
+    fn test_value_to_string() {
+        assert_eq!("foo", value_to_string(&Value::from("foo")));
+        assert_eq!("42", value_to_string(&Value::from(42)));
+        assert_eq!(
+            "[1, 2]",
+            value_to_string(&Value::from(vec![1.into(), 2.into()]))
+        );
+    }
+
+    #[test]
+    fn test_string_param() {
+        assert_eq!(
+            Some(String::from("/tmp/foo")),
+            string_param(Some(&"/tmp/foo".to_variant()))
+        );
+        assert_eq!(None, string_param(Some(&42.to_variant())));
+        assert_eq!(None, string_param(None));
+    }
+}
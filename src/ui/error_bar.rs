@@ -0,0 +1,93 @@
+//! Bar for showing errors (e.g. failed calls to nvim) to the user, without
+//! interrupting them.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gtk::prelude::*;
+
+/// Time (in ms) an error is shown for.
+const TIMEOUT_MS: u32 = 5000;
+
+/// Error bar, shown at the bottom of the grids for a moment when an error
+/// is reported. Cloning returns a handle to the same bar.
+#[derive(Clone)]
+pub struct ErrorBar {
+    revealer: gtk::Revealer,
+    label: gtk::Label,
+    /// Source id of the pending hide.
+    hide_source_id: Rc<RefCell<Option<glib::SourceId>>>,
+}
+
+impl ErrorBar {
+    pub fn new(parent: &gtk::Overlay) -> Self {
+        let label = gtk::Label::new(None);
+        label.set_line_wrap(true);
+        label.set_selectable(true);
+
+        let close = gtk::Button::from_icon_name(
+            Some("window-close-symbolic"),
+            gtk::IconSize::Menu,
+        );
+        close.set_relief(gtk::ReliefStyle::None);
+
+        let b = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+        b.pack_start(&label, true, true, 0);
+        b.pack_start(&close, false, false, 0);
+
+        let frame = gtk::Frame::new(None);
+        frame.set_widget_name("error-bar");
+        frame.get_style_context().add_class("app-notification");
+        frame.add(&b);
+
+        let revealer = gtk::Revealer::new();
+        revealer.set_transition_type(gtk::RevealerTransitionType::SlideUp);
+        revealer.set_halign(gtk::Align::Center);
+        revealer.set_valign(gtk::Align::End);
+        // Don't cover the grids when there's nothing to show.
+        revealer.set_no_show_all(true);
+        revealer.add(&frame);
+        frame.show_all();
+
+        parent.add_overlay(&revealer);
+
+        let bar = ErrorBar {
+            revealer,
+            label,
+            hide_source_id: Rc::new(RefCell::new(None)),
+        };
+
+        close.connect_clicked(clone!(bar => move |_| bar.hide()));
+
+        bar
+    }
+
+    /// Shows `msg`, replacing the currently shown error (if any).
+    pub fn show(&self, msg: &str) {
+        self.cancel_hide();
+
+        self.label.set_text(msg);
+        self.revealer.show();
+        self.revealer.set_reveal_child(true);
+
+        let bar = self.clone();
+        let id = gtk::timeout_add(TIMEOUT_MS, move || {
+            // The source is removed once we return, so forget about it.
+            bar.hide_source_id.borrow_mut().take();
+            bar.hide();
+            Continue(false)
+        });
+        self.hide_source_id.replace(Some(id));
+    }
+
+    pub fn hide(&self) {
+        self.cancel_hide();
+        self.revealer.set_reveal_child(false);
+    }
+
+    fn cancel_hide(&self) {
+        if let Some(id) = self.hide_source_id.borrow_mut().take() {
+            glib::source::source_remove(id);
+        }
+    }
+}
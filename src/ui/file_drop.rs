@@ -0,0 +1,103 @@
+//! Opening files by dragging them onto gnvim.
+
+use std::path::{Path, PathBuf};
+
+use gtk::prelude::*;
+
+/// Makes `widget` accept files dropped onto it. `f` param is the nvim
+/// command for opening the dropped files.
+pub fn connect_file_drop<W, F>(widget: &W, f: F)
+where
+    W: IsA<gtk::Widget>,
+    F: Fn(String) + 'static,
+{
+    let targets = [gtk::TargetEntry::new(
+        "text/uri-list",
+        gtk::TargetFlags::OTHER_APP,
+        0,
+    )];
+    widget.drag_dest_set(
+        gtk::DestDefaults::ALL,
+        &targets,
+        gdk::DragAction::COPY,
+    );
+
+    widget.connect_drag_data_received(move |_, _, _, _, data, _, _| {
+        let paths: Vec<PathBuf> = data
+            .get_uris()
+            .iter()
+            .filter_map(|uri| glib::filename_from_uri(uri).ok())
+            .map(|(path, _)| path)
+            .collect();
+
+        if paths.is_empty() {
+            return;
+        }
+
+        let state = gtk::get_current_event_state()
+            .unwrap_or_else(gdk::ModifierType::empty);
+        f(drop_command(&paths, state));
+    });
+}
+
+/// Escapes `path` for a vim command, e.g. `edit`.
+pub fn escape_path(path: &Path) -> String {
+    format!(
+        "fnameescape('{}')",
+        path.to_string_lossy().replace("'", "''")
+    )
+}
+
+/// Returns the nvim command for opening `paths` in new tabs.
+pub fn tabedit_command(paths: &[PathBuf]) -> String {
+    drop_command(paths, gdk::ModifierType::CONTROL_MASK)
+}
+
+/// Returns the nvim command for opening `paths`. Normally, the files are
+/// opened with `:edit`. With control held, they're opened in tabs, and
+/// with shift held, they replace the argument list.
+fn drop_command(paths: &[PathBuf], state: gdk::ModifierType) -> String {
+    if state.contains(gdk::ModifierType::SHIFT_MASK) {
+        let args: Vec<String> =
+            paths.iter().map(|path| escape_path(path)).collect();
+        return format!("execute 'args ' . {}", args.join(" . ' ' . "));
+    }
+
+    let cmd = if state.contains(gdk::ModifierType::CONTROL_MASK) {
+        "tabedit"
+    } else {
+        "edit"
+    };
+
+    let cmds: Vec<String> = paths
+        .iter()
+        .map(|path| format!("execute '{} ' . {}", cmd, escape_path(path)))
+        .collect();
+    cmds.join(" | ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drop_command() {
+        let paths = vec![PathBuf::from("/tmp/foo"), PathBuf::from("/tmp/it's")];
+
+        assert_eq!(
+            "execute 'edit ' . fnameescape('/tmp/foo') | \
+             execute 'edit ' . fnameescape('/tmp/it''s')",
+            drop_command(&paths, gdk::ModifierType::empty())
+        );
+        assert_eq!(
+            "execute 'tabedit ' . fnameescape('/tmp/foo') | \
+             execute 'tabedit ' . fnameescape('/tmp/it''s')",
+            drop_command(&paths, gdk::ModifierType::CONTROL_MASK)
+        );
+        assert_eq!(
+            "execute 'args ' . fnameescape('/tmp/foo') . ' ' . \
+             fnameescape('/tmp/it''s')",
+            drop_command(&paths, gdk::ModifierType::SHIFT_MASK)
+        );
+    }
+}
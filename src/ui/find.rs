@@ -0,0 +1,83 @@
+//! GUI find bar, that highlights the matches of a query on the grids. The
+//! find is independent of nvim's own search: it only looks at the text on
+//! the grids, and doesn't touch the search register.
+
+use gtk::prelude::*;
+
+/// Find bar, shown at the top right corner of the grids.
+pub struct FindBar {
+    frame: gtk::Frame,
+    entry: gtk::SearchEntry,
+}
+
+impl FindBar {
+    pub fn new(parent: &gtk::Overlay) -> Self {
+        let entry = gtk::SearchEntry::new();
+        entry.set_width_chars(30);
+
+        let frame = gtk::Frame::new(None);
+        frame.set_widget_name("find-bar");
+        // Hidden until opened.
+        frame.set_no_show_all(true);
+        frame.set_halign(gtk::Align::End);
+        frame.set_valign(gtk::Align::Start);
+        frame.add(&entry);
+        entry.show();
+
+        parent.add_overlay(&frame);
+
+        FindBar { frame, entry }
+    }
+
+    /// Shows the find bar, and focuses it.
+    pub fn open(&self) {
+        self.frame.show();
+        self.entry.grab_focus();
+        // Typing replaces the previous query.
+        self.entry.select_region(0, -1);
+    }
+
+    pub fn close(&self) {
+        self.frame.hide();
+    }
+
+    /// If the find bar should get the key presses (instead of nvim).
+    pub fn has_focus(&self) -> bool {
+        self.frame.is_visible() && self.entry.has_focus()
+    }
+
+    /// The query to highlight, or None if there's nothing to highlight.
+    pub fn query(&self) -> Option<String> {
+        if !self.frame.is_visible() {
+            return None;
+        }
+
+        Some(self.entry.get_text().to_string()).filter(|q| !q.is_empty())
+    }
+
+    /// Connects `f` to changes in the query.
+    pub fn connect_changed<F: Fn() + 'static>(&self, f: F) {
+        self.entry.connect_search_changed(move |_| f());
+    }
+
+    /// Connects `f` to moving between the matches: enter moves forward, and
+    /// shift+enter backward.
+    pub fn connect_step<F: Fn(bool) + 'static>(&self, f: F) {
+        self.entry.connect_key_press_event(move |_, e| {
+            match e.get_keyval().name().as_deref() {
+                Some("Return") | Some("KP_Enter") => {
+                    let backward =
+                        e.get_state().contains(gdk::ModifierType::SHIFT_MASK);
+                    f(!backward);
+                    Inhibit(true)
+                }
+                _ => Inhibit(false),
+            }
+        });
+    }
+
+    /// Connects `f` to closing the find bar with escape.
+    pub fn connect_close<F: Fn() + 'static>(&self, f: F) {
+        self.entry.connect_stop_search(move |_| f());
+    }
+}
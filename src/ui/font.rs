@@ -64,6 +64,23 @@ impl Font {
         Ok(font)
     }
 
+    /// Returns the font's family, or comma separated families in the order
+    /// they should be used as fallbacks.
+    pub fn family(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the font's families, quoted for CSS's `font-family`.
+    fn css_font_family(&self) -> String {
+        self.name
+            .split(',')
+            .map(str::trim)
+            .filter(|f| !f.is_empty())
+            .map(|f| format!("\"{}\"", f))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
     /// Returns a CSS representation of self for a wild (`*`) CSS selector.
     /// On gtk version below 3.20 unit needs to be `FontUnit::Pixel` and
     /// with version 3.20 and up, unit needs to be `FontUnit::Point`. This is
@@ -71,15 +88,23 @@ impl Font {
     pub fn as_wild_css(&self, unit: FontUnit) -> String {
         format!(
             "* {{ \
-             font-family: \"{font_family}\"; \
+             font-family: {font_family}; \
              font-size: {font_size}{font_unit}; \
              }}",
-            font_family = self.name,
+            font_family = self.css_font_family(),
             font_size = self.height,
             font_unit = unit,
         )
     }
 
+    /// Returns a copy of self, with its height multiplied by `scale`.
+    pub fn scaled(&self, scale: f32) -> Self {
+        Font {
+            name: self.name.clone(),
+            height: self.height * scale,
+        }
+    }
+
     /// Returns a pango::FontDescription version of self.
     pub fn as_pango_font(&self) -> pango::FontDescription {
         let mut font_desc = pango::FontDescription::from_string(&format!(
@@ -134,6 +159,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_css_font_family() {
+        let font = Font {
+            name: "JetBrains Mono, Noto Color Emoji,".to_string(),
+            height: 10.0,
+        };
+
+        assert_eq!(
+            font.css_font_family(),
+            "\"JetBrains Mono\", \"Noto Color Emoji\""
+        );
+    }
+
+    #[test]
+    fn test_scaled() {
+        let font = Font {
+            name: "foo".to_string(),
+            height: 10.0,
+        };
+
+        let f = font.scaled(1.5);
+        assert_eq!(f.name, "foo");
+        assert_eq!(f.height, 15.0);
+    }
+
     #[test]
     fn test_from_guifont() {
         // Font with proper height.
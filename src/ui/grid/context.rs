@@ -1,11 +1,17 @@
 use gtk::prelude::*;
 use gtk::DrawingArea;
 
-use crate::ui::color::HlDefs;
+use crate::nvim_bridge::GridLineSegment;
+use crate::ui::color::{Color, Highlight, HlDefs};
 use crate::ui::font::Font;
-use crate::ui::grid::cursor::Cursor;
+use crate::ui::grid::cursor::{Cursor, DEFAULT_ANIMATION_DURATION_MS};
+use crate::ui::grid::glyph_cache::GlyphCache;
+use crate::ui::grid::grid::cell_to_pixel;
 use crate::ui::grid::render;
 use crate::ui::grid::row::{Cell, Row};
+use crate::ui::grid::scroll_animation::{
+    ScrollAnimation, DEFAULT_SCROLL_ANIMATION_DURATION_MS,
+};
 
 /// Context is manipulated by Grid.
 pub struct Context {
@@ -30,8 +36,195 @@ pub struct Context {
     /// If the grid that this context belongs to is active or not.
     pub active: bool,
 
-    /// Areas to call queue_draw_area on the drawing area on flush.
+    /// If the grid is currently flashed by the visual bell.
+    pub flash: bool,
+    /// Opacity (0.0-1.0) of the darkening drawn over the grid, e.g. when
+    /// the window isn't focused.
+    pub dim: f64,
+
+    /// Input method's preedit (composition) text, drawn over the cursor.
+    pub preedit: Option<Preedit>,
+
+    /// Grid lines received since the last flush. The lines are applied
+    /// (and rendered) all at once on flush, or before any other operation
+    /// that depends on the rows.
+    pub pending_lines: Vec<GridLineSegment>,
+
+    /// Areas to call queue_draw_area on the drawing area on the next frame.
     pub queue_draw_area: Vec<(f64, f64, f64, f64)>,
+
+    /// Maximum frame rate, or 0 for no limit.
+    pub max_fps: u64,
+    /// Frame time (in µs) of the previous frame that was drawn.
+    pub last_frame_time: i64,
+
+    /// Options for shaping the text.
+    pub text_options: TextOptions,
+    /// Shaped text, reused between redraws.
+    pub glyph_cache: GlyphCache,
+
+    /// Matches of the GUI find, drawn over the grid.
+    pub find: FindMatches,
+    /// Ongoing flash over yanked text.
+    pub yank_flash: Option<YankFlash>,
+    /// Cursor line and column drawn by us.
+    pub cursor_overlay: CursorOverlay,
+
+    /// If scrolled regions are animated.
+    pub enable_scroll_animation: bool,
+    /// Duration (in ms) of the scroll animation.
+    pub scroll_animation_duration: u64,
+    pub scroll_animation: Option<ScrollAnimation>,
+    /// Current shift (in pixels) of the animated scroll region's content.
+    pub scroll_offset: f64,
+}
+
+/// Matches of the GUI find (see `ui::find`).
+#[derive(Default)]
+pub struct FindMatches {
+    /// Row, and start and end (exclusive) columns of each match.
+    pub ranges: Vec<(usize, usize, usize)>,
+    /// Index of the current match in `ranges`.
+    pub current: Option<usize>,
+    pub color: Color,
+}
+
+/// Cursor line and column that we draw under the text, with the
+/// `CursorLine` and `CursorColumn` highlights' backgrounds, instead of nvim
+/// drawing them cell by cell (see `render::put_segments`).
+#[derive(Default)]
+pub struct CursorOverlay {
+    pub line: bool,
+    pub column: bool,
+    /// Cursor position (row, col) to draw the line and column at.
+    pub pos: Option<(usize, usize)>,
+    /// Positions that the line and column have been rendered at. These
+    /// have to be rerendered when `pos` changes.
+    pub rendered: Vec<(usize, usize)>,
+}
+
+/// What to draw of the cursor line and column on a row.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RowOverlay {
+    /// If the row is the cursor line.
+    pub line: bool,
+    /// The cursor column.
+    pub column: Option<usize>,
+}
+
+impl CursorOverlay {
+    pub fn is_enabled(&self) -> bool {
+        self.line || self.column
+    }
+
+    /// Returns what to draw of the overlay on `row`. Like in nvim, the
+    /// cursor column isn't drawn on the cursor line.
+    pub fn row(&self, row: usize) -> RowOverlay {
+        let (cursor_row, cursor_col) = match self.pos {
+            Some(pos) => pos,
+            None => return RowOverlay::default(),
+        };
+
+        RowOverlay {
+            line: self.line && row == cursor_row,
+            column: if self.column && row != cursor_row {
+                Some(cursor_col)
+            } else {
+                None
+            },
+        }
+    }
+}
+
+/// Opacity of the yank flash, when it starts.
+const YANK_FLASH_ALPHA: f64 = 0.5;
+
+/// Flash over yanked text, that fades out.
+pub struct YankFlash {
+    /// Row, and start and end (exclusive) columns of each flashed range.
+    pub ranges: Vec<(u64, u64, u64)>,
+    pub color: Color,
+    /// Frame times (in µs) of the flash's start and end.
+    pub start_time: i64,
+    pub end_time: i64,
+}
+
+impl YankFlash {
+    /// Returns the flash's opacity at frame time `now`.
+    pub fn alpha(&self, now: i64) -> f64 {
+        let duration = (self.end_time - self.start_time).max(1) as f64;
+        let t = ((now - self.start_time) as f64 / duration)
+            .max(0.0)
+            .min(1.0);
+        YANK_FLASH_ALPHA * (1.0 - t)
+    }
+}
+
+/// Input method's preedit text.
+pub struct Preedit {
+    pub text: String,
+    /// Attributes (e.g. underline) for the text, from the input method.
+    pub attrs: pango::AttrList,
+    /// Cursor position within the text, in chars.
+    pub cursor: usize,
+    pub fg: Color,
+    pub bg: Color,
+}
+
+/// Options for shaping the grid's text.
+#[derive(Clone, Default)]
+pub struct TextOptions {
+    /// If text is shaped so that ligatures are formed.
+    pub ligatures: bool,
+    /// OpenType font features used when shaping text, e.g. `"zero" 1`.
+    pub font_features: String,
+    /// Font families (comma separated) preferred for double width cells.
+    pub font_wide: Option<String>,
+    /// If bold and italic are synthesized for fonts that don't have those
+    /// faces.
+    pub synthetic_styles: bool,
+    /// If bold text with one of the base ANSI colors is drawn with the
+    /// color's bright variant.
+    pub bold_is_bright: bool,
+    /// If backgrounds are drawn with the highlights' blend values, so that
+    /// whatever is under the grid shows through (e.g. for `winblend`).
+    pub transparent: bool,
+    /// Opacity (0.0-1.0) of the cells with the default background.
+    pub background_opacity: f64,
+}
+
+impl TextOptions {
+    /// If the grid's backgrounds can be (partly) transparent.
+    pub fn has_alpha(&self) -> bool {
+        self.transparent || self.background_opacity < 1.0
+    }
+
+    /// Returns the alpha for the background of cells highlighted with `hl`.
+    pub fn bg_alpha(&self, hl: &Highlight) -> f64 {
+        let alpha = if self.transparent {
+            1.0 - hl.blend
+        } else {
+            1.0
+        };
+
+        if !hl.reverse && hl.background.is_none() {
+            alpha * self.background_opacity
+        } else {
+            alpha
+        }
+    }
+
+    /// Sets `cr`'s source to the default background color.
+    pub fn set_default_bg_source(&self, cr: &cairo::Context, hl_defs: &HlDefs) {
+        let bg = &hl_defs.default_bg;
+        if self.has_alpha() {
+            // Replace whatever was drawn before, instead of blending with it.
+            cr.set_operator(cairo::Operator::Source);
+            cr.set_source_rgba(bg.r, bg.g, bg.b, self.background_opacity);
+        } else {
+            cr.set_source_rgb(bg.r, bg.g, bg.b);
+        }
+    }
 }
 
 impl Context {
@@ -54,13 +247,13 @@ impl Context {
         let mut cell_metrics = CellMetrics::default();
         cell_metrics.font = font;
         cell_metrics.line_space = line_space;
-        cell_metrics.update(&pango_context);
+        cell_metrics.update(&pango_context, da.get_scale_factor());
 
         let w = cell_metrics.width * cols as f64;
         let h = cell_metrics.height * rows as f64;
         let surface = win
             .create_similar_surface(
-                cairo::Content::Color,
+                surface_content(false),
                 w.ceil() as i32,
                 h.ceil() as i32,
             )
@@ -91,6 +284,7 @@ impl Context {
 
         let cursor = Cursor {
             disable_animation: !enable_cursor_animations,
+            animation_duration: DEFAULT_ANIMATION_DURATION_MS,
             ..Cursor::default()
         };
 
@@ -105,8 +299,30 @@ impl Context {
 
             busy: false,
             active: false,
+            flash: false,
+            dim: 0.0,
+            preedit: None,
 
+            pending_lines: vec![],
             queue_draw_area: vec![],
+
+            max_fps: 0,
+            last_frame_time: 0,
+
+            text_options: TextOptions {
+                ligatures: true,
+                synthetic_styles: true,
+                background_opacity: 1.0,
+                ..TextOptions::default()
+            },
+            glyph_cache: GlyphCache::default(),
+            find: FindMatches::default(),
+            yank_flash: None,
+            cursor_overlay: CursorOverlay::default(),
+            enable_scroll_animation: false,
+            scroll_animation_duration: DEFAULT_SCROLL_ANIMATION_DURATION_MS,
+            scroll_animation: None,
+            scroll_offset: 0.0,
         }
     }
 
@@ -122,6 +338,10 @@ impl Context {
         let prev_rows = self.rows.len();
         let prev_cols = self.rows.get(0).map(|r| r.len()).unwrap_or(0);
 
+        // The scrolled region might not exist anymore.
+        self.scroll_animation = None;
+        self.scroll_offset = 0.0;
+
         if self.rows.len() != rows {
             self.rows.resize_with(rows, || Row::new(cols));
         }
@@ -135,13 +355,13 @@ impl Context {
         let pctx = da.get_pango_context();
         pctx.set_font_description(&self.cell_metrics.font.as_pango_font());
 
-        self.cell_metrics.update(&pctx);
+        self.cell_metrics.update(&pctx, da.get_scale_factor());
 
         let w = self.cell_metrics.width * cols as f64;
         let h = self.cell_metrics.height * rows as f64;
         let surface = win
             .create_similar_surface(
-                cairo::Content::Color,
+                surface_content(self.text_options.has_alpha()),
                 w.ceil() as i32,
                 h.ceil() as i32,
             )
@@ -150,11 +370,7 @@ impl Context {
 
         // Fill the context with default bg color.
         ctx.save();
-        ctx.set_source_rgb(
-            hl_defs.default_bg.r,
-            hl_defs.default_bg.g,
-            hl_defs.default_bg.b,
-        );
+        self.text_options.set_default_bg_source(&ctx, hl_defs);
         ctx.paint();
         ctx.restore();
 
@@ -195,7 +411,9 @@ impl Context {
 
         self.cell_metrics.font = font;
         self.cell_metrics.line_space = line_space;
-        self.cell_metrics.update(&pango_context);
+        self.cell_metrics
+            .update(&pango_context, da.get_scale_factor());
+        self.glyph_cache.clear();
 
         self.cursor_context = {
             let surface = win
@@ -257,53 +475,143 @@ impl Context {
         ));
     }
 
-    pub fn tick(&mut self, da: &DrawingArea, clock: &gdk::FrameClock) {
+    /// Steps the animations for the frame clock's current frame. Returns
+    /// false if the frame is skipped, to keep under `max_fps`. We're still
+    /// ticked on every frame, and the grid is still rendered to its surface
+    /// on every flush, so skipping only saves the repaints.
+    pub fn tick(&mut self, da: &DrawingArea, clock: &gdk::FrameClock) -> bool {
+        let now = clock.get_frame_time();
+        if !frame_due(self.last_frame_time, now, self.max_fps) {
+            return false;
+        }
+        self.last_frame_time = now;
+
         let (x, y, w, h) = self.get_cursor_rect();
         da.queue_draw_area(x, y, w, h);
 
-        self.cursor.tick(clock.get_frame_time());
+        self.cursor.tick(now);
+        self.tick_scroll_animation(now);
+        self.tick_yank_flash(now);
+
+        true
+    }
 
-        // We're not blinking, so skip the blink animation phase.
-        if self.cursor.blink_on == 0 {
+    /// Queues the yank flash for redraw, and removes it once it has faded
+    /// out.
+    fn tick_yank_flash(&mut self, now: i64) {
+        let (areas, done) = match self.yank_flash {
+            Some(ref flash) => {
+                let cm = &self.cell_metrics;
+                let areas = flash
+                    .ranges
+                    .iter()
+                    .map(|&(row, start, end)| {
+                        let (x, y) = cell_to_pixel(self, row, start);
+                        let w = end.saturating_sub(start) as f64 * cm.width;
+                        (x, y, w, cm.height)
+                    })
+                    .collect::<Vec<_>>();
+                (areas, now >= flash.end_time)
+            }
+            None => return,
+        };
+
+        self.queue_draw_area.extend(areas);
+        if done {
+            self.yank_flash = None;
+        }
+    }
+
+    /// Starts animating the scroll of region `reg` (top, bot, left, right)
+    /// by `count` rows, beginning at `frame_time`. If the region is still
+    /// animating from an earlier scroll, the new animation continues from
+    /// the current shift.
+    pub fn start_scroll_animation(
+        &mut self,
+        reg: [u64; 4],
+        count: i64,
+        bg: Color,
+        frame_time: i64,
+    ) {
+        if !self.enable_scroll_animation
+            || self.scroll_animation_duration == 0
+            || count == 0
+        {
             return;
         }
 
-        let (x, y, w, h) = self.get_cursor_rect();
+        let cm = &self.cell_metrics;
+        let (x1, y1, x2, y2) = render::get_rect(
+            cm.height,
+            cm.width,
+            reg[0] as f64,
+            reg[1] as f64,
+            reg[2] as f64,
+            reg[3] as f64,
+        );
+        let rect = (x1, y1, x2 - x1, y2 - y1);
 
-        let mut alpha = self.cursor.alpha;
-        if alpha > 1.0 {
-            alpha = 2.0 - alpha;
+        let mut offset = count as f64 * cm.height;
+        if let Some(ref anim) = self.scroll_animation {
+            if anim.rect == rect {
+                offset += self.scroll_offset;
+            } else {
+                // Let the old region settle at once.
+                let (x, y, w, h) = anim.rect;
+                self.queue_draw_area.push((x, y, w, h));
+            }
         }
+        // Don't shift the content further than the region's height.
+        let offset = offset.max(-rect.3).min(rect.3);
+
+        self.scroll_animation = Some(ScrollAnimation::new(
+            rect,
+            bg,
+            offset,
+            frame_time,
+            self.scroll_animation_duration,
+        ));
+        self.scroll_offset = offset;
+        self.queue_draw_area.push(rect);
+    }
 
-        let cr = &self.cursor_context;
-        cr.save();
-        cr.rectangle(0.0, 0.0, 100.0, 100.0);
-        cr.set_operator(cairo::Operator::Source);
-        cr.set_source_rgba(
-            self.cursor.color.r,
-            self.cursor.color.g,
-            self.cursor.color.b,
-            alpha,
-        );
-        cr.fill();
-        cr.restore();
+    fn tick_scroll_animation(&mut self, now: i64) {
+        let anim = match self.scroll_animation {
+            Some(ref anim) => anim,
+            None => return,
+        };
 
-        // Don't use the queue_draw_area, because those draws will only
-        // happen once nvim sends 'flush' event. This draw needs to happen
-        // on each tick so the cursor blinks.
-        da.queue_draw_area(x, y, w, h);
+        // Redraw the region on each frame, and once more after the end,
+        // so that the content settles to its place.
+        self.queue_draw_area.push(anim.rect);
+        match anim.offset_at(now) {
+            Some(offset) => self.scroll_offset = offset,
+            None => {
+                self.scroll_animation = None;
+                self.scroll_offset = 0.0;
+            }
+        }
     }
 
     pub fn cell_at_cursor(&self) -> Option<&Cell> {
-        self.cursor.get_position().and_then(|pos| {
-            self.rows
-                .get(pos.0.ceil() as usize)
-                .and_then(|row| row.cell_at(pos.1.ceil() as usize))
+        self.cursor_cell_position().and_then(|(row, col)| {
+            self.rows.get(row).and_then(|row| row.cell_at(col))
         })
     }
+
+    /// Returns the (row, col) of the cell that the cursor is on, or is
+    /// moving to.
+    pub fn cursor_cell_position(&self) -> Option<(usize, usize)> {
+        self.cursor
+            .get_position()
+            .map(|pos| (pos.0.ceil() as usize, pos.1.ceil() as usize))
+    }
 }
 
 /// Cell metrics tells the size (and other metrics) of the cells in a grid.
+/// All values are in logical (application) pixels, but the cell sizes are
+/// snapped to whole device pixels so that the cells line up on HiDPI
+/// screens too.
 #[derive(Default, Debug, Clone)]
 pub struct CellMetrics {
     pub height: f64,
@@ -318,16 +626,26 @@ pub struct CellMetrics {
 }
 
 impl CellMetrics {
-    pub fn update(&mut self, ctx: &pango::Context) {
+    /// Updates the metrics from the current font, for a widget whose scale
+    /// factor is `scale_factor`.
+    pub fn update(&mut self, ctx: &pango::Context, scale_factor: i32) {
         let fm = ctx
             .get_metrics(Some(&self.font.as_pango_font()), None)
             .unwrap();
-        let extra = self.line_space as f64 / 2.0;
         let scale = f64::from(pango::SCALE);
-        self.ascent = (f64::from(fm.get_ascent()) / scale + extra).ceil();
-        self.decent = (f64::from(fm.get_descent()) / scale + extra).ceil();
+        let ascent = f64::from(fm.get_ascent()) / scale;
+        let descent = f64::from(fm.get_descent()) / scale;
+        // Half of the line space goes above the text and half below, so
+        // that the text is centered vertically.
+        let extra = line_space_extra(self.line_space, ascent + descent);
+        let scale_factor = f64::from(scale_factor.max(1));
+        self.ascent = ceil_to_device(ascent + extra, scale_factor);
+        self.decent = ceil_to_device(descent + extra, scale_factor);
         self.height = self.ascent + self.decent;
-        self.width = f64::from(fm.get_approximate_char_width()) / scale;
+        self.width = round_to_device(
+            f64::from(fm.get_approximate_char_width()) / scale,
+            scale_factor,
+        );
 
         self.underline_position =
             f64::from(fm.get_underline_position()) / scale - extra;
@@ -336,3 +654,159 @@ impl CellMetrics {
             f64::from(fm.get_underline_thickness()) / scale * 2.0;
     }
 }
+
+/// Returns the space to add above and below the text for `line_space`,
+/// when the font's natural line height is `height`. Negative line space
+/// can't shrink the cells smaller than a pixel.
+fn line_space_extra(line_space: i64, height: f64) -> f64 {
+    (line_space as f64).max(1.0 - height) / 2.0
+}
+
+/// Rounds `v` (in logical pixels) up to the nearest whole device pixel,
+/// when there are `scale_factor` device pixels per logical one.
+fn ceil_to_device(v: f64, scale_factor: f64) -> f64 {
+    (v * scale_factor).ceil() / scale_factor
+}
+
+/// Rounds `v` (in logical pixels) to the nearest whole device pixel, but
+/// to at least one.
+fn round_to_device(v: f64, scale_factor: f64) -> f64 {
+    (v * scale_factor).round().max(1.0) / scale_factor
+}
+
+/// Returns true if a frame at `now` should be drawn, when the previous one
+/// was drawn at `last` (both in µs), and the frame rate is limited to
+/// `max_fps` (0 for no limit).
+fn frame_due(last: i64, now: i64, max_fps: u64) -> bool {
+    if max_fps == 0 {
+        return true;
+    }
+
+    // Frames don't come in exactly even intervals, so allow a bit of slack
+    // for e.g. 30 fps on a 60 Hz screen.
+    let interval = 1_000_000 / max_fps as i64;
+    now - last >= interval - 2_000
+}
+
+/// Returns the content for the grid's surface. Only grids with `alpha` need
+/// the alpha channel, which otherwise would cost us e.g. subpixel
+/// antialiasing of the text.
+fn surface_content(alpha: bool) -> cairo::Content {
+    if alpha {
+        cairo::Content::ColorAlpha
+    } else {
+        cairo::Content::Color
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snap_to_device() {
+        assert!((ceil_to_device(10.2, 1.0) - 11.0).abs() < f64::EPSILON);
+        assert!((ceil_to_device(10.2, 2.0) - 10.5).abs() < f64::EPSILON);
+        assert!((round_to_device(7.2, 1.0) - 7.0).abs() < f64::EPSILON);
+        assert!((round_to_device(7.2, 2.0) - 7.0).abs() < f64::EPSILON);
+        assert!((round_to_device(7.3, 2.0) - 7.5).abs() < f64::EPSILON);
+        assert!((round_to_device(0.1, 2.0) - 0.5).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_cursor_overlay_row() {
+        let mut overlay = CursorOverlay {
+            line: true,
+            column: true,
+            pos: Some((2, 5)),
+            rendered: vec![],
+        };
+
+        assert_eq!(
+            RowOverlay {
+                line: true,
+                column: None,
+            },
+            overlay.row(2)
+        );
+        assert_eq!(
+            RowOverlay {
+                line: false,
+                column: Some(5),
+            },
+            overlay.row(0)
+        );
+
+        overlay.line = false;
+        assert_eq!(RowOverlay::default(), overlay.row(2));
+
+        overlay.pos = None;
+        assert_eq!(RowOverlay::default(), overlay.row(0));
+    }
+
+    #[test]
+    fn test_frame_due() {
+        assert!(frame_due(0, 1, 0));
+        // 30 fps on a 60 Hz screen.
+        assert!(!frame_due(0, 16_667, 30));
+        assert!(frame_due(0, 33_300, 30));
+        assert!(frame_due(0, 40_000, 30));
+    }
+
+    #[test]
+    fn test_bg_alpha() {
+        let opts = TextOptions {
+            background_opacity: 0.8,
+            ..TextOptions::default()
+        };
+        let default_bg = Highlight {
+            blend: 0.5,
+            ..Highlight::default()
+        };
+        let bg = Highlight {
+            background: Some(Color::default()),
+            ..default_bg
+        };
+        let reverse = Highlight {
+            reverse: true,
+            ..default_bg
+        };
+
+        assert!((0.8 - opts.bg_alpha(&default_bg)).abs() < f64::EPSILON);
+        assert!((1.0 - opts.bg_alpha(&bg)).abs() < f64::EPSILON);
+        assert!((1.0 - opts.bg_alpha(&reverse)).abs() < f64::EPSILON);
+
+        // Blending applies to all cells.
+        let opts = TextOptions {
+            transparent: true,
+            ..opts
+        };
+        assert!((0.4 - opts.bg_alpha(&default_bg)).abs() < f64::EPSILON);
+        assert!((0.5 - opts.bg_alpha(&bg)).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_yank_flash_alpha() {
+        let flash = YankFlash {
+            ranges: vec![],
+            color: Color::default(),
+            start_time: 1000,
+            end_time: 3000,
+        };
+
+        assert_eq!(YANK_FLASH_ALPHA, flash.alpha(0));
+        assert_eq!(YANK_FLASH_ALPHA, flash.alpha(1000));
+        assert_eq!(YANK_FLASH_ALPHA / 2.0, flash.alpha(2000));
+        assert_eq!(0.0, flash.alpha(3000));
+        assert_eq!(0.0, flash.alpha(4000));
+    }
+
+    #[test]
+    fn test_line_space_extra() {
+        assert_eq!(0.0, line_space_extra(0, 16.0));
+        assert_eq!(2.0, line_space_extra(4, 16.0));
+        assert_eq!(-2.0, line_space_extra(-4, 16.0));
+        // Cells don't collapse with large negative values.
+        assert_eq!(-7.5, line_space_extra(-100, 16.0));
+    }
+}
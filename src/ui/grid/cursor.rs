@@ -1,4 +1,9 @@
+use crate::nvim_bridge::CursorShape;
 use crate::ui::color::Color;
+use crate::ui::common::ease_out_cubic;
+
+/// Default duration (in ms) of the cursor's movement animation.
+pub const DEFAULT_ANIMATION_DURATION_MS: u64 = 100;
 
 #[derive(Default)]
 pub struct Animation {
@@ -8,22 +13,48 @@ pub struct Animation {
     end_time: i64,
 }
 
+/// Phase of the cursor's blink cycle.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum BlinkPhase {
+    /// Waiting for the blinking to start (after e.g. the cursor moved).
+    Wait,
+    On,
+    Off,
+}
+
+impl Default for BlinkPhase {
+    fn default() -> Self {
+        BlinkPhase::Wait
+    }
+}
+
 #[derive(Default)]
 pub struct Cursor {
     /// Position, (row, col).
     pub pos: Option<(f64, f64)>,
     /// Flag for disabling the movement animation.
     pub disable_animation: bool,
+    /// Duration (in ms) of the movement animation.
+    pub animation_duration: u64,
     pub animation: Option<Animation>,
 
-    /// Alpha color. Used to make the cursor blink.
-    pub alpha: f64,
-    /// The duration of the blink.
+    /// Time (in ms) the cursor is shown when blinking.
     pub blink_on: u64,
+    /// Time (in ms) the cursor is hidden when blinking.
+    pub blink_off: u64,
+    /// Delay (in ms) before the cursor starts blinking.
+    pub blink_wait: u64,
+    blink_phase: BlinkPhase,
+    /// If the cursor is currently hidden by the blinking.
+    pub blink_hidden: bool,
     /// Width of the cursor.
     pub cell_percentage: f64,
-    /// Color of the cursor.
-    pub color: Color,
+    pub shape: CursorShape,
+    /// Highlight id of the cursor's colors. When 0, the cell under the
+    /// cursor is drawn inverted.
+    pub attr_id: u64,
+    /// Fixed color for the cursor, overriding the highlight's.
+    pub color: Option<Color>,
 }
 
 impl Cursor {
@@ -34,11 +65,12 @@ impl Cursor {
         }
 
         // If cursor animation is disabled, set the position directly. Otherwise, set the animation
-        // so that we can animate cursor position change.
+        // so that we can animate cursor position change. Any earlier animation is retargeted,
+        // starting from the current (animated) position.
         if self.disable_animation {
             self.pos = Some((row, col));
         } else {
-            let duration = 100;
+            let duration = self.animation_duration as i64;
             self.animation = Some(Animation {
                 start: self.pos.unwrap(),
                 end: (row, col),
@@ -49,24 +81,49 @@ impl Cursor {
     }
 
     pub fn tick(&mut self, frame_time: i64) {
-        self.blink();
         self.animate_position(frame_time);
     }
 
-    fn blink(&mut self) {
-        // If we dont need to blink, return.
-        if self.blink_on == 0 {
-            return;
-        }
+    /// If the cursor should blink. Like in nvim, if any of the blink times
+    /// is zero, there is no blinking.
+    pub fn is_blinking(&self) -> bool {
+        self.blink_on > 0 && self.blink_off > 0 && self.blink_wait > 0
+    }
 
-        // Assuming a 60hz framerate
-        self.alpha += 100.0 / (6.0 * self.blink_on as f64);
+    /// Restarts the blink cycle, showing the cursor. Returns the time (in
+    /// ms) until the next blink phase, if the cursor is blinking.
+    pub fn reset_blink(&mut self) -> Option<u64> {
+        self.blink_phase = BlinkPhase::Wait;
+        self.blink_hidden = false;
 
-        if self.alpha > 2.0 {
-            self.alpha = 0.0;
+        if self.is_blinking() {
+            Some(self.blink_wait)
+        } else {
+            None
         }
     }
 
+    /// Moves to the next blink phase. Returns the time (in ms) until the
+    /// next phase, if the cursor is blinking.
+    pub fn advance_blink(&mut self) -> Option<u64> {
+        if !self.is_blinking() {
+            self.blink_hidden = false;
+            return None;
+        }
+
+        let (phase, hidden, duration) = match self.blink_phase {
+            BlinkPhase::Wait | BlinkPhase::On => {
+                (BlinkPhase::Off, true, self.blink_off)
+            }
+            BlinkPhase::Off => (BlinkPhase::On, false, self.blink_on),
+        };
+
+        self.blink_phase = phase;
+        self.blink_hidden = hidden;
+
+        Some(duration)
+    }
+
     fn animate_position(&mut self, frame_time: i64) {
         if let Some(Animation {
             start,
@@ -92,6 +149,23 @@ impl Cursor {
         }
     }
 
+    /// Returns the area the cursor covers out of the cell area (`x`, `y`,
+    /// `w`, `h`), based on the cursor's shape.
+    pub fn shape_rect(
+        &self,
+        x: f64,
+        y: f64,
+        w: f64,
+        h: f64,
+    ) -> (f64, f64, f64, f64) {
+        let p = self.cell_percentage.max(0.0).min(1.0);
+        match self.shape {
+            CursorShape::Block => (x, y, w, h),
+            CursorShape::Vertical => (x, y, w * p, h),
+            CursorShape::Horizontal => (x, y + h * (1.0 - p), w, h * p),
+        }
+    }
+
     /// Gets the position of the cursor.
     pub fn get_position(&self) -> Option<(f64, f64)> {
         if let Some(ref a) = self.animation {
@@ -104,35 +178,44 @@ impl Cursor {
     }
 }
 
-/// From clutter-easing.c, based on Robert Penner's
-/// infamous easing equations, MIT license.
-fn ease_out_cubic(t: f64) -> f64 {
-    let p = t - 1f64;
-    p * p * p + 1f64
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn test_cursor_blink100() {
+    fn test_cursor_blink() {
         let mut cursor = Cursor::default();
         cursor.blink_on = 100;
-        cursor.alpha = 1.0;
+        cursor.blink_off = 200;
+        cursor.blink_wait = 300;
+
+        assert_eq!(cursor.reset_blink(), Some(300));
+        assert!(!cursor.blink_hidden);
 
-        cursor.blink();
-        assert_eq!(cursor.alpha, 1.1666666666666667);
+        assert_eq!(cursor.advance_blink(), Some(200));
+        assert!(cursor.blink_hidden);
+        assert_eq!(cursor.advance_blink(), Some(100));
+        assert!(!cursor.blink_hidden);
+        assert_eq!(cursor.advance_blink(), Some(200));
+        assert!(cursor.blink_hidden);
+
+        // Resetting shows the cursor, and starts waiting again.
+        assert_eq!(cursor.reset_blink(), Some(300));
+        assert!(!cursor.blink_hidden);
     }
 
     #[test]
     fn test_cursor_blink0() {
         let mut cursor = Cursor::default();
         cursor.blink_on = 0;
-        cursor.alpha = 1.0;
+        cursor.blink_off = 200;
+        cursor.blink_wait = 300;
 
-        cursor.blink();
-        assert_eq!(cursor.alpha, 1.0);
+        // Cursor stays solid.
+        assert_eq!(cursor.reset_blink(), None);
+        assert!(!cursor.blink_hidden);
+        assert_eq!(cursor.advance_blink(), None);
+        assert!(!cursor.blink_hidden);
     }
 
     #[test]
@@ -151,7 +234,10 @@ mod tests {
 
     #[test]
     fn test_animate_position() {
-        let mut cursor = Cursor::default();
+        let mut cursor = Cursor {
+            animation_duration: DEFAULT_ANIMATION_DURATION_MS,
+            ..Cursor::default()
+        };
 
         // When we first set the position, it should be set immediately.
         cursor.goto(15.0, 15.0, 1);
@@ -162,6 +248,31 @@ mod tests {
         assert_eq!(cursor.pos, Some((12.109459376125006, 12.109459376125006)));
     }
 
+    #[test]
+    fn test_animate_position_duration() {
+        let mut cursor = Cursor {
+            animation_duration: 200,
+            ..Cursor::default()
+        };
+
+        cursor.goto(15.0, 15.0, 1);
+        cursor.goto(10.0, 10.0, 1);
+        // Half way through the animation.
+        cursor.tick(100_001);
+        assert_eq!(cursor.pos, Some((10.625, 10.625)));
+
+        // Retargeting starts from the current position.
+        cursor.goto(20.0, 20.0, 100_001);
+        assert_eq!(cursor.animation.as_ref().unwrap().start, (10.625, 10.625));
+
+        // With zero duration, the position is set on the next tick.
+        cursor.animation_duration = 0;
+        cursor.goto(5.0, 5.0, 150_000);
+        cursor.tick(150_000);
+        assert_eq!(cursor.pos, Some((5.0, 5.0)));
+        assert!(cursor.animation.is_none());
+    }
+
     #[test]
     fn test_animate_position_animation_disabled() {
         let mut cursor = Cursor::default();
@@ -179,6 +290,32 @@ mod tests {
         assert_eq!(cursor.pos, Some((10.0, 10.0)));
     }
 
+    #[test]
+    fn test_shape_rect() {
+        let mut cursor = Cursor {
+            cell_percentage: 0.25,
+            ..Cursor::default()
+        };
+
+        cursor.shape = CursorShape::Block;
+        assert_eq!(
+            (10.0, 20.0, 8.0, 16.0),
+            cursor.shape_rect(10.0, 20.0, 8.0, 16.0)
+        );
+
+        cursor.shape = CursorShape::Vertical;
+        assert_eq!(
+            (10.0, 20.0, 2.0, 16.0),
+            cursor.shape_rect(10.0, 20.0, 8.0, 16.0)
+        );
+
+        cursor.shape = CursorShape::Horizontal;
+        assert_eq!(
+            (10.0, 32.0, 8.0, 4.0),
+            cursor.shape_rect(10.0, 20.0, 8.0, 16.0)
+        );
+    }
+
     #[test]
     fn test_get_position() {
         let mut cursor = Cursor::default();
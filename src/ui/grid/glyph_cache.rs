@@ -0,0 +1,153 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Maximum number of shaped texts kept in the cache.
+const CAPACITY: usize = 4096;
+
+/// Key for the shaped text in the glyph cache.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GlyphKey {
+    pub text: String,
+    /// Byte offsets in `text` where each cell's text starts. Needed, since
+    /// double width cells might be shaped with a different font.
+    pub cells: Vec<usize>,
+    pub bold: bool,
+    pub italic: bool,
+}
+
+/// A run of glyphs, shaped with a single font.
+pub struct ShapedItem {
+    pub font: pango::Font,
+    pub glyphs: pango::GlyphString,
+    /// Column of the item's first cell, relative to the text's first cell.
+    pub col: usize,
+    /// How many columns the item spans.
+    pub cols: usize,
+    /// Styles that the item's font doesn't have a face for.
+    pub bold_missing: bool,
+    pub italic_missing: bool,
+}
+
+/// Cache for shaped text, so that the same text doesn't need to be itemized
+/// and shaped again each time it's rendered. Colors aren't part of the key,
+/// since they're applied only when the glyphs are drawn.
+///
+/// The cache has to be cleared when the font (or anything else affecting
+/// the shaping) changes.
+pub struct GlyphCache {
+    items: RefCell<Lru<GlyphKey, Vec<ShapedItem>>>,
+}
+
+impl Default for GlyphCache {
+    fn default() -> Self {
+        GlyphCache {
+            items: RefCell::new(Lru::new(CAPACITY)),
+        }
+    }
+}
+
+impl GlyphCache {
+    /// Calls `f` with the shaped items for `key`. If the items aren't
+    /// cached yet, they're shaped with `shape`.
+    pub fn with<S, F>(&self, key: GlyphKey, shape: S, f: F)
+    where
+        S: FnOnce() -> Vec<ShapedItem>,
+        F: FnOnce(&mut [ShapedItem]),
+    {
+        let mut items = self.items.borrow_mut();
+        f(items.get_or_insert_with(key, shape));
+    }
+
+    pub fn clear(&self) {
+        self.items.borrow_mut().clear();
+    }
+}
+
+/// Hash map that drops its least recently used entries once it's full.
+struct Lru<K, V> {
+    map: HashMap<K, (V, u64)>,
+    /// Incremented on each access, to tell the entries' ages.
+    tick: u64,
+    capacity: usize,
+}
+
+impl<K: Eq + Hash, V> Lru<K, V> {
+    fn new(capacity: usize) -> Self {
+        Lru {
+            map: HashMap::new(),
+            tick: 0,
+            capacity,
+        }
+    }
+
+    fn get_or_insert_with<F: FnOnce() -> V>(&mut self, key: K, f: F) -> &mut V {
+        self.tick += 1;
+        let tick = self.tick;
+
+        if self.map.len() >= self.capacity && !self.map.contains_key(&key) {
+            self.evict();
+        }
+
+        let entry = self.map.entry(key).or_insert_with(|| (f(), tick));
+        entry.1 = tick;
+        &mut entry.0
+    }
+
+    /// Drops the least recently used half of the entries. Dropping many at
+    /// once keeps us from having to go through all of the entries on each
+    /// insert.
+    fn evict(&mut self) {
+        let mut ticks = self.map.values().map(|(_, t)| *t).collect::<Vec<_>>();
+        ticks.sort_unstable();
+
+        if let Some(cutoff) = ticks.get((ticks.len() / 2).saturating_sub(1)) {
+            let cutoff = *cutoff;
+            self.map.retain(|_, (_, t)| *t > cutoff);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.map.clear();
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru_get_or_insert_with() {
+        let mut lru = Lru::new(4);
+        assert_eq!(1, *lru.get_or_insert_with("a", || 1));
+        // Cached values are returned as they are.
+        assert_eq!(1, *lru.get_or_insert_with("a", || 2));
+
+        *lru.get_or_insert_with("a", || 3) += 1;
+        assert_eq!(2, *lru.get_or_insert_with("a", || 4));
+
+        lru.clear();
+        assert_eq!(5, *lru.get_or_insert_with("a", || 5));
+    }
+
+    #[test]
+    fn test_lru_evict() {
+        let mut lru = Lru::new(4);
+        for i in 0..4 {
+            lru.get_or_insert_with(i, || i);
+        }
+        // Use the first entry, so it's not the oldest one anymore.
+        lru.get_or_insert_with(0, || 10);
+
+        lru.get_or_insert_with(4, || 4);
+        assert!(lru.len() <= 4);
+        assert_eq!(0, *lru.get_or_insert_with(0, || 10));
+        assert_eq!(4, *lru.get_or_insert_with(4, || 10));
+        assert_eq!(10, *lru.get_or_insert_with(1, || 10));
+    }
+}
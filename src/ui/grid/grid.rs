@@ -1,4 +1,4 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::fmt;
 use std::fmt::Display;
 use std::rc::Rc;
@@ -8,11 +8,38 @@ use gtk::{DrawingArea, EventBox};
 
 use gtk::prelude::*;
 
+use rmpv::Value;
+
 use crate::nvim_bridge::{GridLineSegment, ModeInfo};
-use crate::ui::color::HlDefs;
+use crate::ui::color::{Color, HlDefs};
 use crate::ui::font::Font;
-use crate::ui::grid::context::Context;
+use crate::ui::grid::context::{Context, FindMatches, Preedit, YankFlash};
+use crate::ui::grid::kinetic::KineticScroll;
 use crate::ui::grid::render;
+use crate::ui::grid::row::Row;
+
+/// Maximum number of separate areas to queue for redraw on a flush. More
+/// than that, and the redraw is done for their bounding box instead.
+const MAX_DAMAGE_RECTS: usize = 16;
+
+/// Mouse selection on a grid, with the selected rows as they were when the
+/// selection was made.
+pub struct Selection {
+    rows: Vec<Row>,
+    /// Start and end (both `(row, col)`, `end`'s col being exclusive) of the
+    /// selection in `rows`.
+    start: (usize, usize),
+    end: (usize, usize),
+}
+
+impl Selection {
+    /// Returns the selected text. The first `skip_cols` columns of each
+    /// row, e.g. the window's number and sign columns, are left out, and so
+    /// is the trailing whitespace of each row.
+    pub fn text(&self, skip_cols: usize) -> String {
+        selection_lines(&self.rows, self.start, self.end, skip_cols).join("\n")
+    }
+}
 
 pub struct GridMetrics {
     // Row count in the grid.
@@ -60,8 +87,58 @@ impl Display for MouseButton {
     }
 }
 
+/// Counts consecutive clicks of a mouse button.
+#[derive(Default)]
+struct ClickCounter {
+    /// Button, time (in ms) and cell (`(row, col)`) of the last press.
+    last: Option<(u32, u32, (u64, u64))>,
+    count: u8,
+}
+
+impl ClickCounter {
+    /// Registers a press of `button` on `cell` at `time`, and returns how
+    /// many consecutive clicks it makes (1-3). Presses of other buttons, on
+    /// other cells or more than `timeout` ms after the previous one start
+    /// over, and so does the click after a triple click.
+    fn press(
+        &mut self,
+        button: u32,
+        time: u32,
+        cell: (u64, u64),
+        timeout: u32,
+    ) -> u8 {
+        let consecutive = match self.last {
+            Some((b, t, c)) => {
+                b == button && c == cell && time.wrapping_sub(t) <= timeout
+            }
+            None => false,
+        };
+
+        self.count = if consecutive && self.count < 3 {
+            self.count + 1
+        } else {
+            1
+        };
+        self.last = Some((button, time, cell));
+
+        self.count
+    }
+}
+
+/// Returns the time (in ms) in which consecutive clicks are counted as a
+/// double click, per GTK's settings.
+fn double_click_time(widget: &EventBox) -> u32 {
+    widget
+        .get_settings()
+        .map(|settings| settings.get_property_gtk_double_click_time())
+        .unwrap_or(400) as u32
+}
+
 /// Single grid in the neovim UI. This matches the `ui-linegrid` stuff in
 /// the ui.txt documentation for neovim.
+///
+/// Clones share the same underlying grid.
+#[derive(Clone)]
 pub struct Grid {
     pub id: i64,
     /// Our internal "widget". This is what is drawn to the screen.
@@ -73,6 +150,21 @@ pub struct Grid {
     /// Pointer position for dragging if we should call callback from
     /// `connect_motion_events_for_drag`.
     drag_position: Rc<RefCell<(u64, u64)>>,
+    /// Counts the clicks for double and triple clicks.
+    clicks: Rc<RefCell<ClickCounter>>,
+    /// Start and end (`(row, col)`, both inclusive) of the ongoing mouse
+    /// selection.
+    selection: Rc<RefCell<Option<((u64, u64), (u64, u64))>>>,
+    /// Accumulated, not yet "used", delta from smooth scroll events.
+    scroll_remainder: Rc<RefCell<f64>>,
+    /// Kinetic scrolling, continues smooth scrolls after they end.
+    kinetic: KineticScroll,
+    /// Nvim window that is shown on this grid, if any.
+    nvim_win: Rc<RefCell<Option<Value>>>,
+    /// Source id of the cursor's next blink phase.
+    blink_source_id: Rc<RefCell<Option<glib::SourceId>>>,
+    /// Source id of the visual bell flash's end.
+    flash_source_id: Rc<RefCell<Option<glib::SourceId>>>,
     /// Input context that need to be updated for the cursor position
     im_context: Option<gtk::IMMulticontext>,
 }
@@ -88,6 +180,7 @@ impl Grid {
         rows: usize,
         hl_defs: &HlDefs,
         enable_cursor_animations: bool,
+        kinetic: KineticScroll,
     ) -> Self {
         let da = DrawingArea::new();
         let ctx = Rc::new(RefCell::new(Context::new(
@@ -108,12 +201,27 @@ impl Grid {
         }));
 
         let eb = EventBox::new();
-        eb.add_events(EventMask::SCROLL_MASK);
+        eb.add_events(
+            EventMask::SCROLL_MASK
+                | EventMask::SMOOTH_SCROLL_MASK
+                | EventMask::BUTTON_PRESS_MASK
+                | EventMask::BUTTON_RELEASE_MASK
+                | EventMask::BUTTON_MOTION_MASK
+                | EventMask::POINTER_MOTION_MASK
+                | EventMask::LEAVE_NOTIFY_MASK,
+        );
         eb.add(&da);
 
+        // Everything that has changed since the previous frame is painted
+        // on the frame clock's ticks.
         da.add_tick_callback(clone!(ctx => move |da, clock| {
             let mut ctx = ctx.borrow_mut();
-            ctx.tick(da, clock);
+            if ctx.tick(da, clock) {
+                let areas = std::mem::take(&mut ctx.queue_draw_area);
+                for (x, y, w, h) in damage_rects(&areas) {
+                    da.queue_draw_area(x, y, w, h);
+                }
+            }
             glib::Continue(true)
         }));
 
@@ -123,6 +231,13 @@ impl Grid {
             eb,
             context: ctx,
             drag_position: Rc::new(RefCell::new((0, 0))),
+            clicks: Rc::new(RefCell::new(ClickCounter::default())),
+            selection: Rc::new(RefCell::new(None)),
+            scroll_remainder: Rc::new(RefCell::new(0.0)),
+            kinetic,
+            nvim_win: Rc::new(RefCell::new(None)),
+            blink_source_id: Rc::new(RefCell::new(None)),
+            flash_source_id: Rc::new(RefCell::new(None)),
             im_context: None,
         }
     }
@@ -131,34 +246,46 @@ impl Grid {
         self.eb.clone().upcast()
     }
 
+    /// Destroys the grid's widget. The mouse event handlers hold clones of
+    /// the grid, so the widget isn't freed when the grid is dropped.
+    pub fn destroy(&self) {
+        self.eb.destroy();
+    }
+
     pub fn flush(&self, hl_defs: &HlDefs) {
         let mut ctx = self.context.borrow_mut();
+        render::put_pending_lines(
+            &mut ctx,
+            &self.da.get_pango_context(),
+            hl_defs,
+        );
 
         if let Some(cell) = ctx.cell_at_cursor() {
-            // If cursor isn't blinking, drawn the inverted cell into
-            // the cursor's cairo context.
-            if ctx.cursor.blink_on == 0 {
-                render::cursor_cell(
-                    &ctx.cursor_context,
-                    &self.da.get_pango_context(),
-                    &cell,
-                    &ctx.cell_metrics,
-                    hl_defs,
-                );
-            }
-
-            // Update cursor color.
-            let hl = hl_defs.get(&cell.hl_id).unwrap();
-            ctx.cursor.color = hl.foreground.unwrap_or(hl_defs.default_fg);
+            // Draw the cell with the cursor's colors into the cursor's
+            // cairo context.
+            render::cursor_cell(
+                &ctx.cursor_context,
+                &self.da.get_pango_context(),
+                &ctx.glyph_cache,
+                &cell,
+                &ctx.cell_metrics,
+                hl_defs,
+                ctx.cursor.attr_id,
+                ctx.cursor.color,
+                &ctx.text_options,
+            );
         }
 
-        while let Some(area) = ctx.queue_draw_area.pop() {
-            self.da.queue_draw_area(
-                area.0.floor() as i32,
-                area.1.floor() as i32,
-                area.2.ceil() as i32,
-                area.3.ceil() as i32,
-            );
+        // The areas are queued for redraw on the next frame (see `tick`).
+        // Keep them from piling up in the meantime, e.g. when the window
+        // is hidden and there are no frames.
+        if ctx.queue_draw_area.len() > MAX_DAMAGE_RECTS * 4 {
+            ctx.queue_draw_area = damage_rects(&ctx.queue_draw_area)
+                .into_iter()
+                .map(|(x, y, w, h)| {
+                    (f64::from(x), f64::from(y), f64::from(w), f64::from(h))
+                })
+                .collect();
         }
     }
 
@@ -167,18 +294,43 @@ impl Grid {
         self.im_context = Some(im_context.clone());
     }
 
+    /// Moves the input method (see `set_im_context`) to this grid and
+    /// focuses it.
+    pub fn focus_im_context(&self) {
+        if let Some(ref im_context) = self.im_context {
+            im_context.set_client_window(self.da.get_window().as_ref());
+            im_context.focus_in();
+        }
+    }
+
+    /// Returns the cell (`(row, col)`) at pixel position `(x, y)`, relative
+    /// to the grid. Positions outside of the grid (e.g. when dragging over
+    /// the grid's edges) are clamped to the nearest cell.
+    pub fn pixel_to_cell(&self, x: f64, y: f64) -> (u64, u64) {
+        let ctx = self.context.borrow();
+        let rows = ctx.rows.len();
+        let cols = ctx.rows.get(0).map(|row| row.len).unwrap_or(0);
+        position_to_cell(
+            (x, y),
+            (ctx.cell_metrics.width, ctx.cell_metrics.height),
+            (rows, cols),
+        )
+    }
+
+    /// Returns the pixel position, relative to the grid, of the top left
+    /// corner of cell `(row, col)`. Cells outside of the grid are clamped to
+    /// the nearest cell.
+    pub fn cell_to_pixel(&self, row: u64, col: u64) -> (f64, f64) {
+        let ctx = self.context.borrow();
+        cell_to_pixel(&ctx, row, col)
+    }
+
     /// Returns position (+ width and height) for cell (row, col) relative
     /// to the top level window of this grid.
     pub fn get_rect_for_cell(&self, row: u64, col: u64) -> gdk::Rectangle {
+        let (x, y) = self.cell_to_pixel(row, col);
         let ctx = self.context.borrow();
 
-        let (x, y) = render::get_coords(
-            ctx.cell_metrics.height,
-            ctx.cell_metrics.width,
-            row as f64,
-            col as f64,
-        );
-
         let (x, y) = self
             .eb
             .translate_coordinates(
@@ -196,27 +348,113 @@ impl Grid {
         }
     }
 
+    /// Sets the nvim window that is shown on this grid.
+    pub fn set_nvim_win(&self, win: Value) {
+        self.nvim_win.replace(Some(win));
+    }
+
+    /// Returns a handle to the nvim window shown on this grid. The handle
+    /// stays up to date when the window changes.
+    pub fn nvim_win(&self) -> Rc<RefCell<Option<Value>>> {
+        self.nvim_win.clone()
+    }
+
     /// Connects `f` to internal widget's scroll events. `f` params are scroll
-    /// direction, row, col.
+    /// direction, if shift is held, row, col.
+    ///
+    /// Smooth scroll events (e.g. from touchpads) are accumulated and `f` is
+    /// called once for each full scroll step. If kinetic scrolling is
+    /// enabled, `f` keeps getting called for a while after a smooth scroll.
     pub fn connect_scroll_events<F: 'static>(&self, f: F)
     where
-        F: Fn(ScrollDirection, u64, u64) -> Inhibit,
+        F: Fn(ScrollDirection, bool, u64, u64) -> Inhibit,
     {
-        let ctx = self.context.clone();
+        let grid = self.clone();
+        let scroll_remainder = self.scroll_remainder.clone();
+        let kinetic = self.kinetic.clone();
+        let f = Rc::new(f);
 
         self.eb.connect_scroll_event(move |_, e| {
-            let ctx = ctx.borrow();
+            let (x, y) = e.get_position();
+            let (row, col) = grid.pixel_to_cell(x, y);
+            let shift = e.get_state().contains(ModifierType::SHIFT_MASK);
+
+            match e.get_direction() {
+                gdk::ScrollDirection::Up => {
+                    kinetic.stop();
+                    f(ScrollDirection::Up, shift, row, col)
+                }
+                gdk::ScrollDirection::Smooth => {
+                    let delta = e.get_delta().1;
+                    kinetic.scroll(
+                        delta,
+                        e.get_time(),
+                        e.is_scroll_stop_event(),
+                        clone!(scroll_remainder, f => move |delta| {
+                            scroll_steps(&scroll_remainder, delta, |dir| {
+                                f(dir, shift, row, col)
+                            });
+                        }),
+                    );
+
+                    scroll_steps(&scroll_remainder, delta, |dir| {
+                        f(dir, shift, row, col)
+                    })
+                }
+                _ => {
+                    kinetic.stop();
+                    f(ScrollDirection::Down, shift, row, col)
+                }
+            }
+        });
+    }
 
-            let dir = match e.get_direction() {
-                gdk::ScrollDirection::Up => ScrollDirection::Up,
-                _ => ScrollDirection::Down,
-            };
+    /// Connects `f` to the pointer moving over the grid, without any buttons
+    /// held. `f` params are row, col and the pointer's position relative to
+    /// the root window. `f` is only called when the cell under the pointer
+    /// changes, and it's called with None when the pointer leaves the grid
+    /// or a button is pressed.
+    pub fn connect_hover<F: 'static>(&self, f: F)
+    where
+        F: Fn(Option<(u64, u64, (f64, f64))>),
+    {
+        let grid = self.clone();
+        let f = Rc::new(f);
+        let hover_position = Rc::new(Cell::new(None));
+
+        self.eb.connect_motion_notify_event(
+            clone!(f, hover_position => move |_, e| {
+                let buttons = ModifierType::BUTTON1_MASK
+                    | ModifierType::BUTTON2_MASK
+                    | ModifierType::BUTTON3_MASK;
+                if e.get_state().intersects(buttons) {
+                    return Inhibit(false);
+                }
+
+                let (x, y) = e.get_position();
+                let (row, col) = grid.pixel_to_cell(x, y);
+
+                if hover_position.get() != Some((row, col)) {
+                    hover_position.set(Some((row, col)));
+                    f(Some((row, col, e.get_root())));
+                }
 
-            let pos = e.get_position();
-            let col = (pos.0 / ctx.cell_metrics.width).floor() as u64;
-            let row = (pos.1 / ctx.cell_metrics.height).floor() as u64;
+                Inhibit(false)
+            }),
+        );
 
-            f(dir, row, col)
+        self.eb.connect_leave_notify_event(
+            clone!(f, hover_position => move |_, _| {
+                hover_position.set(None);
+                f(None);
+                Inhibit(false)
+            }),
+        );
+
+        self.eb.connect_button_press_event(move |_, _| {
+            hover_position.set(None);
+            f(None);
+            Inhibit(false)
         });
     }
 
@@ -226,24 +464,28 @@ impl Grid {
     where
         F: Fn(MouseButton, u64, u64) -> Inhibit,
     {
-        let ctx = self.context.clone();
+        let grid = self.clone();
         let drag_position = self.drag_position.clone();
 
         self.eb.connect_motion_notify_event(move |_, e| {
-            let ctx = ctx.borrow();
             let mut drag_position = drag_position.borrow_mut();
 
-            let button = match e.get_state() {
-                ModifierType::BUTTON3_MASK => MouseButton::Right,
-                ModifierType::BUTTON2_MASK => MouseButton::Middle,
-                _ => MouseButton::Left,
+            // Only motion with a button held is a drag.
+            let state = e.get_state();
+            let button = if state.contains(ModifierType::BUTTON1_MASK) {
+                MouseButton::Left
+            } else if state.contains(ModifierType::BUTTON3_MASK) {
+                MouseButton::Right
+            } else if state.contains(ModifierType::BUTTON2_MASK) {
+                MouseButton::Middle
+            } else {
+                return Inhibit(false);
             };
 
-            let pos = e.get_position();
-            let col = (pos.0 / ctx.cell_metrics.width).floor() as u64;
-            let row = (pos.1 / ctx.cell_metrics.height).floor() as u64;
+            let (x, y) = e.get_position();
+            let (row, col) = grid.pixel_to_cell(x, y);
 
-            if drag_position.0 != col || drag_position.1 != row {
+            if *drag_position != (col, row) {
                 *drag_position = (col, row);
                 f(button, row, col)
             } else {
@@ -253,15 +495,22 @@ impl Grid {
     }
 
     /// Connects `f` to internal widget's mouse button press event. `f` params
-    /// are button, row, col.
+    /// are button, row, col, the click count (e.g. 2 for double clicks) and
+    /// if shift is held.
     pub fn connect_mouse_button_press_events<F: 'static>(&self, f: F)
     where
-        F: Fn(MouseButton, u64, u64) -> Inhibit,
+        F: Fn(MouseButton, u64, u64, u8, bool) -> Inhibit,
     {
-        let ctx = self.context.clone();
+        let grid = self.clone();
+        let drag_position = self.drag_position.clone();
+        let clicks = self.clicks.clone();
 
-        self.eb.connect_button_press_event(move |_, e| {
-            let ctx = ctx.borrow();
+        self.eb.connect_button_press_event(move |eb, e| {
+            // GTK follows the second and third presses with its own double
+            // and triple press events, but we count the clicks ourselves.
+            if e.get_event_type() != gdk::EventType::ButtonPress {
+                return Inhibit(false);
+            }
 
             let button = match e.get_button() {
                 3 => MouseButton::Right,
@@ -269,11 +518,21 @@ impl Grid {
                 _ => MouseButton::Left,
             };
 
-            let pos = e.get_position();
-            let col = (pos.0 / ctx.cell_metrics.width).floor() as u64;
-            let row = (pos.1 / ctx.cell_metrics.height).floor() as u64;
+            let (x, y) = e.get_position();
+            let (row, col) = grid.pixel_to_cell(x, y);
+            // Drags start from the pressed cell.
+            drag_position.replace((col, row));
 
-            f(button, row, col)
+            let count = clicks.borrow_mut().press(
+                e.get_button(),
+                e.get_time(),
+                (row, col),
+                double_click_time(eb),
+            );
+
+            let shift = e.get_state().contains(ModifierType::SHIFT_MASK);
+
+            f(button, row, col, count, shift)
         });
     }
 
@@ -283,25 +542,83 @@ impl Grid {
     where
         F: Fn(MouseButton, u64, u64) -> Inhibit,
     {
-        let ctx = self.context.clone();
+        let grid = self.clone();
 
         self.eb.connect_button_release_event(move |_, e| {
-            let ctx = ctx.borrow();
-
             let button = match e.get_button() {
                 3 => MouseButton::Right,
                 2 => MouseButton::Middle,
                 _ => MouseButton::Left,
             };
 
-            let pos = e.get_position();
-            let col = (pos.0 / ctx.cell_metrics.width).floor() as u64;
-            let row = (pos.1 / ctx.cell_metrics.height).floor() as u64;
+            let (x, y) = e.get_position();
+            let (row, col) = grid.pixel_to_cell(x, y);
 
             f(button, row, col)
         });
     }
 
+    /// Connects `f` to mouse selections, e.g. drags with the left button.
+    /// `f` param is the selection, and it's called when the button is
+    /// released.
+    pub fn connect_selection<F: 'static>(&self, f: F)
+    where
+        F: Fn(Selection),
+    {
+        let grid = self.clone();
+        let selection = self.selection.clone();
+        self.eb.connect_button_press_event(move |_, e| {
+            if e.get_button() == 1 {
+                let (x, y) = e.get_position();
+                let (row, col) = grid.pixel_to_cell(x, y);
+                selection.replace(Some(((row, col), (row, col))));
+            }
+
+            Inhibit(false)
+        });
+
+        let grid = self.clone();
+        let selection = self.selection.clone();
+        self.eb.connect_motion_notify_event(move |_, e| {
+            if let Some((_, ref mut end)) = *selection.borrow_mut() {
+                let (x, y) = e.get_position();
+                let (row, col) = grid.pixel_to_cell(x, y);
+                *end = (row, col);
+            }
+
+            Inhibit(false)
+        });
+
+        let ctx = self.context.clone();
+        let selection = self.selection.clone();
+        self.eb.connect_button_release_event(move |_, e| {
+            if e.get_button() != 1 {
+                return Inhibit(false);
+            }
+
+            // A plain click doesn't select anything.
+            let sel = selection.borrow_mut().take();
+            if let Some((a, b)) = sel.filter(|(a, b)| a != b) {
+                let ctx = ctx.borrow();
+                let (start, end) = selection_range(a, b);
+                let rows = ctx
+                    .rows
+                    .iter()
+                    .skip(start.0)
+                    .take(end.0 + 1 - start.0)
+                    .cloned()
+                    .collect();
+                f(Selection {
+                    rows,
+                    start: (0, start.1),
+                    end: (end.0 - start.0, end.1),
+                });
+            }
+
+            Inhibit(false)
+        });
+    }
+
     /// Connects `f` to internal widget's resize events. `f` params are rows, cols.
     pub fn connect_da_resize<F: 'static>(&self, f: F)
     where
@@ -321,18 +638,26 @@ impl Grid {
         });
     }
 
-    pub fn put_line(&self, line: GridLineSegment, hl_defs: &HlDefs) {
+    /// Queues `line` to be rendered on the next flush.
+    pub fn put_line(&self, line: GridLineSegment) {
         let mut ctx = self.context.borrow_mut();
-
-        render::put_line(&mut ctx, &self.da.get_pango_context(), line, hl_defs);
+        ctx.pending_lines.push(line);
     }
 
     pub fn redraw(&self, hl_defs: &HlDefs) {
         let mut ctx = self.context.borrow_mut();
+        render::put_pending_lines(
+            &mut ctx,
+            &self.da.get_pango_context(),
+            hl_defs,
+        );
         render::redraw(&mut ctx, &self.da.get_pango_context(), hl_defs);
     }
 
     pub fn cursor_goto(&self, row: u64, col: u64) {
+        // Like in nvim, moving the cursor restarts the blinking.
+        self.restart_blink();
+
         let clock = self.da.get_frame_clock().unwrap();
         let mut ctx = self.context.borrow_mut();
         ctx.cursor_goto(row, col, &clock);
@@ -349,6 +674,42 @@ impl Grid {
         }
     }
 
+    /// Returns the text from `start` to `end` (both `(row, col)`, `end`'s
+    /// col being exclusive). Rows are separated by newlines, and trailing
+    /// whitespace (e.g. the padding at the end of a row) is removed.
+    pub fn get_text_range(
+        &self,
+        start: (usize, usize),
+        end: (usize, usize),
+    ) -> String {
+        let text = self.dump_region(start.0, start.1, end.0, end.1);
+        let lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+
+        lines.join("\n").trim_end().to_string()
+    }
+
+    /// Returns the text from (`start_row`, `start_col`) to (`end_row`,
+    /// `end_col`), as it is displayed. `end_col` is exclusive. Rows are
+    /// separated by newlines.
+    pub fn dump_region(
+        &self,
+        start_row: usize,
+        start_col: usize,
+        end_row: usize,
+        end_col: usize,
+    ) -> String {
+        let ctx = self.context.borrow();
+        rows_text(&ctx.rows, (start_row, start_col), (end_row, end_col))
+            .join("\n")
+    }
+
+    /// Returns the text of each row, as it is displayed.
+    #[allow(unused)] // Not used currently, but meant for tooling.
+    pub fn dump_all(&self) -> Vec<String> {
+        let ctx = self.context.borrow();
+        ctx.rows.iter().map(|row| row.text(0, row.len)).collect()
+    }
+
     pub fn get_grid_metrics(&self) -> GridMetrics {
         let ctx = self.context.borrow();
 
@@ -390,13 +751,21 @@ impl Grid {
         hl_defs: &HlDefs,
     ) {
         let mut ctx = self.context.borrow_mut();
+        // Apply the pending lines while the rows are still as nvim
+        // expects them to be.
+        render::put_pending_lines(
+            &mut ctx,
+            &self.da.get_pango_context(),
+            hl_defs,
+        );
         ctx.resize(&self.da, win, cols as usize, rows as usize, hl_defs);
     }
 
     pub fn clear(&self, hl_defs: &HlDefs) {
         let mut ctx = self.context.borrow_mut();
 
-        // Clear internal grid (rows).
+        // Clear internal grid (rows), including the lines not rendered yet.
+        ctx.pending_lines.clear();
         for row in ctx.rows.iter_mut() {
             row.clear();
         }
@@ -413,7 +782,18 @@ impl Grid {
     ) {
         let mut ctx = self.context.borrow_mut();
 
+        // The pending lines are for the rows before the scroll.
+        render::put_pending_lines(
+            &mut ctx,
+            &self.da.get_pango_context(),
+            hl_defs,
+        );
         render::scroll(&mut ctx, hl_defs, reg, rows);
+
+        if let Some(clock) = self.da.get_frame_clock() {
+            let bg = hl_defs.default_bg;
+            ctx.start_scroll_animation(reg, rows, bg, clock.get_frame_time());
+        }
     }
 
     pub fn set_active(&self, active: bool) {
@@ -424,14 +804,24 @@ impl Grid {
 
     /// Set a new font and line space. This will likely change the cell metrics.
     /// Use `calc_size` to receive the updated size (cols and rows) of the grid.
+    /// Updates the cell metrics, and rerenders the grid with them.
     pub fn update_cell_metrics(
         &self,
         font: Font,
         line_space: i64,
         win: &gdk::Window,
+        hl_defs: &HlDefs,
     ) {
         let mut ctx = self.context.borrow_mut();
         ctx.update_metrics(font, line_space, &self.da, win);
+
+        // Recreate the surface for the new metrics, and render the
+        // contents on it. Nvim won't resend the contents for grids that
+        // don't change their size.
+        let rows = ctx.rows.len();
+        let cols = ctx.rows.get(0).map(|r| r.len()).unwrap_or(0);
+        ctx.resize(&self.da, win, cols, rows, hl_defs);
+        render::redraw(&mut ctx, &self.da.get_pango_context(), hl_defs);
     }
 
     /// Get the current line space value.
@@ -447,22 +837,622 @@ impl Grid {
     }
 
     pub fn set_mode(&self, mode: &ModeInfo) {
+        {
+            let mut ctx = self.context.borrow_mut();
+            ctx.cursor.cell_percentage = mode.cell_percentage;
+            ctx.cursor.shape = mode.cursor_shape;
+            ctx.cursor.attr_id = mode.attr_id;
+        }
+
+        self.set_blink(mode.blink_on, mode.blink_off, mode.blink_wait);
+    }
+
+    /// Sets the cursor's blink times (in ms). If any of them is zero, the
+    /// cursor doesn't blink.
+    pub fn set_blink(&self, on_ms: u64, off_ms: u64, wait_ms: u64) {
+        {
+            let mut ctx = self.context.borrow_mut();
+            ctx.cursor.blink_on = on_ms;
+            ctx.cursor.blink_off = off_ms;
+            ctx.cursor.blink_wait = wait_ms;
+        }
+
+        self.restart_blink();
+    }
+
+    /// Shows the cursor and starts its blink cycle from the beginning.
+    fn restart_blink(&self) {
+        if let Some(id) = self.blink_source_id.borrow_mut().take() {
+            glib::source::source_remove(id);
+        }
+
+        let delay = {
+            let mut ctx = self.context.borrow_mut();
+            let was_hidden = ctx.cursor.blink_hidden;
+            let delay = ctx.cursor.reset_blink();
+
+            if was_hidden {
+                let (x, y, w, h) = ctx.get_cursor_rect();
+                self.da.queue_draw_area(x, y, w, h);
+            }
+
+            // The blinking is resumed once nvim isn't busy anymore.
+            delay.filter(|_| !ctx.busy)
+        };
+
+        if let Some(delay) = delay {
+            schedule_blink(
+                self.context.clone(),
+                self.da.clone(),
+                self.blink_source_id.clone(),
+                delay,
+            );
+        }
+    }
+
+    /// Briefly flashes the grid (inverts its colors), for the visual bell.
+    pub fn flash(&self) {
+        // A new flash extends the ongoing one.
+        if let Some(id) = self.flash_source_id.borrow_mut().take() {
+            glib::source::source_remove(id);
+        }
+
+        self.context.borrow_mut().flash = true;
+        self.da.queue_draw();
+
+        let ctx = self.context.clone();
+        let da = self.da.clone();
+        let source_id = self.flash_source_id.clone();
+        let id = gtk::timeout_add(FLASH_DURATION_MS, move || {
+            // The source is removed once we return, so forget about it.
+            source_id.borrow_mut().take();
+
+            ctx.borrow_mut().flash = false;
+            da.queue_draw();
+
+            Continue(false)
+        });
+
+        self.flash_source_id.replace(Some(id));
+    }
+
+    /// Flashes `ranges` (row, and start and end columns) of the grid with
+    /// `color`, fading out in `duration` ms. Used for showing yanked text.
+    pub fn flash_yank(
+        &self,
+        ranges: Vec<(u64, u64, u64)>,
+        color: Color,
+        duration: u64,
+    ) {
+        let clock = match self.da.get_frame_clock() {
+            Some(clock) => clock,
+            None => return,
+        };
+        let now = clock.get_frame_time();
+
         let mut ctx = self.context.borrow_mut();
+        ctx.yank_flash = Some(YankFlash {
+            ranges,
+            color,
+            start_time: now,
+            end_time: now + 1000 * duration as i64,
+        });
+    }
 
-        ctx.cursor.blink_on = mode.blink_on;
-        ctx.cursor.cell_percentage = mode.cell_percentage;
+    /// Sets the input method's preedit text, that is drawn over the cursor.
+    pub fn set_preedit(&self, preedit: Option<Preedit>) {
+        let mut ctx = self.context.borrow_mut();
+        if ctx.preedit.is_none() && preedit.is_none() {
+            return;
+        }
+
+        ctx.preedit = preedit;
+        self.da.queue_draw();
     }
 
-    pub fn set_busy(&self, busy: bool) {
+    /// Highlights the matches of `query` (see `find_matches`) with `color`.
+    /// If the current match is still found, it stays as the current one.
+    pub fn set_find(&self, query: &str, color: Color, hl_defs: &HlDefs) {
         let mut ctx = self.context.borrow_mut();
+        // Search what nvim has sent, rendered or not.
+        render::put_pending_lines(
+            &mut ctx,
+            &self.da.get_pango_context(),
+            hl_defs,
+        );
 
-        ctx.busy = busy;
+        let ranges = find_matches(&ctx.rows, query);
+        let current = ctx
+            .find
+            .current
+            .and_then(|i| ctx.find.ranges.get(i))
+            .and_then(|cur| ranges.iter().position(|r| r == cur));
+
+        ctx.find = FindMatches {
+            ranges,
+            current,
+            color,
+        };
+        self.da.queue_draw();
+    }
+
+    pub fn clear_find(&self) {
+        let mut ctx = self.context.borrow_mut();
+        if ctx.find.ranges.is_empty() {
+            return;
+        }
+
+        ctx.find = FindMatches::default();
+        self.da.queue_draw();
+    }
+
+    /// Moves to the next (or previous, if `forward` is false) match. Without
+    /// a current match, the first (or last) match becomes the current one.
+    /// Returns false if there's no match to move to.
+    pub fn find_step(&self, forward: bool) -> bool {
+        let mut ctx = self.context.borrow_mut();
+        let len = ctx.find.ranges.len();
+        let next = match (ctx.find.current, forward) {
+            (_, _) if len == 0 => None,
+            (None, true) => Some(0),
+            (None, false) => Some(len - 1),
+            (Some(i), true) if i + 1 < len => Some(i + 1),
+            (Some(i), false) if i > 0 => Some(i - 1),
+            _ => None,
+        };
+
+        match next {
+            Some(next) => {
+                ctx.find.current = Some(next);
+                self.da.queue_draw();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Forgets the current match, so that the next `find_step` starts from
+    /// the first (or last) match.
+    pub fn reset_find_current(&self) {
+        self.context.borrow_mut().find.current = None;
+    }
+
+    /// Hides the cursor while nvim is busy, and shows a busy pointer over
+    /// the grid if `pointer` is set.
+    pub fn set_busy(&self, busy: bool, pointer: bool) {
+        {
+            let mut ctx = self.context.borrow_mut();
+            if ctx.busy == busy {
+                return;
+            }
+            ctx.busy = busy;
+
+            let (x, y, w, h) = ctx.get_cursor_rect();
+            self.da.queue_draw_area(x, y, w, h);
+        }
+
+        if busy {
+            // Nothing to blink while the cursor is hidden.
+            if let Some(id) = self.blink_source_id.borrow_mut().take() {
+                glib::source::source_remove(id);
+            }
+        } else {
+            self.restart_blink();
+        }
+
+        if let Some(win) = self.da.get_window() {
+            let cursor = if busy && pointer {
+                gdk::Cursor::new_from_name(&self.da.get_display(), "wait")
+            } else {
+                None
+            };
+            win.set_cursor(cursor.as_ref());
+        }
     }
 
     pub fn enable_cursor_animations(&self, enable: bool) {
         let mut ctx = self.context.borrow_mut();
         ctx.cursor.disable_animation = !enable;
     }
+
+    /// Enables or disables ligatures. Rerenders the grid.
+    pub fn set_ligatures(&self, enable: bool, hl_defs: &HlDefs) {
+        let mut ctx = self.context.borrow_mut();
+        if ctx.text_options.ligatures == enable {
+            return;
+        }
+
+        ctx.text_options.ligatures = enable;
+        render::redraw(&mut ctx, &self.da.get_pango_context(), hl_defs);
+    }
+
+    /// Enables or disables synthetic bold and italic. Rerenders the grid.
+    pub fn set_synthetic_styles(&self, enable: bool, hl_defs: &HlDefs) {
+        let mut ctx = self.context.borrow_mut();
+        if ctx.text_options.synthetic_styles == enable {
+            return;
+        }
+
+        ctx.text_options.synthetic_styles = enable;
+        render::redraw(&mut ctx, &self.da.get_pango_context(), hl_defs);
+    }
+
+    /// Enables or disables drawing the cursor line and the cursor column
+    /// ourselves (see `CursorOverlay`). Rerenders the grid.
+    pub fn set_cursor_overlay(
+        &self,
+        line: bool,
+        column: bool,
+        hl_defs: &HlDefs,
+    ) {
+        let mut ctx = self.context.borrow_mut();
+        let overlay = &mut ctx.cursor_overlay;
+        if overlay.line == line && overlay.column == column {
+            return;
+        }
+
+        overlay.line = line;
+        overlay.column = column;
+        render::redraw(&mut ctx, &self.da.get_pango_context(), hl_defs);
+    }
+
+    /// Enables or disables drawing bold text with base ANSI colors in their
+    /// bright variants. Rerenders the grid.
+    pub fn set_bold_is_bright(&self, enable: bool, hl_defs: &HlDefs) {
+        let mut ctx = self.context.borrow_mut();
+        if ctx.text_options.bold_is_bright == enable {
+            return;
+        }
+
+        ctx.text_options.bold_is_bright = enable;
+        render::redraw(&mut ctx, &self.da.get_pango_context(), hl_defs);
+    }
+
+    /// Enables or disables drawing the backgrounds with the highlights' blend
+    /// values. Recreates the surface and rerenders the grid.
+    pub fn set_transparent(&self, transparent: bool, hl_defs: &HlDefs) {
+        let mut ctx = self.context.borrow_mut();
+        if ctx.text_options.transparent == transparent {
+            return;
+        }
+
+        ctx.text_options.transparent = transparent;
+        self.recreate_surface(&mut ctx, hl_defs);
+    }
+
+    /// Dims the grid by drawing black with `dim` opacity (0.0-1.0) over it.
+    pub fn set_dim(&self, dim: f64) {
+        let mut ctx = self.context.borrow_mut();
+        if (ctx.dim - dim).abs() < f64::EPSILON {
+            return;
+        }
+
+        ctx.dim = dim;
+        self.da.queue_draw();
+    }
+
+    /// Sets the opacity (0.0-1.0) of the cells with the default background.
+    /// Recreates the surface and rerenders the grid.
+    pub fn set_background_opacity(&self, opacity: f64, hl_defs: &HlDefs) {
+        let mut ctx = self.context.borrow_mut();
+        if (ctx.text_options.background_opacity - opacity).abs() < f64::EPSILON
+        {
+            return;
+        }
+
+        ctx.text_options.background_opacity = opacity;
+        self.recreate_surface(&mut ctx, hl_defs);
+    }
+
+    /// Recreates the surface (e.g. when it needs an alpha channel) and
+    /// rerenders the grid.
+    fn recreate_surface(&self, ctx: &mut Context, hl_defs: &HlDefs) {
+        // Without a window, the surface is recreated on the next resize.
+        if let Some(win) = self.da.get_window() {
+            let rows = ctx.rows.len();
+            let cols = ctx.rows.get(0).map(|r| r.len()).unwrap_or(0);
+            ctx.resize(&self.da, &win, cols, rows, hl_defs);
+            render::redraw(ctx, &self.da.get_pango_context(), hl_defs);
+        }
+    }
+
+    /// Sets the OpenType font features, e.g. `"cv01" 1, "zero" 1`. Rerenders
+    /// the grid.
+    pub fn set_font_features(&self, features: &str, hl_defs: &HlDefs) {
+        let mut ctx = self.context.borrow_mut();
+        if ctx.text_options.font_features == features {
+            return;
+        }
+
+        ctx.text_options.font_features = features.to_string();
+        ctx.glyph_cache.clear();
+        render::redraw(&mut ctx, &self.da.get_pango_context(), hl_defs);
+    }
+
+    /// Sets the font families (comma separated) preferred for double
+    /// width cells. Rerenders the grid.
+    pub fn set_font_wide(&self, families: Option<String>, hl_defs: &HlDefs) {
+        let mut ctx = self.context.borrow_mut();
+        if ctx.text_options.font_wide == families {
+            return;
+        }
+
+        ctx.text_options.font_wide = families;
+        ctx.glyph_cache.clear();
+        render::redraw(&mut ctx, &self.da.get_pango_context(), hl_defs);
+    }
+
+    /// Limits the grid's frame rate to `fps`, or if it's 0, draws on every
+    /// frame of the frame clock. This only limits the repaints, see
+    /// `Context::tick`.
+    pub fn set_max_fps(&self, fps: u64) {
+        let mut ctx = self.context.borrow_mut();
+        ctx.max_fps = fps;
+    }
+
+    /// Sets a fixed color for the cursor, instead of the one from its
+    /// highlight. `None` goes back to the highlight's color. The cursor's
+    /// cell is drawn with the new color on the next flush.
+    pub fn set_cursor_color(&self, color: Option<Color>) {
+        let mut ctx = self.context.borrow_mut();
+        ctx.cursor.color = color;
+
+        let (x, y, w, h) = ctx.get_cursor_rect();
+        ctx.queue_draw_area.push((
+            f64::from(x),
+            f64::from(y),
+            f64::from(w),
+            f64::from(h),
+        ));
+    }
+
+    /// Sets the duration (in ms) of the cursor's movement animation.
+    pub fn set_cursor_animation_duration(&self, duration: u64) {
+        let mut ctx = self.context.borrow_mut();
+        ctx.cursor.animation_duration = duration;
+    }
+
+    pub fn enable_scroll_animation(&self, enable: bool) {
+        let mut ctx = self.context.borrow_mut();
+        ctx.enable_scroll_animation = enable;
+    }
+
+    pub fn set_scroll_animation_duration(&self, duration: u64) {
+        let mut ctx = self.context.borrow_mut();
+        ctx.scroll_animation_duration = duration;
+    }
+}
+
+/// Duration (in ms) of the visual bell's flash.
+const FLASH_DURATION_MS: u32 = 80;
+
+/// Moves the cursor to its next blink phase after `delay` ms, and keeps
+/// doing so for the following phases.
+fn schedule_blink(
+    ctx: Rc<RefCell<Context>>,
+    da: DrawingArea,
+    source_id: Rc<RefCell<Option<glib::SourceId>>>,
+    delay: u64,
+) {
+    let id = gtk::timeout_add(
+        delay as u32,
+        clone!(ctx, da, source_id => move || {
+            // The source is removed once we return, so forget about it.
+            source_id.borrow_mut().take();
+
+            let next = {
+                let mut ctx = ctx.borrow_mut();
+                let next = ctx.cursor.advance_blink();
+
+                let (x, y, w, h) = ctx.get_cursor_rect();
+                da.queue_draw_area(x, y, w, h);
+
+                next
+            };
+
+            if let Some(next) = next {
+                schedule_blink(ctx.clone(), da.clone(), source_id.clone(), next);
+            }
+
+            Continue(false)
+        }),
+    );
+
+    source_id.replace(Some(id));
+}
+
+/// Returns the text of `rows` from `start` to `end` (both `(row, col)`,
+/// `end`'s col being exclusive), one string per row.
+fn rows_text(
+    rows: &[Row],
+    start: (usize, usize),
+    end: (usize, usize),
+) -> Vec<String> {
+    rows.iter()
+        .enumerate()
+        .skip(start.0)
+        .take((end.0 + 1).saturating_sub(start.0))
+        .map(|(i, row)| {
+            let from = if i == start.0 { start.1 } else { 0 };
+            let to = if i == end.0 { end.1 } else { row.len };
+            row.text(from, to)
+        })
+        .collect()
+}
+
+/// Returns the lines of a selection from `start` to `end` in `rows`, without
+/// the first `skip_cols` columns of each row and trailing whitespace.
+fn selection_lines(
+    rows: &[Row],
+    start: (usize, usize),
+    end: (usize, usize),
+    skip_cols: usize,
+) -> Vec<String> {
+    rows.iter()
+        .enumerate()
+        .skip(start.0)
+        .take((end.0 + 1).saturating_sub(start.0))
+        .map(|(i, row)| {
+            let from = if i == start.0 { start.1 } else { 0 };
+            let to = if i == end.0 { end.1 } else { row.len };
+            row.text(from.max(skip_cols), to).trim_end().to_string()
+        })
+        .collect()
+}
+
+/// Returns the matches of `query` in `rows`, as the row, and the start and
+/// end (exclusive) columns of each match. Like with 'smartcase', the case
+/// is ignored unless `query` has upper case characters.
+fn find_matches(rows: &[Row], query: &str) -> Vec<(usize, usize, usize)> {
+    if query.is_empty() {
+        return vec![];
+    }
+
+    let ignore_case = !query.chars().any(char::is_uppercase);
+    let query = if ignore_case {
+        query.to_lowercase()
+    } else {
+        query.to_string()
+    };
+
+    let mut matches = vec![];
+    for (i, row) in rows.iter().enumerate() {
+        // The row's text, and the column of each of the text's bytes.
+        let mut text = String::new();
+        let mut cols = vec![];
+        for col in 0..row.len {
+            let cell = row.cell_at(col).unwrap();
+            if ignore_case {
+                text.push_str(&cell.text.to_lowercase());
+            } else {
+                text.push_str(&cell.text);
+            }
+            cols.resize(text.len(), col);
+        }
+        cols.push(row.len);
+
+        let mut from = 0;
+        while let Some(pos) = text[from..].find(query.as_str()) {
+            let start = from + pos;
+            let end = start + query.len();
+            // The end is where the next cell with text starts, so that the
+            // both halves of double width cells are included.
+            matches.push((i, cols[start], cols[end]));
+            from = end;
+        }
+    }
+
+    matches
+}
+
+/// Returns the start and end (`end`'s col being exclusive) of a selection
+/// between cells `a` and `b`, which can be in either order.
+fn selection_range(
+    a: (u64, u64),
+    b: (u64, u64),
+) -> ((usize, usize), (usize, usize)) {
+    let (start, end) = if a <= b { (a, b) } else { (b, a) };
+
+    (
+        (start.0 as usize, start.1 as usize),
+        (end.0 as usize, end.1 as usize + 1),
+    )
+}
+
+/// Adds smooth scroll `delta` to `remainder` and calls `f` once for each
+/// full scroll step.
+fn scroll_steps<F>(remainder: &RefCell<f64>, delta: f64, f: F) -> Inhibit
+where
+    F: Fn(ScrollDirection) -> Inhibit,
+{
+    let (steps, rest) = accumulate_scroll_delta(*remainder.borrow(), delta);
+    remainder.replace(rest);
+
+    let mut inhibit = Inhibit(false);
+    for _ in 0..steps.abs() {
+        let dir = if steps < 0 {
+            ScrollDirection::Up
+        } else {
+            ScrollDirection::Down
+        };
+        inhibit = f(dir);
+    }
+
+    inhibit
+}
+
+/// Adds `delta` to `remainder` and returns the number of whole scroll steps
+/// (negative for up) and the remaining fraction. If the scroll direction
+/// changes, the old remainder is discarded.
+fn accumulate_scroll_delta(remainder: f64, delta: f64) -> (i64, f64) {
+    let remainder = if remainder * delta < 0.0 {
+        0.0
+    } else {
+        remainder
+    };
+
+    let total = remainder + delta;
+    let steps = total.trunc();
+
+    (steps as i64, total - steps)
+}
+
+/// Turns the areas changed since the previous flush into (integer) areas
+/// to redraw. Adjacent areas, like the segments of a line, are merged
+/// together, and if there are still too many areas, their bounding box is
+/// returned instead.
+fn damage_rects(areas: &[(f64, f64, f64, f64)]) -> Vec<(i32, i32, i32, i32)> {
+    let mut rects: Vec<(i32, i32, i32, i32)> = areas
+        .iter()
+        .map(|&(x, y, w, h)| {
+            let (x0, y0) = (x.floor(), y.floor());
+            (
+                x0 as i32,
+                y0 as i32,
+                ((x + w).ceil() - x0) as i32,
+                ((y + h).ceil() - y0) as i32,
+            )
+        })
+        .filter(|&(_, _, w, h)| w > 0 && h > 0)
+        .collect();
+
+    // Merge the areas on the same rows horizontally...
+    rects.sort_by_key(|&(x, y, _, h)| (y, h, x));
+    let mut merged: Vec<(i32, i32, i32, i32)> = vec![];
+    for r in rects {
+        match merged.last_mut() {
+            Some(last)
+                if last.1 == r.1 && last.3 == r.3 && r.0 <= last.0 + last.2 =>
+            {
+                last.2 = (last.0 + last.2).max(r.0 + r.2) - last.0;
+            }
+            _ => merged.push(r),
+        }
+    }
+
+    // ...and then the areas on top of each other vertically.
+    merged.sort_by_key(|&(x, y, w, _)| (x, w, y));
+    let mut rects: Vec<(i32, i32, i32, i32)> = vec![];
+    for r in merged {
+        match rects.last_mut() {
+            Some(last)
+                if last.0 == r.0 && last.2 == r.2 && r.1 <= last.1 + last.3 =>
+            {
+                last.3 = (last.1 + last.3).max(r.1 + r.3) - last.1;
+            }
+            _ => rects.push(r),
+        }
+    }
+
+    if rects.len() <= MAX_DAMAGE_RECTS {
+        return rects;
+    }
+
+    let x0 = rects.iter().map(|r| r.0).min().unwrap();
+    let y0 = rects.iter().map(|r| r.1).min().unwrap();
+    let x1 = rects.iter().map(|r| r.0 + r.2).max().unwrap();
+    let y1 = rects.iter().map(|r| r.1 + r.3).max().unwrap();
+    vec![(x0, y0, x1 - x0, y1 - y0)]
 }
 
 /// Handler for grid's drawingarea's draw event. Draws the internal cairo
@@ -476,21 +1466,405 @@ fn drawingarea_draw(cr: &cairo::Context, ctx: &mut Context) {
     cr.paint();
     cr.restore();
 
+    if ctx.scroll_animation.is_some() {
+        draw_scroll_animation(cr, ctx, &surface);
+    }
+
     // If we're not "busy", draw the cursor.
-    if !ctx.busy && ctx.active {
+    if !ctx.busy && ctx.active && !ctx.cursor.blink_hidden {
         let (x, y, w, h) = ctx.get_cursor_rect();
 
-        cr.save();
-        cr.rectangle(
+        let (rx, ry, rw, rh) = ctx.cursor.shape_rect(
             f64::from(x),
             f64::from(y),
-            f64::from(w) * ctx.cursor.cell_percentage,
+            f64::from(w),
             f64::from(h),
         );
+
+        cr.save();
+        cr.rectangle(rx, ry, rw, rh);
         let surface = ctx.cursor_context.get_target();
         surface.flush();
         cr.set_source_surface(&surface, x.into(), y.into());
         cr.fill();
         cr.restore();
     }
+
+    if !ctx.find.ranges.is_empty() {
+        draw_find_matches(cr, ctx);
+    }
+
+    if ctx.yank_flash.is_some() {
+        draw_yank_flash(cr, ctx);
+    }
+
+    if let Some(ref preedit) = ctx.preedit {
+        let (x, y, _, _) = ctx.get_cursor_rect();
+        render::preedit(
+            cr,
+            &ctx.cell_metrics,
+            preedit,
+            f64::from(x),
+            f64::from(y),
+        );
+    }
+
+    if ctx.flash {
+        cr.save();
+        cr.set_operator(cairo::Operator::Difference);
+        cr.set_source_rgb(1.0, 1.0, 1.0);
+        cr.paint();
+        cr.restore();
+    }
+
+    if ctx.dim > 0.0 {
+        cr.save();
+        cr.set_source_rgba(0.0, 0.0, 0.0, ctx.dim);
+        cr.paint();
+        cr.restore();
+    }
+}
+
+/// Draws the animated scroll region with its content shifted by the
+/// animation's current offset.
+fn draw_scroll_animation(
+    cr: &cairo::Context,
+    ctx: &Context,
+    surface: &cairo::Surface,
+) {
+    let anim = match ctx.scroll_animation {
+        Some(ref anim) => anim,
+        None => return,
+    };
+    let (x, y, w, h) = anim.rect;
+    let offset = ctx.scroll_offset;
+
+    cr.save();
+    cr.rectangle(x, y, w, h);
+    cr.clip();
+    cr.set_operator(cairo::Operator::Source);
+
+    cr.set_source_surface(surface, 0.0, offset);
+    cr.rectangle(x, y + offset, w, h);
+    cr.fill();
+
+    // The part of the region that the shifted content doesn't cover.
+    let (gap_y, gap_h) = if offset > 0.0 {
+        (y, offset)
+    } else {
+        (y + h + offset, -offset)
+    };
+    let bg = anim.bg;
+    let alpha = ctx.text_options.background_opacity;
+    cr.set_source_rgba(bg.r, bg.g, bg.b, alpha);
+    cr.rectangle(x, gap_y, w, gap_h);
+    cr.fill();
+    cr.restore();
+}
+
+/// Implements `Grid::cell_to_pixel`.
+pub fn cell_to_pixel(ctx: &Context, row: u64, col: u64) -> (f64, f64) {
+    let rows = ctx.rows.len();
+    let cols = ctx.rows.get(0).map(|row| row.len).unwrap_or(0);
+    cell_to_position(
+        (row, col),
+        (ctx.cell_metrics.width, ctx.cell_metrics.height),
+        (rows, cols),
+    )
+}
+
+/// Returns the cell (`(row, col)`) at pixel position `pos`, for a grid of
+/// `size` (`(rows, cols)`) cells. Clamps to the grid.
+fn position_to_cell(
+    (x, y): (f64, f64),
+    (cell_width, cell_height): (f64, f64),
+    (rows, cols): (usize, usize),
+) -> (u64, u64) {
+    let clamp = |v: f64, max: usize| {
+        (v.floor().max(0.0) as u64).min(max.saturating_sub(1) as u64)
+    };
+
+    (clamp(y / cell_height, rows), clamp(x / cell_width, cols))
+}
+
+/// Returns the pixel position of the top left corner of `cell` (`(row,
+/// col)`), for a grid of `size` (`(rows, cols)`) cells. Clamps to the grid.
+fn cell_to_position(
+    (row, col): (u64, u64),
+    (cell_width, cell_height): (f64, f64),
+    (rows, cols): (usize, usize),
+) -> (f64, f64) {
+    let clamp = |v: u64, max: usize| v.min(max.saturating_sub(1) as u64) as f64;
+
+    (
+        clamp(col, cols) * cell_width,
+        clamp(row, rows) * cell_height,
+    )
+}
+
+/// Draws translucent rectangles over the find matches, the current one
+/// being more opaque than the rest.
+fn draw_find_matches(cr: &cairo::Context, ctx: &Context) {
+    let cm = &ctx.cell_metrics;
+    let color = &ctx.find.color;
+
+    cr.save();
+    for (i, &(row, start, end)) in ctx.find.ranges.iter().enumerate() {
+        let (x, y) = cell_to_pixel(ctx, row as u64, start as u64);
+        let alpha = if ctx.find.current == Some(i) {
+            0.7
+        } else {
+            0.35
+        };
+
+        cr.set_source_rgba(color.r, color.g, color.b, alpha);
+        cr.rectangle(x, y, (end - start) as f64 * cm.width, cm.height);
+        cr.fill();
+    }
+    cr.restore();
+}
+
+/// Draws the fading yank flash over its ranges.
+fn draw_yank_flash(cr: &cairo::Context, ctx: &Context) {
+    let flash = match ctx.yank_flash {
+        Some(ref flash) => flash,
+        None => return,
+    };
+    let cm = &ctx.cell_metrics;
+    let color = &flash.color;
+
+    cr.save();
+    cr.set_source_rgba(
+        color.r,
+        color.g,
+        color.b,
+        flash.alpha(ctx.last_frame_time),
+    );
+    for &(row, start, end) in flash.ranges.iter() {
+        let (x, y) = cell_to_pixel(ctx, row, start);
+        let w = end.saturating_sub(start) as f64 * cm.width;
+        cr.rectangle(x, y, w, cm.height);
+    }
+    cr.fill();
+    cr.restore();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::nvim_bridge;
+
+    fn rows_from_lines(lines: &[&str], len: usize) -> Vec<Row> {
+        lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                let mut row = Row::new(len);
+                row.update(GridLineSegment {
+                    grid: 1,
+                    row: i as u64,
+                    col_start: 0,
+                    cells: line
+                        .chars()
+                        .map(|c| nvim_bridge::Cell {
+                            text: c.to_string(),
+                            hl_id: 0,
+                            repeat: 1,
+                            double_width: false,
+                        })
+                        .collect(),
+                });
+                row
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_position_to_cell() {
+        let cell = (10.0, 20.0);
+        let size = (5, 8);
+
+        assert_eq!((0, 0), position_to_cell((0.0, 0.0), cell, size));
+        assert_eq!((1, 2), position_to_cell((29.9, 39.9), cell, size));
+        assert_eq!((2, 3), position_to_cell((30.0, 40.0), cell, size));
+        // Outside of the grid.
+        assert_eq!((0, 0), position_to_cell((-15.0, -1.0), cell, size));
+        assert_eq!((4, 7), position_to_cell((500.0, 500.0), cell, size));
+
+        // Cell boundaries belong to the next cell.
+        assert_eq!((0, 0), position_to_cell((9.999, 19.999), cell, size));
+        assert_eq!((1, 1), position_to_cell((10.0, 20.0), cell, size));
+        let cell = (7.5, 15.5);
+        assert_eq!((1, 2), position_to_cell((15.0, 15.5), cell, size));
+        assert_eq!((0, 1), position_to_cell((14.99, 15.49), cell, size));
+    }
+
+    #[test]
+    fn test_cell_to_position() {
+        let cell = (10.0, 20.0);
+        let size = (5, 8);
+
+        assert_eq!((0.0, 0.0), cell_to_position((0, 0), cell, size));
+        assert_eq!((30.0, 40.0), cell_to_position((2, 3), cell, size));
+        // Outside of the grid.
+        assert_eq!((70.0, 80.0), cell_to_position((100, 100), cell, size));
+
+        // Round trip.
+        let cell = (7.5, 15.5);
+        let (x, y) = cell_to_position((3, 6), cell, size);
+        assert_eq!((3, 6), position_to_cell((x, y), cell, size));
+    }
+
+    #[test]
+    fn test_click_counter() {
+        let mut clicks = ClickCounter::default();
+        assert_eq!(1, clicks.press(1, 1000, (0, 0), 400));
+        assert_eq!(2, clicks.press(1, 1200, (0, 0), 400));
+        assert_eq!(3, clicks.press(1, 1400, (0, 0), 400));
+        assert_eq!(1, clicks.press(1, 1600, (0, 0), 400));
+
+        // Too late.
+        assert_eq!(1, clicks.press(1, 3000, (0, 0), 400));
+        // Other cell.
+        assert_eq!(1, clicks.press(1, 3100, (0, 1), 400));
+        // Other button.
+        assert_eq!(1, clicks.press(3, 3200, (0, 1), 400));
+    }
+
+    #[test]
+    fn test_find_matches() {
+        let rows = rows_from_lines(&["foo bar foo", "Foo", "", "fofoo"], 12);
+
+        assert_eq!(
+            vec![(0, 0, 3), (0, 8, 11), (1, 0, 3), (3, 2, 5)],
+            find_matches(&rows, "foo")
+        );
+        // Upper case makes the search case sensitive.
+        assert_eq!(vec![(1, 0, 3)], find_matches(&rows, "Foo"));
+        assert_eq!(
+            vec![(0, 1, 3), (0, 9, 11), (1, 1, 3), (3, 3, 5)],
+            find_matches(&rows, "oo")
+        );
+        // Matches don't overlap.
+        let rows = rows_from_lines(&["aaaaa"], 5);
+        assert_eq!(vec![(0, 0, 2), (0, 2, 4)], find_matches(&rows, "aa"));
+        assert!(find_matches(&rows, "").is_empty());
+        assert!(find_matches(&rows, "baz").is_empty());
+    }
+
+    #[test]
+    fn test_find_matches_double_width() {
+        let mut row = Row::new(6);
+        row.update(GridLineSegment {
+            grid: 1,
+            row: 0,
+            col_start: 0,
+            cells: vec!["a", "日", "", "b"]
+                .into_iter()
+                .map(|c| nvim_bridge::Cell {
+                    text: c.to_string(),
+                    hl_id: 0,
+                    repeat: 1,
+                    double_width: c == "日",
+                })
+                .collect(),
+        });
+
+        // The match covers both halves of the double width cell.
+        assert_eq!(vec![(0, 1, 3)], find_matches(&[row.clone()], "日"));
+        assert_eq!(vec![(0, 0, 4)], find_matches(&[row], "a日b"));
+    }
+
+    #[test]
+    fn test_selection_range() {
+        assert_eq!(((1, 2), (3, 5)), selection_range((1, 2), (3, 4)));
+        // Backwards.
+        assert_eq!(((1, 2), (3, 5)), selection_range((3, 4), (1, 2)));
+        // On a single row.
+        assert_eq!(((1, 2), (1, 7)), selection_range((1, 6), (1, 2)));
+    }
+
+    #[test]
+    fn test_rows_text() {
+        let rows = rows_from_lines(&["foo bar", "baz", "", "qux"], 8);
+
+        assert_eq!(
+            vec!["bar ", "baz     ", "        ", "qu"],
+            rows_text(&rows, (0, 4), (3, 2))
+        );
+        assert_eq!(vec!["oo"], rows_text(&rows, (0, 1), (0, 3)));
+        // Out of bounds.
+        assert_eq!(vec!["qux     "], rows_text(&rows, (3, 0), (10, 8)));
+        assert!(rows_text(&rows, (5, 0), (6, 0)).is_empty());
+    }
+
+    #[test]
+    fn test_selection_lines() {
+        let rows = rows_from_lines(&["  1 foo bar", "  2 baz", "  3 qux"], 12);
+
+        assert_eq!(
+            vec!["bar", "baz", "qu"],
+            selection_lines(&rows, (0, 8), (2, 6), 4)
+        );
+        // Selections starting or ending in the skipped columns.
+        assert_eq!(
+            vec!["foo bar", "baz"],
+            selection_lines(&rows, (0, 1), (1, 12), 4)
+        );
+        assert_eq!(
+            vec!["foo bar", ""],
+            selection_lines(&rows, (0, 0), (1, 2), 4)
+        );
+        assert_eq!(vec!["1 foo"], selection_lines(&rows, (0, 2), (0, 7), 0));
+    }
+
+    #[test]
+    fn test_accumulate_scroll_delta() {
+        // Small deltas accumulate...
+        let (steps, rest) = accumulate_scroll_delta(0.0, 0.4);
+        assert_eq!(0, steps);
+        let (steps, rest) = accumulate_scroll_delta(rest, 0.4);
+        assert_eq!(0, steps);
+        // ...until there is enough for a full step.
+        let (steps, rest) = accumulate_scroll_delta(rest, 0.4);
+        assert_eq!(1, steps);
+        assert!((rest - 0.2).abs() < 0.0001);
+
+        // Changing direction drops the old remainder.
+        let (steps, rest) = accumulate_scroll_delta(0.9, -0.5);
+        assert_eq!(0, steps);
+        assert!((rest + 0.5).abs() < 0.0001);
+
+        let (steps, rest) = accumulate_scroll_delta(-0.5, -2.75);
+        assert_eq!(-3, steps);
+        assert!((rest + 0.25).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_damage_rects() {
+        // Segments of a line are merged, and fractions round outwards.
+        assert_eq!(
+            vec![(0, 0, 30, 10)],
+            damage_rects(&[(10.0, 0.0, 20.0, 10.0), (0.5, 0.0, 9.5, 10.0)])
+        );
+        // Full lines on top of each other are merged too.
+        assert_eq!(
+            vec![(0, 0, 30, 20)],
+            damage_rects(&[(0.0, 0.0, 30.0, 10.0), (0.0, 10.0, 30.0, 10.0)])
+        );
+        // Separate areas are kept apart.
+        assert_eq!(
+            vec![(0, 0, 10, 10), (50, 20, 10, 10)],
+            damage_rects(&[(50.0, 20.0, 10.0, 10.0), (0.0, 0.0, 10.0, 10.0)])
+        );
+        // Empty areas are dropped.
+        assert!(damage_rects(&[(0.0, 0.0, 0.0, 10.0)]).is_empty());
+
+        // Too many areas turn into their bounding box.
+        let areas: Vec<_> = (0..20)
+            .map(|i| (f64::from(i) * 20.0, f64::from(i) * 20.0, 10.0, 10.0))
+            .collect();
+        assert_eq!(vec![(0, 0, 390, 390)], damage_rects(&areas));
+    }
 }
@@ -0,0 +1,159 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gtk::prelude::*;
+
+/// Interval (in ms) between the kinetic scroll's steps.
+const TICK_MS: u32 = 16;
+/// Scroll events further apart (in ms) than this don't contribute to the
+/// velocity.
+const MAX_EVENT_GAP_MS: u32 = 100;
+/// How much of the velocity is kept on each tick.
+const FRICTION: f64 = 0.95;
+/// Velocity (in scroll steps per ms) under which the kinetic scroll stops.
+const MIN_VELOCITY: f64 = 0.001;
+
+#[derive(Default)]
+struct Inner {
+    enabled: bool,
+    /// Current velocity, in scroll steps per ms.
+    velocity: f64,
+    /// Time of the previous smooth scroll event.
+    last_time: Option<u32>,
+    /// Source id of the pending start or the ongoing kinetic scroll.
+    source_id: Option<glib::SourceId>,
+}
+
+/// Kinetic (inertial) scrolling for smooth scroll events. Keeps scrolling,
+/// with decreasing speed, after a touchpad flick.
+///
+/// Cloning returns a handle to the same state, so a single `KineticScroll`
+/// can be shared between grids, and stopped from e.g. key press handlers.
+#[derive(Clone, Default)]
+pub struct KineticScroll {
+    inner: Rc<RefCell<Inner>>,
+}
+
+impl KineticScroll {
+    pub fn set_enabled(&self, enabled: bool) {
+        if !enabled {
+            self.stop();
+        }
+
+        self.inner.borrow_mut().enabled = enabled;
+    }
+
+    /// Stops any ongoing kinetic scroll.
+    pub fn stop(&self) {
+        let mut inner = self.inner.borrow_mut();
+        if let Some(id) = inner.source_id.take() {
+            glib::source::source_remove(id);
+        }
+
+        inner.velocity = 0.0;
+        inner.last_time = None;
+    }
+
+    /// Records a smooth scroll event of `delta` that happened at `time`.
+    /// On the scroll stop event (`is_stop`, sent when the user lifts their
+    /// fingers from the touchpad), `f` starts getting called with
+    /// decelerating deltas until the scroll comes to a stop.
+    pub fn scroll<F: Fn(f64) + 'static>(
+        &self,
+        delta: f64,
+        time: u32,
+        is_stop: bool,
+        f: F,
+    ) {
+        let mut inner = self.inner.borrow_mut();
+        if !inner.enabled {
+            return;
+        }
+
+        // New input cancels the previous kinetic scroll.
+        if let Some(id) = inner.source_id.take() {
+            glib::source::source_remove(id);
+        }
+
+        let last_time = inner.last_time.take();
+        if !is_stop {
+            inner.velocity = match last_time {
+                Some(last) if time > last && time - last < MAX_EVENT_GAP_MS => {
+                    next_velocity(
+                        inner.velocity,
+                        delta / f64::from(time - last),
+                    )
+                }
+                _ => 0.0,
+            };
+            inner.last_time = Some(time);
+            return;
+        }
+
+        // If the fingers stayed still for a while before they were lifted,
+        // there's no motion to continue.
+        match last_time {
+            Some(last) if time >= last && time - last < MAX_EVENT_GAP_MS => {}
+            _ => {
+                inner.velocity = 0.0;
+                return;
+            }
+        }
+
+        let this = self.clone();
+        let id = gtk::timeout_add(TICK_MS, move || {
+            let mut inner = this.inner.borrow_mut();
+            inner.velocity *= FRICTION;
+
+            if inner.velocity.abs() < MIN_VELOCITY {
+                // The source is removed once we return, so forget about it.
+                inner.source_id.take();
+                inner.velocity = 0.0;
+                return Continue(false);
+            }
+
+            let delta = inner.velocity * f64::from(TICK_MS);
+            drop(inner);
+            f(delta);
+
+            Continue(true)
+        });
+
+        inner.source_id = Some(id);
+    }
+}
+
+/// Returns the new velocity, when the previous velocity was `prev` and the
+/// one measured from the latest scroll event is `measured`. Smooths out
+/// jitter from the individual events, but reacts immediately to direction
+/// changes.
+fn next_velocity(prev: f64, measured: f64) -> f64 {
+    if prev * measured < 0.0 {
+        return measured;
+    }
+
+    prev * 0.3 + measured * 0.7
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(expected: f64, actual: f64) {
+        assert!(
+            (expected - actual).abs() < 1e-9,
+            "{} != {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn test_next_velocity() {
+        assert_close(0.7, next_velocity(0.0, 1.0));
+        assert_close(1.0, next_velocity(1.0, 1.0));
+        assert_close(1.7, next_velocity(1.0, 2.0));
+        // Direction change.
+        assert_close(-0.5, next_velocity(1.0, -0.5));
+    }
+}
@@ -1,8 +1,15 @@
 mod context;
 mod cursor;
+mod glyph_cache;
 #[allow(clippy::module_inception)]
 mod grid;
+mod kinetic;
 mod render;
 mod row;
+mod scroll_animation;
 
-pub use self::grid::{Grid, GridMetrics};
+pub use self::context::Preedit;
+pub use self::cursor::DEFAULT_ANIMATION_DURATION_MS;
+pub use self::grid::{Grid, GridMetrics, MouseButton, ScrollDirection};
+pub use self::kinetic::KineticScroll;
+pub use self::scroll_animation::DEFAULT_SCROLL_ANIMATION_DURATION_MS;
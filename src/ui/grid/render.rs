@@ -1,21 +1,41 @@
+use std::collections::BTreeMap;
+
+use glib::translate::ToGlib;
 use gtk::prelude::*;
 use gtk::DrawingArea;
 use pango::Attribute;
 
-use crate::nvim_bridge::GridLineSegment;
-use crate::ui::color::Highlight;
-use crate::ui::color::HlDefs;
-use crate::ui::grid::context::{CellMetrics, Context};
+use crate::ui::color::{Color, Highlight, HlDefs, HlGroup};
+use crate::ui::grid::context::{
+    CellMetrics, Context, CursorOverlay, Preedit, RowOverlay, TextOptions,
+};
+use crate::ui::grid::glyph_cache::{GlyphCache, GlyphKey, ShapedItem};
 use crate::ui::grid::row::{Cell, Segment};
 
+/// Horizontal shear for synthesized italics.
+const ITALIC_SHEAR: f64 = 0.2;
+/// Pango's weight for semibold. Fonts lighter than this get synthesized
+/// bold.
+const SEMIBOLD_WEIGHT: i32 = 600;
+
+/// Text styles to synthesize, if the font doesn't have a face for them.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Styles {
+    bold: bool,
+    italic: bool,
+}
+
 /// Renders text to `cr`.
 ///
 /// * `cr` - The cairo context to render to.
 /// * `pango_context` - The pango context to use for text rendering.
+/// * `cache` - Cache for the shaped text.
 /// * `cm` - Cell metrics to use for text placement.
 /// * `hl` - The highlighting to use.
 /// * `hl_defs` - Global hl defs. Used to get default values.
 /// * `text` - The text to render.
+/// * `cells` - Byte offsets in `text` where each cell's text starts.
+/// * `opts` - Options for shaping the text.
 /// * `x` - Target x coordinate for `cr`.
 /// * `y` - Target y coordinate for `cr`.
 /// * `w` - Target width for `cr`.
@@ -24,15 +44,52 @@ use crate::ui::grid::row::{Cell, Segment};
 fn render_text(
     cr: &cairo::Context,
     pango_context: &pango::Context,
+    cache: &GlyphCache,
     cm: &CellMetrics,
     hl: &Highlight,
     hl_defs: &HlDefs,
     text: &str,
+    cells: &[usize],
+    opts: &TextOptions,
     x: f64,
     y: f64,
     w: f64,
     h: f64,
 ) {
+    let (hl, fg, bg) = hl_colors(hl, hl_defs, opts);
+    fill_background(cr, bg, bg_alpha(&hl, opts), x, y, w, h);
+    draw_text(
+        cr,
+        pango_context,
+        cache,
+        cm,
+        &hl,
+        fg,
+        hl_defs,
+        text,
+        cells,
+        opts,
+        x,
+        y,
+        w,
+        h,
+    );
+}
+
+/// Returns `hl` with `opts` applied to it, and its foreground and
+/// background colors.
+fn hl_colors(
+    hl: &Highlight,
+    hl_defs: &HlDefs,
+    opts: &TextOptions,
+) -> (Highlight, Color, Color) {
+    let mut hl = *hl;
+    if opts.bold_is_bright {
+        if let Some(fg) = hl.bright_foreground() {
+            hl.foreground = Some(fg);
+        }
+    }
+
     let (fg, bg) = if hl.reverse {
         (
             hl.background.unwrap_or(hl_defs.default_bg),
@@ -45,12 +102,74 @@ fn render_text(
         )
     };
 
+    (hl, fg, bg)
+}
+
+/// If `hl` is drawn on the default background, so that the cursor line and
+/// column can be drawn under it.
+fn has_default_bg(hl: &Highlight) -> bool {
+    !hl.reverse && hl.background.is_none()
+}
+
+/// Returns the background of the cursor line or column's highlight `group`,
+/// if it has one.
+fn overlay_bg(hl_defs: &HlDefs, group: &HlGroup) -> Option<Color> {
+    hl_defs.get_hl_group(group).and_then(|hl| hl.background)
+}
+
+/// Returns the alpha for `hl`'s background, or None if the background is
+/// opaque.
+fn bg_alpha(hl: &Highlight, opts: &TextOptions) -> Option<f64> {
+    if opts.has_alpha() {
+        Some(opts.bg_alpha(hl))
+    } else {
+        None
+    }
+}
+
+#[allow(clippy::many_single_char_names)]
+fn fill_background(
+    cr: &cairo::Context,
+    bg: Color,
+    alpha: Option<f64>,
+    x: f64,
+    y: f64,
+    w: f64,
+    h: f64,
+) {
     cr.save();
-    cr.set_source_rgb(bg.r, bg.g, bg.b);
+    if let Some(alpha) = alpha {
+        // Replace whatever was drawn before, instead of blending with it.
+        cr.set_operator(cairo::Operator::Source);
+        cr.set_source_rgba(bg.r, bg.g, bg.b, alpha);
+    } else {
+        cr.set_source_rgb(bg.r, bg.g, bg.b);
+    }
     cr.rectangle(x, y, w, h);
     cr.fill();
     cr.restore();
+}
 
+/// Draws `text` (with its underlines etc.) to `cr` in `fg`, without the
+/// background. `hl` is expected to have the options already applied to it,
+/// see `hl_colors`. The rest of the arguments are like for `render_text`.
+#[allow(clippy::too_many_arguments, clippy::many_single_char_names)]
+fn draw_text(
+    cr: &cairo::Context,
+    pango_context: &pango::Context,
+    cache: &GlyphCache,
+    cm: &CellMetrics,
+    hl: &Highlight,
+    fg: Color,
+    hl_defs: &HlDefs,
+    text: &str,
+    cells: &[usize],
+    opts: &TextOptions,
+    x: f64,
+    y: f64,
+    w: f64,
+    h: f64,
+) {
     let attrs = pango::AttrList::new();
 
     if hl.bold {
@@ -61,30 +180,66 @@ fn render_text(
         let attr = Attribute::new_style(pango::Style::Italic).unwrap();
         attrs.insert(attr);
     }
+    if !opts.font_features.is_empty() {
+        if let Some(attr) = Attribute::new_font_features(&opts.font_features) {
+            attrs.insert(attr);
+        }
+    }
 
     cr.save();
     cr.set_source_rgb(fg.r, fg.g, fg.b);
 
-    let items =
-        pango::itemize(pango_context, text, 0, text.len() as i32, &attrs, None);
-
-    let mut x_offset = 0.0;
-    for item in items {
-        let a = item.analysis();
-        let item_offset = item.offset() as usize;
-        let mut glyphs = pango::GlyphString::new();
-
-        pango::shape(
-            &text[item_offset..item_offset + item.length() as usize],
-            &a,
-            &mut glyphs,
+    let font_wide = opts.font_wide.as_deref();
+    let styles = Styles {
+        bold: hl.bold,
+        italic: hl.italic,
+    };
+    if opts.ligatures {
+        show_text(
+            cr,
+            pango_context,
+            cache,
+            cm,
+            &attrs,
+            font_wide,
+            styles,
+            opts.synthetic_styles,
+            text,
+            cells,
+            x,
+            y,
         );
+    } else {
+        // Shape each cell on its own, so no ligatures are formed.
+        let ranges = cell_ranges(cells, text.len()).collect::<Vec<_>>();
+        for (col, &(start, end)) in ranges.iter().enumerate() {
+            if start == end {
+                continue;
+            }
 
-        cr.move_to(x + x_offset, y + cm.ascent);
-        pangocairo::functions::show_glyph_string(&cr, &a.font(), &mut glyphs);
+            // Keep the empty cell after a double width cell, so it's still
+            // known to be double width.
+            let cells = match ranges.get(col + 1) {
+                Some((s, e)) if s == e => vec![0, end - start],
+                _ => vec![0],
+            };
 
-        x_offset += f64::from(item.num_chars()) * cm.width;
-        //x_offset += f64::from(glyphs.get_width());
+            let x = x + col as f64 * cm.width;
+            show_text(
+                cr,
+                pango_context,
+                cache,
+                cm,
+                &attrs,
+                font_wide,
+                styles,
+                opts.synthetic_styles,
+                &text[start..end],
+                &cells,
+                x,
+                y,
+            );
+        }
     }
 
     // Since we can't (for some reason) use pango attributes to draw
@@ -100,26 +255,80 @@ fn render_text(
             cm.underline_thickness * 2.0,
         );
     }
+    if hl.underdouble {
+        let y = y + h + cm.underline_position;
+        let t = cm.underline_thickness / 2.0;
+        cr.rectangle(x, y, w, t);
+        cr.rectangle(x, y - t * 2.0, w, t);
+        cr.fill();
+    }
+    if hl.underdotted {
+        let t = cm.underline_thickness;
+        stroke_dashed_line(cr, cm, x, y + h, w, &[t, t]);
+    }
+    if hl.underdashed {
+        let t = cm.underline_thickness;
+        stroke_dashed_line(cr, cm, x, y + h, w, &[t * 3.0, t * 2.0]);
+    }
     if hl.underline {
         let y = y + h + cm.underline_position;
         cr.rectangle(x, y, w, cm.underline_thickness);
         cr.fill();
     }
 
+    if hl.strikethrough {
+        // Strikethrough uses the special color only if one is explicitly
+        // set, otherwise the text's color.
+        let c = hl.special.unwrap_or(fg);
+        cr.set_source_rgb(c.r, c.g, c.b);
+
+        let t = cm.underline_thickness / 2.0;
+        cr.rectangle(x, (y + h / 2.0 - t / 2.0).round(), w, t);
+        cr.fill();
+    }
+
     cr.restore();
 }
 
-/// Draws (inverted) cell to `cr`.
+/// Draws cell to `cr` with the cursor's colors. The colors come from
+/// `attr_id`, or if it's 0, the cell is inverted. `color` overrides the
+/// cursor's background.
+#[allow(clippy::too_many_arguments)]
 pub fn cursor_cell(
     cr: &cairo::Context,
     pango_context: &pango::Context,
+    cache: &GlyphCache,
     cell: &Cell,
     cm: &CellMetrics,
     hl_defs: &HlDefs,
+    attr_id: u64,
+    color: Option<Color>,
+    opts: &TextOptions,
 ) {
-    let mut hl = *hl_defs.get(&cell.hl_id).unwrap();
+    let attr = if attr_id == 0 {
+        None
+    } else {
+        hl_defs.get(&attr_id)
+    };
+    let hl = cursor_highlight(
+        hl_defs.get(&cell.hl_id).unwrap(),
+        attr,
+        color,
+        hl_defs,
+    );
 
-    hl.reverse = !hl.reverse;
+    // The cursor's cell is rendered on its own, without the next cell, so
+    // tell about double width cells with an empty cell after the text.
+    let cells = [0, cell.text.len()];
+    let cells = if cell.double_width {
+        &cells[..]
+    } else {
+        &cells[..1]
+    };
+    let opts = TextOptions {
+        ligatures: false,
+        ..opts.clone()
+    };
 
     let x = 0.0;
     let y = 0.0;
@@ -130,86 +339,580 @@ pub fn cursor_cell(
     };
     let h = cm.height;
 
-    render_text(cr, pango_context, cm, &hl, hl_defs, &cell.text, x, y, w, h);
+    render_text(
+        cr,
+        pango_context,
+        cache,
+        cm,
+        &hl,
+        hl_defs,
+        &cell.text,
+        cells,
+        &opts,
+        x,
+        y,
+        w,
+        h,
+    );
+}
+
+/// Renders the input method's preedit text to `cr`, starting from `x`, `y`.
+/// The text is drawn over a background, so that it covers the cells under it.
+pub fn preedit(
+    cr: &cairo::Context,
+    cm: &CellMetrics,
+    preedit: &Preedit,
+    x: f64,
+    y: f64,
+) {
+    let layout = match pangocairo::functions::create_layout(cr) {
+        Some(layout) => layout,
+        None => return,
+    };
+    layout.set_font_description(Some(&cm.font.as_pango_font()));
+    layout.set_text(&preedit.text);
+    layout.set_attributes(Some(&preedit.attrs));
+
+    let (w, h) = layout.get_pixel_size();
+    let w = f64::from(w).max(cm.width);
+
+    cr.save();
+    cr.set_source_rgb(preedit.bg.r, preedit.bg.g, preedit.bg.b);
+    cr.rectangle(x, y, w, cm.height);
+    cr.fill();
+
+    // Center the text vertically to the cell.
+    let text_y = y + (cm.height - f64::from(h)) / 2.0;
+    cr.set_source_rgb(preedit.fg.r, preedit.fg.g, preedit.fg.b);
+    cr.move_to(x, text_y);
+    pangocairo::functions::show_layout(cr, &layout);
+
+    // The input method's cursor.
+    let index = char_to_byte_index(&preedit.text, preedit.cursor);
+    let pos = layout.index_to_pos(index as i32);
+    let scale = f64::from(pango::SCALE);
+    cr.rectangle(
+        x + f64::from(pos.x) / scale,
+        text_y + f64::from(pos.y) / scale,
+        1.0,
+        f64::from(pos.height) / scale,
+    );
+    cr.fill();
+    cr.restore();
+}
+
+/// Returns the byte index of the `n`th char in `text`. If `n` is past the
+/// end of the text, returns the text's length.
+fn char_to_byte_index(text: &str, n: usize) -> usize {
+    text.char_indices()
+        .nth(n)
+        .map(|(i, _)| i)
+        .unwrap_or_else(|| text.len())
+}
+
+/// Strokes an underline with the `dashes` pattern at the bottom of a cell
+/// row, where `y` is the bottom of the row. The pattern is aligned to the
+/// grid's left edge, so it continues seamlessly between segments.
+fn stroke_dashed_line(
+    cr: &cairo::Context,
+    cm: &CellMetrics,
+    x: f64,
+    y: f64,
+    w: f64,
+    dashes: &[f64],
+) {
+    let t = cm.underline_thickness;
+    let y = y + cm.underline_position + t / 2.0;
+
+    cr.save();
+    cr.set_line_width(t);
+    cr.set_dash(dashes, x % dashes.iter().sum::<f64>());
+    cr.move_to(x, y);
+    cr.line_to(x + w, y);
+    cr.stroke();
+    cr.restore();
+}
+
+/// Shapes and draws `text` to `cr`. Each of pango's items (runs of text with
+/// the same font, script etc.) is placed at the cell it starts from, so
+/// wide characters and glyphs from fallback fonts don't push the rest of the
+/// text off the grid. If `font_wide` is set, double width cells prefer its
+/// font families. With `synthesize`, bold and italic `styles` are
+/// synthesized if the font doesn't have a face for them. The shaped text
+/// is stored in (and reused from) `cache`.
+#[allow(clippy::too_many_arguments)]
+fn show_text(
+    cr: &cairo::Context,
+    pango_context: &pango::Context,
+    cache: &GlyphCache,
+    cm: &CellMetrics,
+    attrs: &pango::AttrList,
+    font_wide: Option<&str>,
+    styles: Styles,
+    synthesize: bool,
+    text: &str,
+    cells: &[usize],
+    x: f64,
+    y: f64,
+) {
+    let key = GlyphKey {
+        text: text.to_string(),
+        cells: cells.to_vec(),
+        bold: styles.bold,
+        italic: styles.italic,
+    };
+    let shape = || shape_text(pango_context, attrs, font_wide, text, cells);
+
+    cache.with(key, shape, |items| {
+        for item in items {
+            // Glyphs from other fonts (e.g. color emoji) can be wider than
+            // the cells they're in, so shrink those to fit.
+            let width =
+                f64::from(item.glyphs.get_width()) / f64::from(pango::SCALE);
+            let scale = glyph_scale(width, item.cols as f64 * cm.width);
+
+            let synthetic = if synthesize {
+                Styles {
+                    bold: styles.bold && item.bold_missing,
+                    italic: styles.italic && item.italic_missing,
+                }
+            } else {
+                Styles::default()
+            };
+
+            cr.save();
+            cr.translate(x + item.col as f64 * cm.width, y + cm.ascent);
+            cr.scale(scale, scale);
+            if synthetic.italic {
+                // Slant the glyphs around the baseline.
+                cr.transform(cairo::Matrix::new(
+                    1.0,
+                    0.0,
+                    -ITALIC_SHEAR,
+                    1.0,
+                    0.0,
+                    0.0,
+                ));
+            }
+            cr.move_to(0.0, 0.0);
+            pangocairo::functions::show_glyph_string(
+                &cr,
+                &item.font,
+                &mut item.glyphs,
+            );
+            if synthetic.bold {
+                // Embolden by drawing the glyphs again, slightly offset.
+                cr.move_to(1.0 / scale, 0.0);
+                pangocairo::functions::show_glyph_string(
+                    &cr,
+                    &item.font,
+                    &mut item.glyphs,
+                );
+            }
+            cr.restore();
+        }
+    });
+}
+
+/// Itemizes and shapes `text` for `show_text`.
+fn shape_text(
+    pango_context: &pango::Context,
+    attrs: &pango::AttrList,
+    font_wide: Option<&str>,
+    text: &str,
+    cells: &[usize],
+) -> Vec<ShapedItem> {
+    let wide_attrs;
+    let attrs = if let Some(family) = font_wide {
+        wide_attrs = attrs.copy().unwrap();
+        for (start, end) in wide_cells(cells, text.len()) {
+            let mut attr = Attribute::new_family(family).unwrap();
+            attr.set_start_index(start as u32);
+            attr.set_end_index(end as u32);
+            wide_attrs.insert(attr);
+        }
+
+        &wide_attrs
+    } else {
+        attrs
+    };
+
+    let items =
+        pango::itemize(pango_context, text, 0, text.len() as i32, attrs, None);
+
+    items
+        .into_iter()
+        .map(|item| {
+            let a = item.analysis();
+            let item_offset = item.offset() as usize;
+            let item_end = item_offset + item.length() as usize;
+            let mut glyphs = pango::GlyphString::new();
+
+            pango::shape(&text[item_offset..item_end], &a, &mut glyphs);
+
+            let col = cell_at_offset(cells, text.len(), item_offset);
+            let cols = cell_at_offset(cells, text.len(), item_end) - col;
+
+            let font = a.font();
+            let all = Styles {
+                bold: true,
+                italic: true,
+            };
+            let missing = font
+                .describe()
+                .map(|desc| missing_styles(all, &desc))
+                .unwrap_or_default();
+
+            ShapedItem {
+                font,
+                glyphs,
+                col,
+                cols,
+                bold_missing: missing.bold,
+                italic_missing: missing.italic,
+            }
+        })
+        .collect()
+}
+
+/// Returns the `requested` styles that the font described by `desc` doesn't
+/// have.
+fn missing_styles(requested: Styles, desc: &pango::FontDescription) -> Styles {
+    Styles {
+        bold: requested.bold && desc.get_weight().to_glib() < SEMIBOLD_WEIGHT,
+        italic: requested.italic && desc.get_style() == pango::Style::Normal,
+    }
 }
 
-/// Renders `segments` to `cr`.
+/// Returns the scale to draw glyphs that are `width` wide with, so they fit
+/// in `available` width.
+fn glyph_scale(width: f64, available: f64) -> f64 {
+    // Allow some rounding errors, so regular text isn't scaled.
+    if width <= available + 0.5 || available <= 0.0 {
+        return 1.0;
+    }
+
+    available / width
+}
+
+/// Returns the (start, end) byte ranges of each cell's text, when `cells`
+/// has the starting offsets of the cells in a text of `len` bytes.
+fn cell_ranges(
+    cells: &[usize],
+    len: usize,
+) -> impl Iterator<Item = (usize, usize)> + '_ {
+    cells
+        .iter()
+        .enumerate()
+        .map(move |(i, start)| (*start, *cells.get(i + 1).unwrap_or(&len)))
+}
+
+/// Returns the byte ranges of double width cells, e.g. cells that are
+/// followed by an empty cell.
+fn wide_cells(cells: &[usize], len: usize) -> Vec<(usize, usize)> {
+    let ranges = cell_ranges(cells, len).collect::<Vec<_>>();
+    ranges
+        .windows(2)
+        .filter(|w| w[0].0 != w[0].1 && w[1].0 == w[1].1)
+        .map(|w| w[0])
+        .collect()
+}
+
+/// Returns the column (relative to the first cell in `cells`) of the text
+/// at byte `offset`. Empty cells (the right halves of double width cells)
+/// count as columns too.
+fn cell_at_offset(cells: &[usize], len: usize, offset: usize) -> usize {
+    cell_ranges(cells, len)
+        .take_while(|(_, end)| *end <= offset)
+        .count()
+}
+
+/// Returns the highlight to draw a cell with `cell_hl` under the cursor
+/// with. The cell is inverted, unless the cursor has its own colors in
+/// `attr`. With `color`, the cursor is filled with it, and the text is drawn
+/// with the cell's background color.
+fn cursor_highlight(
+    cell_hl: &Highlight,
+    attr: Option<&Highlight>,
+    color: Option<Color>,
+    hl_defs: &HlDefs,
+) -> Highlight {
+    let mut hl = *cell_hl;
+    hl.reverse = !hl.reverse;
+
+    if let Some(attr) = attr {
+        // Resolve the inverted colors, so we can override only the ones
+        // the cursor's highlight has.
+        let fg = hl.foreground.unwrap_or(hl_defs.default_fg);
+        let bg = hl.background.unwrap_or(hl_defs.default_bg);
+        let (fg, bg) = if hl.reverse { (bg, fg) } else { (fg, bg) };
+
+        hl.reverse = attr.reverse;
+        hl.foreground = Some(attr.foreground.unwrap_or(fg));
+        hl.background = Some(attr.background.unwrap_or(bg));
+    }
+
+    if let Some(color) = color {
+        let fg = cell_hl.foreground.unwrap_or(hl_defs.default_fg);
+        let bg = cell_hl.background.unwrap_or(hl_defs.default_bg);
+        let bg = if cell_hl.reverse { fg } else { bg };
+
+        hl.reverse = false;
+        hl.foreground = Some(bg);
+        hl.background = Some(color);
+    }
+
+    hl
+}
+
+/// Renders `segments` to `cr`, with the cursor line and column in
+/// `overlay`.
+#[allow(clippy::too_many_arguments)]
 fn put_segments(
     cr: &cairo::Context,
     pango_context: &pango::Context,
+    cache: &GlyphCache,
     queue_draw_area: &mut Vec<(f64, f64, f64, f64)>,
     cm: &CellMetrics,
     hl_defs: &HlDefs,
     segments: Vec<Segment>,
     row: usize,
+    overlay: RowOverlay,
+    opts: &TextOptions,
 ) {
     let cw = cm.width;
     let ch = cm.height;
+    let y = (row as f64 * ch).floor();
+    let h = ch.ceil();
 
-    for seg in segments {
-        let hl = hl_defs.get(&seg.hl_id).unwrap();
+    let segments = segments
+        .into_iter()
+        .map(|seg| {
+            let x = (seg.start as f64 * cw).floor();
+            let w = (seg.len as f64 * cw).ceil();
+            let colors =
+                hl_colors(hl_defs.get(&seg.hl_id).unwrap(), hl_defs, opts);
+            (seg, x, w, colors)
+        })
+        .collect::<Vec<_>>();
 
-        let x = (seg.start as f64 * cw).floor();
-        let y = (row as f64 * ch).floor();
-        let w = (seg.len as f64 * cw).ceil();
-        let h = ch.ceil();
+    // Fill all of the backgrounds before the text, so that runs of cells
+    // with the same background (like the cursor line, which is made of
+    // many highlights) are filled with a single rectangle, and so that the
+    // backgrounds don't cut off text overhanging from the previous segment.
+    // Our cursor line goes under the cells that don't have a background of
+    // their own, so e.g. the visual selection and search matches are drawn
+    // over it, like in nvim.
+    let line_bg =
+        overlay_bg(hl_defs, &HlGroup::CursorLine).filter(|_| overlay.line);
+    let backgrounds =
+        background_runs(segments.iter().map(|(_, x, w, (hl, _, bg))| {
+            let bg = match line_bg {
+                Some(line_bg) if has_default_bg(hl) => line_bg,
+                _ => *bg,
+            };
+            (*x, *w, bg, bg_alpha(hl, opts))
+        }));
+    for (x, w, bg, alpha) in backgrounds {
+        fill_background(cr, bg, alpha, x, y, w, h);
+    }
+
+    // Likewise for our cursor column.
+    let column = overlay.column.and_then(|col| {
+        Some((col, overlay_bg(hl_defs, &HlGroup::CursorColumn)?))
+    });
+    if let Some((col, col_bg)) = column {
+        let cell = segments
+            .iter()
+            .find(|(seg, ..)| seg.start <= col && col < seg.start + seg.len);
+        if let Some((_, _, _, (hl, _, _))) = cell {
+            if has_default_bg(hl) {
+                let x = (col as f64 * cw).floor();
+                let alpha = bg_alpha(hl, opts);
+                fill_background(cr, col_bg, alpha, x, y, cw.ceil(), h);
+            }
+        }
+    }
 
-        let text = &seg.text;
-        render_text(cr, pango_context, cm, &hl, hl_defs, &text, x, y, w, h);
+    for (seg, x, w, (hl, fg, _)) in segments {
+        draw_text(
+            cr,
+            pango_context,
+            cache,
+            cm,
+            &hl,
+            fg,
+            hl_defs,
+            &seg.text,
+            &seg.cells,
+            opts,
+            x,
+            y,
+            w,
+            h,
+        );
 
         queue_draw_area.push((x, y, w, h));
     }
 }
 
+/// Merges adjacent (and overlapping) backgrounds, given as x, width, color
+/// and alpha, that have the same color and alpha. The backgrounds can be in
+/// any order, the runs are returned from left to right.
+fn background_runs<I>(backgrounds: I) -> Vec<(f64, f64, Color, Option<f64>)>
+where
+    I: Iterator<Item = (f64, f64, Color, Option<f64>)>,
+{
+    let mut backgrounds: Vec<_> = backgrounds.collect();
+    backgrounds.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+    let mut runs: Vec<(f64, f64, Color, Option<f64>)> = vec![];
+    for (x, w, bg, alpha) in backgrounds {
+        match runs.last_mut() {
+            Some(run)
+                if run.2 == bg && run.3 == alpha && run.0 + run.1 >= x =>
+            {
+                run.1 = (x + w).max(run.0 + run.1) - run.0;
+            }
+            _ => runs.push((x, w, bg, alpha)),
+        }
+    }
+
+    runs
+}
+
 pub fn redraw(
     context: &mut Context,
     pango_context: &pango::Context,
     hl_defs: &HlDefs,
 ) {
+    let pos = context.cursor_cell_position();
+    let overlay = &mut context.cursor_overlay;
+    overlay.pos = pos;
+    overlay.rendered = rendered_positions(overlay);
+
     for (i, row) in context.rows.iter_mut().enumerate() {
         let segments = row.as_segments(0, row.len);
+        let overlay = context.cursor_overlay.row(i);
 
         put_segments(
             &context.cairo_context,
             pango_context,
+            &context.glyph_cache,
             &mut context.queue_draw_area,
             &context.cell_metrics,
             hl_defs,
             segments,
             i,
+            overlay,
+            &context.text_options,
         );
     }
 }
 
-/// Renders `line` to `context.cairo_context`.
-pub fn put_line(
+/// Returns the positions that `overlay` is rendered at, once it's rendered
+/// at its position.
+fn rendered_positions(overlay: &CursorOverlay) -> Vec<(usize, usize)> {
+    match overlay.pos {
+        Some(pos) if overlay.is_enabled() => vec![pos],
+        _ => vec![],
+    }
+}
+
+/// Marks the cells from `start` to `end` (inclusive) on `row` to be
+/// rerendered.
+fn mark_dirty(
+    dirty: &mut BTreeMap<usize, (usize, usize)>,
+    row: usize,
+    (start, end): (usize, usize),
+) {
+    dirty
+        .entry(row)
+        .and_modify(|r| *r = (r.0.min(start), r.1.max(end)))
+        .or_insert((start, end));
+}
+
+/// Marks the cells to be rerendered for moving the cursor line and column
+/// (whichever are enabled in `overlay`) from or to `positions`, in a grid
+/// of `rows` rows and `cols` columns.
+fn mark_overlay_dirty(
+    dirty: &mut BTreeMap<usize, (usize, usize)>,
+    overlay: &CursorOverlay,
+    positions: &[(usize, usize)],
+    rows: usize,
+    cols: usize,
+) {
+    for &(row, col) in positions {
+        if overlay.line && row < rows && cols > 0 {
+            mark_dirty(dirty, row, (0, cols - 1));
+        }
+
+        if overlay.column && col < cols {
+            for row in 0..rows {
+                mark_dirty(dirty, row, (col, col));
+            }
+        }
+    }
+}
+
+/// Updates `context.rows` with the lines in `context.pending_lines`, and
+/// renders the changes to `context.cairo_context`. Each changed row is
+/// rendered only once, no matter how many of the lines touched it.
+pub fn put_pending_lines(
     context: &mut Context,
     pango_context: &pango::Context,
-    line: GridLineSegment,
     hl_defs: &HlDefs,
 ) {
-    let row = line.row as usize;
-    let mut affected_segments = context
-        .rows
-        .get_mut(row)
-        .unwrap_or_else(|| panic!("Failed to get row {}", line.row))
-        .update(line);
-
-    // NOTE(ville): I haven't noticed any cases where a character is overflowing
-    //              to the left. Probably doesn't apply to languages that goes
-    //              from right to left, instead of left to right.
-    // Rendering the segments in reversed order fixes issues when some character
-    // is overflowing to the right.
-    affected_segments.reverse();
-    put_segments(
-        &context.cairo_context,
-        pango_context,
-        &mut context.queue_draw_area,
-        &context.cell_metrics,
-        hl_defs,
-        affected_segments,
-        row,
-    );
+    let mut dirty: BTreeMap<usize, (usize, usize)> = BTreeMap::new();
+    for line in std::mem::take(&mut context.pending_lines) {
+        let row = line.row as usize;
+        let affected_segments = context
+            .rows
+            .get_mut(row)
+            .unwrap_or_else(|| panic!("Failed to get row {}", line.row))
+            .update(line);
+
+        if let Some(range) = segments_range(&affected_segments) {
+            mark_dirty(&mut dirty, row, range);
+        }
+    }
+
+    // Move our cursor line and column to the cursor.
+    let pos = context.cursor_cell_position();
+    let rows = context.rows.len();
+    let cols = context.rows.get(0).map(|r| r.len()).unwrap_or(0);
+    let overlay = &mut context.cursor_overlay;
+    overlay.pos = pos;
+    let rendered = rendered_positions(overlay);
+    if overlay.rendered != rendered {
+        let mut positions = std::mem::replace(&mut overlay.rendered, rendered);
+        positions.extend(pos);
+        mark_overlay_dirty(&mut dirty, overlay, &positions, rows, cols);
+    }
+
+    for (row, (start, end)) in dirty {
+        let affected_segments = context.rows[row].as_segments(start, end);
+        let overlay = context.cursor_overlay.row(row);
+        put_segments(
+            &context.cairo_context,
+            pango_context,
+            &context.glyph_cache,
+            &mut context.queue_draw_area,
+            &context.cell_metrics,
+            hl_defs,
+            affected_segments,
+            row,
+            overlay,
+            &context.text_options,
+        );
+    }
+}
+
+/// Returns the first and the last (inclusive) cell covered by `segments`.
+fn segments_range(segments: &[Segment]) -> Option<(usize, usize)> {
+    let start = segments.iter().map(|seg| seg.start).min()?;
+    let end = segments.iter().map(|seg| seg.start + seg.len - 1).max()?;
+    Some((start, end))
 }
 
 /// Clears whole `da` with `hl_defs.default_bg`.
@@ -217,23 +920,24 @@ pub fn clear(da: &DrawingArea, ctx: &mut Context, hl_defs: &HlDefs) {
     let cr = &ctx.cairo_context;
     let w = da.get_allocated_width();
     let h = da.get_allocated_height();
-    let bg = &hl_defs.default_bg;
 
     cr.save();
-    cr.set_source_rgb(bg.r, bg.g, bg.b);
+    ctx.text_options.set_default_bg_source(cr, hl_defs);
     cr.rectangle(0.0, 0.0, f64::from(w), f64::from(h));
     cr.fill();
     cr.restore();
 
     ctx.queue_draw_area
         .push((0.0, 0.0, f64::from(w), f64::from(h)));
+
+    // Our cursor line and column are gone too.
+    ctx.cursor_overlay.rendered.clear();
 }
 
 /// Scrolls contents in `ctx.cairo_context` and `ctx.rows`, based on `reg`.
 pub fn scroll(ctx: &mut Context, hl_defs: &HlDefs, reg: [u64; 4], count: i64) {
     let cr = &ctx.cairo_context;
     let cm = &ctx.cell_metrics;
-    let bg = &hl_defs.default_bg;
 
     let s = cr.get_target();
 
@@ -317,11 +1021,26 @@ pub fn scroll(ctx: &mut Context, hl_defs: &HlDefs, reg: [u64; 4], count: i64) {
     let w = x2 - x1;
     let h = y2 - y1;
     cr.rectangle(x1, y1, x2 - x1, y2 - y1);
-    cr.set_source_rgb(bg.r, bg.g, bg.b);
+    ctx.text_options.set_default_bg_source(cr, hl_defs);
     cr.fill();
     ctx.queue_draw_area.push((x1, y1, w, h));
 
     cr.restore();
+
+    // Our cursor line and column moved with the rows, so they're rendered
+    // at the moved positions too.
+    let region = top as i64..bot as i64;
+    let moved = ctx
+        .cursor_overlay
+        .rendered
+        .iter()
+        .map(|&(row, col)| (row as i64, row as i64 - count, col))
+        .filter(|(row, moved, _)| {
+            region.contains(row) && region.contains(moved)
+        })
+        .map(|(_, moved, col)| (moved as usize, col))
+        .collect::<Vec<_>>();
+    ctx.cursor_overlay.rendered.extend(moved);
 }
 
 pub fn get_rect(
@@ -342,3 +1061,299 @@ pub fn get_coords(h: f64, w: f64, row: f64, col: f64) -> (f64, f64) {
     let y = row * h;
     (x, y)
 }
+
+#[cfg(all(feature = "unstable", test))]
+mod benches {
+    extern crate test;
+    use self::test::Bencher;
+
+    use pango::prelude::*;
+
+    use super::*;
+
+    const ROWS: usize = 50;
+    const COLS: usize = 200;
+
+    /// Renders a full screen of text, like on a redraw.
+    fn bench_render_screen(b: &mut Bencher, clear_cache: bool) {
+        let font_map = pangocairo::FontMap::get_default().unwrap();
+        let pango_context = font_map.create_context().unwrap();
+        pango_context.set_font_description(
+            &pango::FontDescription::from_string("Monospace 12"),
+        );
+
+        let cm = CellMetrics {
+            width: 8.0,
+            height: 16.0,
+            ascent: 12.0,
+            decent: 4.0,
+            underline_thickness: 1.0,
+            ..CellMetrics::default()
+        };
+        let surface = cairo::ImageSurface::create(
+            cairo::Format::Rgb24,
+            (COLS as f64 * cm.width) as i32,
+            (ROWS as f64 * cm.height) as i32,
+        )
+        .unwrap();
+        let cr = cairo::Context::new(&surface);
+
+        let hl = Highlight::default();
+        let hl_defs = HlDefs::default();
+        let opts = TextOptions {
+            ligatures: true,
+            ..TextOptions::default()
+        };
+        let cache = GlyphCache::default();
+
+        let lines = (0..ROWS)
+            .map(|row| {
+                let text = format!("{} fn foo(bar: &str) -> i32 {{ 0 }} ", row)
+                    .chars()
+                    .cycle()
+                    .take(COLS)
+                    .collect::<String>();
+                let cells = (0..COLS).collect::<Vec<_>>();
+                (text, cells)
+            })
+            .collect::<Vec<_>>();
+
+        b.iter(|| {
+            if clear_cache {
+                cache.clear();
+            }
+
+            for (row, (text, cells)) in lines.iter().enumerate() {
+                render_text(
+                    &cr,
+                    &pango_context,
+                    &cache,
+                    &cm,
+                    &hl,
+                    &hl_defs,
+                    text,
+                    cells,
+                    &opts,
+                    0.0,
+                    row as f64 * cm.height,
+                    COLS as f64 * cm.width,
+                    cm.height,
+                );
+            }
+        });
+    }
+
+    #[bench]
+    fn bench_render_screen_cached(b: &mut Bencher) {
+        bench_render_screen(b, false);
+    }
+
+    #[bench]
+    fn bench_render_screen_uncached(b: &mut Bencher) {
+        bench_render_screen(b, true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_char_to_byte_index() {
+        assert_eq!(0, char_to_byte_index("日本", 0));
+        assert_eq!(3, char_to_byte_index("日本", 1));
+        assert_eq!(6, char_to_byte_index("日本", 2));
+        assert_eq!(6, char_to_byte_index("日本", 10));
+        assert_eq!(1, char_to_byte_index("ab", 1));
+    }
+
+    #[test]
+    fn test_cell_at_offset() {
+        // "a", "=", ">"
+        assert_eq!(0, cell_at_offset(&[0, 1, 2], 3, 0));
+        assert_eq!(1, cell_at_offset(&[0, 1, 2], 3, 1));
+        assert_eq!(2, cell_at_offset(&[0, 1, 2], 3, 2));
+
+        // "あ", "", "b"
+        assert_eq!(0, cell_at_offset(&[0, 3, 3], 4, 0));
+        assert_eq!(2, cell_at_offset(&[0, 3, 3], 4, 3));
+    }
+
+    #[test]
+    fn test_background_runs() {
+        let a = Color::from_u64(0xff0000);
+        let b = Color::from_u64(0x00ff00);
+
+        let runs = background_runs(
+            vec![
+                (0.0, 10.0, a, None),
+                (10.0, 5.0, a, None),
+                (15.0, 5.0, b, None),
+                (20.0, 5.0, b, Some(0.5)),
+                (25.0, 5.0, b, Some(0.5)),
+                // Not adjacent to the previous one.
+                (40.0, 5.0, b, Some(0.5)),
+            ]
+            .into_iter(),
+        );
+
+        assert_eq!(
+            vec![
+                (0.0, 15.0, a, None),
+                (15.0, 5.0, b, None),
+                (20.0, 10.0, b, Some(0.5)),
+                (40.0, 5.0, b, Some(0.5)),
+            ],
+            runs
+        );
+
+        // The segments can be in any order, e.g. from right to left.
+        let runs = background_runs(
+            vec![
+                (20.0, 5.0, b, None),
+                (10.0, 10.0, a, None),
+                (0.0, 10.0, a, None),
+            ]
+            .into_iter(),
+        );
+
+        assert_eq!(vec![(0.0, 20.0, a, None), (20.0, 5.0, b, None)], runs);
+    }
+
+    #[test]
+    fn test_segments_range() {
+        let seg = |start, len| Segment {
+            text: String::new(),
+            cells: vec![],
+            hl_id: 0,
+            start,
+            len,
+        };
+
+        assert_eq!(None, segments_range(&[]));
+        assert_eq!(Some((2, 4)), segments_range(&[seg(2, 3)]));
+        assert_eq!(Some((0, 9)), segments_range(&[seg(5, 5), seg(0, 5)]));
+    }
+
+    #[test]
+    fn test_cell_ranges() {
+        assert_eq!(
+            vec![(0, 3), (3, 3), (3, 4)],
+            cell_ranges(&[0, 3, 3], 4).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_missing_styles() {
+        let both = Styles {
+            bold: true,
+            italic: true,
+        };
+
+        let mut desc = pango::FontDescription::new();
+        desc.set_weight(pango::Weight::Normal);
+        desc.set_style(pango::Style::Normal);
+        assert_eq!(both, missing_styles(both, &desc));
+        assert_eq!(Styles::default(), missing_styles(Styles::default(), &desc));
+
+        desc.set_weight(pango::Weight::Bold);
+        desc.set_style(pango::Style::Oblique);
+        assert_eq!(Styles::default(), missing_styles(both, &desc));
+    }
+
+    #[test]
+    fn test_glyph_scale() {
+        assert_eq!(1.0, glyph_scale(20.0, 20.0));
+        assert_eq!(1.0, glyph_scale(20.3, 20.0));
+        assert_eq!(1.0, glyph_scale(10.0, 20.0));
+        assert_eq!(0.5, glyph_scale(40.0, 20.0));
+    }
+
+    #[test]
+    fn test_wide_cells() {
+        // "a", "あ", "", "b", "い", ""
+        assert_eq!(vec![(1, 4), (5, 8)], wide_cells(&[0, 1, 4, 4, 5, 8], 8));
+        assert!(wide_cells(&[0, 1, 2], 3).is_empty());
+    }
+
+    #[test]
+    fn test_cursor_highlight() {
+        let red = Color {
+            r: 1.0,
+            g: 0.0,
+            b: 0.0,
+        };
+        let blue = Color {
+            r: 0.0,
+            g: 0.0,
+            b: 1.0,
+        };
+        let green = Color {
+            r: 0.0,
+            g: 1.0,
+            b: 0.0,
+        };
+
+        let cell = Highlight {
+            foreground: Some(red),
+            background: Some(blue),
+            ..Highlight::default()
+        };
+
+        let hl_defs = HlDefs::default();
+
+        // Without the cursor's own highlight, the cell is inverted.
+        let hl = cursor_highlight(&cell, None, None, &hl_defs);
+        assert!(hl.reverse);
+        assert_eq!(Some(red), hl.foreground);
+        assert_eq!(Some(blue), hl.background);
+
+        // Cursor's colors override the inverted ones.
+        let attr = Highlight {
+            background: Some(green),
+            ..Highlight::default()
+        };
+        let hl = cursor_highlight(&cell, Some(&attr), None, &hl_defs);
+        assert!(!hl.reverse);
+        assert_eq!(Some(blue), hl.foreground);
+        assert_eq!(Some(green), hl.background);
+
+        // Fixed color overrides both, and the text gets the cell's
+        // background.
+        let hl = cursor_highlight(&cell, Some(&attr), Some(red), &hl_defs);
+        assert!(!hl.reverse);
+        assert_eq!(Some(blue), hl.foreground);
+        assert_eq!(Some(red), hl.background);
+    }
+
+    #[test]
+    fn test_mark_overlay_dirty() {
+        let mut overlay = CursorOverlay {
+            line: true,
+            ..CursorOverlay::default()
+        };
+
+        let mut dirty = BTreeMap::new();
+        mark_dirty(&mut dirty, 1, (2, 3));
+        mark_overlay_dirty(&mut dirty, &overlay, &[(1, 4), (3, 4)], 4, 10);
+        assert_eq!(
+            vec![(1, (0, 9)), (3, (0, 9))],
+            dirty.into_iter().collect::<Vec<_>>()
+        );
+
+        overlay.line = false;
+        overlay.column = true;
+        let mut dirty = BTreeMap::new();
+        mark_overlay_dirty(&mut dirty, &overlay, &[(1, 4), (1, 6)], 2, 10);
+        assert_eq!(
+            vec![(0, (4, 6)), (1, (4, 6))],
+            dirty.into_iter().collect::<Vec<_>>()
+        );
+
+        // Positions outside of the grid are ignored.
+        overlay.line = true;
+        let mut dirty = BTreeMap::new();
+        mark_overlay_dirty(&mut dirty, &overlay, &[(5, 20)], 2, 10);
+        assert!(dirty.is_empty());
+    }
+}
@@ -15,6 +15,8 @@ pub struct Cell {
 pub struct Segment {
     //pub cell: &'a Cell,
     pub text: String,
+    /// Byte offsets in `text` where each of the segment's cells start.
+    pub cells: Vec<usize>,
     pub hl_id: u64,
     pub start: usize,
     pub len: usize,
@@ -96,6 +98,19 @@ impl Row {
         self.cells[from..to].to_vec()
     }
 
+    /// Returns the text from `from` to `to`.
+    pub fn text(&self, from: usize, to: usize) -> String {
+        let to = to.min(self.len);
+        if from >= to {
+            return String::new();
+        }
+
+        self.cells[from..to]
+            .iter()
+            .map(|c| c.text.as_str())
+            .collect()
+    }
+
     /// Inserts rope to `at`. What ever is between `at` and `rope.len()` is
     /// replaced.
     pub fn insert_at(&mut self, at: usize, cells: Vec<Cell>) {
@@ -169,6 +184,7 @@ impl Row {
 
             if let Some(ref mut seg) = segs.last_mut() {
                 if seg.hl_id == cell.hl_id {
+                    seg.cells.push(seg.text.len());
                     seg.text.push_str(&cell.text);
                     seg.len += 1;
 
@@ -179,6 +195,7 @@ impl Row {
 
             segs.push(Segment {
                 text: cell.text.clone(),
+                cells: vec![0],
                 hl_id: cell.hl_id,
                 start,
                 len: 1,
@@ -467,6 +484,42 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_row_text() {
+        let mut row = Row::new(10);
+        row.insert_at(
+            2,
+            vec![
+                Cell {
+                    text: "a".to_string(),
+                    hl_id: 0,
+                    double_width: false,
+                },
+                Cell {
+                    text: "世".to_string(),
+                    hl_id: 0,
+                    double_width: true,
+                },
+                Cell {
+                    text: "".to_string(),
+                    hl_id: 0,
+                    double_width: false,
+                },
+                Cell {
+                    text: "b".to_string(),
+                    hl_id: 0,
+                    double_width: false,
+                },
+            ],
+        );
+
+        assert_eq!("  a世b    ", row.text(0, 10));
+        assert_eq!("a世", row.text(2, 5));
+        assert_eq!("b    ", row.text(5, 100));
+        assert_eq!("", row.text(5, 5));
+        assert_eq!("", row.text(20, 30));
+    }
+
     #[test]
     fn test_row_update() {
         let mut row = Row::new(10);
@@ -608,12 +661,14 @@ mod tests {
             vec![
                 Segment {
                     text: "==".to_string(),
+                    cells: vec![0, 1],
                     hl_id: 1,
                     start: 2,
                     len: 2,
                 },
                 Segment {
                     text: " ".to_string(),
+                    cells: vec![0],
                     hl_id: 2,
                     start: 4,
                     len: 1,
@@ -0,0 +1,84 @@
+use crate::ui::color::Color;
+use crate::ui::common::ease_out_cubic;
+
+/// Default duration (in ms) of the scroll animation.
+pub const DEFAULT_SCROLL_ANIMATION_DURATION_MS: u64 = 150;
+
+/// Animation of a scrolled region of a grid. Instead of jumping to its new
+/// position, the region's content is drawn shifted back towards where it
+/// was before the scroll, and the shift eases out over the animation.
+pub struct ScrollAnimation {
+    /// The scrolled region, (x, y, w, h) in pixels.
+    pub rect: (f64, f64, f64, f64),
+    /// Color for the part of the region that the shifted content doesn't
+    /// cover.
+    pub bg: Color,
+    /// Shift (in pixels) of the content at the start of the animation.
+    offset: f64,
+    start_time: i64,
+    end_time: i64,
+}
+
+impl ScrollAnimation {
+    /// Creates a new animation, starting at `frame_time`. `offset` is how
+    /// much (in pixels) the content moved up with the scroll.
+    pub fn new(
+        rect: (f64, f64, f64, f64),
+        bg: Color,
+        offset: f64,
+        frame_time: i64,
+        duration: u64,
+    ) -> Self {
+        ScrollAnimation {
+            rect,
+            bg,
+            offset,
+            start_time: frame_time,
+            end_time: frame_time + 1000 * duration as i64,
+        }
+    }
+
+    /// Returns the content's shift at `frame_time`, or None if the
+    /// animation is over.
+    pub fn offset_at(&self, frame_time: i64) -> Option<f64> {
+        if frame_time >= self.end_time {
+            return None;
+        }
+
+        let t = (frame_time - self.start_time) as f64
+            / (self.end_time - self.start_time) as f64;
+        Some(self.offset * (1.0 - ease_out_cubic(t.max(0.0))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset_at() {
+        let anim = ScrollAnimation::new(
+            (0.0, 0.0, 100.0, 100.0),
+            Color::default(),
+            30.0,
+            1000,
+            100,
+        );
+
+        assert_eq!(Some(30.0), anim.offset_at(1000));
+        // The shift eases out, so it's mostly done half way through.
+        let half = anim.offset_at(1000 + 50_000).unwrap();
+        assert!(half > 0.0 && half < 15.0);
+        assert_eq!(None, anim.offset_at(1000 + 100_000));
+
+        // Scrolling up shifts the content the other way.
+        let anim = ScrollAnimation::new(
+            (0.0, 0.0, 100.0, 100.0),
+            Color::default(),
+            -30.0,
+            0,
+            100,
+        );
+        assert!(anim.offset_at(50_000).unwrap() < 0.0);
+    }
+}
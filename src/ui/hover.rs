@@ -0,0 +1,180 @@
+//! Hover tooltip, that shows information (e.g. from a language server)
+//! about the text under the pointer.
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use gtk::prelude::*;
+
+use rmpv::Value;
+
+use crate::nvim_gio::GioNeovim;
+use crate::ui::common::{report_error, spawn_local};
+use crate::ui::font::Font;
+
+/// Time (in ms) the pointer has to stay still before the hover is shown.
+const HOVER_DELAY_MS: u32 = 500;
+/// Offset (in pixels) of the tooltip from the pointer.
+const POINTER_OFFSET: i32 = 12;
+
+/// Tooltip for hovering over the grids. After the pointer has stayed still
+/// on a cell for a moment, `gnvim#hover#get()` is called for the cell, and
+/// the lines it returns are shown in a tooltip next to the pointer.
+///
+/// Cloning returns a handle to the same tooltip, so a single `Hover` can be
+/// shared between grids.
+#[derive(Clone)]
+pub struct Hover {
+    nvim: GioNeovim,
+    popup: gtk::Window,
+    label: gtk::Label,
+
+    enabled: Rc<Cell<bool>>,
+    /// Source id of the pending hover.
+    source_id: Rc<RefCell<Option<glib::SourceId>>>,
+    /// Incremented on each pointer motion, so that replies for earlier
+    /// hovers can be ignored.
+    generation: Rc<Cell<u64>>,
+}
+
+impl Hover {
+    pub fn new(nvim: GioNeovim, parent: &gtk::Window) -> Self {
+        let popup = gtk::Window::new(gtk::WindowType::Popup);
+        popup.set_transient_for(Some(parent));
+        popup.set_type_hint(gdk::WindowTypeHint::Tooltip);
+        // Let the theme style us like a regular tooltip.
+        popup.get_style_context().add_class("tooltip");
+
+        let label = gtk::Label::new(None);
+        label.set_xalign(0.0);
+        popup.add(&label);
+        label.show();
+
+        Hover {
+            nvim,
+            popup,
+            label,
+            enabled: Rc::new(Cell::new(false)),
+            source_id: Rc::new(RefCell::new(None)),
+            generation: Rc::new(Cell::new(0)),
+        }
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.set(enabled);
+        if !enabled {
+            self.cancel();
+        }
+    }
+
+    pub fn set_font(&self, font: Font) {
+        let attrs = pango::AttrList::new();
+        attrs.insert(pango::Attribute::new_font_desc(&font.as_pango_font()));
+        self.label.set_attributes(Some(&attrs));
+    }
+
+    /// Hides the tooltip, and cancels any pending hover.
+    pub fn cancel(&self) {
+        self.generation.set(self.generation.get() + 1);
+        if let Some(id) = self.source_id.borrow_mut().take() {
+            glib::source::source_remove(id);
+        }
+
+        self.popup.hide();
+    }
+
+    /// Starts a hover over cell `row`, `col` of nvim window `win`. `pos` is
+    /// the pointer's position, relative to the root window.
+    pub fn start(
+        &self,
+        win: Option<Value>,
+        row: u64,
+        col: u64,
+        pos: (f64, f64),
+    ) {
+        self.cancel();
+
+        let win = match win {
+            Some(win) if self.enabled.get() => win,
+            _ => return,
+        };
+
+        let this = self.clone();
+        let generation = self.generation.get();
+        let id = gtk::timeout_add(HOVER_DELAY_MS, move || {
+            // The source is removed once we return, so forget about it.
+            this.source_id.borrow_mut().take();
+
+            let this = this.clone();
+            let win = win.clone();
+            spawn_local(async move {
+                let args = vec![win, row.into(), col.into()];
+                let lines = match this
+                    .nvim
+                    .call_function("gnvim#hover#get", args)
+                    .await
+                {
+                    Ok(lines) => lines,
+                    Err(err) => {
+                        report_error("Failed to get hover", err);
+                        return;
+                    }
+                };
+
+                // The pointer has moved on since.
+                if this.generation.get() != generation {
+                    return;
+                }
+
+                if let Some(text) = hover_text(&lines) {
+                    this.show(&text, pos);
+                }
+            });
+
+            Continue(false)
+        });
+
+        self.source_id.replace(Some(id));
+    }
+
+    fn show(&self, text: &str, (x, y): (f64, f64)) {
+        self.label.set_text(text);
+        // Shrink back to the new text's size.
+        self.popup.resize(1, 1);
+        self.popup
+            .move_(x as i32 + POINTER_OFFSET, y as i32 + POINTER_OFFSET);
+        self.popup.show();
+    }
+}
+
+/// Returns the text to show for the `lines` returned by
+/// `gnvim#hover#get()`, or None if there's nothing to show.
+fn hover_text(lines: &Value) -> Option<String> {
+    let lines = lines
+        .as_array()?
+        .iter()
+        .filter_map(Value::as_str)
+        .collect::<Vec<_>>();
+    let text = lines.join("\n");
+
+    if text.trim().is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hover_text() {
+        let lines = Value::from(vec![Value::from("foo"), Value::from("bar")]);
+        assert_eq!(Some(String::from("foo\nbar")), hover_text(&lines));
+
+        let empty = Value::from(vec![Value::from(""), Value::from(" ")]);
+        assert_eq!(None, hover_text(&empty));
+        assert_eq!(None, hover_text(&Value::from(0)));
+    }
+}
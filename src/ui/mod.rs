@@ -47,18 +47,31 @@ macro_rules! upgrade_weak {
     };
 }
 
+mod blur;
+mod clipboard;
 mod cmdline;
 pub mod color;
 mod common;
+mod context_menu;
 #[cfg(feature = "libwebkit2gtk")]
 mod cursor_tooltip;
+mod dbus;
+mod error_bar;
+mod file_drop;
+mod find;
 mod font;
 mod grid;
+mod hover;
 mod popupmenu;
 mod state;
 mod tabline;
+mod theme;
 #[allow(clippy::module_inception)]
 mod ui;
 mod wildmenu;
 mod window;
-pub use self::ui::UI;
+mod window_geometry;
+pub use self::dbus::remote_eval;
+pub use self::file_drop::tabedit_command;
+pub use self::ui::{RESTART_ACTION, UI};
+pub use self::window_geometry::WindowGeometry;
@@ -31,6 +31,7 @@ impl CompletionItemWidgetWrap {
         item: CompletionItem,
         show_kind: bool,
         show_menu: bool,
+        prefix: &str,
         css_provider: &gtk::CssProvider,
         icon_fg: &Color,
         size: f64,
@@ -62,6 +63,19 @@ impl CompletionItemWidgetWrap {
         word.set_ellipsize(pango::EllipsizeMode::End);
         grid.attach(&word, 1, 0, 1, 1);
 
+        let matches = fuzzy_match(&item.word, prefix);
+        if !matches.is_empty() {
+            let attrs = pango::AttrList::new();
+            for (start, end) in matches {
+                let mut attr =
+                    pango::Attribute::new_weight(pango::Weight::Bold).unwrap();
+                attr.set_start_index(start as u32);
+                attr.set_end_index(end as u32);
+                attrs.insert(attr);
+            }
+            word.set_attributes(Some(&attrs));
+        }
+
         let info = gtk::Label::new(Some(shorten_info(&item.info).as_str()));
         info.set_halign(gtk::Align::Start);
         info.set_ellipsize(pango::EllipsizeMode::End);
@@ -101,6 +115,32 @@ impl CompletionItemWidgetWrap {
     }
 }
 
+/// Returns the byte ranges of the characters in `word` that match `prefix`,
+/// when `prefix` is (case insensitively) a subsequence of `word`. Otherwise
+/// returns nothing.
+fn fuzzy_match(word: &str, prefix: &str) -> Vec<(usize, usize)> {
+    let mut ranges = vec![];
+    let mut prefix = prefix.chars().peekable();
+
+    for (i, c) in word.char_indices() {
+        let p = match prefix.peek() {
+            Some(p) => p,
+            None => break,
+        };
+
+        if c.to_lowercase().eq(p.to_lowercase()) {
+            ranges.push((i, i + c.len_utf8()));
+            prefix.next();
+        }
+    }
+
+    if prefix.peek().is_some() {
+        return vec![];
+    }
+
+    ranges
+}
+
 /// Returns first line of `info`.
 fn shorten_info(info: &str) -> String {
     let lines = info.split('\n').collect::<Vec<&str>>();
@@ -161,3 +201,17 @@ fn get_icon_name_for_kind(
         _ => icon!("../../../assets/icons/help-circle.svg", color, size),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert_eq!(vec![(0, 1), (1, 2)], fuzzy_match("foobar", "fo"));
+        assert_eq!(vec![(0, 1), (3, 4)], fuzzy_match("foobar", "FB"));
+        assert_eq!(vec![(1, 3)], fuzzy_match("a\u{e4}\u{e4}", "\u{c4}"));
+        assert!(fuzzy_match("foobar", "fz").is_empty());
+        assert!(fuzzy_match("foobar", "").is_empty());
+    }
+}
@@ -71,6 +71,7 @@ impl LazyLoader {
         icon_fg: Color,
         size: f64,
         show_menu: bool,
+        prefix: String,
     ) {
         let mut state = self.state.borrow_mut();
         state.clear();
@@ -100,6 +101,7 @@ impl LazyLoader {
                     item,
                     state.show_kind,
                     show_menu,
+                    &prefix,
                     &state.css_provider,
                     &icon_fg,
                     size,
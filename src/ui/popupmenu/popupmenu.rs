@@ -2,21 +2,20 @@ use std::cell::RefCell;
 use std::rc::Rc;
 
 use gtk::prelude::*;
-use log::error;
 
 use crate::nvim_bridge::CompletionItem;
 use crate::nvim_gio::GioNeovim;
 use crate::ui::color::{Highlight, HlDefs, HlGroup};
 use crate::ui::common::{
     calc_line_space, get_preferred_horizontal_position,
-    get_preferred_vertical_position, spawn_local,
+    get_preferred_vertical_position, report_error, spawn_local,
 };
 use crate::ui::font::{Font, FontUnit};
 use crate::ui::grid::GridMetrics;
 use crate::ui::popupmenu::get_icon_pixbuf;
 use crate::ui::popupmenu::LazyLoader;
 
-/// Maximum height of completion menu.
+/// Default maximum height of completion menu.
 const MAX_HEIGHT: i32 = 500;
 /// Fixed width of completion menu.
 const DEFAULT_WIDTH_NO_DETAILS: i32 = 430;
@@ -42,6 +41,12 @@ struct State {
 
     width_no_details: i32,
     width_with_details: i32,
+
+    /// Maximum height, in rows. When 0, `MAX_HEIGHT` is used.
+    max_rows: u64,
+    /// Height of the list's rows, measured from the first row. 0 until the
+    /// rows have been allocated.
+    row_height: i32,
 }
 
 impl State {
@@ -60,8 +65,29 @@ impl State {
             current_width: DEFAULT_WIDTH_NO_DETAILS,
             width_no_details: DEFAULT_WIDTH_NO_DETAILS,
             width_with_details: DEFAULT_WIDTH_WITH_DETAILS,
+            max_rows: 0,
+            row_height: 0,
         }
     }
+
+    /// Returns the maximum height of the popupmenu, in pixels.
+    fn max_height(&self) -> i32 {
+        if self.max_rows == 0 {
+            return MAX_HEIGHT;
+        }
+
+        // Until the rows have been allocated, estimate their height from
+        // the grid's cells.
+        let row_height = if self.row_height > 0 {
+            Some(f64::from(self.row_height))
+        } else {
+            self.base_metrics.as_ref().map(|m| m.cell_height)
+        };
+
+        row_height
+            .map(|h| rows_to_height(self.max_rows, h))
+            .unwrap_or(MAX_HEIGHT)
+    }
 }
 
 pub struct Popupmenu {
@@ -85,6 +111,10 @@ pub struct Popupmenu {
     info_label: gtk::Label,
     /// Flag telling if the menu label should be shown on inactive items too.
     show_menu_on_all_items: bool,
+    /// Flag telling if the items' parts matching `prefix` are highlighted.
+    highlight_matches: bool,
+    /// Text that the user has typed for the current completion.
+    prefix: String,
 
     state: Rc<RefCell<State>>,
     items: LazyLoader,
@@ -181,9 +211,9 @@ impl Popupmenu {
 
             let nvim = nvim.clone();
             spawn_local(async move {
-                nvim.input(payload.as_str())
-                    .await
-                    .unwrap();
+                if let Err(err) = nvim.input(payload.as_str()).await {
+                    report_error("Failed to select completion item", err);
+                }
             });
         }));
 
@@ -194,15 +224,32 @@ impl Popupmenu {
                 // And if so, tell neovim to select the current completion item.
                 let nvim = nvim.clone();
                 spawn_local(async move {
-                    nvim.input("<C-y>")
-                        .await
-                        .unwrap();
+                    if let Err(err) = nvim.input("<C-y>").await {
+                        report_error("Failed to accept completion item", err);
+                    }
                 });
             }
 
             Inhibit(false)
         }));
 
+        // The rows have their own font and padding, so measure their height
+        // for the maximum height.
+        let box_weak = box_.downgrade();
+        list.connect_size_allocate(clone!(state, box_weak => move |list, _| {
+            let box_ = upgrade_weak!(box_weak);
+            let height = match list.get_row_at_index(0) {
+                Some(row) => row.get_allocated_height(),
+                None => return,
+            };
+
+            let mut state = state.borrow_mut();
+            if height > 0 && height != state.row_height {
+                state.row_height = height;
+                box_.set_size_request(state.current_width, state.max_height());
+            }
+        }));
+
         // TODO(ville): Should use gtk::Fixed here.
         let layout = gtk::Layout::new(
             None::<&gtk::Adjustment>,
@@ -233,13 +280,24 @@ impl Popupmenu {
                 let (y, height) = get_preferred_vertical_position(
                     &area,
                     &pos,
-                    alloc.height.min(MAX_HEIGHT),
+                    alloc.height.min(state.max_height()),
                 );
 
                 layout.move_(box_, x, y);
 
                 box_.set_size_request(width, height);
 
+                // If we had to move the popupmenu to the left to fit it,
+                // flip the info to the left side of the list so the list
+                // stays close to the anchor.
+                let info_pos = if x < pos.x { 0 } else { 1 };
+                let info_widget = scrolled_info.clone().upcast::<gtk::Widget>();
+                if box_.get_children().iter().position(|c| *c == info_widget)
+                    != Some(info_pos)
+                {
+                    box_.reorder_child(&scrolled_info, info_pos as i32);
+                }
+
                 if let Some(ref base_metrics) = state.base_metrics {
                     let width = width as f64 / base_metrics.cell_width as f64;
                     let height = height as f64 / base_metrics.cell_height as f64;
@@ -249,7 +307,7 @@ impl Popupmenu {
                     let nvim = nvim.clone();
                     spawn_local(async move {
                         if let Err(err) = nvim.ui_pum_set_bounds(width, height, row, col).await {
-                            error!("Failed to set pum bounds: {}", err);
+                            report_error("Failed to set pum bounds", err);
                         }
                     });
                 }
@@ -290,6 +348,8 @@ impl Popupmenu {
         Popupmenu {
             items: LazyLoader::new(list.clone(), css_provider.clone()),
             show_menu_on_all_items: false,
+            highlight_matches: true,
+            prefix: String::new(),
             box_,
             layout,
             css_provider,
@@ -309,6 +369,10 @@ impl Popupmenu {
         self.show_menu_on_all_items = b;
     }
 
+    pub fn set_highlight_matches(&mut self, b: bool) {
+        self.highlight_matches = b;
+    }
+
     #[allow(unused)]
     pub fn is_above_anchor(&self) -> bool {
         self.scrolled_list.get_child().unwrap().get_valign() == gtk::Align::End
@@ -320,54 +384,53 @@ impl Popupmenu {
     }
 
     pub fn toggle_show_info(&mut self) {
-        {
-            let state = self.state.borrow();
+        self.info_shown = !self.info_shown;
 
-            self.info_shown = !self.info_shown;
-
-            let selected = state.selected as usize;
-            let info_shown = self.info_shown;
-            let info_label = self.info_label.clone();
-            self.items.once_loaded(Some(state.selected), move |items| {
-                if let Some(item) = items.get(selected) {
-                    item.info.set_visible(!info_shown);
-                    item.menu.set_visible(!info_shown);
-
-                    if item.item.info.is_empty() {
-                        item.info.set_visible(false);
-                    }
+        let selected = self.state.borrow().selected;
+        let info_shown = self.info_shown;
+        let info_label = self.info_label.clone();
+        let state = self.state.clone();
+        let box_ = self.box_.clone();
+        let scrolled_info = self.scrolled_info.clone();
+        self.items.once_loaded(Some(selected), move |items| {
+            let mut has_info_content = false;
+            if let Some(item) = items.get(selected as usize) {
+                item.info.set_visible(!info_shown);
+                item.menu.set_visible(!info_shown);
 
-                    info_label.set_visible(
-                        info_shown
-                            && item.item.menu.len() + item.item.info.len() > 0,
-                    );
+                if item.item.info.is_empty() {
+                    item.info.set_visible(false);
                 }
-            });
 
-            if !self.info_shown {
-                let adj = self.scrolled_info.get_vadjustment().unwrap();
-                adj.set_value(0.0);
-                // TODO(ville): There is a bug in GTK+ and some adjustment animations,
-                //              where the adjustment's value is set back to upper - page-size
-                //              if the user has "overshot" the scrolling. Work around this.
+                has_info_content =
+                    item.item.menu.len() + item.item.info.len() > 0;
             }
 
-            self.scrolled_info.set_visible(self.info_shown);
-        }
+            info_label.set_visible(info_shown && has_info_content);
+            set_info_visible(
+                &mut state.borrow_mut(),
+                &box_,
+                &scrolled_info,
+                info_shown && has_info_content,
+            );
+        });
 
-        self.ensure_container_width();
+        if !self.info_shown {
+            let adj = self.scrolled_info.get_vadjustment().unwrap();
+            adj.set_value(0.0);
+            // TODO(ville): There is a bug in GTK+ and some adjustment animations,
+            //              where the adjustment's value is set back to upper - page-size
+            //              if the user has "overshot" the scrolling. Work around this.
+        }
     }
 
     fn ensure_container_width(&mut self) {
-        let mut state = self.state.borrow_mut();
-
-        state.current_width = if self.info_shown {
-            state.width_with_details
-        } else {
-            state.width_no_details
-        };
-
-        self.box_.set_size_request(state.current_width, MAX_HEIGHT);
+        set_info_visible(
+            &mut self.state.borrow_mut(),
+            &self.box_,
+            &self.scrolled_info,
+            self.scrolled_info.get_visible(),
+        );
     }
 
     pub fn set_width(&mut self, w: i32) {
@@ -378,6 +441,13 @@ impl Popupmenu {
         self.ensure_container_width();
     }
 
+    /// Sets the maximum height of the popupmenu, in rows. With 0, the default
+    /// maximum height is used.
+    pub fn set_max_height(&mut self, rows: u64) {
+        self.state.borrow_mut().max_rows = rows;
+        self.ensure_container_width();
+    }
+
     pub fn set_width_details(&mut self, w: i32) {
         {
             let mut state = self.state.borrow_mut();
@@ -389,6 +459,7 @@ impl Popupmenu {
     /// Hides the popupmenu.
     pub fn hide(&mut self) {
         self.layout.hide();
+        self.prefix.clear();
     }
 
     /// Shows the popupmenu.
@@ -404,12 +475,33 @@ impl Popupmenu {
         state.anchor = rect;
     }
 
-    pub fn set_items(&mut self, items: Vec<CompletionItem>, hl_defs: &HlDefs) {
+    /// Sets the completion items.
+    ///
+    /// * `selected` - Index of the selected item, or -1.
+    /// * `text` - Text (on the grid) from where the completed word starts.
+    pub fn set_items(
+        &mut self,
+        items: Vec<CompletionItem>,
+        selected: i64,
+        text: &str,
+        hl_defs: &HlDefs,
+    ) {
+        let inserted =
+            items.get(selected as usize).map(|item| item.word.as_str());
+        self.prefix = completion_prefix(text, inserted, &self.prefix);
+
+        let prefix = if self.highlight_matches {
+            self.prefix.clone()
+        } else {
+            String::new()
+        };
+
         self.items.set_items(
             items,
             self.colors.hl.foreground.unwrap_or(hl_defs.default_fg),
             self.font.height as f64,
             self.show_menu_on_all_items,
+            prefix,
         );
 
         self.list.show_all();
@@ -426,6 +518,8 @@ impl Popupmenu {
         let info_label = self.info_label.clone();
         let info_shown = self.info_shown;
         let show_kind = self.items.get_show_kind();
+        let box_ = self.box_.clone();
+        let scrolled_info = self.scrolled_info.clone();
 
         let show_menu_on_all_items = self.show_menu_on_all_items;
 
@@ -450,6 +544,7 @@ impl Popupmenu {
                 list.unselect_all();
                 info_label.set_text("");
                 info_label.hide();
+                set_info_visible(&mut state, &box_, &scrolled_info, false);
 
                 // If selection is removed, move the scrolled window to the top.
                 let adj = scrolled_list.get_vadjustment().unwrap();
@@ -517,6 +612,13 @@ impl Popupmenu {
                 let has_info_content =
                     item.item.menu.len() + item.item.info.len() > 0;
                 info_label.set_visible(info_shown && has_info_content);
+                // Don't waste space on an empty info.
+                set_info_visible(
+                    &mut state,
+                    &box_,
+                    &scrolled_info,
+                    info_shown && has_info_content,
+                );
             }
         });
     }
@@ -675,6 +777,48 @@ impl Popupmenu {
     }
 }
 
+/// Returns the height (in pixels) for `rows` rows of `row_height` tall
+/// completion items.
+fn rows_to_height(rows: u64, row_height: f64) -> i32 {
+    // Add room for the list's borders.
+    (rows as f64 * row_height).ceil() as i32 + 2
+}
+
+/// Returns the text the user has typed for the completion, when `text` is
+/// the text from where the completed word starts. If the `inserted` (selected)
+/// item is already in the text, we can't tell what the user typed, so `prev`
+/// is kept.
+fn completion_prefix(text: &str, inserted: Option<&str>, prev: &str) -> String {
+    let word: String = text
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_')
+        .collect();
+
+    match inserted {
+        Some(inserted) if inserted == word => prev.to_string(),
+        _ => word,
+    }
+}
+
+/// Shows or hides the info next to the list, and updates the popupmenu's width
+/// accordingly.
+fn set_info_visible(
+    state: &mut State,
+    box_: &gtk::Box,
+    scrolled_info: &gtk::ScrolledWindow,
+    visible: bool,
+) {
+    scrolled_info.set_visible(visible);
+
+    state.current_width = if visible {
+        state.width_with_details
+    } else {
+        state.width_no_details
+    };
+
+    box_.set_size_request(state.current_width, state.max_height());
+}
+
 fn ensure_row_visible(list: &gtk::ListBox, row: &gtk::ListBoxRow) {
     if let Some(adj) = list.get_adjustment() {
         let alloc = row.get_allocation();
@@ -684,3 +828,23 @@ fn ensure_row_visible(list: &gtk::ListBox, row: &gtk::ListBoxRow) {
         adj.clamp_page(y.into(), (y + height).into());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rows_to_height() {
+        assert_eq!(12, rows_to_height(1, 10.0));
+        assert_eq!(53, rows_to_height(3, 16.5));
+    }
+
+    #[test]
+    fn test_completion_prefix() {
+        assert_eq!("foo", completion_prefix("foo bar", None, ""));
+        assert_eq!("foo_1", completion_prefix("foo_1.bar", None, "fo"));
+        // The selected item is inserted.
+        assert_eq!("fo", completion_prefix("foobar", Some("foobar"), "fo"));
+        assert_eq!("foob", completion_prefix("foob", Some("foobar"), "fo"));
+    }
+}
@@ -1,4 +1,4 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::rc::Rc;
 
@@ -12,24 +12,49 @@ use crate::nvim_bridge::{
     CmdlineSpecialChar, DefaultColorsSet, GnvimEvent, GridCursorGoto,
     GridLineSegment, GridResize, GridScroll, HlAttrDefine, HlGroupSet,
     ModeChange, ModeInfo, ModeInfoSet, MsgSetPos, Notify, OptionSet,
-    PopupmenuShow, RedrawEvent, TablineUpdate, WindowExternalPos,
-    WindowFloatPos, WindowPos,
+    PopupmenuShow, RedrawEvent, ScrollbarPosition, TablineUpdate,
+    WindowExternalPos, WindowFloatPos, WindowPos, WindowViewport,
 };
 use crate::nvim_gio::GioNeovim;
+use crate::ui::blur;
+use crate::ui::clipboard;
 use crate::ui::cmdline::Cmdline;
-use crate::ui::color::{HlDefs, HlGroup};
-use crate::ui::common::spawn_local;
+use crate::ui::color::{Color, HlDefs, HlGroup};
+use crate::ui::common::{report_error, spawn_local, toggle_fullscreen};
+use crate::ui::context_menu::ContextMenu;
 #[cfg(feature = "libwebkit2gtk")]
 use crate::ui::cursor_tooltip::{CursorTooltip, Gravity};
+use crate::ui::find::FindBar;
 use crate::ui::font::Font;
-use crate::ui::grid::{Grid, GridMetrics};
+use crate::ui::grid::{
+    Grid, GridMetrics, KineticScroll, MouseButton, Preedit, ScrollDirection,
+};
+use crate::ui::hover::Hover;
 use crate::ui::popupmenu::Popupmenu;
 use crate::ui::tabline::Tabline;
-use crate::ui::window::{MsgWindow, Window};
+use crate::ui::window::{
+    scroll_window, ExternalGeometries, ExternalOptions, KeyInput,
+    MouseReporting, MsgWindow, ScrollSpeed, Window,
+};
 
 pub(crate) type Windows = HashMap<i64, Window>;
 pub(crate) type Grids = HashMap<i64, Grid>;
 
+/// Font size change requested from the GUI (e.g. with ctrl+=).
+pub(crate) enum FontZoom {
+    In,
+    Out,
+    Reset,
+}
+
+/// How much each zoom step changes the font's scale.
+const FONT_SCALE_STEP: f32 = 0.1;
+const FONT_SCALE_MIN: f32 = 0.5;
+const FONT_SCALE_MAX: f32 = 3.0;
+
+/// Title of the main window when nvim doesn't set one.
+pub const DEFAULT_TITLE: &str = "Neovim";
+
 pub(crate) struct ResizeOptions {
     pub font: Font,
     pub line_space: i64,
@@ -58,6 +83,8 @@ pub(crate) struct UIState {
     pub current_mode: Option<ModeInfo>,
     /// Id of the current active grid.
     pub current_grid: i64,
+    /// Nvim's title (see 'title'), or `None` for the default title.
+    pub title: Option<String>,
 
     pub popupmenu: Popupmenu,
     pub cmdline: Cmdline,
@@ -75,15 +102,132 @@ pub(crate) struct UIState {
     pub resize_source_id: Rc<RefCell<Option<glib::SourceId>>>,
     /// Resize options that is some if a resize should be send to nvim on flush.
     pub resize_on_flush: Option<ResizeOptions>,
+    /// Latest position of the msg window, so it can be repositioned when
+    /// the font changes.
+    pub msg_pos: Option<MsgSetPos>,
 
     /// Flag for flush to update GUI colors on components that depend on
     /// highlight defs and groups.
     pub hl_changed: bool,
 
+    /// Font from 'guifont', without the zoom applied.
     pub font: Font,
+    /// Zoom applied on top of `font`.
+    pub font_scale: f32,
+    /// Font from 'guifontwide', for double width cells.
+    pub font_wide: Option<Font>,
     pub line_space: i64,
 
     pub enable_cursor_animations: bool,
+    /// Duration (in ms) of the cursor's movement animation.
+    pub cursor_animation_duration: u64,
+    /// Fixed color for the cursors, see `Grid::set_cursor_color`.
+    pub cursor_color: Option<Color>,
+    /// Cursor colors of the modes, by the modes' names. Override
+    /// `cursor_color`.
+    pub mode_cursor_colors: HashMap<String, Color>,
+    /// Name of the current mode, from `mode_change`.
+    pub mode_name: String,
+    pub enable_scroll_animation: bool,
+    /// Duration (in ms) of the scroll animation.
+    pub scroll_animation_duration: u64,
+    pub enable_ligatures: bool,
+    pub enable_synthetic_styles: bool,
+    pub bold_is_bright: bool,
+    /// If the bell flashes the grid instead of beeping.
+    pub visual_bell: bool,
+    /// How much the grids are dimmed when the window isn't focused.
+    pub inactive_dim: f64,
+    /// If the main window has the focus.
+    pub focused: bool,
+    /// Color of the yank flash. `None` uses the default foreground.
+    pub yank_flash_color: Option<Color>,
+    /// Duration (in ms) of the yank flash, 0 to disable it.
+    pub yank_flash_duration: u64,
+    /// If the pointer is shown as busy over the grids while nvim is busy.
+    pub busy_pointer: bool,
+    pub context_menu: ContextMenu,
+    pub hover: Hover,
+    /// Startup splash, removed on the first flush.
+    pub splash: Option<gtk::Widget>,
+    /// Size (cols, rows) to fit the main window to while nvim is starting,
+    /// from `--size` or `gnvim#set_size`. The font from the user's config
+    /// isn't known before nvim has started, so the size is applied again
+    /// whenever the font changes (see `fit_on_flush`).
+    pub initial_size: Option<(u64, u64)>,
+    /// If `initial_size` is from `--size`, which wins over the config.
+    pub size_from_cli: bool,
+    /// If `initial_size` is applied on the next flush.
+    pub fit_on_flush: bool,
+    /// True until nvim's `UIEnter`.
+    pub starting: bool,
+    pub scroll_speed: ScrollSpeed,
+    /// Tells if the mouse input is sent to nvim, per 'mouse'.
+    pub mouse: MouseReporting,
+    /// If alt is sent to nvim as meta, instead of going through the input
+    /// method.
+    pub alt_is_meta: bool,
+    /// Keys that are left for the window manager (or GTK), instead of
+    /// being sent to nvim.
+    pub key_passthrough: Vec<String>,
+    /// If F11 toggles fullscreen, instead of going to nvim.
+    pub fullscreen_key: bool,
+    pub find: FindBar,
+    /// If ctrl+f opens the find bar, instead of going to nvim.
+    pub find_key: bool,
+    /// Direction of the find step to take after the next flush, once
+    /// nvim has scrolled to the next page of matches.
+    pub find_pending_step: Option<bool>,
+    /// OpenType font features for the grids.
+    pub font_features: String,
+    /// User CSS, appended to our styles.
+    pub user_css: String,
+    /// Strip at the bottom of the window, colored by the current mode's
+    /// accent color (see `mode_colors`).
+    pub mode_indicator: gtk::Frame,
+    /// Accent colors of the modes, by the modes' names.
+    pub mode_colors: HashMap<String, Color>,
+    /// Css class of the current mode, set on the main window (e.g.
+    /// `mode-insert`).
+    pub mode_class: Option<String>,
+    /// Kinetic scrolling, shared between all grids.
+    pub kinetic_scroll: KineticScroll,
+
+    /// Nvim's 'scrolloff' value, passed to our windows.
+    pub scrolloff: u64,
+    pub scrollbar_click_to_jump: bool,
+    pub scrollbar_autohide: bool,
+    /// Width of the scrollbars in pixels, 0 for GTK's default.
+    pub scrollbar_width: u64,
+    pub scrollbar_opacity: f64,
+    pub scrollbar_position: ScrollbarPosition,
+    /// Opacity of the cells with the default background, and the window's
+    /// background.
+    pub background_opacity: f64,
+    /// If the content behind the window is blurred, when the background
+    /// is translucent.
+    pub background_blur: bool,
+    /// Maximum frame rate for the grids, or 0 for no limit.
+    pub max_fps: u64,
+    /// If we draw the cursor line and column (see `set_cursor_overlay`).
+    pub cursor_overlay: (bool, bool),
+    /// If a border is drawn around floating windows.
+    pub float_border: bool,
+    /// If floating windows' position changes are animated.
+    pub float_animation: bool,
+    /// Last geometries of external windows, restored when a grid is
+    /// externalized again.
+    pub external_geometries: ExternalGeometries,
+    /// Options for new external windows.
+    pub external_options: ExternalOptions,
+    /// Keyboard input, shared with the external windows. Set once the
+    /// state exists, since the key handler needs it.
+    pub key_input: Option<KeyInput>,
+    /// If 'background' follows the GTK theme.
+    pub auto_background: Rc<Cell<bool>>,
+    /// Keeps the theme's watch on the portal alive, see `theme::watch`.
+    #[allow(unused)]
+    pub theme_portal: Option<gio::DBusProxy>,
 }
 
 impl UIState {
@@ -100,7 +244,7 @@ impl UIState {
                 });
             }
             Notify::GnvimEvent(event) => match event {
-                Ok(event) => self.handle_gnvim_event(&event, nvim),
+                Ok(event) => self.handle_gnvim_event(window, &event, nvim),
                 Err(err) => {
                     let nvim = nvim.clone();
                     let msg = format!(
@@ -109,7 +253,7 @@ impl UIState {
                     );
                     spawn_local(async move {
                         if let Err(err) = nvim.command(&msg).await {
-                            error!("Failed to execute nvim command: {}", err)
+                            report_error("Failed to execute nvim command", err)
                         }
                     });
                 }
@@ -118,7 +262,34 @@ impl UIState {
     }
 
     fn set_title(&mut self, window: &gtk::ApplicationWindow, title: &str) {
-        window.set_title(title);
+        window.set_title(if title.is_empty() {
+            DEFAULT_TITLE
+        } else {
+            title
+        });
+
+        self.title = Some(title.to_string()).filter(|t| !t.is_empty());
+        self.update_window_titles();
+    }
+
+    /// External windows show nvim's title when they're the current window,
+    /// since that's what the title is about. Nvim doesn't send the title
+    /// again when only the current window changes, so this is called then
+    /// too.
+    fn update_window_titles(&self) {
+        for (grid, w) in self.windows.iter() {
+            w.set_nvim_title(
+                self.title.clone().filter(|_| *grid == self.current_grid),
+            );
+        }
+    }
+
+    /// Sets the icon name (see 'iconstring'), shown e.g. when the window is
+    /// minimized. Empty `icon` resets it to the window's title.
+    fn set_icon(&mut self, window: &gtk::ApplicationWindow, icon: &str) {
+        if let Some(win) = window.get_window() {
+            win.set_icon_name(Some(icon).filter(|icon| !icon.is_empty()));
+        }
     }
 
     fn grid_cursor_goto(
@@ -137,6 +308,7 @@ impl UIState {
             let grid = self.grids.get(&self.current_grid).unwrap();
             grid.set_active(false);
             self.current_grid = grid_id;
+            self.update_window_titles();
 
             // And set the new current grid to active.
             let grid = self.grids.get(&grid_id).unwrap();
@@ -162,8 +334,8 @@ impl UIState {
 
             // If the grid is in a window (which is likely), resize the window
             // to match the grid's size.
-            if let Some(ref w) =
-                self.windows.values().find(|w| w.grid_id == grid.id)
+            if let Some(w) =
+                self.windows.values_mut().find(|w| w.grid_id == grid.id)
             {
                 let grid_metrics = grid.get_grid_metrics();
                 w.resize((
@@ -181,26 +353,49 @@ impl UIState {
             let grid = Grid::new(
                 e.grid,
                 &window.get_window().unwrap(),
-                self.font.clone(),
+                self.scaled_font(),
                 self.line_space,
                 e.width as usize,
                 e.height as usize,
                 &self.hl_defs,
                 self.enable_cursor_animations,
+                self.kinetic_scroll.clone(),
             );
 
+            grid.set_cursor_animation_duration(self.cursor_animation_duration);
+            grid.set_cursor_color(self.current_cursor_color());
+            grid.set_dim(self.current_dim());
+            grid.enable_scroll_animation(self.enable_scroll_animation);
+            grid.set_scroll_animation_duration(self.scroll_animation_duration);
+            grid.set_max_fps(self.max_fps);
+            grid.set_ligatures(self.enable_ligatures, &self.hl_defs);
+            grid.set_synthetic_styles(
+                self.enable_synthetic_styles,
+                &self.hl_defs,
+            );
+            grid.set_bold_is_bright(self.bold_is_bright, &self.hl_defs);
+            grid.set_background_opacity(self.background_opacity, &self.hl_defs);
+            grid.set_font_features(&self.font_features, &self.hl_defs);
+            grid.set_font_wide(self.font_wide_families(), &self.hl_defs);
             if let Some(ref mode) = self.current_mode {
                 grid.set_mode(&mode);
             }
             grid.resize(&win, e.width, e.height, &self.hl_defs);
-            attach_grid_events(&grid, nvim.clone());
+            attach_grid_events(
+                &grid,
+                nvim.clone(),
+                self.context_menu.clone(),
+                self.hover.clone(),
+                self.scroll_speed.clone(),
+                self.mouse.clone(),
+            );
             self.grids.insert(e.grid, grid);
         }
     }
 
     fn grid_line(&mut self, line: GridLineSegment) {
         let grid = self.grids.get(&line.grid).unwrap();
-        grid.put_line(line, &self.hl_defs);
+        grid.put_line(line);
     }
 
     fn grid_clear(&mut self, grid: &i64) {
@@ -210,11 +405,12 @@ impl UIState {
 
     fn grid_destroy(&mut self, grid: &i64) {
         // Drop grid.
-        if self.grids.remove(grid).is_none() {
-            warn!(
+        match self.grids.remove(grid) {
+            Some(grid) => grid.destroy(),
+            None => warn!(
                 "Nvim instructed to close a grid that we don't have (grid: {})",
                 grid
-            );
+            ),
         }
         if self.windows.contains_key(grid) {
             self.windows.remove(grid).unwrap(); // Drop window that the grid belongs to.
@@ -223,6 +419,7 @@ impl UIState {
         // Make the current grid to point to the default grid. We relay on the fact
         // that current_grid is always pointing to a existing grid.
         self.current_grid = 1;
+        self.update_window_titles();
     }
 
     fn grid_scroll(&mut self, info: GridScroll, nvim: &GioNeovim) {
@@ -234,7 +431,7 @@ impl UIState {
         let nvim = nvim.clone();
         spawn_local(async move {
             if let Err(err) = nvim.command("if exists('#User#GnvimScroll') | doautocmd User GnvimScroll | endif").await {
-                error!("GnvimScroll error: {:?}", err);
+                report_error("Failed to trigger GnvimScroll", err);
             }
         });
     }
@@ -291,6 +488,16 @@ impl UIState {
             "MsgSeparator" => {
                 self.hl_defs.set_hl_group(HlGroup::MsgSeparator, evt.hl_id)
             }
+            "FloatBorder" => {
+                self.hl_defs.set_hl_group(HlGroup::FloatBorder, evt.hl_id)
+            }
+            "Search" => self.hl_defs.set_hl_group(HlGroup::Search, evt.hl_id),
+            "CursorLine" => {
+                self.hl_defs.set_hl_group(HlGroup::CursorLine, evt.hl_id)
+            }
+            "CursorColumn" => {
+                self.hl_defs.set_hl_group(HlGroup::CursorColumn, evt.hl_id)
+            }
             _ => None,
         };
 
@@ -300,9 +507,11 @@ impl UIState {
     fn option_set(&mut self, opt: OptionSet) {
         match opt {
             OptionSet::GuiFont(font) => {
-                let font = Font::from_guifont(&font).unwrap_or_default();
+                self.font = Font::from_guifont(&font).unwrap_or_default();
+                let font = self.scaled_font();
 
-                self.font = font.clone();
+                // The primary font is used as the fallback for the wide font.
+                self.set_font_wide(self.font_wide.clone());
 
                 let mut opts =
                     self.resize_on_flush.take().unwrap_or_else(|| {
@@ -316,6 +525,12 @@ impl UIState {
                 opts.font = font;
 
                 self.resize_on_flush = Some(opts);
+                self.fit_on_flush = true;
+            }
+            OptionSet::GuiFontWide(font) => {
+                // Only the font's family is used, so that the double width
+                // cells still line up with the rest of the grid.
+                self.set_font_wide(Font::from_guifont(&font).ok());
             }
             OptionSet::LineSpace(val) => {
                 self.line_space = val;
@@ -331,6 +546,7 @@ impl UIState {
                 opts.line_space = val;
 
                 self.resize_on_flush = Some(opts);
+                self.fit_on_flush = true;
             }
             OptionSet::ExtTabline(enable) => {
                 let widget = self.tabline.get_widget();
@@ -359,11 +575,90 @@ impl UIState {
         }
     }
 
+    /// Returns the current font, with the zoom applied.
+    fn scaled_font(&self) -> Font {
+        self.font.scaled(self.font_scale)
+    }
+
+    /// Returns the font families to use for double width cells: the wide
+    /// font, falling back to the primary font.
+    fn font_wide_families(&self) -> Option<String> {
+        self.font_wide
+            .as_ref()
+            .map(|wide| format!("{},{}", wide.family(), self.font.family()))
+    }
+
+    fn set_font_wide(&mut self, font: Option<Font>) {
+        self.font_wide = font;
+
+        let families = self.font_wide_families();
+        for grid in self.grids.values() {
+            grid.set_font_wide(families.clone(), &self.hl_defs);
+        }
+    }
+
+    /// Zooms the font in or out, and resizes everything accordingly.
+    pub fn zoom_font(
+        &mut self,
+        zoom: FontZoom,
+        nvim: &GioNeovim,
+        window: &gtk::ApplicationWindow,
+    ) {
+        let scale = next_font_scale(self.font_scale, zoom);
+        if (scale - self.font_scale).abs() < f32::EPSILON {
+            return;
+        }
+
+        self.font_scale = scale;
+        self.resize_on_flush = Some(ResizeOptions {
+            font: self.scaled_font(),
+            line_space: self.line_space,
+        });
+
+        // There is no flush coming from nvim, so do it ourselves.
+        self.flush(nvim, window);
+    }
+
+    /// Recalculates the cell metrics (and the grid sizes) when the window
+    /// moves to a monitor with a different scale factor.
+    pub fn scale_factor_changed(
+        &mut self,
+        nvim: &GioNeovim,
+        window: &gtk::ApplicationWindow,
+    ) {
+        self.resize_on_flush = Some(ResizeOptions {
+            font: self.scaled_font(),
+            line_space: self.line_space,
+        });
+
+        self.flush(nvim, window);
+    }
+
     fn mode_info_set(&mut self, ModeInfoSet { mode_info, .. }: ModeInfoSet) {
         self.mode_infos = mode_info;
     }
 
-    fn mode_change(&mut self, ModeChange { index, .. }: ModeChange) {
+    fn mode_change(
+        &mut self,
+        window: &gtk::ApplicationWindow,
+        ModeChange { name, index }: ModeChange,
+    ) {
+        self.context_menu.set_mode(&name);
+        self.mouse.set_mode(&name);
+        self.mode_name = name.clone();
+        if !self.mode_cursor_colors.is_empty() {
+            self.update_cursor_color();
+        }
+
+        // Lets the css style things by the mode.
+        let class = format!("mode-{}", name);
+        let style = window.get_style_context();
+        if let Some(prev) = self.mode_class.take() {
+            style.remove_class(&prev);
+        }
+        style.add_class(&class);
+        self.mode_class = Some(class);
+
         let mode = self.mode_infos.get(index as usize).unwrap();
         self.current_mode = Some(mode.clone());
         // Broadcast the mode change to all grids.
@@ -374,27 +669,100 @@ impl UIState {
         }
     }
 
+    /// Sets the input method's preedit text on the current grid. Empty text
+    /// removes the preedit.
+    pub fn set_preedit(
+        &self,
+        text: String,
+        attrs: pango::AttrList,
+        cursor: usize,
+    ) {
+        let preedit = if text.is_empty() {
+            None
+        } else {
+            Some(Preedit {
+                text,
+                attrs,
+                cursor,
+                fg: self.hl_defs.default_fg,
+                bg: self.hl_defs.default_bg,
+            })
+        };
+
+        for (id, grid) in self.grids.iter() {
+            if *id != self.current_grid {
+                grid.set_preedit(None);
+            }
+        }
+
+        if let Some(grid) = self.grids.get(&self.current_grid) {
+            grid.set_preedit(preedit);
+        }
+    }
+
     fn set_busy(&mut self, busy: bool) {
         for grid in self.grids.values() {
-            grid.set_busy(busy);
+            grid.set_busy(busy, self.busy_pointer);
+        }
+    }
+
+    fn bell(&self, window: &gtk::ApplicationWindow) {
+        if self.visual_bell {
+            self.flash_grid();
+        } else {
+            window.error_bell();
+        }
+    }
+
+    fn flash_grid(&self) {
+        if let Some(grid) = self.grids.get(&self.current_grid) {
+            grid.flash();
         }
     }
 
     fn flush(&mut self, nvim: &GioNeovim, window: &gtk::ApplicationWindow) {
-        for grid in self.grids.values() {
-            grid.flush(&self.hl_defs);
+        if let Some(splash) = self.splash.take() {
+            self.overlay.remove(&splash);
         }
 
         if let Some(opts) = self.resize_on_flush.take() {
             let win = window.get_window().unwrap();
+            let prev_metrics = self.grids.get(&1).unwrap().get_grid_metrics();
             for grid in self.grids.values() {
                 grid.update_cell_metrics(
                     opts.font.clone(),
                     opts.line_space,
                     &win,
+                    &self.hl_defs,
                 );
             }
 
+            // Move the windows to where they'd be with the new metrics.
+            // Nvim will tell us the new positions, if the windows' sizes
+            // end up changing.
+            let metrics = self.grids.get(&1).unwrap().get_grid_metrics();
+            for window in self.windows.values_mut() {
+                let grid = self.grids.get(&window.grid_id).unwrap();
+                let grid_metrics = grid.get_grid_metrics();
+                let (x, y) = rescale_position(
+                    (window.x, window.y),
+                    &prev_metrics,
+                    &metrics,
+                );
+                window.update_position(
+                    x,
+                    y,
+                    grid_metrics.width,
+                    grid_metrics.height,
+                );
+            }
+
+            if let Some(pos) = self.msg_pos.clone() {
+                if self.grids.contains_key(&pos.grid) {
+                    self.msg_set_pos(pos);
+                }
+            }
+
             let grid = self.grids.get(&1).unwrap();
             let (cols, rows) = grid.calc_size();
 
@@ -409,12 +777,13 @@ impl UIState {
                 if let Err(err) =
                     nvim.ui_try_resize(cols as i64, rows as i64).await
                 {
-                    error!("Error: failed to resize nvim ({:?})", err);
+                    report_error("Failed to resize nvim", err);
                 }
             });
 
             self.popupmenu.set_font(opts.font.clone(), &self.hl_defs);
             self.cmdline.set_font(opts.font.clone(), &self.hl_defs);
+            self.hover.set_font(opts.font.clone());
             self.tabline.set_font(opts.font.clone(), &self.hl_defs);
             #[cfg(feature = "libwebkit2gtk")]
             self.cursor_tooltip.set_font(opts.font.clone());
@@ -425,52 +794,137 @@ impl UIState {
             self.tabline.set_line_space(opts.line_space, &self.hl_defs);
         }
 
+        for grid in self.grids.values() {
+            grid.flush(&self.hl_defs);
+        }
+
+        if std::mem::take(&mut self.fit_on_flush) {
+            if let Some((cols, rows)) = self.initial_size {
+                self.fit_window_to_grid(window, cols, rows);
+            }
+        }
+        // Nvim sends the redraw events from the user's config after
+        // `UIEnter`, so the size is kept until the flush after it.
+        if !self.starting {
+            self.initial_size = None;
+        }
+
+        // The grids' content might've changed, so find the matches again.
+        if self.find.query().is_some() {
+            self.update_find();
+
+            if let Some(forward) = self.find_pending_step.take() {
+                if let Some(grid) = self.grids.get(&self.current_grid) {
+                    grid.reset_find_current();
+                    grid.find_step(forward);
+                }
+            }
+        }
+
         if self.hl_changed {
             self.popupmenu.set_colors(&self.hl_defs);
             self.tabline.set_colors(&self.hl_defs);
             self.cmdline.set_colors(&self.hl_defs);
             self.cmdline.wildmenu_set_colors(&self.hl_defs);
 
-            let msgsep = self
-                .hl_defs
-                .get_hl_group(&HlGroup::MsgSeparator)
-                .cloned()
-                .unwrap_or_default()
-                .foreground;
-
-            // Set the styles for our main window.
-            CssProviderExt::load_from_data(
-                &self.css_provider,
-                format!(
-                    "* {{
-                        background: #{bg};
-                    }}
-
-                    frame > border {{
-                        border: none;
-                    }}
-
-                    #message-grid-contianer frame.scrolled {{
-                        border-top: 1px solid #{msgsep}
-                    }}
-                    ",
-                    bg = self.hl_defs.default_bg.to_hex(),
-                    msgsep = msgsep.unwrap_or(self.hl_defs.default_fg).to_hex(),
-                )
-                .as_bytes(),
-            )
-            .unwrap();
+            self.set_styles();
 
             self.hl_changed = false;
         }
     }
 
+    /// Resizes the main window so that the main grid gets `cols` x `rows`
+    /// cells. Everything around the grid (e.g. the tabline) keeps its size.
+    fn fit_window_to_grid(
+        &self,
+        window: &gtk::ApplicationWindow,
+        cols: u64,
+        rows: u64,
+    ) {
+        if window.is_maximized() {
+            return;
+        }
+
+        let grid = self.grids.get(&1).unwrap();
+        let metrics = grid.get_grid_metrics();
+        let widget = grid.widget();
+
+        let (win_w, win_h) = window.get_size();
+        let extra_w = win_w - widget.get_allocated_width();
+        let extra_h = win_h - widget.get_allocated_height();
+        window.resize(
+            (cols as f64 * metrics.cell_width).ceil() as i32 + extra_w,
+            (rows as f64 * metrics.cell_height).ceil() as i32 + extra_h,
+        );
+    }
+
+    /// Highlights the find bar's matches on all of the grids.
+    pub fn update_find(&self) {
+        let query = self.find.query();
+        // Draw the matches like nvim's own search matches.
+        let color = self
+            .hl_defs
+            .get_hl_group(&HlGroup::Search)
+            .and_then(|hl| hl.background)
+            .unwrap_or(self.hl_defs.default_fg);
+
+        for grid in self.grids.values() {
+            match query {
+                Some(ref query) => grid.set_find(query, color, &self.hl_defs),
+                None => grid.clear_find(),
+            }
+        }
+    }
+
+    pub fn close_find(&mut self) {
+        self.find.close();
+        self.find_pending_step = None;
+        self.update_find();
+    }
+
+    /// Moves to the next (or previous) find match on the current grid.
+    /// Returns false if there are no more matches on the grid. Then nvim
+    /// has to scroll to the next (or previous) page, and the step is taken
+    /// on the flush after that.
+    pub fn find_step(&mut self, forward: bool) -> bool {
+        if self.find.query().is_none() {
+            return true;
+        }
+
+        let grid = match self.grids.get(&self.current_grid) {
+            Some(grid) => grid,
+            None => return true,
+        };
+        if grid.find_step(forward) {
+            return true;
+        }
+
+        self.find_pending_step = Some(forward);
+        false
+    }
+
     fn popupmenu_show(&mut self, popupmenu: PopupmenuShow) {
         if popupmenu.grid == -1 {
             self.wildmenu_shown = true;
             self.cmdline.wildmenu_show(&popupmenu.items)
         } else {
-            self.popupmenu.set_items(popupmenu.items, &self.hl_defs);
+            // The text where the completed word starts, for highlighting
+            // the items' matching parts.
+            let text = self
+                .grids
+                .get(&popupmenu.grid)
+                .map(|grid| {
+                    let (row, col) =
+                        (popupmenu.row as usize, popupmenu.col as usize);
+                    grid.get_text_range((row, col), (row, usize::MAX))
+                })
+                .unwrap_or_default();
+            self.popupmenu.set_items(
+                popupmenu.items,
+                popupmenu.selected,
+                &text,
+                &self.hl_defs,
+            );
 
             let grid = self.grids.get(&self.current_grid).unwrap();
             let mut rect = grid.get_rect_for_cell(popupmenu.row, popupmenu.col);
@@ -579,6 +1033,18 @@ impl UIState {
         let width = evt.width as f64 * base_metrics.cell_width;
         let height = evt.height as f64 * base_metrics.cell_height;
 
+        self.grids
+            .get(&evt.grid)
+            .unwrap()
+            .set_transparent(false, &self.hl_defs);
+
+        let (line, column) = self.cursor_overlay;
+        self.grids.get(&evt.grid).unwrap().set_cursor_overlay(
+            line,
+            column,
+            &self.hl_defs,
+        );
+
         let window = self.get_or_create_window(
             evt.grid,
             self.windows_container.clone().upcast(),
@@ -586,6 +1052,7 @@ impl UIState {
             evt.win,
         );
 
+        window.float = false;
         window.set_position(x, y, width, height);
         window.show();
     }
@@ -618,6 +1085,10 @@ impl UIState {
     ) -> &mut Window {
         let grid = self.grids.get(&grid).unwrap();
         let css_provider = self.css_provider.clone();
+        let scrolloff = self.scrolloff;
+        let click_to_jump = self.scrollbar_click_to_jump;
+        let autohide = self.scrollbar_autohide;
+        let scrollbar_position = self.scrollbar_position;
         self.windows
             .entry(grid.id)
             .and_modify(clone!(container => move |w| {
@@ -625,12 +1096,18 @@ impl UIState {
                 w.set_parent(container.upcast());
             }))
             .or_insert_with(|| {
-                Window::new(
+                let window = Window::new(
                     NvimWindow::new(win, nvim.clone()),
                     container,
                     &grid,
                     Some(css_provider),
-                )
+                    nvim.clone(),
+                    scrollbar_position,
+                );
+                window.set_scrolloff(scrolloff);
+                window.set_scrollbar_click_to_jump(click_to_jump);
+                window.set_scrollbar_autohide(autohide);
+                window
             })
     }
 
@@ -681,19 +1158,36 @@ impl UIState {
                 if let Err(err) =
                     nvim.ui_try_resize_grid(grid, cols, rows).await
                 {
-                    error!("Failed to resize grid({}): {}", grid, err);
+                    report_error(
+                        &format!("Failed to resize grid({})", grid),
+                        err,
+                    );
                 }
             });
         }
 
+        let float_animation = self.float_animation;
+
+        // Floats can be blended with what's under them ('winblend'). Our
+        // cursor line and column are only for the regular windows, since
+        // floats rarely have the cursor.
+        let grid = self.grids.get(&evt.grid).unwrap();
+        grid.set_transparent(true, &self.hl_defs);
+        grid.set_cursor_overlay(false, false, &self.hl_defs);
+
         let window = self.get_or_create_window(
             evt.grid,
             self.windows_float_container.clone().upcast(),
             nvim,
             evt.win,
         );
+        window.float = true;
 
-        window.set_position(x, y, width, height);
+        if float_animation {
+            window.animate_position(x, y, width, height);
+        } else {
+            window.set_position(x, y, width, height);
+        }
         window.show();
     }
 
@@ -704,8 +1198,10 @@ impl UIState {
         nvim: &GioNeovim,
     ) {
         let parent_win = window.clone().upcast::<gtk::Window>();
-        let grid_metrics = {
-            let grid = self.grids.get(&evt.grid).unwrap();
+        let keys = self.key_input.clone().unwrap();
+        let grid = {
+            let grid = self.grids.get_mut(&evt.grid).unwrap();
+            grid.set_im_context(&keys.im_context);
             let grid_metrics = grid.get_grid_metrics();
 
             // NOTE(ville): Without this, "new" grids (e.g. once added to a external
@@ -718,9 +1214,11 @@ impl UIState {
                 &self.hl_defs,
             );
 
-            grid_metrics
+            grid.clone()
         };
 
+        let geometries = self.external_geometries.clone();
+        let opts = self.external_options;
         let window = self.get_or_create_window(
             evt.grid,
             self.windows_float_container.clone().upcast(),
@@ -728,13 +1226,8 @@ impl UIState {
             evt.win,
         );
 
-        window.set_external(
-            &parent_win,
-            (
-                grid_metrics.width.ceil() as i32,
-                grid_metrics.height.ceil() as i32,
-            ),
-        );
+        window.set_external(&parent_win, &grid, &geometries, &keys, opts);
+        self.update_window_titles();
     }
 
     fn window_hide(&mut self, grid_id: i64) {
@@ -748,12 +1241,229 @@ impl UIState {
         }
     }
 
+    fn window_viewport(&mut self, evt: WindowViewport) {
+        if let Some(window) = self.windows.get(&evt.grid) {
+            window.set_adjustment(&evt);
+        } else {
+            debug!("Viewport update for grid without a window: {}", evt.grid);
+        }
+    }
+
     fn msg_set_pos(&mut self, e: MsgSetPos) {
         let base_grid = self.grids.get(&1).unwrap();
         let base_metrics = base_grid.get_grid_metrics();
         let grid = self.grids.get(&e.grid).unwrap();
         let h = base_metrics.height - e.row as f64 * base_metrics.cell_height;
         self.msg_window.set_pos(&grid, e.row as f64, h, e.scrolled);
+        self.msg_pos = Some(e);
+    }
+
+    pub fn set_scrolloff(&mut self, scrolloff: u64) {
+        self.scrolloff = scrolloff;
+        self.windows
+            .values()
+            .for_each(|w| w.set_scrolloff(scrolloff));
+    }
+
+    fn set_scrollbar_click_to_jump(&mut self, enable: bool) {
+        self.scrollbar_click_to_jump = enable;
+        self.windows
+            .values()
+            .for_each(|w| w.set_scrollbar_click_to_jump(enable));
+    }
+
+    fn set_scrollbar_width(&mut self, width: u64) {
+        self.scrollbar_width = width;
+        self.set_styles();
+    }
+
+    fn set_scrollbar_opacity(&mut self, opacity: f64) {
+        self.scrollbar_opacity = opacity.max(0.0).min(1.0);
+        self.set_styles();
+    }
+
+    /// Returns how much the grids should be dimmed right now.
+    fn current_dim(&self) -> f64 {
+        if self.focused {
+            0.0
+        } else {
+            self.inactive_dim
+        }
+    }
+
+    /// Checks if gnvim has the focus, i.e. if the main window (`window`),
+    /// one of the external windows or the context menu has it. Without it,
+    /// the grids are dimmed.
+    pub fn update_focused(&mut self, window: &gtk::ApplicationWindow) {
+        let focused = window.is_active()
+            || self.context_menu.is_shown()
+            || self.windows.values().any(|w| w.is_external_active());
+        self.set_focused(focused);
+    }
+
+    fn set_focused(&mut self, focused: bool) {
+        self.focused = focused;
+        let dim = self.current_dim();
+        self.grids.values().for_each(|g| g.set_dim(dim));
+    }
+
+    fn set_background_opacity(&mut self, opacity: f64) {
+        self.background_opacity = opacity.max(0.0).min(1.0);
+        let hl_defs = &self.hl_defs;
+        self.grids.values().for_each(|g| {
+            g.set_background_opacity(self.background_opacity, hl_defs)
+        });
+        self.set_styles();
+    }
+
+    /// Blurs the content behind the window, if enabled and the background
+    /// is translucent.
+    fn update_blur(&self, window: &gtk::ApplicationWindow) {
+        if let Some(win) = window.get_window() {
+            blur::set_blur(
+                &win,
+                self.background_blur && self.background_opacity < 1.0,
+            );
+        }
+    }
+
+    fn set_max_fps(&mut self, fps: u64) {
+        self.max_fps = fps;
+        self.grids.values().for_each(|g| g.set_max_fps(fps));
+    }
+
+    /// Insets the grids from the window's edges. The margins show the
+    /// window's background, which is the default background color. The
+    /// main grid loses the margins from its allocation, so its resize
+    /// handler tells nvim the new size.
+    fn set_padding(&self, top: u64, right: u64, bottom: u64, left: u64) {
+        self.overlay.set_margin_top(top as i32);
+        self.overlay.set_margin_end(right as i32);
+        self.overlay.set_margin_bottom(bottom as i32);
+        self.overlay.set_margin_start(left as i32);
+    }
+
+    /// Sets the styles for our main window.
+    fn set_styles(&self) {
+        let msgsep = self
+            .hl_defs
+            .get_hl_group(&HlGroup::MsgSeparator)
+            .cloned()
+            .unwrap_or_default()
+            .foreground;
+
+        let float_border = if self.float_border {
+            let hl = self
+                .hl_defs
+                .get_hl_group(&HlGroup::FloatBorder)
+                .cloned()
+                .unwrap_or_default();
+
+            // Use a shadow instead of a border, so the border is drawn
+            // around the window instead of taking space from the grid.
+            format!(
+                "#windows-contianer-float > frame {{
+                    box-shadow: 0px 0px 0px 1px {color};
+                }}",
+                color = hl.apply_blend(
+                    &hl.foreground.unwrap_or(self.hl_defs.default_fg)
+                ),
+            )
+        } else {
+            String::new()
+        };
+
+        // The window's background shows through the grids, so the frames
+        // around them can't have one.
+        let translucent = if self.background_opacity < 1.0 {
+            "frame {
+                background: transparent;
+            }"
+        } else {
+            ""
+        };
+
+        let scrollbar_width = if self.scrollbar_width > 0 {
+            format!(
+                "scrollbar.gnvim-scrollbar.vertical slider {{
+                    min-width: {width}px;
+                }}
+
+                scrollbar.gnvim-scrollbar.horizontal slider {{
+                    min-height: {width}px;
+                }}",
+                width = self.scrollbar_width,
+            )
+        } else {
+            String::new()
+        };
+
+        let res = CssProviderExt::load_from_data(
+            &self.css_provider,
+            format!(
+                "* {{
+                    background: {bg};
+                }}
+
+                {translucent}
+
+                frame > border {{
+                    border: none;
+                }}
+
+                #windows-contianer-float * {{
+                    background: transparent;
+                }}
+
+                #message-grid-contianer frame.scrolled {{
+                    border-top: 1px solid #{msgsep}
+                }}
+
+                scrollbar.gnvim-scrollbar {{
+                    opacity: {scrollbar_opacity};
+                }}
+
+                {scrollbar_width}
+
+                {float_border}
+
+                {mode_colors}
+
+                {user_css}
+                ",
+                bg = self
+                    .hl_defs
+                    .default_bg
+                    .to_rgba(1.0 - self.background_opacity),
+                translucent = translucent,
+                msgsep = msgsep.unwrap_or(self.hl_defs.default_fg).to_hex(),
+                scrollbar_opacity = self.scrollbar_opacity,
+                scrollbar_width = scrollbar_width,
+                float_border = float_border,
+                mode_colors = mode_colors_css(&self.mode_colors),
+                user_css = self.user_css,
+            )
+            .as_bytes(),
+        );
+
+        // The user css might be invalid.
+        if let Err(err) = res {
+            error!("Failed to load styles: {}", err);
+        }
+    }
+
+    fn set_scrollbar_position(&mut self, position: ScrollbarPosition) {
+        self.scrollbar_position = position;
+        self.windows
+            .values()
+            .for_each(|w| w.set_scrollbar_position(position));
+    }
+
+    fn set_scrollbar_autohide(&mut self, autohide: bool) {
+        self.scrollbar_autohide = autohide;
+        self.windows
+            .values()
+            .for_each(|w| w.set_scrollbar_autohide(autohide));
     }
 
     fn enable_cursor_animations(&mut self, enable: bool) {
@@ -763,6 +1473,86 @@ impl UIState {
             .for_each(|g| g.enable_cursor_animations(enable));
     }
 
+    fn set_cursor_animation_duration(&mut self, duration: u64) {
+        self.cursor_animation_duration = duration;
+        self.grids
+            .values()
+            .for_each(|g| g.set_cursor_animation_duration(duration));
+    }
+
+    /// Returns the cursor's color in the current mode, if it has a fixed
+    /// one.
+    fn current_cursor_color(&self) -> Option<Color> {
+        mode_cursor_color(&self.mode_cursor_colors, &self.mode_name)
+            .or(self.cursor_color)
+    }
+
+    /// Sets the cursors' colors. They're drawn on nvim's next flush, which
+    /// for mode changes is at the end of the same redraw batch.
+    fn update_cursor_color(&self) {
+        let color = self.current_cursor_color();
+        for grid in self.grids.values() {
+            grid.set_cursor_color(color);
+        }
+    }
+
+    fn enable_scroll_animation(&mut self, enable: bool) {
+        self.enable_scroll_animation = enable;
+        self.grids
+            .values()
+            .for_each(|g| g.enable_scroll_animation(enable));
+    }
+
+    fn set_scroll_animation_duration(&mut self, duration: u64) {
+        self.scroll_animation_duration = duration;
+        self.grids
+            .values()
+            .for_each(|g| g.set_scroll_animation_duration(duration));
+    }
+
+    fn enable_ligatures(&mut self, enable: bool) {
+        self.enable_ligatures = enable;
+        for grid in self.grids.values() {
+            grid.set_ligatures(enable, &self.hl_defs);
+            grid.flush(&self.hl_defs);
+        }
+    }
+
+    fn enable_synthetic_styles(&mut self, enable: bool) {
+        self.enable_synthetic_styles = enable;
+        for grid in self.grids.values() {
+            grid.set_synthetic_styles(enable, &self.hl_defs);
+            grid.flush(&self.hl_defs);
+        }
+    }
+
+    /// Sets if we draw the cursor line and the cursor column, on the grids
+    /// of nvim's regular windows (e.g. not on the message grid or floats).
+    fn set_cursor_overlay(&mut self, line: bool, column: bool) {
+        self.cursor_overlay = (line, column);
+        for window in self.windows.values().filter(|w| !w.float) {
+            if let Some(grid) = self.grids.get(&window.grid_id) {
+                grid.set_cursor_overlay(line, column, &self.hl_defs);
+            }
+        }
+    }
+
+    fn set_bold_is_bright(&mut self, enable: bool) {
+        self.bold_is_bright = enable;
+        for grid in self.grids.values() {
+            grid.set_bold_is_bright(enable, &self.hl_defs);
+            grid.flush(&self.hl_defs);
+        }
+    }
+
+    fn set_font_features(&mut self, features: String) {
+        for grid in self.grids.values() {
+            grid.set_font_features(&features, &self.hl_defs);
+            grid.flush(&self.hl_defs);
+        }
+        self.font_features = features;
+    }
+
     fn handle_redraw_event(
         &mut self,
         window: &gtk::ApplicationWindow,
@@ -773,6 +1563,9 @@ impl UIState {
             RedrawEvent::SetTitle(evt) => {
                 evt.iter().for_each(|e| self.set_title(&window, e));
             }
+            RedrawEvent::SetIcon(evt) => {
+                evt.iter().for_each(|e| self.set_icon(&window, e));
+            }
             RedrawEvent::GridLine(evt) => {
                 evt.into_iter().for_each(|line| self.grid_line(line))
             }
@@ -807,9 +1600,11 @@ impl UIState {
                 evt.into_iter().for_each(|e| self.mode_info_set(e));
             }
             RedrawEvent::ModeChange(evt) => {
-                evt.into_iter().for_each(|e| self.mode_change(e));
+                evt.into_iter().for_each(|e| self.mode_change(window, e));
             }
             RedrawEvent::SetBusy(busy) => self.set_busy(busy),
+            RedrawEvent::Bell() => self.bell(window),
+            RedrawEvent::VisualBell() => self.flash_grid(),
             RedrawEvent::Flush() => self.flush(nvim, window),
             RedrawEvent::PopupmenuShow(evt) => {
                 evt.into_iter().for_each(|e| self.popupmenu_show(e));
@@ -854,6 +1649,9 @@ impl UIState {
             RedrawEvent::WindowClose(evt) => {
                 evt.into_iter().for_each(|e| self.window_close(e));
             }
+            RedrawEvent::WindowViewport(evt) => {
+                evt.into_iter().for_each(|e| self.window_viewport(e));
+            }
             RedrawEvent::MsgSetPos(evt) => {
                 evt.into_iter().for_each(|e| self.msg_set_pos(e));
             }
@@ -869,12 +1667,17 @@ impl UIState {
             if let Err(err) =
                 nvim.ui_set_option(&opt, rmpv::Value::Boolean(enable)).await
             {
-                error!("Failed to set '{}' option: {}", opt, err);
+                report_error(&format!("Failed to set '{}' option", opt), err);
             }
         });
     }
 
-    fn handle_gnvim_event(&mut self, event: &GnvimEvent, nvim: &GioNeovim) {
+    fn handle_gnvim_event(
+        &mut self,
+        window: &gtk::ApplicationWindow,
+        event: &GnvimEvent,
+        nvim: &GioNeovim,
+    ) {
         match event {
             GnvimEvent::CompletionMenuToggleInfo => {
                 self.popupmenu.toggle_show_info()
@@ -885,12 +1688,198 @@ impl UIState {
             GnvimEvent::PopupmenuWidthDetails(width) => {
                 self.popupmenu.set_width_details(*width as i32);
             }
+            GnvimEvent::PopupmenuMaxHeight(rows) => {
+                self.popupmenu.set_max_height(*rows);
+            }
             GnvimEvent::PopupmenuShowMenuOnAllItems(should_show) => {
                 self.popupmenu.set_show_menu_on_all_items(*should_show);
             }
+            GnvimEvent::PopupmenuHighlightMatches(enable) => {
+                self.popupmenu.set_highlight_matches(*enable);
+            }
+            GnvimEvent::WildmenuHorizontal(horizontal) => {
+                self.cmdline.wildmenu_set_horizontal(*horizontal);
+            }
             GnvimEvent::EnableCursorAnimations(enable) => {
                 self.enable_cursor_animations(*enable);
             }
+            GnvimEvent::CursorAnimationDuration(duration) => {
+                self.set_cursor_animation_duration(*duration);
+            }
+            GnvimEvent::CursorColor(color) => {
+                self.cursor_color = *color;
+                self.update_cursor_color();
+            }
+            GnvimEvent::ModeCursorColor(mode, color) => {
+                match color {
+                    Some(color) => {
+                        self.mode_cursor_colors.insert(mode.clone(), *color)
+                    }
+                    None => self.mode_cursor_colors.remove(mode),
+                };
+                self.update_cursor_color();
+            }
+            GnvimEvent::EnableScrollAnimation(enable) => {
+                self.enable_scroll_animation(*enable);
+            }
+            GnvimEvent::ScrollAnimationDuration(duration) => {
+                self.set_scroll_animation_duration(*duration);
+            }
+            GnvimEvent::EnableLigatures(enable) => {
+                self.enable_ligatures(*enable);
+            }
+            GnvimEvent::EnableSyntheticStyles(enable) => {
+                self.enable_synthetic_styles(*enable);
+            }
+            GnvimEvent::ClipboardSet(register, lines, regtype) => {
+                if let Err(err) = clipboard::set(register, lines, regtype) {
+                    error!("Failed to set the clipboard: {}", err);
+                }
+            }
+            GnvimEvent::MouseScrollLines(lines) => {
+                self.scroll_speed.set_lines(*lines);
+            }
+            GnvimEvent::MouseScrollShiftMultiplier(multiplier) => {
+                self.scroll_speed.set_shift_multiplier(*multiplier);
+            }
+            GnvimEvent::EnableHover(enable) => {
+                self.hover.set_enabled(*enable);
+            }
+            GnvimEvent::EnableContextMenu(enable) => {
+                self.context_menu.set_enabled(*enable);
+            }
+            GnvimEvent::KeyPassthrough(keys) => {
+                self.key_passthrough = keys.clone();
+            }
+            GnvimEvent::AltIsMeta(enable) => {
+                self.alt_is_meta = *enable;
+            }
+            GnvimEvent::EnableFullscreenKey(enable) => {
+                self.fullscreen_key = *enable;
+            }
+            GnvimEvent::ToggleFullscreen => toggle_fullscreen(window),
+            GnvimEvent::EnableFindKey(enable) => {
+                self.find_key = *enable;
+            }
+            GnvimEvent::OpenFind => self.find.open(),
+            GnvimEvent::EnableVisualBell(enable) => {
+                self.visual_bell = *enable;
+            }
+            GnvimEvent::YankFlash(ranges) => {
+                if self.yank_flash_duration == 0 {
+                    return;
+                }
+
+                if let Some(grid) = self.grids.get(&self.current_grid) {
+                    let color = self
+                        .yank_flash_color
+                        .unwrap_or(self.hl_defs.default_fg);
+                    grid.flash_yank(
+                        ranges.clone(),
+                        color,
+                        self.yank_flash_duration,
+                    );
+                }
+            }
+            GnvimEvent::YankFlashColor(color) => {
+                self.yank_flash_color = *color;
+            }
+            GnvimEvent::YankFlashDuration(duration) => {
+                self.yank_flash_duration = *duration;
+            }
+            GnvimEvent::EnableBusyPointer(enable) => {
+                self.busy_pointer = *enable;
+            }
+            GnvimEvent::EnableFloatBorder(enable) => {
+                self.float_border = *enable;
+                self.set_styles();
+            }
+            GnvimEvent::EnableFloatAnimation(enable) => {
+                self.float_animation = *enable;
+            }
+            GnvimEvent::EnableExternalFocus(enable) => {
+                self.external_options.accept_focus = *enable;
+            }
+            GnvimEvent::EnableExternalResize(enable) => {
+                self.external_options.resizable = *enable;
+            }
+            GnvimEvent::AutoBackground(enable) => {
+                self.auto_background.set(*enable);
+            }
+            GnvimEvent::BoldIsBright(enable) => {
+                self.set_bold_is_bright(*enable);
+            }
+            GnvimEvent::FontFeatures(features) => {
+                self.set_font_features(features.clone());
+            }
+            GnvimEvent::ModeColor(mode, color) => {
+                match color {
+                    Some(color) => {
+                        self.mode_colors.insert(mode.clone(), *color)
+                    }
+                    None => self.mode_colors.remove(mode),
+                };
+
+                self.mode_indicator
+                    .set_visible(!self.mode_colors.is_empty());
+                self.set_styles();
+            }
+            GnvimEvent::UserCss(css) => {
+                // Reloaded in place, so all the widgets using our css
+                // provider (e.g. the scrollbars) are restyled as is.
+                self.user_css = css.clone();
+                self.set_styles();
+            }
+            GnvimEvent::EnableKineticScrolling(enable) => {
+                self.kinetic_scroll.set_enabled(*enable);
+            }
+            GnvimEvent::ScrollOff(scrolloff) => {
+                self.set_scrolloff(*scrolloff);
+            }
+            GnvimEvent::Mouse(mouse) => self.mouse.set_option(mouse),
+            GnvimEvent::ScrollbarClickToJump(enable) => {
+                self.set_scrollbar_click_to_jump(*enable);
+            }
+            GnvimEvent::ScrollbarAutohide(autohide) => {
+                self.set_scrollbar_autohide(*autohide);
+            }
+            GnvimEvent::ScrollbarWidth(width) => {
+                self.set_scrollbar_width(*width);
+            }
+            GnvimEvent::ScrollbarOpacity(opacity) => {
+                self.set_scrollbar_opacity(*opacity);
+            }
+            GnvimEvent::ScrollbarPosition(position) => {
+                self.set_scrollbar_position(*position);
+            }
+            GnvimEvent::InactiveDim(dim) => {
+                self.inactive_dim = dim.max(0.0).min(1.0);
+                self.set_focused(self.focused);
+            }
+            GnvimEvent::BackgroundOpacity(opacity) => {
+                self.set_background_opacity(*opacity);
+                self.update_blur(window);
+            }
+            GnvimEvent::BackgroundBlur(enable) => {
+                self.background_blur = *enable;
+                self.update_blur(window);
+            }
+            GnvimEvent::MaxFps(fps) => self.set_max_fps(*fps),
+            GnvimEvent::Padding(top, right, bottom, left) => {
+                self.set_padding(*top, *right, *bottom, *left);
+            }
+            GnvimEvent::CursorOverlay(line, column) => {
+                self.set_cursor_overlay(*line, *column);
+            }
+            GnvimEvent::Size(cols, rows) => {
+                if !self.starting {
+                    self.fit_window_to_grid(window, *cols, *rows);
+                } else if !self.size_from_cli {
+                    self.initial_size = Some((*cols, *rows));
+                    self.fit_on_flush = true;
+                }
+            }
+            GnvimEvent::UIEnter => self.starting = false,
             GnvimEvent::EnableExtTabline(enable) => {
                 self.set_ui_option("ext_tabline".into(), *enable, nvim.clone());
             }
@@ -904,6 +1893,9 @@ impl UIState {
                     nvim.clone(),
                 );
             }
+            GnvimEvent::BufEnter => {
+                self.windows.values().for_each(|w| w.update_title());
+            }
             GnvimEvent::Unknown(msg) => {
                 debug!("Received unknown GnvimEvent: {}", msg);
             }
@@ -912,13 +1904,14 @@ impl UIState {
             GnvimEvent::CursorTooltipLoadStyle(..)
             | GnvimEvent::CursorTooltipShow(..)
             | GnvimEvent::CursorTooltipHide
+            | GnvimEvent::CursorTooltipScroll(..)
             | GnvimEvent::CursorTooltipSetStyle(..) => {
                 let nvim = nvim.clone();
                 let msg =
                     "echom \"Cursor tooltip not supported in this build\"";
                 spawn_local(async move {
                     if let Err(err) = nvim.command(&msg).await {
-                        error!("Failed to execute nvim command: {}", err)
+                        report_error("Failed to execute nvim command", err)
                     }
                 });
             }
@@ -927,6 +1920,7 @@ impl UIState {
             GnvimEvent::CursorTooltipLoadStyle(..)
             | GnvimEvent::CursorTooltipShow(..)
             | GnvimEvent::CursorTooltipHide
+            | GnvimEvent::CursorTooltipScroll(..)
             | GnvimEvent::CursorTooltipSetStyle(..) => match event {
                 GnvimEvent::CursorTooltipLoadStyle(path) => {
                     if let Err(err) =
@@ -939,9 +1933,9 @@ impl UIState {
                         let nvim = nvim.clone();
                         spawn_local(async move {
                             if let Err(err) = nvim.command(&msg).await {
-                                error!(
-                                    "Failed to execute nvim command: {}",
-                                    err
+                                report_error(
+                                    "Failed to execute nvim command",
+                                    err,
                                 )
                             }
                         });
@@ -956,6 +1950,9 @@ impl UIState {
                     self.cursor_tooltip.move_to(&rect);
                 }
                 GnvimEvent::CursorTooltipHide => self.cursor_tooltip.hide(),
+                GnvimEvent::CursorTooltipScroll(lines) => {
+                    self.cursor_tooltip.scroll(*lines)
+                }
                 GnvimEvent::CursorTooltipSetStyle(style) => {
                     self.cursor_tooltip.set_style(style)
                 }
@@ -965,14 +1962,46 @@ impl UIState {
     }
 }
 
-pub fn attach_grid_events(grid: &Grid, nvim: GioNeovim) {
+/// Lines to scroll per wheel step when the mouse isn't enabled in nvim (and
+/// no scroll speed is set). Same as nvim's default 'mousescroll'.
+const UNREPORTED_SCROLL_LINES: u64 = 3;
+
+pub fn attach_grid_events(
+    grid: &Grid,
+    nvim: GioNeovim,
+    context_menu: ContextMenu,
+    hover: Hover,
+    scroll_speed: ScrollSpeed,
+    mouse: MouseReporting,
+) {
     let id = grid.id;
     // Mouse button press event.
+    let widget_weak = grid.widget().downgrade();
     grid.connect_mouse_button_press_events(
-        clone!(nvim => move |button, row, col| {
+        clone!(nvim, context_menu, mouse => move |button, row, col, clicks, shift| {
+            match button {
+                MouseButton::Middle => {
+                    clipboard::paste(nvim.clone(), "*");
+                    return Inhibit(false);
+                }
+                MouseButton::Right if context_menu.is_enabled() => {
+                    let widget = upgrade_weak!(widget_weak, Inhibit(true));
+                    context_menu.popup(&widget);
+                    return Inhibit(true);
+                }
+                _ => {}
+            }
+
+            if !mouse.is_enabled() {
+                return Inhibit(false);
+            }
+
             let nvim = nvim.clone();
+            let modifier = mouse_modifier(clicks, shift);
             spawn_local(async move {
-                nvim.input_mouse(&button.to_string(), "press", "", id, row as i64, col as i64).await.expect("Couldn't send mouse input");
+                if let Err(err) = nvim.input_mouse(&button.to_string(), "press", &modifier, id, row as i64, col as i64).await {
+                    report_error("Failed to send mouse input", err);
+                }
             });
 
             Inhibit(false)
@@ -981,10 +2010,17 @@ pub fn attach_grid_events(grid: &Grid, nvim: GioNeovim) {
 
     // Mouse button release events.
     grid.connect_mouse_button_release_events(
-        clone!(nvim => move |button, row, col| {
+        clone!(nvim, context_menu, mouse => move |button, row, col| {
+            // Middle and right clicks might be handled by us, see above.
+            if handled_locally(&button, &context_menu) || !mouse.is_enabled() {
+                return Inhibit(false);
+            }
+
             let nvim = nvim.clone();
             spawn_local(async move {
-                nvim.input_mouse(&button.to_string(), "release", "", id, row as i64, col as i64).await.expect("Couldn't send mouse input");
+                if let Err(err) = nvim.input_mouse(&button.to_string(), "release", "", id, row as i64, col as i64).await {
+                    report_error("Failed to send mouse input", err);
+                }
             });
 
             Inhibit(false)
@@ -993,25 +2029,128 @@ pub fn attach_grid_events(grid: &Grid, nvim: GioNeovim) {
 
     // Mouse drag events.
     grid.connect_motion_events_for_drag(
-        clone!(nvim => move |button, row, col| {
+        clone!(nvim, context_menu, mouse => move |button, row, col| {
+            if handled_locally(&button, &context_menu) || !mouse.is_enabled() {
+                return Inhibit(false);
+            }
+
             let nvim = nvim.clone();
             spawn_local(async move {
-                nvim.input_mouse(&button.to_string(), "drag", "", id, row as i64, col as i64).await.expect("Couldn't send mouse input");
+                if let Err(err) = nvim.input_mouse(&button.to_string(), "drag", "", id, row as i64, col as i64).await {
+                    report_error("Failed to send mouse input", err);
+                }
             });
 
             Inhibit(false)
         }),
     );
 
+    // Mouse selections, mirrored to the primary selection.
+    let nvim_win = grid.nvim_win();
+    grid.connect_selection(clone!(nvim => move |selection| {
+        let nvim = nvim.clone();
+        let win = nvim_win.borrow().clone();
+        spawn_local(async move {
+            // Leave out the number, sign and fold columns.
+            let info = match win {
+                Some(win) => nvim.call_function("getwininfo", vec![win]).await,
+                None => Ok(nvim_rs::Value::Nil),
+            };
+            let textoff = match info {
+                Ok(info) => wininfo_textoff(&info),
+                Err(err) => {
+                    report_error("Failed to get window info", err);
+                    0
+                }
+            };
+
+            let text = selection.text(textoff as usize);
+            if let Err(err) = clipboard::set_text("*", &text) {
+                error!("Failed to set the primary selection: {}", err);
+            }
+        });
+    }));
+
     // Scrolling events.
-    grid.connect_scroll_events(clone!(nvim => move |dir, row, col| {
+    let nvim_win = grid.nvim_win();
+    grid.connect_scroll_events(clone!(nvim => move |dir, shift, row, col| {
+        let win = nvim_win.borrow().clone();
+        // Without the mouse, nvim would ignore the wheel, so scroll the
+        // window ourselves, like the scrollbar does.
+        let lines = scroll_speed.lines(shift).or_else(|| {
+            if mouse.is_enabled() {
+                None
+            } else {
+                Some(UNREPORTED_SCROLL_LINES)
+            }
+        });
+        if let (Some(lines), Some(win)) = (lines, win) {
+            let delta = match dir {
+                ScrollDirection::Up => -(lines as i64),
+                ScrollDirection::Down => lines as i64,
+            };
+            scroll_window(nvim.clone(), win, delta);
+            return Inhibit(false);
+        }
+
         let nvim = nvim.clone();
         spawn_local(async move {
-            nvim.input_mouse("wheel", &dir.to_string(), "", id, row as i64, col as i64).await.expect("Couldn't send mouse input");
+            if let Err(err) = nvim.input_mouse("wheel", &dir.to_string(), "", id, row as i64, col as i64).await {
+                report_error("Failed to send mouse input", err);
+            }
         });
 
         Inhibit(false)
     }));
+
+    // Hover tooltip.
+    let nvim_win = grid.nvim_win();
+    grid.connect_hover(move |pos| match pos {
+        Some((row, col, pos)) => {
+            hover.start(nvim_win.borrow().clone(), row, col, pos)
+        }
+        None => hover.cancel(),
+    });
+}
+
+/// Returns the width of the number, sign and fold columns from the window
+/// info returned by `getwininfo()`.
+fn wininfo_textoff(info: &nvim_rs::Value) -> u64 {
+    info.as_array()
+        .and_then(|info| info.get(0))
+        .and_then(nvim_rs::Value::as_map)
+        .and_then(|info| {
+            info.iter()
+                .find(|(k, _)| k.as_str() == Some("textoff"))
+                .and_then(|(_, v)| v.as_u64())
+        })
+        .unwrap_or(0)
+}
+
+/// Returns the modifier for `nvim_input_mouse()`, for the `clicks`th
+/// consecutive click (e.g. `2-` for a double click, which makes nvim select
+/// the word under the pointer). With `shift`, nvim extends the selection to
+/// the clicked cell.
+fn mouse_modifier(clicks: u8, shift: bool) -> String {
+    let mut modifier = String::new();
+    if shift {
+        modifier.push_str("S-");
+    }
+    if clicks > 1 {
+        modifier.push_str(&format!("{}-", clicks));
+    }
+
+    modifier
+}
+
+/// Returns true if presses of `button` are handled by gnvim, instead of
+/// being sent to nvim.
+fn handled_locally(button: &MouseButton, context_menu: &ContextMenu) -> bool {
+    match button {
+        MouseButton::Middle => true,
+        MouseButton::Right => context_menu.is_enabled(),
+        MouseButton::Left => false,
+    }
 }
 
 fn widget_show(widget: &gtk::Widget, show: bool) {
@@ -1045,6 +2184,66 @@ fn win_float_anchor_pos(
     (x, y)
 }
 
+/// Returns the font scale after applying `zoom` to `scale`.
+fn next_font_scale(scale: f32, zoom: FontZoom) -> f32 {
+    let scale = match zoom {
+        FontZoom::In => scale + FONT_SCALE_STEP,
+        FontZoom::Out => scale - FONT_SCALE_STEP,
+        FontZoom::Reset => 1.0,
+    };
+
+    scale.max(FONT_SCALE_MIN).min(FONT_SCALE_MAX)
+}
+
+/// Returns the pixel position `(x, y)`, that was calculated with `prev`
+/// cell metrics, scaled to `metrics`.
+fn rescale_position(
+    (x, y): (f64, f64),
+    prev: &GridMetrics,
+    metrics: &GridMetrics,
+) -> (f64, f64) {
+    (
+        x / prev.cell_width * metrics.cell_width,
+        y / prev.cell_height * metrics.cell_height,
+    )
+}
+
+/// Returns the cursor color for `mode` from `colors`. Modes without their
+/// own color use the color of their "base" mode, e.g. `cmdline_insert` uses
+/// `cmdline`'s.
+fn mode_cursor_color(
+    colors: &HashMap<String, Color>,
+    mode: &str,
+) -> Option<Color> {
+    colors
+        .get(mode)
+        .or_else(|| {
+            let base = mode.split('_').next()?;
+            colors.get(base)
+        })
+        .copied()
+}
+
+/// Returns the css that colors the mode indicator with `colors` (by the
+/// modes' names).
+fn mode_colors_css(colors: &HashMap<String, Color>) -> String {
+    let mut modes = colors.iter().collect::<Vec<_>>();
+    // Keep the output stable.
+    modes.sort_by(|a, b| a.0.cmp(b.0));
+
+    modes
+        .into_iter()
+        .map(|(mode, color)| {
+            format!(
+                ".mode-{} #mode-indicator {{ background: #{}; }}",
+                mode,
+                color.to_hex()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1184,4 +2383,85 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_rescale_position() {
+        let metrics = |cell_width, cell_height| GridMetrics {
+            cell_width,
+            cell_height,
+            rows: 0.0,
+            cols: 0.0,
+            width: 0.0,
+            height: 0.0,
+        };
+
+        assert_eq!(
+            (30.0, 80.0),
+            rescale_position(
+                (20.0, 40.0),
+                &metrics(10.0, 20.0),
+                &metrics(15.0, 40.0),
+            ),
+        );
+    }
+
+    #[test]
+    fn test_next_font_scale() {
+        assert!((1.1 - next_font_scale(1.0, FontZoom::In)).abs() < 1e-6);
+        assert!((0.9 - next_font_scale(1.0, FontZoom::Out)).abs() < 1e-6);
+        assert_eq!(1.0, next_font_scale(2.5, FontZoom::Reset));
+
+        // Clamped to the min and max.
+        assert_eq!(FONT_SCALE_MAX, next_font_scale(3.0, FontZoom::In));
+        assert_eq!(FONT_SCALE_MIN, next_font_scale(0.5, FontZoom::Out));
+    }
+
+    #[test]
+    fn test_mouse_modifier() {
+        assert_eq!("", mouse_modifier(1, false));
+        assert_eq!("2-", mouse_modifier(2, false));
+        assert_eq!("3-", mouse_modifier(3, false));
+        assert_eq!("S-", mouse_modifier(1, true));
+        assert_eq!("S-2-", mouse_modifier(2, true));
+    }
+
+    #[test]
+    fn test_wininfo_textoff() {
+        let info = Value::from(vec![Value::Map(vec![
+            (Value::from("width"), Value::from(80)),
+            (Value::from("textoff"), Value::from(6)),
+        ])]);
+        assert_eq!(6, wininfo_textoff(&info));
+
+        assert_eq!(0, wininfo_textoff(&Value::from(Vec::<Value>::new())));
+        assert_eq!(0, wininfo_textoff(&Value::from(0)));
+    }
+
+    #[test]
+    fn test_mode_cursor_color() {
+        let red = Color::from_u64(0xff0000);
+        let green = Color::from_u64(0x00ff00);
+
+        let mut colors = HashMap::new();
+        colors.insert(String::from("insert"), red);
+        colors.insert(String::from("cmdline"), green);
+
+        assert_eq!(Some(red), mode_cursor_color(&colors, "insert"));
+        assert_eq!(Some(green), mode_cursor_color(&colors, "cmdline_insert"));
+        assert_eq!(None, mode_cursor_color(&colors, "normal"));
+    }
+
+    #[test]
+    fn test_mode_colors_css() {
+        let mut colors = HashMap::new();
+        assert_eq!("", mode_colors_css(&colors));
+
+        colors.insert(String::from("normal"), Color::from_u64(0x0000ff));
+        colors.insert(String::from("insert"), Color::from_u64(0x00ff00));
+        assert_eq!(
+            ".mode-insert #mode-indicator { background: #00ff00; }\n\
+             .mode-normal #mode-indicator { background: #0000ff; }",
+            mode_colors_css(&colors)
+        );
+    }
 }
@@ -3,11 +3,13 @@ use std::rc::Rc;
 
 use gtk::prelude::*;
 
+use log::error;
+use nvim_rs::error::CallError;
 use nvim_rs::Tabpage;
 
 use crate::nvim_gio::{GioNeovim, GioWriter};
 use crate::ui::color::{Color, HlDefs, HlGroup};
-use crate::ui::common::{calc_line_space, spawn_local};
+use crate::ui::common::{calc_line_space, report_error, spawn_local};
 use crate::ui::font::{Font, FontUnit};
 
 #[derive(Default)]
@@ -21,11 +23,14 @@ pub struct TablineColors {
 }
 
 pub struct Tabline {
+    nvim: GioNeovim,
     notebook: gtk::Notebook,
     css_provider: gtk::CssProvider,
     switch_tab_signal: glib::SignalHandlerId,
 
     tabpage_data: Rc<RefCell<Vec<Tabpage<GioWriter>>>>,
+    /// Our pages' (child) widgets, in the order nvim has the tabs.
+    pages: Rc<RefCell<Vec<gtk::Widget>>>,
 
     /// Our colors.
     colors: TablineColors,
@@ -46,26 +51,54 @@ impl Tabline {
         let tabpage_data = Rc::new(RefCell::new(vec![]));
         let switch_tab_signal = notebook.connect_switch_page(
             clone!(tabpage_data, nvim => move |_, _, page_num| {
-                let tabpage_data = tabpage_data.clone();
+                // Don't hold the borrow over the await, the tabs might get
+                // updated meanwhile.
+                let page = match tabpage_data.borrow().get(page_num as usize) {
+                    Some(page) => page.clone(),
+                    None => {
+                        error!("Failed to get tab page {}", page_num);
+                        return;
+                    }
+                };
+
                 let nvim = nvim.clone();
                 spawn_local(async move {
-                    let pages = tabpage_data.borrow();
-                    if let Some(ref page) = pages.get(page_num as usize) {
-                        nvim.set_current_tabpage(&page)
-                            .await
-                            .unwrap();
-                    } else {
-                        println!("Failed to get tab page {}", page_num);
+                    if let Err(err) = nvim.set_current_tabpage(&page).await {
+                        report_error("Failed to set current tab page", err);
                     }
                 });
             }),
         );
 
+        let pages: Rc<RefCell<Vec<gtk::Widget>>> =
+            Rc::new(RefCell::new(vec![]));
+        notebook.connect_page_reordered(
+            clone!(pages, nvim => move |_, child, page_num| {
+                let from = match pages.borrow().iter().position(|p| p == child) {
+                    Some(from) => from,
+                    None => return,
+                };
+
+                // The notebook has now reordered the tabs itself, but we'll
+                // rebuild them anyways once nvim sends us the new tabline.
+                if let Some(cmd) = move_command(from, page_num as usize) {
+                    let nvim = nvim.clone();
+                    spawn_local(async move {
+                        if let Err(err) = nvim.command(&cmd).await {
+                            report_error("Failed to move tab page", err);
+                        }
+                    });
+                }
+            }),
+        );
+
         Tabline {
+            nvim,
             notebook,
             css_provider,
             switch_tab_signal,
             tabpage_data,
+            pages,
             colors: TablineColors::default(),
             font: Font::default(),
             line_space: 0,
@@ -94,17 +127,65 @@ impl Tabline {
 
         glib::signal_handler_block(&self.notebook, &self.switch_tab_signal);
 
+        let mut pages: Vec<gtk::Widget> = vec![];
         let mut page = 0;
         for (i, tab) in tabs.iter().enumerate() {
             let tab_label = gtk::Label::new(Some(tab.1.as_str()));
             tab_label.set_hexpand(true);
             tab_label.set_ellipsize(pango::EllipsizeMode::End);
-            add_css_provider!(&self.css_provider, tab_label);
+            // Show the full name, in case it doesn't fit.
+            tab_label.set_tooltip_text(Some(tab.1.as_str()));
 
-            self.notebook.append_page(
-                &gtk::Box::new(gtk::Orientation::Vertical, 0),
-                Some(&tab_label),
+            let close = gtk::Button::from_icon_name(
+                Some("window-close-symbolic"),
+                gtk::IconSize::Menu,
             );
+            close.set_relief(gtk::ReliefStyle::None);
+            close.set_can_focus(false);
+            close.set_tooltip_text(Some("Close tab"));
+            // The button is only shown while hovering the tab. Use opacity
+            // instead of hiding it, so the tab doesn't change its size.
+            close.set_opacity(0.0);
+
+            let nvim = self.nvim.clone();
+            let cmd = close_command(i, tabs.len());
+            close.connect_clicked(clone!(cmd, nvim => move |_| {
+                close_tab(&nvim, cmd.clone());
+            }));
+
+            let content = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+            content.pack_start(&tab_label, true, true, 0);
+            content.pack_start(&close, false, false, 0);
+
+            let eb = gtk::EventBox::new();
+            eb.set_visible_window(false);
+            eb.add(&content);
+            eb.connect_enter_notify_event(clone!(close => move |_, _| {
+                close.set_opacity(1.0);
+                Inhibit(false)
+            }));
+            eb.connect_leave_notify_event(clone!(close => move |_, e| {
+                // Moving the pointer over the close button gives us a leave
+                // event too, which we'll want to ignore.
+                if e.get_detail() != gdk::NotifyType::Inferior {
+                    close.set_opacity(0.0);
+                }
+                Inhibit(false)
+            }));
+            eb.connect_button_press_event(clone!(cmd, nvim => move |_, e| {
+                if e.get_button() == 2 {
+                    close_tab(&nvim, cmd.clone());
+                    return Inhibit(true);
+                }
+                Inhibit(false)
+            }));
+
+            add_css_provider!(&self.css_provider, tab_label, close);
+
+            let child = gtk::Box::new(gtk::Orientation::Vertical, 0);
+            self.notebook.append_page(&child, Some(&eb));
+            self.notebook.set_tab_reorderable(&child, true);
+            pages.push(child.upcast());
 
             if tab.0.get_value() == current.get_value() {
                 page = i;
@@ -117,6 +198,7 @@ impl Tabline {
 
         self.tabpage_data
             .replace(tabs.into_iter().map(|t| t.0).collect());
+        self.pages.replace(pages);
 
         glib::signal_handler_unblock(&self.notebook, &self.switch_tab_signal);
     }
@@ -209,6 +291,12 @@ impl Tabline {
             tab:hover {{
                 box-shadow: inset 73px 0px 0px -70px #{selected_fg};
             }}
+            tab button {{
+                padding: 0px;
+                min-height: 0px;
+                min-width: 0px;
+                color: #{normal_fg};
+            }}
             ",
             font_wild = self.font.as_wild_css(FontUnit::Point),
             normal_fg = self.colors.fg.unwrap_or(hl_defs.default_fg).to_hex(),
@@ -265,6 +353,10 @@ impl Tabline {
             tab:hover {{
                 box-shadow: inset 73px 0px 0px -70px #{selected_fg};
             }}
+            GtkNotebook GtkButton {{
+                padding: 0px;
+                color: #{normal_fg};
+            }}
             ",
             font_wild = self.font.as_wild_css(FontUnit::Pixel),
             normal_fg = self.colors.fg.unwrap_or(hl_defs.default_fg).to_hex(),
@@ -281,3 +373,81 @@ impl Tabline {
             .unwrap();
     }
 }
+
+/// Returns the command for closing the tab at `index` (zero based), when
+/// there are `count` tabs. The last tab can't be closed, so for that `None`
+/// is returned.
+fn close_command(index: usize, count: usize) -> Option<String> {
+    if count < 2 {
+        return None;
+    }
+
+    Some(format!("tabclose {}", index + 1))
+}
+
+/// Returns the command for moving the tab at `from` to `to` (both zero
+/// based), or `None` if there is nothing to move.
+fn move_command(from: usize, to: usize) -> Option<String> {
+    if from == to {
+        return None;
+    }
+
+    // `:tabmove N` places the current tab after the N:th tab, which, when
+    // moving right, still counts the tab being moved.
+    let n = if to < from { to } else { to + 1 };
+    Some(format!("tabnext {} | tabmove {}", from + 1, n))
+}
+
+fn close_tab(nvim: &GioNeovim, cmd: Option<String>) {
+    let cmd = match cmd {
+        Some(cmd) => cmd,
+        None => return,
+    };
+
+    let nvim = nvim.clone();
+    spawn_local(async move {
+        if let Err(err) = nvim.command(&cmd).await {
+            // Most likely the tab has unsaved changes. Let nvim show the
+            // error, so it ends up in the message window.
+            let msg = match *err {
+                CallError::NeovimError(_, msg) => msg,
+                err => err.to_string(),
+            };
+
+            if let Err(err) = nvim.err_writeln(&msg).await {
+                report_error("Failed to show tabclose error", err);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_close_command() {
+        assert_eq!(Some("tabclose 1".to_string()), close_command(0, 2));
+        assert_eq!(Some("tabclose 3".to_string()), close_command(2, 3));
+        assert_eq!(None, close_command(0, 1));
+    }
+
+    #[test]
+    fn test_move_command() {
+        assert_eq!(None, move_command(1, 1));
+        // Left.
+        assert_eq!(
+            Some("tabnext 4 | tabmove 0".to_string()),
+            move_command(3, 0)
+        );
+        assert_eq!(
+            Some("tabnext 4 | tabmove 1".to_string()),
+            move_command(3, 1)
+        );
+        // Right.
+        assert_eq!(
+            Some("tabnext 1 | tabmove 3".to_string()),
+            move_command(0, 2)
+        );
+    }
+}
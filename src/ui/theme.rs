@@ -0,0 +1,254 @@
+//! Following the desktop's dark/light preference with nvim's 'background'.
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use gio::prelude::*;
+use gtk::prelude::*;
+
+use log::debug;
+
+use crate::nvim_gio::GioNeovim;
+use crate::ui::common::{report_error, spawn_local};
+
+const PORTAL_BUS_NAME: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_OBJECT_PATH: &str = "/org/freedesktop/portal/desktop";
+const PORTAL_SETTINGS_INTERFACE: &str = "org.freedesktop.portal.Settings";
+const APPEARANCE_NAMESPACE: &str = "org.freedesktop.appearance";
+const COLOR_SCHEME_KEY: &str = "color-scheme";
+/// Timeout (in ms) for reading the portal's color scheme at startup.
+const PORTAL_TIMEOUT_MS: i32 = 1000;
+
+/// The desktop's dark/light preferences.
+#[derive(Clone, Copy)]
+struct Preferences {
+    /// If the GTK theme is dark.
+    gtk_dark: bool,
+    /// The portal's color scheme, if it has a preference.
+    portal_dark: Option<bool>,
+}
+
+impl Preferences {
+    /// The portal's color scheme wins, since the GTK theme isn't
+    /// necessarily changed along with it.
+    fn is_dark(&self) -> bool {
+        self.portal_dark.unwrap_or(self.gtk_dark)
+    }
+}
+
+/// Sets nvim's 'background' from the desktop's dark/light preference, and
+/// again whenever it changes, so that colorschemes follow it. The
+/// preference comes from the freedesktop portal's color scheme, or without
+/// one, from the GTK theme. Does nothing while `enabled` is false.
+///
+/// Returns the portal's proxy, which has to be kept alive for following
+/// the portal's changes.
+pub fn watch(
+    nvim: GioNeovim,
+    enabled: Rc<Cell<bool>>,
+) -> Option<gio::DBusProxy> {
+    let settings = gtk::Settings::get_default();
+    let portal = portal_settings();
+
+    let prefs = Rc::new(Cell::new(Preferences {
+        gtk_dark: settings.as_ref().map(is_dark).unwrap_or(false),
+        portal_dark: portal.as_ref().and_then(read_portal_dark),
+    }));
+
+    let dark = Cell::new(None);
+    let update = Rc::new(move |prefs: Preferences| {
+        let is_dark = prefs.is_dark();
+        if dark.replace(Some(is_dark)) == Some(is_dark) || !enabled.get() {
+            return;
+        }
+
+        let nvim = nvim.clone();
+        spawn_local(async move {
+            if let Err(err) = nvim.command(background_command(is_dark)).await {
+                report_error("Failed to set 'background'", err);
+            }
+        });
+    });
+
+    update(prefs.get());
+
+    if let Some(ref settings) = settings {
+        let f = {
+            let prefs = prefs.clone();
+            let update = update.clone();
+            Rc::new(move |settings: &gtk::Settings| {
+                let mut p = prefs.get();
+                p.gtk_dark = is_dark(settings);
+                prefs.set(p);
+                update(p);
+            })
+        };
+
+        settings.connect_property_gtk_application_prefer_dark_theme_notify(
+            clone!(f => move |settings| f(settings)),
+        );
+        settings.connect_property_gtk_theme_name_notify(move |settings| {
+            f(settings)
+        });
+    }
+
+    if let Some(ref portal) = portal {
+        portal.connect_g_signal(move |_, _, signal, params| {
+            if signal != "SettingChanged" {
+                return;
+            }
+
+            let namespace = params.get_child_value(0);
+            let key = params.get_child_value(1);
+            if namespace.get_str() != Some(APPEARANCE_NAMESPACE)
+                || key.get_str() != Some(COLOR_SCHEME_KEY)
+            {
+                return;
+            }
+
+            let mut p = prefs.get();
+            p.portal_dark = unwrap_u32(&params.get_child_value(2))
+                .and_then(color_scheme_is_dark);
+            prefs.set(p);
+            update(p);
+        });
+    }
+
+    portal
+}
+
+/// Returns a proxy for the portal's settings, or `None` if there's no
+/// session bus.
+fn portal_settings() -> Option<gio::DBusProxy> {
+    let res = gio::DBusProxy::new_for_bus_sync(
+        gio::BusType::Session,
+        gio::DBusProxyFlags::DO_NOT_LOAD_PROPERTIES,
+        None,
+        PORTAL_BUS_NAME,
+        PORTAL_OBJECT_PATH,
+        PORTAL_SETTINGS_INTERFACE,
+        None::<&gio::Cancellable>,
+    );
+
+    match res {
+        Ok(portal) => Some(portal),
+        Err(err) => {
+            debug!("Failed to connect to the portal: {}", err);
+            None
+        }
+    }
+}
+
+/// Reads the portal's color scheme, see `color_scheme_is_dark`. `None` if
+/// there's no portal, or it doesn't have the setting.
+fn read_portal_dark(portal: &gio::DBusProxy) -> Option<bool> {
+    let params = glib::Variant::from_tuple(&[
+        APPEARANCE_NAMESPACE.to_variant(),
+        COLOR_SCHEME_KEY.to_variant(),
+    ]);
+
+    match portal.call_sync(
+        "Read",
+        Some(&params),
+        gio::DBusCallFlags::NONE,
+        PORTAL_TIMEOUT_MS,
+        None::<&gio::Cancellable>,
+    ) {
+        Ok(res) => {
+            unwrap_u32(&res.get_child_value(0)).and_then(color_scheme_is_dark)
+        }
+        Err(err) => {
+            debug!("Failed to read the portal's color scheme: {}", err);
+            None
+        }
+    }
+}
+
+/// Returns the `u32` in `val`. Some versions of the portal wrap the setting
+/// in an extra variant.
+fn unwrap_u32(val: &glib::Variant) -> Option<u32> {
+    match val.get_variant() {
+        Some(inner) => unwrap_u32(&inner),
+        None => val.get::<u32>(),
+    }
+}
+
+/// Returns if the portal's color scheme `scheme` prefers dark: 1 prefers
+/// dark and 2 light. Others (0) have no preference.
+fn color_scheme_is_dark(scheme: u32) -> Option<bool> {
+    match scheme {
+        1 => Some(true),
+        2 => Some(false),
+        _ => None,
+    }
+}
+
+fn is_dark(settings: &gtk::Settings) -> bool {
+    let theme = settings
+        .get_property_gtk_theme_name()
+        .map(|name| name.to_string())
+        .unwrap_or_default();
+    theme_is_dark(
+        settings.get_property_gtk_application_prefer_dark_theme(),
+        &theme,
+    )
+}
+
+/// Returns if the theme is dark, when the dark variant is preferred
+/// (`prefer_dark`) and the theme's name is `theme`. Themes without a dark
+/// variant usually have their dark version named e.g. `Foo-dark`.
+fn theme_is_dark(prefer_dark: bool, theme: &str) -> bool {
+    let theme = theme.to_lowercase();
+    prefer_dark || theme.ends_with("-dark") || theme.ends_with(":dark")
+}
+
+fn background_command(dark: bool) -> &'static str {
+    if dark {
+        "set background=dark"
+    } else {
+        "set background=light"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_is_dark() {
+        assert!(!theme_is_dark(false, "Adwaita"));
+        assert!(theme_is_dark(true, "Adwaita"));
+        assert!(theme_is_dark(false, "Adwaita-dark"));
+        assert!(theme_is_dark(false, "Adwaita:dark"));
+        assert!(theme_is_dark(false, "Arc-Dark"));
+        assert!(!theme_is_dark(false, "Darkly"));
+    }
+
+    #[test]
+    fn test_color_scheme_is_dark() {
+        assert_eq!(None, color_scheme_is_dark(0));
+        assert_eq!(Some(true), color_scheme_is_dark(1));
+        assert_eq!(Some(false), color_scheme_is_dark(2));
+    }
+
+    #[test]
+    fn test_unwrap_u32() {
+        assert_eq!(Some(1), unwrap_u32(&1u32.to_variant()));
+        assert_eq!(None, unwrap_u32(&"1".to_variant()));
+    }
+
+    #[test]
+    fn test_preferences_is_dark() {
+        let prefs = Preferences {
+            gtk_dark: true,
+            portal_dark: None,
+        };
+        assert!(prefs.is_dark());
+
+        let prefs = Preferences {
+            gtk_dark: true,
+            portal_dark: Some(false),
+        };
+        assert!(!prefs.is_dark());
+    }
+}
@@ -1,25 +1,55 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::rc::Rc;
 
 use gtk::prelude::*;
 
+use futures::channel::oneshot;
 use log::{debug, error};
 use rmpv::Value;
 
-use crate::nvim_bridge::{Message, Request};
+use crate::nvim_bridge::{Message, Request, ScrollbarPosition};
 use crate::nvim_gio::GioNeovim;
+use crate::ui::clipboard;
 use crate::ui::cmdline::Cmdline;
 use crate::ui::color::{Highlight, HlDefs};
-use crate::ui::common::spawn_local;
+use crate::ui::common::{
+    report_error, set_error_sink, spawn_local, toggle_fullscreen,
+};
+use crate::ui::context_menu::ContextMenu;
 #[cfg(feature = "libwebkit2gtk")]
 use crate::ui::cursor_tooltip::CursorTooltip;
+use crate::ui::dbus;
+use crate::ui::error_bar::ErrorBar;
+use crate::ui::file_drop;
+use crate::ui::find::FindBar;
 use crate::ui::font::Font;
-use crate::ui::grid::Grid;
+use crate::ui::grid::{
+    Grid, KineticScroll, DEFAULT_ANIMATION_DURATION_MS,
+    DEFAULT_SCROLL_ANIMATION_DURATION_MS,
+};
+use crate::ui::hover::Hover;
 use crate::ui::popupmenu::Popupmenu;
-use crate::ui::state::{attach_grid_events, UIState, Windows};
+use crate::ui::state::{
+    attach_grid_events, FontZoom, UIState, Windows, DEFAULT_TITLE,
+};
 use crate::ui::tabline::Tabline;
-use crate::ui::window::MsgWindow;
+use crate::ui::theme;
+use crate::ui::window::{
+    ExternalGeometries, ExternalOptions, KeyHandler, KeyInput, MouseReporting,
+    MsgWindow, ScrollSpeed,
+};
+use crate::ui::window_geometry::WindowGeometry;
+
+/// Name of the application action that starts a new nvim, with a new window
+/// for it. Used for restarting nvim after it has crashed.
+pub const RESTART_ACTION: &str = "restart-nvim";
+
+/// Default duration (in ms) of the yank flash.
+const DEFAULT_YANK_FLASH_DURATION_MS: u64 = 200;
+
+/// Height (in pixels) of the mode indicator.
+const MODE_INDICATOR_HEIGHT: i32 = 2;
 
 /// Main UI structure.
 pub struct UI {
@@ -32,6 +62,8 @@ pub struct UI {
     /// Our internal state, containing basically everything we manipulate
     /// when we receive an event from nvim.
     state: Rc<RefCell<UIState>>,
+    /// If the evaluating actions are added to our DBus interface.
+    remote_eval: bool,
 }
 
 impl UI {
@@ -39,18 +71,74 @@ impl UI {
     ///
     /// * `app` - GTK application for the UI.
     /// * `rx` - Channel to receive nvim UI events.
+    /// * `geometry` - Initial geometry of the main window.
+    /// * `save_geometry` - If the window's geometry is saved on exit, to be
+    ///                     restored on the next start.
+    /// * `size` - Size (cols, rows) for the main grid. The window is resized
+    ///            to fit it once the font is known.
+    /// * `restore_session` - If the session should be restored (see
+    ///                       `gnvim#session#restore()`) once nvim has the
+    ///                       window's size.
     /// * `nvim` - Neovim instance to use. Should be the same that is the source
     ///            of `rx` events.
     pub fn init(
         app: &gtk::Application,
         rx: glib::Receiver<Message>,
-        window_size: (i32, i32),
+        geometry: WindowGeometry,
+        save_geometry: bool,
+        size: Option<(u64, u64)>,
+        restore_session: bool,
+        remote_eval: bool,
         nvim: GioNeovim,
     ) -> Self {
         // Create the main window.
         let window = gtk::ApplicationWindow::new(app);
-        window.set_title("Neovim");
-        window.set_default_size(window_size.0, window_size.1);
+        window.set_title(DEFAULT_TITLE);
+        window.set_default_size(geometry.width, geometry.height);
+
+        // Needed for 'gnvim#set_background_opacity()'. Has to be set before
+        // the window is realized.
+        let visual = gtk::GtkWindowExt::get_screen(&window)
+            .and_then(|screen| screen.get_rgba_visual());
+        if let Some(visual) = visual {
+            window.set_visual(Some(&visual));
+        }
+
+        if geometry.maximized {
+            window.maximize();
+        }
+
+        if save_geometry {
+            // The size of the unmaximized (and not fullscreen) window, so
+            // that unmaximizing still works after a restart.
+            let size = Rc::new(Cell::new((geometry.width, geometry.height)));
+            let fullscreen = Rc::new(Cell::new(false));
+            window.connect_window_state_event(
+                clone!(fullscreen => move |_, e| {
+                    fullscreen.set(
+                        e.get_new_window_state()
+                            .contains(gdk::WindowState::FULLSCREEN),
+                    );
+                    Inhibit(false)
+                }),
+            );
+            window.connect_configure_event(clone!(size => move |window, _| {
+                if !window.is_maximized() && !fullscreen.get() {
+                    size.set(window.get_size());
+                }
+                Inhibit(false)
+            }));
+            window.connect_delete_event(move |window, _| {
+                let (width, height) = size.get();
+                WindowGeometry {
+                    width,
+                    height,
+                    maximized: window.is_maximized(),
+                }
+                .save();
+                Inhibit(false)
+            });
+        }
 
         // Realize window resources.
         window.realize();
@@ -66,6 +154,14 @@ impl UI {
         let overlay = gtk::Overlay::new();
         b.pack_start(&overlay, true, true, 0);
 
+        // Colored by the current mode, see `gnvim#set_mode_color()`. Hidden
+        // until some mode has a color.
+        let mode_indicator = gtk::Frame::new(None);
+        mode_indicator.set_widget_name("mode-indicator");
+        mode_indicator.set_size_request(-1, MODE_INDICATOR_HEIGHT);
+        mode_indicator.set_no_show_all(true);
+        b.pack_start(&mode_indicator, false, false, 0);
+
         // Create hl defs and initialize 0th element because we'll need to have
         // something that is accessible for the default grid that we're gonna
         // make next.
@@ -75,6 +171,9 @@ impl UI {
         let font = Font::from_guifont("Monospace:h12").unwrap();
         let line_space = 0;
 
+        // Shared between all grids, so we can stop it on key presses.
+        let kinetic_scroll = KineticScroll::default();
+
         // Create default grid.
         let mut grid = Grid::new(
             1,
@@ -85,11 +184,26 @@ impl UI {
             30,
             &hl_defs,
             true,
+            kinetic_scroll.clone(),
         );
         // Mark the default grid as active at the beginning.
         grid.set_active(true);
         overlay.add(&grid.widget());
 
+        // Files dropped outside of any window are opened in the current
+        // window.
+        file_drop::connect_file_drop(
+            &grid.widget(),
+            clone!(nvim => move |cmd| {
+                let nvim = nvim.clone();
+                spawn_local(async move {
+                    if let Err(err) = nvim.command(&cmd).await {
+                        report_error("Failed to open dropped files", err);
+                    }
+                });
+            }),
+        );
+
         let windows_container = gtk::Fixed::new();
         windows_container.set_widget_name("windows-contianer");
         let windows_float_container = gtk::Fixed::new();
@@ -100,6 +214,12 @@ impl UI {
         overlay.add_overlay(&msg_window_container);
         overlay.add_overlay(&windows_float_container);
 
+        // Shown until nvim has drawn something, so slow startups don't look
+        // like a blank window.
+        let splash = splash();
+        overlay.add_overlay(&splash);
+        overlay.set_overlay_pass_through(&splash, true);
+
         let css_provider = gtk::CssProvider::new();
         let msg_window =
             MsgWindow::new(msg_window_container.clone(), css_provider.clone());
@@ -115,14 +235,24 @@ impl UI {
         // redraw even handler if we receive a message that changes the size
         // of the main grid.
         let source_id = Rc::new(RefCell::new(None));
-        grid.connect_da_resize(clone!(nvim, source_id => move |rows, cols| {
+        // Restored after the first resize, so that the nvim windows' sizes
+        // are restored relative to our window's size.
+        let restore_session = Rc::new(Cell::new(restore_session));
+        grid.connect_da_resize(clone!(nvim, source_id, restore_session => move |rows, cols| {
 
             // Set timeout to notify nvim about the new size.
-            let new = gtk::timeout_add(30, clone!(nvim, source_id => move || {
+            let new = gtk::timeout_add(30, clone!(nvim, source_id, restore_session => move || {
                 let nvim = nvim.clone();
+                let restore_session = restore_session.replace(false);
                 spawn_local(async move {
                     if let Err(err) = nvim.ui_try_resize(cols as i64, rows as i64).await {
-                        error!("Error: failed to resize nvim when grid size changed ({:?})", err);
+                        report_error("Failed to resize nvim", err);
+                    }
+
+                    if restore_session {
+                        if let Err(err) = nvim.call_function("gnvim#session#restore", vec![]).await {
+                            report_error("Failed to restore the session", err);
+                        }
                     }
                 });
 
@@ -144,40 +274,32 @@ impl UI {
             false
         }));
 
-        attach_grid_events(&grid, nvim.clone());
+        let context_menu = ContextMenu::new(nvim.clone());
+        let hover = Hover::new(nvim.clone(), window.upcast_ref());
+        let scroll_speed = ScrollSpeed::default();
+        let mouse = MouseReporting::default();
+        attach_grid_events(
+            &grid,
+            nvim.clone(),
+            context_menu.clone(),
+            hover.clone(),
+            scroll_speed.clone(),
+            mouse.clone(),
+        );
 
         // IMMulticontext is used to handle most of the inputs.
         let im_context = gtk::IMMulticontext::new();
-        im_context.set_use_preedit(false);
+        im_context.set_use_preedit(true);
         im_context.connect_commit(clone!(nvim => move |_, input| {
             // "<" needs to be escaped for nvim.input()
             let nvim_input = input.replace("<", "<lt>");
 
             let nvim = nvim.clone();
             spawn_local(async move {
-                nvim.input(&nvim_input).await.expect("Couldn't send input");
-            });
-        }));
-
-        window.connect_key_press_event(clone!(nvim, im_context => move |_, e| {
-            if im_context.filter_keypress(e) {
-                Inhibit(true)
-            } else {
-                if let Some(input) = event_to_nvim_input(e) {
-                    let nvim = nvim.clone();
-                    spawn_local(async move {
-                        nvim.input(input.as_str()).await.expect("Couldn't send input");
-                    });
-                    return Inhibit(true);
-                } else {
-                    debug!(
-                        "Failed to turn input event into nvim key (keyval: {})",
-                        e.get_keyval()
-                    )
+                if let Err(err) = nvim.input(&nvim_input).await {
+                    report_error("Failed to send input", err);
                 }
-
-                Inhibit(false)
-            }
+            });
         }));
 
         window.connect_key_release_event(clone!(im_context => move |_, e| {
@@ -185,17 +307,15 @@ impl UI {
             Inhibit(false)
         }));
 
-        window.connect_focus_in_event(clone!(im_context => move |_, _| {
-            im_context.focus_in();
-            Inhibit(false)
-        }));
-
         window.connect_focus_out_event(clone!(im_context => move |_, _| {
             im_context.focus_out();
             Inhibit(false)
         }));
 
         let cmdline = Cmdline::new(&overlay, nvim.clone());
+        let find = FindBar::new(&overlay);
+        let error_bar = ErrorBar::new(&overlay);
+        set_error_sink(move |msg| error_bar.show(msg));
         #[cfg(feature = "libwebkit2gtk")]
         let cursor_tooltip = CursorTooltip::new(&overlay);
 
@@ -203,45 +323,296 @@ impl UI {
 
         grid.set_im_context(&im_context);
 
+        // External windows take the input method to their grid when they
+        // get the focus, so bring it back to the main grid.
+        window.connect_focus_in_event(clone!(grid => move |_, _| {
+            grid.focus_im_context();
+            Inhibit(false)
+        }));
+
         cmdline.hide();
+        find.close();
         #[cfg(feature = "libwebkit2gtk")]
         cursor_tooltip.hide();
 
         let mut grids = HashMap::new();
         grids.insert(1, grid);
 
-        add_css_provider!(&css_provider, window);
+        add_css_provider!(&css_provider, window, mode_indicator);
+
+        let auto_background = Rc::new(Cell::new(true));
+        let theme_portal = theme::watch(nvim.clone(), auto_background.clone());
+
+        let state = Rc::new(RefCell::new(UIState {
+            css_provider,
+            windows: Windows::new(),
+            windows_container,
+            msg_window_container,
+            msg_window,
+            windows_float_container,
+            grids,
+            mode_infos: vec![],
+            current_grid: 1,
+            title: None,
+            wildmenu_shown: false,
+            popupmenu: Popupmenu::new(&overlay, nvim.clone()),
+            cmdline,
+            overlay,
+            tabline,
+            #[cfg(feature = "libwebkit2gtk")]
+            cursor_tooltip,
+            resize_source_id: source_id,
+            hl_defs,
+            resize_on_flush: None,
+            msg_pos: None,
+            hl_changed: false,
+            font,
+            font_scale: 1.0,
+            font_wide: None,
+            line_space,
+            current_mode: None,
+            enable_cursor_animations: true,
+            cursor_animation_duration: DEFAULT_ANIMATION_DURATION_MS,
+            cursor_color: None,
+            mode_cursor_colors: HashMap::new(),
+            mode_name: String::new(),
+            enable_scroll_animation: false,
+            scroll_animation_duration: DEFAULT_SCROLL_ANIMATION_DURATION_MS,
+            enable_ligatures: true,
+            enable_synthetic_styles: true,
+            bold_is_bright: false,
+            visual_bell: false,
+            inactive_dim: 0.0,
+            focused: true,
+            yank_flash_color: None,
+            yank_flash_duration: DEFAULT_YANK_FLASH_DURATION_MS,
+            busy_pointer: false,
+            alt_is_meta: false,
+            key_passthrough: vec![],
+            fullscreen_key: true,
+            find,
+            find_key: true,
+            find_pending_step: None,
+            context_menu,
+            hover: hover.clone(),
+            splash: Some(splash),
+            initial_size: size,
+            size_from_cli: size.is_some(),
+            fit_on_flush: true,
+            starting: true,
+            scroll_speed,
+            mouse,
+            font_features: String::new(),
+            user_css: String::new(),
+            mode_indicator,
+            mode_colors: HashMap::new(),
+            mode_class: None,
+            kinetic_scroll: kinetic_scroll.clone(),
+            scrolloff: 0,
+            scrollbar_click_to_jump: false,
+            scrollbar_autohide: false,
+            scrollbar_width: 0,
+            scrollbar_opacity: 1.0,
+            scrollbar_position: ScrollbarPosition::default(),
+            background_opacity: 1.0,
+            background_blur: false,
+            max_fps: 0,
+            cursor_overlay: (false, false),
+            float_border: false,
+            float_animation: false,
+            external_geometries: ExternalGeometries::default(),
+            external_options: ExternalOptions::default(),
+            key_input: None,
+            auto_background: auto_background.clone(),
+            theme_portal,
+        }));
+
+        // The preedit text is drawn on the current grid, which is known by
+        // the state.
+        im_context.connect_preedit_changed(clone!(state => move |im_context| {
+            let (text, attrs, cursor) = im_context.get_preedit_string();
+            state.borrow().set_preedit(
+                text.to_string(),
+                attrs,
+                cursor.max(0) as usize,
+            );
+        }));
+
+        im_context.connect_preedit_end(clone!(state => move |_| {
+            state.borrow().set_preedit(
+                String::new(),
+                pango::AttrList::new(),
+                0,
+            );
+        }));
+
+        // Moving the focus between our toplevels makes one lose it before
+        // the other gets it, so the focus is checked once both are done.
+        let on_focus_changed: Rc<dyn Fn()> = {
+            let state = state.clone();
+            let window_weak = window.downgrade();
+            Rc::new(move || {
+                let state = state.clone();
+                let window_weak = window_weak.clone();
+                gtk::idle_add(move || {
+                    let window = upgrade_weak!(window_weak, Continue(false));
+                    state.borrow_mut().update_focused(&window);
+                    Continue(false)
+                });
+            })
+        };
+
+        window.connect_property_is_active_notify(
+            clone!(on_focus_changed => move |_| on_focus_changed()),
+        );
+
+        // The cell metrics are snapped to device pixels, so they need to be
+        // recalculated when the scale factor changes.
+        window.connect_property_scale_factor_notify(
+            clone!(nvim, state => move |window| {
+                state.borrow_mut().scale_factor_changed(&nvim, window);
+            }),
+        );
+
+        {
+            let state_ref = state.borrow();
+            let find = &state_ref.find;
+            find.connect_changed(clone!(state => move || {
+                state.borrow().update_find();
+            }));
+            find.connect_step(clone!(nvim, state => move |forward| {
+                if state.borrow_mut().find_step(forward) {
+                    return;
+                }
+
+                let nvim = nvim.clone();
+                let state = state.clone();
+                spawn_local(async move {
+                    let args = vec![forward.into()];
+                    let res = nvim.call_function("gnvim#find#scroll", args);
+                    // If the window didn't scroll, e.g. at the end of the
+                    // buffer, there's no flush to take the step on.
+                    match res.await {
+                        Ok(scrolled) if scrolled.as_i64() == Some(1) => {}
+                        Ok(_) => state.borrow_mut().find_pending_step = None,
+                        Err(err) => {
+                            state.borrow_mut().find_pending_step = None;
+                            report_error("Failed to scroll for find", err);
+                        }
+                    }
+                });
+            }));
+            find.connect_close(clone!(state => move || {
+                state.borrow_mut().close_find();
+            }));
+        }
+
+        // Key presses are handled here, once we have the state, which is
+        // needed for zooming the font. The external windows share the
+        // handler with the main window.
+        let on_key_press: KeyHandler = {
+            let nvim = nvim.clone();
+            let im_context = im_context.clone();
+            let kinetic_scroll = kinetic_scroll.clone();
+            let hover = hover.clone();
+            let state = state.clone();
+            let window_weak = window.downgrade();
+            Rc::new(move |e: &gdk::EventKey| {
+                let window = upgrade_weak!(window_weak, Inhibit(false));
+                kinetic_scroll.stop();
+                hover.cancel();
+
+                // Let the window's default handler pass the key to the find
+                // bar.
+                if state.borrow().find.has_focus() {
+                    return Inhibit(false);
+                }
+
+                if passes_through(&state.borrow().key_passthrough, e) {
+                    return Inhibit(false);
+                }
+
+                if let Some(zoom) = font_zoom_for_event(e) {
+                    state.borrow_mut().zoom_font(zoom, &nvim, &window);
+                    return Inhibit(true);
+                }
+
+                if state.borrow().fullscreen_key && is_fullscreen_key(e) {
+                    toggle_fullscreen(&window);
+                    return Inhibit(true);
+                }
+
+                if state.borrow().find_key && is_find_key(e) {
+                    state.borrow().find.open();
+                    return Inhibit(true);
+                }
+
+                if is_paste_key(e) {
+                    clipboard::paste(nvim.clone(), "+");
+                    return Inhibit(true);
+                }
+
+                let composing = !im_context.get_preedit_string().0.is_empty();
+
+                // Escape cancels the composition, without going to nvim.
+                if composing
+                    && e.get_keyval().name().as_deref() == Some("Escape")
+                {
+                    im_context.reset();
+                    return Inhibit(true);
+                }
+
+                // Control and meta chords bypass the input method, so that
+                // they're available for mappings. Dead keys and compose
+                // sequences go through it, and their result is committed to
+                // nvim as a whole.
+                let alt_is_meta = state.borrow().alt_is_meta;
+                let filtered =
+                    if !composing && bypasses_im(e.get_state(), alt_is_meta) {
+                        false
+                    } else {
+                        im_context.filter_keypress(e)
+                    };
+
+                if filtered {
+                    Inhibit(true)
+                } else {
+                    if let Some(input) = event_to_nvim_input(e, alt_is_meta) {
+                        let nvim = nvim.clone();
+                        spawn_local(async move {
+                            if let Err(err) = nvim.input(input.as_str()).await {
+                                report_error("Failed to send input", err);
+                            }
+                        });
+                        return Inhibit(true);
+                    } else {
+                        debug!(
+                        "Failed to turn input event into nvim key (keyval: {})",
+                        e.get_keyval()
+                    )
+                    }
+
+                    Inhibit(false)
+                }
+            })
+        };
+
+        window.connect_key_press_event(clone!(on_key_press => move |_, e| {
+            on_key_press(e)
+        }));
+
+        state.borrow_mut().key_input = Some(KeyInput {
+            im_context,
+            on_key_press,
+            on_focus_changed,
+        });
 
         UI {
             win: window,
             rx,
-            state: Rc::new(RefCell::new(UIState {
-                css_provider,
-                windows: Windows::new(),
-                windows_container,
-                msg_window_container,
-                msg_window,
-                windows_float_container,
-                grids,
-                mode_infos: vec![],
-                current_grid: 1,
-                wildmenu_shown: false,
-                popupmenu: Popupmenu::new(&overlay, nvim.clone()),
-                cmdline,
-                overlay,
-                tabline,
-                #[cfg(feature = "libwebkit2gtk")]
-                cursor_tooltip,
-                resize_source_id: source_id,
-                hl_defs,
-                resize_on_flush: None,
-                hl_changed: false,
-                font,
-                line_space,
-                current_mode: None,
-                enable_cursor_animations: true,
-            })),
+            state,
             nvim,
+            remote_eval,
         }
     }
 
@@ -253,8 +624,30 @@ impl UI {
             state,
             win,
             nvim,
+            remote_eval,
         } = self;
 
+        dbus::register(&win, &nvim, remote_eval);
+
+        // Nvim doesn't send 'scrolloff' to UIs, so query the initial value
+        // here. Later changes are sent by our runtime files.
+        {
+            let state = state.clone();
+            let nvim = nvim.clone();
+            spawn_local(async move {
+                match nvim.get_option("scrolloff").await {
+                    Ok(val) => {
+                        if let Some(val) = val.as_u64() {
+                            state.borrow_mut().set_scrolloff(val);
+                        }
+                    }
+                    Err(err) => {
+                        report_error("Failed to get 'scrolloff'", err);
+                    }
+                }
+            });
+        }
+
         rx.attach(None, move |message| {
             match message {
                 // Handle a notify.
@@ -266,14 +659,19 @@ impl UI {
                 // Handle a request.
                 Message::Request(tx, request) => {
                     let mut state = state.borrow_mut();
-                    let res = handle_request(&request, &mut state);
-                    tx.send(res).expect("Failed to respond to a request");
+                    handle_request(request, &mut state, tx);
                 }
                 // Handle close.
                 Message::Close => {
                     win.close();
                     return Continue(false);
                 }
+                // Handle nvim going away unexpectedly.
+                Message::Crash(reason) => {
+                    error!("{}", reason);
+                    show_crash_dialog(&win, &reason);
+                    return Continue(false);
+                }
             }
 
             Continue(true)
@@ -281,12 +679,72 @@ impl UI {
     }
 }
 
+/// Creates the splash (logo and a spinner) shown on startup.
+fn splash() -> gtk::Widget {
+    let b = gtk::Box::new(gtk::Orientation::Vertical, 16);
+    b.set_widget_name("splash");
+    b.set_halign(gtk::Align::Center);
+    b.set_valign(gtk::Align::Center);
+
+    let logo = include_bytes!("../../desktop/gnvim_128.png");
+    let stream =
+        gio::MemoryInputStream::from_bytes(&glib::Bytes::from_static(logo));
+    match gdk_pixbuf::Pixbuf::from_stream(&stream, None::<&gio::Cancellable>) {
+        Ok(pixbuf) => b.pack_start(
+            &gtk::Image::from_pixbuf(Some(&pixbuf)),
+            false,
+            false,
+            0,
+        ),
+        Err(err) => error!("Failed to load the logo: {}", err),
+    }
+
+    let spinner = gtk::Spinner::new();
+    spinner.start();
+    b.pack_start(&spinner, false, false, 0);
+
+    b.upcast()
+}
+
+/// Tells the user that nvim went away unexpectedly, and lets them either
+/// restart it (in a new window, see `RESTART_ACTION`) or quit.
+fn show_crash_dialog(win: &gtk::ApplicationWindow, reason: &str) {
+    let dialog = gtk::MessageDialog::new(
+        Some(win),
+        gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+        gtk::MessageType::Error,
+        gtk::ButtonsType::None,
+        "Nvim exited unexpectedly",
+    );
+    dialog.set_property_secondary_text(Some(reason));
+    dialog.add_button("Quit", gtk::ResponseType::Close);
+    dialog.add_button("Restart", gtk::ResponseType::Accept);
+    dialog.set_default_response(gtk::ResponseType::Accept);
+
+    dialog.connect_response(clone!(win => move |dialog, response| {
+        dialog.close();
+
+        if response == gtk::ResponseType::Accept {
+            if let Some(app) = win.get_application() {
+                app.activate_action(RESTART_ACTION, None);
+            }
+        }
+
+        win.close();
+    }));
+
+    dialog.show();
+}
+
+/// Handles `request`, and sends the response to `tx`. The response might be
+/// sent only after this function returns.
 #[cfg_attr(not(feature = "libwebkit2gtk"), allow(unused_variables))] // Silence clippy
 fn handle_request(
-    request: &Request,
+    request: Request,
     state: &mut UIState,
-) -> Result<Value, Value> {
-    match request {
+    tx: oneshot::Sender<Result<Value, Value>>,
+) {
+    let res = match request {
         #[cfg(feature = "libwebkit2gtk")]
         Request::CursorTooltipStyles => {
             let styles = state.cursor_tooltip.get_styles();
@@ -300,6 +758,29 @@ fn handle_request(
         Request::CursorTooltipStyles => {
             Err("Cursor tooltip is not supported in this build".into())
         }
+        Request::ClipboardGet(register) => {
+            // The clipboard's contents arrive asynchronously.
+            clipboard::get(&register, move |res| {
+                let res = res.map(|(lines, regtype)| {
+                    let lines = lines.into_iter().map(Value::from).collect();
+                    Value::Array(vec![Value::Array(lines), regtype.into()])
+                });
+                respond(tx, res.map_err(Value::from));
+            });
+            return;
+        }
+    };
+
+    respond(tx, res);
+}
+
+fn respond(
+    tx: oneshot::Sender<Result<Value, Value>>,
+    res: Result<Value, Value>,
+) {
+    // Nvim's side is gone if the receiver is, so there is no one to tell.
+    if tx.send(res).is_err() {
+        error!("Failed to respond to a request");
     }
 }
 
@@ -363,12 +844,162 @@ fn keyname_to_nvim_key(s: &str) -> Option<&str> {
     }
 }
 
-fn event_to_nvim_input(e: &gdk::EventKey) -> Option<String> {
-    let mut input = String::from("");
+/// Returns the font zoom for ctrl+= (or ctrl++), ctrl+- and ctrl+0.
+fn font_zoom_for_event(e: &gdk::EventKey) -> Option<FontZoom> {
+    let state = e.get_state();
+    let mods = gdk::ModifierType::CONTROL_MASK
+        | gdk::ModifierType::MOD1_MASK
+        | gdk::ModifierType::SUPER_MASK;
+    if state & mods != gdk::ModifierType::CONTROL_MASK {
+        return None;
+    }
+
+    match e.get_keyval().name()?.as_str() {
+        "equal" | "plus" | "KP_Add" => Some(FontZoom::In),
+        "minus" | "KP_Subtract" => Some(FontZoom::Out),
+        "0" | "KP_0" => Some(FontZoom::Reset),
+        _ => None,
+    }
+}
+
+/// Returns true for ctrl+f, which opens the find bar.
+fn is_find_key(e: &gdk::EventKey) -> bool {
+    let mods = gdk::ModifierType::SHIFT_MASK
+        | gdk::ModifierType::CONTROL_MASK
+        | gdk::ModifierType::MOD1_MASK
+        | gdk::ModifierType::SUPER_MASK;
+    e.get_state() & mods == gdk::ModifierType::CONTROL_MASK
+        && e.get_keyval().name().as_deref() == Some("f")
+}
+
+/// Returns true for ctrl+shift+v, which pastes the clipboard.
+fn is_paste_key(e: &gdk::EventKey) -> bool {
+    let mods = gdk::ModifierType::SHIFT_MASK
+        | gdk::ModifierType::CONTROL_MASK
+        | gdk::ModifierType::MOD1_MASK
+        | gdk::ModifierType::SUPER_MASK;
+    e.get_state() & mods
+        == gdk::ModifierType::CONTROL_MASK | gdk::ModifierType::SHIFT_MASK
+        && matches!(e.get_keyval().name().as_deref(), Some("V") | Some("v"))
+}
+
+/// Returns true for a plain F11, which toggles fullscreen.
+fn is_fullscreen_key(e: &gdk::EventKey) -> bool {
+    let mods = gdk::ModifierType::SHIFT_MASK
+        | gdk::ModifierType::CONTROL_MASK
+        | gdk::ModifierType::MOD1_MASK
+        | gdk::ModifierType::SUPER_MASK;
+    !e.get_state().intersects(mods)
+        && e.get_keyval().name().as_deref() == Some("F11")
+}
+
+/// Returns true if key presses with modifiers `state` should go directly to
+/// nvim, instead of the input method. Alt goes to the input method, unless
+/// it's treated as meta.
+fn bypasses_im(state: gdk::ModifierType, alt_is_meta: bool) -> bool {
+    let mut mods =
+        gdk::ModifierType::CONTROL_MASK | gdk::ModifierType::META_MASK;
+    if alt_is_meta {
+        mods |= gdk::ModifierType::MOD1_MASK;
+    }
+
+    state.intersects(mods)
+}
 
+/// Returns nvim's name for the key of `e`, e.g. `Tab` or `x`.
+fn event_key_name(e: &gdk::EventKey) -> Option<String> {
     let keyval = e.get_keyval();
     let keyname = keyval.name()?;
 
+    if keyname.chars().count() > 1 {
+        keyname_to_nvim_key(keyname.as_str()).map(String::from)
+    } else {
+        keyval.to_unicode().map(String::from)
+    }
+}
+
+/// Returns true if `e` matches one of the `patterns` (see `key_matches`),
+/// so that it shouldn't be sent to nvim. Super is matched as `D-`.
+fn passes_through(patterns: &[String], e: &gdk::EventKey) -> bool {
+    if patterns.is_empty() {
+        return false;
+    }
+
+    let name = match event_key_name(e) {
+        Some(name) => name,
+        None => return false,
+    };
+
+    let state = e.get_state();
+    let mut key = String::from("<");
+    for (mask, modifier) in &[
+        (gdk::ModifierType::SHIFT_MASK, "S-"),
+        (gdk::ModifierType::CONTROL_MASK, "C-"),
+        (gdk::ModifierType::MOD1_MASK, "A-"),
+        (gdk::ModifierType::SUPER_MASK, "D-"),
+    ] {
+        if state.contains(*mask) {
+            key.push_str(modifier);
+        }
+    }
+    key.push_str(&name);
+    key.push('>');
+
+    patterns.iter().any(|pattern| key_matches(pattern, &key))
+}
+
+/// Splits key notation like `<C-S-x>` (or plain `x`) into its modifiers
+/// (sorted, with meta as alt) and the key's name.
+fn parse_key(key: &str) -> (String, &str) {
+    let mut rest =
+        if key.len() > 2 && key.starts_with('<') && key.ends_with('>') {
+            &key[1..key.len() - 1]
+        } else {
+            key
+        };
+
+    let mut mods = vec![];
+    loop {
+        let mut chars = rest.chars();
+        match (chars.next(), chars.next()) {
+            (Some(m), Some('-'))
+                if rest.len() > 2
+                    && "SCAMD".contains(m.to_ascii_uppercase()) =>
+            {
+                let m = m.to_ascii_uppercase();
+                mods.push(if m == 'M' { 'A' } else { m });
+                rest = &rest[2..];
+            }
+            _ => break,
+        }
+    }
+    mods.sort_unstable();
+    mods.dedup();
+
+    (mods.into_iter().collect(), rest)
+}
+
+/// Returns true if `key` matches `pattern`, both in nvim's key notation.
+/// The modifiers have to be the same, and `*` as the pattern's key matches
+/// any key. Named keys (e.g. `Tab`) are matched ignoring the case.
+fn key_matches(pattern: &str, key: &str) -> bool {
+    let (pattern_mods, pattern_key) = parse_key(pattern);
+    let (key_mods, key_name) = parse_key(key);
+
+    let name_matches = if pattern_key.chars().count() > 1 {
+        pattern_key.eq_ignore_ascii_case(key_name)
+    } else {
+        pattern_key == "*" || pattern_key == key_name
+    };
+
+    pattern_mods == key_mods && name_matches
+}
+
+fn event_to_nvim_input(e: &gdk::EventKey, alt_is_meta: bool) -> Option<String> {
+    let mut input = String::from("");
+
+    let name = event_key_name(e)?;
+
     let state = e.get_state();
 
     if state.contains(gdk::ModifierType::SHIFT_MASK) {
@@ -378,15 +1009,49 @@ fn event_to_nvim_input(e: &gdk::EventKey) -> Option<String> {
         input.push_str("C-");
     }
     if state.contains(gdk::ModifierType::MOD1_MASK) {
-        input.push_str("A-");
+        input.push_str(if alt_is_meta { "M-" } else { "A-" });
     }
 
-    if keyname.chars().count() > 1 {
-        let n = keyname_to_nvim_key(keyname.as_str())?;
-        input.push_str(n);
-    } else {
-        input.push(keyval.to_unicode()?);
-    }
+    input.push_str(&name);
 
     Some(format!("<{}>", input))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bypasses_im() {
+        assert!(!bypasses_im(gdk::ModifierType::empty(), false));
+        assert!(!bypasses_im(gdk::ModifierType::SHIFT_MASK, false));
+        assert!(bypasses_im(gdk::ModifierType::CONTROL_MASK, false));
+        assert!(bypasses_im(
+            gdk::ModifierType::CONTROL_MASK | gdk::ModifierType::SHIFT_MASK,
+            false
+        ));
+
+        assert!(!bypasses_im(gdk::ModifierType::MOD1_MASK, false));
+        assert!(bypasses_im(gdk::ModifierType::MOD1_MASK, true));
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!((String::from("CS"), "x"), parse_key("<S-C-x>"));
+        assert_eq!((String::from("A"), "Left"), parse_key("<M-Left>"));
+        assert_eq!((String::from("C"), "-"), parse_key("<C-->"));
+        assert_eq!((String::new(), "x"), parse_key("x"));
+        assert_eq!((String::new(), "Tab"), parse_key("<Tab>"));
+    }
+
+    #[test]
+    fn test_key_matches() {
+        assert!(key_matches("<D-*>", "<D-x>"));
+        assert!(key_matches("<D-*>", "<D-Tab>"));
+        assert!(!key_matches("<D-*>", "<S-D-Tab>"));
+        assert!(key_matches("<C-A-left>", "<A-C-Left>"));
+        assert!(key_matches("<M-x>", "<A-x>"));
+        assert!(!key_matches("<C-x>", "<C-X>"));
+        assert!(!key_matches("<C-x>", "<x>"));
+    }
+}
@@ -5,7 +5,7 @@ use std::rc::Rc;
 use crate::nvim_bridge;
 use crate::nvim_gio::GioNeovim;
 use crate::ui::color::{Color, HlDefs, HlGroup};
-use crate::ui::common::spawn_local;
+use crate::ui::common::{report_error, spawn_local};
 
 const MAX_HEIGHT: i32 = 500;
 
@@ -18,7 +18,14 @@ struct State {
 pub struct Wildmenu {
     css_provider: gtk::CssProvider,
     frame: gtk::Frame,
+    scrolledwindow: gtk::ScrolledWindow,
+    /// Contains the items when they're laid out vertically.
     list: gtk::ListBox,
+    /// Contains the items when they're laid out horizontally.
+    flow: gtk::FlowBox,
+
+    /// If the items are laid out horizontally.
+    horizontal: bool,
 
     state: Rc<RefCell<State>>,
 }
@@ -38,7 +45,20 @@ impl Wildmenu {
         );
         scrolledwindow
             .set_policy(gtk::PolicyType::Automatic, gtk::PolicyType::Automatic);
-        scrolledwindow.add(&list);
+
+        let flow = gtk::FlowBox::new();
+        flow.set_selection_mode(gtk::SelectionMode::Single);
+        flow.set_homogeneous(false);
+        // Keep all the items on a single line.
+        flow.set_min_children_per_line(1000);
+        flow.set_max_children_per_line(1000);
+        flow.set_hadjustment(&scrolledwindow.get_hadjustment().unwrap());
+        flow.set_no_show_all(true);
+
+        let box_ = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        box_.pack_start(&list, true, true, 0);
+        box_.pack_start(&flow, true, true, 0);
+        scrolledwindow.add(&box_);
 
         frame.add(&scrolledwindow);
 
@@ -61,37 +81,55 @@ impl Wildmenu {
 
         let state = Rc::new(RefCell::new(State::default()));
 
-        // If user selects some row with a mouse, notify nvim about it.
-        list.connect_row_activated(clone!(state => move |_, row| {
+        // If user selects some item with a mouse, notify nvim about it.
+        list.connect_row_activated(clone!(state, nvim => move |_, row| {
             let prev = state.borrow().selected;
-            let new = row.get_index();
-
-            let op = if new > prev { "<Tab>" } else { "<S-Tab>" };
-
-            for _ in 0..(new - prev).abs() {
-                // NOTE(ville): nvim doesn't like single input with many
-                //              tabs in it, so we'll have to send each
-                //              individually.
-                let nvim = nvim.clone();
-                spawn_local(async move {
-                    nvim.input(&op)
-                        .await
-                        .unwrap();
-                })
-            }
+            select_with_tabs(&nvim, prev, row.get_index());
+        }));
+        flow.connect_child_activated(clone!(state, nvim => move |_, child| {
+            let prev = state.borrow().selected;
+            select_with_tabs(&nvim, prev, child.get_index());
         }));
 
-        add_css_provider!(&css_provider, list, frame);
+        add_css_provider!(&css_provider, list, flow, frame);
 
         Wildmenu {
             css_provider,
             list,
+            flow,
             frame,
+            scrolledwindow,
+            horizontal: false,
 
             state,
         }
     }
 
+    /// Sets if the items are laid out horizontally (on a single line)
+    /// instead of vertically. Takes effect on the next `set_items`.
+    pub fn set_horizontal(&mut self, horizontal: bool) {
+        self.clear();
+        self.horizontal = horizontal;
+
+        self.list.set_no_show_all(horizontal);
+        self.list.set_visible(!horizontal);
+        self.flow.set_no_show_all(!horizontal);
+        self.flow.set_visible(horizontal);
+
+        if horizontal {
+            self.scrolledwindow
+                .set_policy(gtk::PolicyType::Automatic, gtk::PolicyType::Never);
+            // The list's size allocation would otherwise leave the frame
+            // with the list's height.
+            self.frame.set_size_request(-1, -1);
+        } else {
+            self.scrolledwindow.set_policy(
+                gtk::PolicyType::Automatic,
+                gtk::PolicyType::Automatic,
+            );
+        }
+    }
+
     pub fn widget(&self) -> gtk::Widget {
         self.frame.clone().upcast()
     }
@@ -109,6 +147,11 @@ impl Wildmenu {
         while let Some(item) = children.pop() {
             self.list.remove(&item);
         }
+
+        let mut children = self.flow.get_children();
+        while let Some(item) = children.pop() {
+            self.flow.remove(&item);
+        }
     }
 
     pub fn set_items(&mut self, items: &[nvim_bridge::CompletionItem]) {
@@ -118,21 +161,41 @@ impl Wildmenu {
             let label = gtk::Label::new(Some(item.word.as_str()));
             label.set_halign(gtk::Align::Start);
 
-            let row = gtk::ListBoxRow::new();
-            row.add(&label);
+            if self.horizontal {
+                let child = gtk::FlowBoxChild::new();
+                child.add(&label);
+
+                add_css_provider!(&self.css_provider, child, label);
+
+                self.flow.add(&child);
+            } else {
+                let row = gtk::ListBoxRow::new();
+                row.add(&label);
 
-            add_css_provider!(&self.css_provider, row, label);
+                add_css_provider!(&self.css_provider, row, label);
 
-            self.list.add(&row);
+                self.list.add(&row);
+            }
         }
 
-        self.list.show_all();
+        if self.horizontal {
+            self.flow.show_all();
+        } else {
+            self.list.show_all();
+        }
     }
 
     pub fn select(&mut self, item_num: i32) {
         self.state.borrow_mut().selected = item_num;
 
-        if item_num < 0 {
+        if self.horizontal {
+            if item_num < 0 {
+                self.flow.unselect_all();
+            } else if let Some(child) = self.flow.get_child_at_index(item_num) {
+                self.flow.select_child(&child);
+                child.grab_focus();
+            }
+        } else if item_num < 0 {
             self.list.unselect_all();
         } else if let Some(row) = self.list.get_row_at_index(item_num) {
             self.list.select_row(Some(&row));
@@ -182,9 +245,22 @@ impl Wildmenu {
                 outline: none;
             }}
 
-            GtkListBoxRow:selected, GtkListBoxRow:selected > GtkLabel {{
+            GtkListBoxRow:selected, GtkListBoxRow:selected > GtkLabel,
+            GtkFlowBoxChild:selected, GtkFlowBoxChild:selected > GtkLabel {{
                 color: #{sel_fg};
                 background: #{sel_bg};
+            }}
+
+            GtkFlowBox {{
+                padding: 0px;
+                background-color: #{bg};
+            }}
+
+            GtkFlowBoxChild {{
+                padding: 6px;
+                color: #{fg};
+                background-color: #{bg};
+                outline: none;
             }}",
             fg = fg.to_hex(),
             bg = bg.to_hex(),
@@ -214,9 +290,22 @@ impl Wildmenu {
                 outline: none;
             }}
 
-            row:selected, row:selected > label {{
+            row:selected, row:selected > label,
+            flowboxchild:selected, flowboxchild:selected > label {{
                 color: #{sel_fg};
                 background: #{sel_bg};
+            }}
+
+            flowbox {{
+                padding: 0px;
+                background-color: #{bg};
+            }}
+
+            flowboxchild {{
+                padding: 6px;
+                color: #{fg};
+                background-color: #{bg};
+                outline: none;
             }}",
             fg = fg.to_hex(),
             bg = bg.to_hex(),
@@ -227,3 +316,19 @@ impl Wildmenu {
             .unwrap();
     }
 }
+
+/// Tells nvim to move the wildmenu selection from `prev` to `new`.
+fn select_with_tabs(nvim: &GioNeovim, prev: i32, new: i32) {
+    let op = if new > prev { "<Tab>" } else { "<S-Tab>" };
+
+    for _ in 0..(new - prev).abs() {
+        // nvim doesn't like single input with many tabs in it, so we'll
+        // have to send each individually.
+        let nvim = nvim.clone();
+        spawn_local(async move {
+            if let Err(err) = nvim.input(&op).await {
+                report_error("Failed to select wildmenu item", err);
+            }
+        })
+    }
+}
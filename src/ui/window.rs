@@ -1,24 +1,136 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
 use gtk::prelude::*;
 
+use log::{error, warn};
 use nvim_rs::Window as NvimWindow;
+use rmpv::Value;
 
-use crate::nvim_gio::GioWriter;
+use crate::nvim_bridge::{ScrollbarPosition, WindowViewport};
+use crate::nvim_gio::{GioNeovim, GioWriter};
+use crate::ui::common::{ease_out_cubic, report_error, spawn_local};
+use crate::ui::file_drop;
 use crate::ui::grid::Grid;
 
+/// Delay (in ms) used to coalesce horizontal scrollbar value changes before
+/// sending the scroll to nvim.
+const SCROLL_DEBOUNCE_MS: u32 = 16;
+/// Minimum interval (in ms) between the scrolls sent to nvim while the
+/// vertical scrollbar is being dragged.
+const SCROLL_THROTTLE_MS: u32 = 16;
+/// Delay (in ms) used to coalesce the scrollbar info queries of a window.
+const SCROLLBAR_INFO_DEBOUNCE_MS: u32 = 50;
+
+/// Duration (in ms) of the message window's slide animation.
+const MSG_SLIDE_MS: u32 = 120;
+/// Interval (in ms) between the slide animation's steps.
+const MSG_SLIDE_STEP_MS: u32 = 16;
+
+/// Handles the key presses of the main window and the external windows.
+pub type KeyHandler = Rc<dyn Fn(&gdk::EventKey) -> Inhibit>;
+
+/// Keyboard input of the UI, shared by its toplevel windows.
+#[derive(Clone)]
+pub struct KeyInput {
+    pub im_context: gtk::IMMulticontext,
+    pub on_key_press: KeyHandler,
+    /// Called when one of the toplevels gets or loses the focus.
+    pub on_focus_changed: Rc<dyn Fn()>,
+}
+
+/// Duration (in ms) of a floating window's move animation.
+const FLOAT_MOVE_MS: u32 = 100;
+/// Interval (in ms) between the move animation's steps.
+const FLOAT_MOVE_STEP_MS: u32 = 16;
+
+/// Delay (in ms) used to coalesce an external window's size changes before
+/// resizing its grid.
+const RESIZE_DEBOUNCE_MS: u32 = 50;
+
+/// Time (in ms) after the last scroll before an auto hiding scrollbar starts
+/// to fade out.
+const SCROLLBAR_FADE_DELAY_MS: u32 = 1000;
+/// Duration (in ms) of the scrollbar's fade out.
+const SCROLLBAR_FADE_MS: u32 = 200;
+/// Interval (in ms) between the fade out's steps.
+const SCROLLBAR_FADE_STEP_MS: u32 = 16;
+
 pub struct MsgWindow {
     fixed: gtk::Fixed,
     frame: gtk::Frame,
+    /// Scrolls the message grid when it doesn't fit in the frame.
+    scrolled_window: gtk::ScrolledWindow,
+    viewport: gtk::Viewport,
+    /// Currently shown grid.
+    grid: Rc<RefCell<Option<Grid>>>,
+    /// Current y position of the frame, changed by the slide animation.
+    y: Rc<Cell<Option<f64>>>,
+    /// Source id of the ongoing slide animation.
+    slide_source_id: Rc<RefCell<Option<glib::SourceId>>>,
 }
 
 impl MsgWindow {
     pub fn new(fixed: gtk::Fixed, css_provider: gtk::CssProvider) -> Self {
         let frame = gtk::Frame::new(None);
 
+        let viewport = gtk::Viewport::new(
+            None::<&gtk::Adjustment>,
+            None::<&gtk::Adjustment>,
+        );
+        viewport.set_shadow_type(gtk::ShadowType::None);
+
+        let scrolled_window = gtk::ScrolledWindow::new(
+            None::<&gtk::Adjustment>,
+            None::<&gtk::Adjustment>,
+        );
+        scrolled_window
+            .set_policy(gtk::PolicyType::Never, gtk::PolicyType::Automatic);
+        scrolled_window.add(&viewport);
+
+        let overlay = gtk::Overlay::new();
+        overlay.add(&scrolled_window);
+        frame.add(&overlay);
+
+        let grid: Rc<RefCell<Option<Grid>>> = Rc::new(RefCell::new(None));
+
+        // Button for copying the message to the clipboard.
+        let copy = gtk::Button::from_icon_name(
+            Some("edit-copy-symbolic"),
+            gtk::IconSize::Menu,
+        );
+        copy.set_tooltip_text(Some("Copy message"));
+        copy.set_halign(gtk::Align::End);
+        copy.set_valign(gtk::Align::Start);
+        copy.set_can_focus(false);
+        copy.connect_clicked(clone!(grid => move |_| {
+            if let Some(ref grid) = *grid.borrow() {
+                let text = msg_text(grid);
+                gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(&text);
+            }
+        }));
+        overlay.add_overlay(&copy);
+
         fixed.put(&frame, 0, 0);
 
-        add_css_provider!(&css_provider, frame);
+        add_css_provider!(
+            &css_provider,
+            frame,
+            scrolled_window,
+            viewport,
+            copy
+        );
 
-        Self { fixed, frame }
+        Self {
+            fixed,
+            frame,
+            scrolled_window,
+            viewport,
+            grid,
+            y: Rc::new(Cell::new(None)),
+            slide_source_id: Rc::new(RefCell::new(None)),
+        }
     }
 
     /// Set the position of the message window.
@@ -30,20 +142,20 @@ impl MsgWindow {
     ///         on the `grid` and `row`, we can't calculate the height automatically.
     ///         The height is mainly needed so we don't show any artifacts that
     ///         will likely be visible on the `grid`'s drawingarea from earlier renders.
+    ///         If the `grid` is taller than this, its contents can be scrolled.
     pub fn set_pos(&self, grid: &Grid, row: f64, h: f64, scrolled: bool) {
         let w = grid.widget();
 
         // Only add/change the child widget if its different
         // from the previous one.
-        if let Some(child) = self.frame.get_child() {
+        if let Some(child) = self.viewport.get_child() {
             if w != child {
-                self.frame.remove(&child);
-                w.unparent(); // Unparent the grid.
-                self.frame.add(&w);
+                self.viewport.remove(&child);
             }
-        } else {
-            self.frame.add(&w);
         }
+        reparent(&w, &self.viewport);
+
+        self.grid.replace(Some(grid.clone()));
 
         let c = self.frame.get_style_context();
         if scrolled {
@@ -54,26 +166,288 @@ impl MsgWindow {
 
         let metrics = grid.get_grid_metrics();
         let w = metrics.cols * metrics.cell_width;
+
+        // Request the grid's full size so the overflow ends up in the
+        // scrolled window instead of being clipped.
+        grid.widget()
+            .set_size_request(w.ceil() as i32, metrics.height.ceil() as i32);
+
         self.frame
             .set_size_request(w.ceil() as i32, h.ceil() as i32);
+        self.scrolled_window.set_size_request(-1, h.ceil() as i32);
+
+        // When shown for the first time, slide in from below.
+        let to = metrics.cell_height as f64 * row;
+        let from = self.y.get().unwrap_or(to + h);
+        self.slide(from, to);
 
-        self.fixed.move_(
-            &self.frame,
-            0,
-            (metrics.cell_height as f64 * row) as i32,
-        );
         self.fixed.show_all();
     }
+
+    /// Slides the frame from `from` to `to`. If there is an earlier slide
+    /// going on, it is replaced.
+    fn slide(&self, from: f64, to: f64) {
+        if let Some(id) = self.slide_source_id.borrow_mut().take() {
+            glib::source::source_remove(id);
+        }
+
+        self.y.set(Some(from));
+        self.fixed.move_(&self.frame, 0, from as i32);
+
+        if (from - to).abs() < 1.0 {
+            self.y.set(Some(to));
+            self.fixed.move_(&self.frame, 0, to as i32);
+            return;
+        }
+
+        let start = glib::get_monotonic_time();
+        let fixed = self.fixed.clone();
+        let frame = self.frame.clone();
+        let y = self.y.clone();
+        let slide_source_id = self.slide_source_id.clone();
+        let id = gtk::timeout_add(MSG_SLIDE_STEP_MS, move || {
+            // Monotonic time is in microseconds.
+            let elapsed = (glib::get_monotonic_time() - start) as f64 / 1000.0;
+            let (pos, done) = slide_position(from, to, elapsed);
+
+            y.set(Some(pos));
+            fixed.move_(&frame, 0, pos as i32);
+
+            if done {
+                // The source is removed once we return, so forget about it.
+                slide_source_id.borrow_mut().take();
+                return Continue(false);
+            }
+
+            Continue(true)
+        });
+
+        self.slide_source_id.replace(Some(id));
+    }
+}
+
+/// Returns the position of the message window's slide animation from `from`
+/// to `to`, `elapsed` ms after it started, and if the animation is done.
+fn slide_position(from: f64, to: f64, elapsed: f64) -> (f64, bool) {
+    ease_position(from, to, elapsed, MSG_SLIDE_MS)
+}
+
+/// Returns the position of an animation from `from` to `to` that lasts
+/// `duration` ms, `elapsed` ms after it started, and if the animation is
+/// done.
+fn ease_position(
+    from: f64,
+    to: f64,
+    elapsed: f64,
+    duration: u32,
+) -> (f64, bool) {
+    let t = elapsed / f64::from(duration);
+    if t >= 1.0 {
+        return (to, true);
+    }
+
+    (from + ease_out_cubic(t.max(0.0)) * (to - from), false)
+}
+
+/// Moves `widget` to `container`, removing it from its current parent
+/// (e.g. another window's overlay) first.
+fn reparent<C: IsA<gtk::Container>>(widget: &gtk::Widget, container: &C) {
+    let container = container.upcast_ref::<gtk::Container>();
+    if let Some(parent) = widget.get_parent() {
+        if &parent == container.upcast_ref::<gtk::Widget>() {
+            return;
+        }
+
+        match parent.downcast::<gtk::Container>() {
+            Ok(parent) => parent.remove(widget),
+            Err(_) => widget.unparent(),
+        }
+    }
+
+    container.add(widget);
+}
+
+/// Returns the text of the message window's `grid`.
+fn msg_text(grid: &Grid) -> String {
+    let metrics = grid.get_grid_metrics();
+    let rows = metrics.rows as usize;
+    let cols = metrics.cols as usize;
+
+    grid.get_text_range((0, 0), (rows.saturating_sub(1), cols))
+}
+
+/// Shows and hides a scrollbar. When auto hiding is enabled, the scrollbar
+/// fades out after a while of no scrolling.
+#[derive(Clone)]
+struct ScrollbarFade {
+    scrollbar: gtk::Scrollbar,
+    autohide: Rc<Cell<bool>>,
+    /// Source id of the pending fade delay or fade animation.
+    source_id: Rc<RefCell<Option<glib::SourceId>>>,
+}
+
+impl ScrollbarFade {
+    fn new(scrollbar: gtk::Scrollbar) -> Self {
+        Self {
+            scrollbar,
+            autohide: Rc::new(Cell::new(false)),
+            source_id: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    fn set_autohide(&self, autohide: bool) {
+        self.autohide.set(autohide);
+
+        // Check the scrollbar's own visibility, since `is_visible` is also
+        // false while e.g. the window is hidden, and the scrollbar would
+        // then never fade once the window is shown again.
+        if !self.scrollbar.get_visible() {
+            return;
+        }
+
+        self.cancel();
+        if autohide {
+            self.schedule_fade();
+        }
+    }
+
+    /// Shows the scrollbar (again). Any ongoing fade is reset.
+    fn show(&self) {
+        self.cancel();
+        self.scrollbar.show();
+
+        if self.autohide.get() {
+            self.schedule_fade();
+        }
+    }
+
+    fn hide(&self) {
+        self.cancel();
+        self.scrollbar.hide();
+    }
+
+    /// Cancels the pending fade, if any, and restores the scrollbar's
+    /// opacity.
+    fn cancel(&self) {
+        if let Some(id) = self.source_id.borrow_mut().take() {
+            glib::source::source_remove(id);
+        }
+
+        self.scrollbar.set_opacity(1.0);
+    }
+
+    fn schedule_fade(&self) {
+        let fade = self.clone();
+        let id = gtk::timeout_add(SCROLLBAR_FADE_DELAY_MS, move || {
+            fade.start_fade();
+            Continue(false)
+        });
+
+        self.source_id.replace(Some(id));
+    }
+
+    fn start_fade(&self) {
+        let steps = SCROLLBAR_FADE_MS / SCROLLBAR_FADE_STEP_MS;
+        let step = Cell::new(0);
+        let fade = self.clone();
+        let id = gtk::timeout_add(SCROLLBAR_FADE_STEP_MS, move || {
+            step.set(step.get() + 1);
+
+            let opacity = fade_opacity(step.get(), steps);
+            if opacity > 0.0 {
+                fade.scrollbar.set_opacity(opacity);
+                return Continue(true);
+            }
+
+            // The source is removed once we return, so forget about it.
+            fade.source_id.borrow_mut().take();
+            fade.scrollbar.hide();
+            fade.scrollbar.set_opacity(1.0);
+
+            Continue(false)
+        });
+
+        // The previous source (the fade delay) is done at this point, so
+        // we'll just replace it.
+        self.source_id.replace(Some(id));
+    }
+}
+
+/// Returns the horizontal alignment of the vertical scrollbar at `position`,
+/// or None if there's no scrollbar.
+fn scrollbar_align(position: ScrollbarPosition) -> Option<gtk::Align> {
+    match position {
+        ScrollbarPosition::Left => Some(gtk::Align::Start),
+        ScrollbarPosition::Right => Some(gtk::Align::End),
+        ScrollbarPosition::None => None,
+    }
+}
+
+/// Horizontal scrollbar of a window.
+struct HScrollbar {
+    fade: ScrollbarFade,
+    adjustment: gtk::Adjustment,
+    /// Set while we're updating `adjustment` ourselves.
+    updating: Rc<Cell<bool>>,
+    /// Current 'leftcol' of the window.
+    leftcol: Rc<Cell<u64>>,
+}
+
+/// Viewport of a window, as last reported by nvim.
+#[derive(Default, Clone, Copy)]
+struct Viewport {
+    topline: u64,
+    botline: u64,
+    curline: u64,
+    curcol: u64,
+    line_count: u64,
 }
 
 pub struct Window {
     fixed: gtk::Fixed,
     frame: gtk::Frame,
+    overlay: gtk::Overlay,
+
+    scrollbar: ScrollbarFade,
+    adjustment: gtk::Adjustment,
+    adjustment_signal: glib::SignalHandlerId,
+    viewport: Rc<RefCell<Viewport>>,
+    /// Value of nvim's 'scrolloff' option.
+    scrolloff: Rc<Cell<u64>>,
+    /// If clicking the scrollbar's trough should jump to the clicked
+    /// position instead of paging.
+    click_to_jump: Rc<Cell<bool>>,
+    /// If the window has scrollbars, as set with `w:gnvim_scrollbar`.
+    scrollbars_enabled: Rc<Cell<bool>>,
+    /// Source id of the pending scrollbar info query.
+    info_source_id: Rc<RefCell<Option<glib::SourceId>>>,
+
+    hscrollbar: HScrollbar,
+
+    nvim: GioNeovim,
 
     external_win: Option<gtk::Window>,
+    /// If `external_win` can be resized by the user.
+    external_resizable: bool,
+    /// Source id of the pending grid resize of `external_win`.
+    resize_source_id: Rc<RefCell<Option<glib::SourceId>>>,
+    /// Externalization waiting for the grid to get a size.
+    pending_external: Option<PendingExternal>,
+    /// Nvim's title (see 'titlestring'), shown on `external_win` instead of
+    /// the buffer's name while the window is the current one.
+    nvim_title: Rc<RefCell<Option<String>>>,
+
+    /// Current on screen position of the frame, changed by the move
+    /// animation. `None` when the frame hasn't been positioned in its
+    /// current parent yet.
+    pos: Rc<Cell<Option<(f64, f64)>>>,
+    /// Source id of the ongoing move animation.
+    move_source_id: Rc<RefCell<Option<glib::SourceId>>>,
 
     pub x: f64,
     pub y: f64,
+    /// If the window is a floating window.
+    pub float: bool,
 
     /// Currently shown grid's id.
     pub grid_id: i64,
@@ -86,72 +460,461 @@ impl Window {
         fixed: gtk::Fixed,
         grid: &Grid,
         css_provider: Option<gtk::CssProvider>,
+        nvim: GioNeovim,
+        scrollbar_position: ScrollbarPosition,
     ) -> Self {
         let frame = gtk::Frame::new(None);
         fixed.put(&frame, 0, 0);
 
+        grid.set_nvim_win(win.get_value().clone());
+
+        let overlay = gtk::Overlay::new();
+        frame.add(&overlay);
+
+        // Files dropped onto the window are opened in it.
+        let drop_win = win.get_value().clone();
+        file_drop::connect_file_drop(
+            &frame,
+            clone!(nvim, drop_win => move |cmd| {
+                let nvim = nvim.clone();
+                let win = NvimWindow::new(drop_win.clone(), nvim.clone());
+                spawn_local(async move {
+                    if let Err(err) = nvim.set_current_win(&win).await {
+                        report_error("Failed to set current window", err);
+                        return;
+                    }
+
+                    if let Err(err) = nvim.command(&cmd).await {
+                        report_error("Failed to open dropped files", err);
+                    }
+                });
+            }),
+        );
+
         let widget = grid.widget();
-        frame.add(&widget);
+        reparent(&widget, &overlay);
+
+        let adjustment = gtk::Adjustment::new(0.0, 0.0, 0.0, 1.0, 0.0, 0.0);
+        let scrollbar =
+            gtk::Scrollbar::new(gtk::Orientation::Vertical, Some(&adjustment));
+        // The scrollbar's visibility is controlled by the viewport, so
+        // don't let `show_all` show it.
+        scrollbar.set_no_show_all(true);
+        scrollbar.get_style_context().add_class("gnvim-scrollbar");
+        // Without a scrollbar, don't add it to the overlay at all, so the
+        // overlay doesn't need to allocate it.
+        if let Some(align) = scrollbar_align(scrollbar_position) {
+            scrollbar.set_halign(align);
+            overlay.add_overlay(&scrollbar);
+        }
 
-        if let Some(css_provider) = css_provider {
-            add_css_provider!(&css_provider, frame);
+        if let Some(ref css_provider) = css_provider {
+            add_css_provider!(css_provider, frame, scrollbar);
         }
 
+        let fade = ScrollbarFade::new(scrollbar.clone());
+
+        let click_to_jump = Rc::new(Cell::new(false));
+        scrollbar.connect_button_press_event(
+            clone!(adjustment, click_to_jump => move |scrollbar, e| {
+                if !click_to_jump.get() || e.get_button() != 1 {
+                    return Inhibit(false);
+                }
+
+                let y = e.get_position().1;
+                // Let gtk handle clicks on the slider itself (e.g. dragging).
+                let (start, end) = scrollbar.get_slider_range();
+                if y >= f64::from(start) && y <= f64::from(end) {
+                    return Inhibit(false);
+                }
+
+                adjustment.set_value(trough_click_value(
+                    y,
+                    f64::from(scrollbar.get_allocated_height()),
+                    adjustment.get_upper(),
+                    adjustment.get_page_size(),
+                ));
+
+                Inhibit(true)
+            }),
+        );
+
+        let viewport = Rc::new(RefCell::new(Viewport::default()));
+        let scrolloff = Rc::new(Cell::new(0));
+        let win_value = win.get_value().clone();
+        // Dragging the scrollbar emits value changes rapidly, so they're
+        // throttled to keep nvim from lagging behind.
+        let throttle = Throttle::default();
+        let adjustment_signal = adjustment.connect_value_changed(
+            clone!(viewport, scrolloff, throttle, nvim, win_value, fade => move |adj| {
+                fade.show();
+
+                let target = adj.get_value().round().max(0.0) as u64;
+
+                let f = clone!(viewport, scrolloff, nvim, win_value => move || {
+                    // The viewport might not be up to date yet, if nvim
+                    // hasn't caught up with our previous scroll. Only the
+                    // cursor's position relative to the viewport is taken
+                    // from it, the scroll target is absolute.
+                    let vp = *viewport.borrow();
+                    let delta = target as i64 - vp.topline as i64;
+                    if delta == 0 {
+                        return;
+                    }
+
+                    // Move the cursor along with the viewport, like
+                    // keyboard scrolling does, instead of leaving it
+                    // pinned to the window's edge.
+                    let line =
+                        cursor_line_after_scroll(&vp, delta, scrolloff.get());
+                    win_execute(
+                        nvim.clone(),
+                        win_value.clone(),
+                        vec![scroll_to_command(target, line).into()],
+                    );
+                });
+                throttle.call(SCROLL_THROTTLE_MS, f);
+            }),
+        );
+
+        // Horizontal scrollbar, only shown for 'nowrap' windows.
+        let hadjustment = gtk::Adjustment::new(0.0, 0.0, 0.0, 1.0, 0.0, 0.0);
+        let hscrollbar = gtk::Scrollbar::new(
+            gtk::Orientation::Horizontal,
+            Some(&hadjustment),
+        );
+        hscrollbar.set_valign(gtk::Align::End);
+        hscrollbar.set_no_show_all(true);
+        hscrollbar.get_style_context().add_class("gnvim-scrollbar");
+        overlay.add_overlay(&hscrollbar);
+
+        if let Some(ref css_provider) = css_provider {
+            add_css_provider!(css_provider, hscrollbar);
+        }
+
+        let hfade = ScrollbarFade::new(hscrollbar);
+
+        let leftcol = Rc::new(Cell::new(0));
+        let hupdating = Rc::new(Cell::new(false));
+        let hsource_id: Rc<RefCell<Option<glib::SourceId>>> =
+            Rc::new(RefCell::new(None));
+        hadjustment.connect_value_changed(
+            clone!(leftcol, hupdating, hsource_id, nvim, win_value, hfade => move |adj| {
+                if hupdating.get() {
+                    return;
+                }
+
+                hfade.show();
+
+                let target = adj.get_value().round() as i64;
+
+                let f = clone!(leftcol, nvim, win_value => move || {
+                    let delta = target - leftcol.get() as i64;
+                    if let Some(cmd) = hscroll_command(delta) {
+                        win_execute(
+                            nvim.clone(),
+                            win_value.clone(),
+                            vec![cmd.into()],
+                        );
+                    }
+                });
+                debounce(&hsource_id, SCROLL_DEBOUNCE_MS, f);
+            }),
+        );
+
         Self {
             fixed,
             frame,
+            overlay,
+            scrollbar: fade,
+            adjustment,
+            adjustment_signal,
+            viewport,
+            scrolloff,
+            click_to_jump,
+            scrollbars_enabled: Rc::new(Cell::new(true)),
+            info_source_id: Rc::new(RefCell::new(None)),
+            hscrollbar: HScrollbar {
+                fade: hfade,
+                adjustment: hadjustment,
+                updating: hupdating,
+                leftcol,
+            },
+            nvim,
             external_win: None,
+            external_resizable: false,
+            resize_source_id: Rc::new(RefCell::new(None)),
+            pending_external: None,
+            nvim_title: Rc::new(RefCell::new(None)),
+            pos: Rc::new(Cell::new(None)),
+            move_source_id: Rc::new(RefCell::new(None)),
             grid_id: grid.id,
             nvim_win: win,
             x: 0.0,
             y: 0.0,
+            float: false,
         }
     }
 
     pub fn set_parent(&mut self, fixed: gtk::Fixed) {
         if self.fixed != fixed {
+            self.stop_move();
+            self.pos.set(None);
+
             self.fixed.remove(&self.frame);
             self.fixed = fixed;
             self.fixed.put(&self.frame, 0, 0);
         }
     }
 
-    pub fn resize(&self, size: (i32, i32)) {
-        self.frame.set_size_request(size.0, size.1);
+    pub fn resize(&mut self, size: (i32, i32)) {
+        if let Some(pending) = self.pending_external.take() {
+            self.set_external(
+                &pending.parent,
+                &pending.grid,
+                &pending.geometries,
+                &pending.keys,
+                pending.opts,
+            );
+            return;
+        }
+
+        match self.external_win {
+            // The frame can't have the grid's size as its minimum size,
+            // otherwise the user couldn't make the window smaller.
+            Some(ref win) if self.external_resizable => {
+                win.resize(size.0, size.1)
+            }
+            _ => self.frame.set_size_request(size.0, size.1),
+        }
     }
 
-    pub fn set_external(&mut self, parent: &gtk::Window, size: (i32, i32)) {
+    /// Moves the window to its own toplevel window. If the grid has been
+    /// external before, the toplevel is placed where it was left.
+    ///
+    /// See `ExternalOptions` for how the toplevel behaves. If the grid
+    /// doesn't have a size yet, the window is externalized once it gets one.
+    pub fn set_external(
+        &mut self,
+        parent: &gtk::Window,
+        grid: &Grid,
+        geometries: &ExternalGeometries,
+        keys: &KeyInput,
+        opts: ExternalOptions,
+    ) {
         if self.external_win.is_some() {
             return;
         }
 
-        self.frame.set_size_request(size.0, size.1);
+        let metrics = grid.get_grid_metrics();
+        let size = match external_size(metrics.width, metrics.height) {
+            Some(size) => size,
+            None => {
+                warn!(
+                    "Grid {} doesn't have a size yet, deferring its external window",
+                    self.grid_id
+                );
+                self.pending_external = Some(PendingExternal {
+                    parent: parent.clone(),
+                    grid: grid.clone(),
+                    geometries: geometries.clone(),
+                    keys: keys.clone(),
+                    opts,
+                });
+                return;
+            }
+        };
+
+        self.stop_move();
+        self.pos.set(None);
 
         let win = gtk::Window::new(gtk::WindowType::Toplevel);
         self.fixed.remove(&self.frame);
         win.add(&self.frame);
 
-        win.set_accept_focus(false);
+        win.set_accept_focus(opts.accept_focus);
         win.set_deletable(false);
-        win.set_resizable(false);
+        win.set_resizable(opts.resizable);
+
+        self.external_resizable = opts.resizable;
+        if opts.resizable {
+            // The grid needs at least one cell.
+            let (w, h) =
+                min_external_size((metrics.cell_width, metrics.cell_height));
+            self.frame.set_size_request(w, h);
+            win.set_default_size(size.0, size.1);
+        } else {
+            self.frame.set_size_request(size.0, size.1);
+        }
 
         win.set_transient_for(Some(parent));
         win.set_attached_to(Some(parent));
 
+        let grid_id = self.grid_id;
+        if let Some((x, y, w, h)) = geometries.get(grid_id) {
+            // Unless the window is resizable, it can't get smaller than the
+            // grid, so the size only matters when the grid is smaller than
+            // it was.
+            win.set_default_size(w, h);
+            win.move_(x, y);
+        } else if let Some(area) = monitor_geometry(parent) {
+            // Otherwise, the window manager might place the window on a
+            // different monitor than the one we're on.
+            let (x, y) =
+                center_in((area.x, area.y, area.width, area.height), size);
+            win.move_(x, y);
+        }
+
+        if opts.accept_focus {
+            let nvim = self.nvim.clone();
+            let nvim_win = self.nvim_win.get_value().clone();
+            win.connect_focus_in_event(clone!(grid => move |_, _| {
+                grid.focus_im_context();
+
+                let nvim = nvim.clone();
+                let win = NvimWindow::new(nvim_win.clone(), nvim.clone());
+                spawn_local(async move {
+                    if let Err(err) = nvim.set_current_win(&win).await {
+                        report_error("Failed to set current window", err);
+                    }
+                });
+
+                Inhibit(false)
+            }));
+
+            let on_focus_changed = keys.on_focus_changed.clone();
+            win.connect_property_is_active_notify(move |_| on_focus_changed());
+
+            let im_context = keys.im_context.clone();
+            win.connect_focus_out_event(clone!(im_context => move |_, _| {
+                im_context.focus_out();
+                Inhibit(false)
+            }));
+
+            // Key presses are sent to nvim like the main window's, through
+            // the input method which is now on our grid.
+            let on_key_press = keys.on_key_press.clone();
+            win.connect_key_press_event(move |_, e| on_key_press(e));
+            win.connect_key_release_event(move |_, e| {
+                im_context.filter_keypress(e);
+                Inhibit(false)
+            });
+        }
+
+        let nvim = self.nvim.clone();
+        let grid = grid.clone();
+        let resize_source_id = self.resize_source_id.clone();
+        win.connect_configure_event(clone!(geometries => move |win, _| {
+            let (x, y) = win.get_position();
+            let (w, h) = win.get_size();
+            geometries.set(grid_id, (x, y, w, h));
+
+            if opts.resizable {
+                let f = clone!(nvim, grid => move || {
+                    let metrics = grid.get_grid_metrics();
+                    let (cols, rows) = grid_size_for(
+                        (f64::from(w), f64::from(h)),
+                        (metrics.cell_width, metrics.cell_height),
+                    );
+
+                    let current = (metrics.cols as i64, metrics.rows as i64);
+                    if (cols, rows) == current {
+                        return;
+                    }
+
+                    let nvim = nvim.clone();
+                    spawn_local(async move {
+                        if let Err(err) =
+                            nvim.ui_try_resize_grid(grid_id, cols, rows).await
+                        {
+                            report_error(
+                                &format!("Failed to resize grid({})", grid_id),
+                                err,
+                            );
+                        }
+                    });
+                });
+                debounce(&resize_source_id, RESIZE_DEBOUNCE_MS, f);
+            }
+
+            Inhibit(false)
+        }));
+
         win.show_all();
 
         self.external_win = Some(win);
+        self.update_title();
+    }
+
+    /// If the window is external, and its toplevel has the focus.
+    pub fn is_external_active(&self) -> bool {
+        self.external_win
+            .as_ref()
+            .map(|win| win.is_active())
+            .unwrap_or(false)
+    }
+
+    /// Sets nvim's title, to be shown instead of the buffer's name. `None`
+    /// goes back to the buffer's name.
+    pub fn set_nvim_title(&self, title: Option<String>) {
+        self.nvim_title.replace(title);
+        self.update_title();
+    }
+
+    /// Sets the external window's title to nvim's title (see
+    /// `set_nvim_title`), or to the name of the nvim window's buffer.
+    pub fn update_title(&self) {
+        let win = match self.external_win {
+            Some(ref win) => win.clone(),
+            None => return,
+        };
+
+        if let Some(ref title) = *self.nvim_title.borrow() {
+            win.set_title(title);
+            return;
+        }
+
+        let nvim_win = NvimWindow::new(
+            self.nvim_win.get_value().clone(),
+            self.nvim.clone(),
+        );
+        let nvim_title = self.nvim_title.clone();
+        spawn_local(async move {
+            let name = match nvim_win.get_buf().await {
+                Ok(buf) => buf.get_name().await,
+                Err(err) => Err(err),
+            };
+
+            // Nvim's title was set while we were waiting.
+            if nvim_title.borrow().is_some() {
+                return;
+            }
+
+            match name {
+                Ok(name) => win.set_title(&external_title(&name)),
+                Err(err) => report_error("Failed to get buffer name", err),
+            }
+        });
     }
 
     pub fn set_position(&mut self, x: f64, y: f64, w: f64, h: f64) {
+        self.pending_external = None;
+
         if let Some(win) = self.external_win.take() {
+            if let Some(id) = self.resize_source_id.borrow_mut().take() {
+                glib::source::source_remove(id);
+            }
+
             win.remove(&self.frame);
             self.fixed.add(&self.frame);
             win.close();
         }
 
+        self.stop_move();
+
         self.x = x;
         self.y = y;
+        self.pos.set(Some((x, y)));
         self.fixed
             .move_(&self.frame, x.floor() as i32, y.floor() as i32);
 
@@ -159,6 +922,268 @@ impl Window {
             .set_size_request(w.ceil() as i32, h.ceil() as i32);
     }
 
+    /// Like `set_position`, but animates the move from the window's current
+    /// position. If there is an earlier move going on, it is retargeted.
+    /// Windows that aren't visible yet are positioned immediately.
+    pub fn animate_position(&mut self, x: f64, y: f64, w: f64, h: f64) {
+        let from = match self.pos.get() {
+            Some(from)
+                if self.external_win.is_none() && self.frame.is_visible() =>
+            {
+                from
+            }
+            _ => {
+                self.set_position(x, y, w, h);
+                return;
+            }
+        };
+
+        self.stop_move();
+
+        self.x = x;
+        self.y = y;
+        self.frame
+            .set_size_request(w.ceil() as i32, h.ceil() as i32);
+
+        if (from.0 - x).abs() < 1.0 && (from.1 - y).abs() < 1.0 {
+            self.pos.set(Some((x, y)));
+            self.fixed
+                .move_(&self.frame, x.floor() as i32, y.floor() as i32);
+            return;
+        }
+
+        let start = glib::get_monotonic_time();
+        let fixed = self.fixed.clone();
+        let frame = self.frame.clone();
+        let pos = self.pos.clone();
+        let move_source_id = self.move_source_id.clone();
+        let id = gtk::timeout_add(FLOAT_MOVE_STEP_MS, move || {
+            // Monotonic time is in microseconds.
+            let elapsed = (glib::get_monotonic_time() - start) as f64 / 1000.0;
+            let (px, done) = ease_position(from.0, x, elapsed, FLOAT_MOVE_MS);
+            let (py, _) = ease_position(from.1, y, elapsed, FLOAT_MOVE_MS);
+
+            pos.set(Some((px, py)));
+            fixed.move_(&frame, px.floor() as i32, py.floor() as i32);
+
+            if done {
+                // The source is removed once we return, so forget about it.
+                move_source_id.borrow_mut().take();
+                return Continue(false);
+            }
+
+            Continue(true)
+        });
+
+        self.move_source_id.replace(Some(id));
+    }
+
+    /// Stops the ongoing move animation, leaving the frame where it is.
+    fn stop_move(&self) {
+        if let Some(id) = self.move_source_id.borrow_mut().take() {
+            glib::source::source_remove(id);
+        }
+    }
+
+    /// Moves the window to `(x, y)` and resizes it to `(w, h)`. External
+    /// windows are only resized.
+    pub fn update_position(&mut self, x: f64, y: f64, w: f64, h: f64) {
+        if self.external_win.is_some() || self.pending_external.is_some() {
+            self.resize((w.ceil() as i32, h.ceil() as i32));
+        } else {
+            self.set_position(x, y, w, h);
+        }
+    }
+
+    /// Updates the scrollbar to match the viewport reported by nvim.
+    pub fn set_adjustment(&self, evt: &WindowViewport) {
+        // Older nvim versions don't send the line count, fall back to
+        // the bottom line so we can at least show something sensible.
+        let line_count = evt.line_count.unwrap_or(evt.botline);
+
+        let prev = self.viewport.replace(Viewport {
+            topline: evt.topline,
+            botline: evt.botline,
+            curline: evt.curline,
+            curcol: evt.curcol,
+            line_count,
+        });
+
+        let (value, upper, page_size) =
+            adjustment_values(evt.topline, evt.botline, line_count);
+
+        // Don't let our own updates look like user scrolling.
+        glib::signal_handler_block(&self.adjustment, &self.adjustment_signal);
+        self.adjustment
+            .configure(value, 0.0, upper, 1.0, page_size, page_size);
+        glib::signal_handler_unblock(&self.adjustment, &self.adjustment_signal);
+
+        if page_size >= upper || !self.scrollbars_enabled.get() {
+            self.hide_scrollbar();
+        } else if prev.topline != evt.topline || !self.scrollbar.autohide.get()
+        {
+            // When auto hiding, only show the scrollbar when the window
+            // actually scrolls (and not when e.g. the cursor moves).
+            self.show_scrollbar();
+        }
+
+        if needs_scrollbar_info(&prev, &self.viewport.borrow()) {
+            self.update_scrollbar_info();
+        }
+    }
+
+    /// Queries the horizontal scroll state of the window, and if the window
+    /// should have scrollbars, from nvim and updates the scrollbars
+    /// accordingly. Queries made in quick succession (e.g. while moving
+    /// the cursor around) are coalesced.
+    fn update_scrollbar_info(&self) {
+        let handles = (
+            self.nvim.clone(),
+            self.nvim_win.get_value().clone(),
+            self.scrollbar.clone(),
+            self.scrollbars_enabled.clone(),
+            self.hscrollbar.fade.clone(),
+            self.hscrollbar.adjustment.clone(),
+            self.hscrollbar.updating.clone(),
+            self.hscrollbar.leftcol.clone(),
+        );
+
+        let f = move || {
+            let (
+                nvim,
+                win,
+                vfade,
+                enabled,
+                fade,
+                adjustment,
+                updating,
+                leftcol,
+            ) = handles.clone();
+
+            spawn_local(async move {
+                let info = match nvim
+                    .call_function("gnvim#scrollbar#info", vec![win])
+                    .await
+                {
+                    Ok(info) => info,
+                    Err(err) => {
+                        report_error("Failed to get scrollbar info", err);
+                        return;
+                    }
+                };
+
+                let info: Vec<u64> = info
+                    .as_array()
+                    .map(|a| a.iter().filter_map(Value::as_u64).collect())
+                    .unwrap_or_default();
+
+                let (show, wrap, left, width, max_width) = match info.as_slice()
+                {
+                    [show, wrap, left, width, max_width] => {
+                        (*show == 1, *wrap == 1, *left, *width, *max_width)
+                    }
+                    _ => {
+                        error!("Invalid scrollbar info: {:?}", info);
+                        return;
+                    }
+                };
+
+                // The vertical scrollbar was already updated with the previous
+                // value, so it'll follow the new value from the next viewport
+                // change on, unless it needs to be hidden now.
+                enabled.set(show);
+                if !show {
+                    vfade.hide();
+                    fade.hide();
+                    return;
+                }
+
+                let scrolled = leftcol.replace(left) != left;
+
+                // Horizontal scrolling is only meaningful if lines don't wrap
+                // and something doesn't fit on the screen.
+                if wrap || (left == 0 && max_width <= width) {
+                    fade.hide();
+                    return;
+                }
+
+                let upper = max_width.max(left + width) as f64;
+                updating.set(true);
+                adjustment.configure(
+                    left as f64,
+                    0.0,
+                    upper,
+                    1.0,
+                    width as f64,
+                    width as f64,
+                );
+                updating.set(false);
+
+                if scrolled || !fade.autohide.get() {
+                    fade.show();
+                }
+            });
+        };
+        debounce(&self.info_source_id, SCROLLBAR_INFO_DEBOUNCE_MS, f);
+    }
+
+    /// Sets the 'scrolloff' value used when scrolling with the scrollbar.
+    pub fn set_scrolloff(&self, n: u64) {
+        self.scrolloff.set(n);
+    }
+
+    /// Sets if clicking the scrollbar's trough jumps to the clicked position.
+    pub fn set_scrollbar_click_to_jump(&self, enable: bool) {
+        self.click_to_jump.set(enable);
+    }
+
+    /// Sets if the scrollbars should fade out when the window isn't being
+    /// scrolled.
+    pub fn set_scrollbar_autohide(&self, autohide: bool) {
+        self.scrollbar.set_autohide(autohide);
+        self.hscrollbar.fade.set_autohide(autohide);
+    }
+
+    /// Moves the vertical scrollbar to `position`, adding it to or
+    /// removing it from the window as needed.
+    pub fn set_scrollbar_position(&self, position: ScrollbarPosition) {
+        let scrollbar = &self.scrollbar.scrollbar;
+        let added = scrollbar.get_parent().is_some();
+
+        match scrollbar_align(position) {
+            Some(align) => {
+                scrollbar.set_halign(align);
+                if added {
+                    return;
+                }
+
+                self.overlay.add_overlay(scrollbar);
+                // Show the scrollbar right away if the content doesn't fit
+                // in the window, unless it would fade out anyway.
+                let adj = &self.adjustment;
+                if adj.get_page_size() < adj.get_upper()
+                    && self.scrollbars_enabled.get()
+                    && !self.scrollbar.autohide.get()
+                {
+                    self.show_scrollbar();
+                }
+            }
+            None if added => {
+                self.scrollbar.hide();
+                self.overlay.remove(scrollbar);
+            }
+            None => {}
+        }
+    }
+
+    pub fn show_scrollbar(&self) {
+        self.scrollbar.show();
+    }
+
+    pub fn hide_scrollbar(&self) {
+        self.scrollbar.hide();
+    }
+
     pub fn show(&self) {
         self.frame.show_all();
     }
@@ -170,10 +1195,16 @@ impl Window {
 
 impl Drop for Window {
     fn drop(&mut self) {
-        if let Some(child) = self.frame.get_child() {
+        self.stop_move();
+
+        if let Some(id) = self.info_source_id.borrow_mut().take() {
+            glib::source::source_remove(id);
+        }
+
+        if let Some(child) = self.overlay.get_child() {
             // We don't want to destroy the child widget, so just remove the child from our
             // container.
-            self.frame.remove(&child);
+            self.overlay.remove(&child);
         }
 
         self.fixed.remove(&self.frame);
@@ -183,3 +1214,636 @@ impl Drop for Window {
         }
     }
 }
+
+/// Calls `f` after `delay` ms. If there is an earlier call pending on
+/// `source_id`, it is canceled.
+fn debounce<F: Fn() + 'static>(
+    source_id: &Rc<RefCell<Option<glib::SourceId>>>,
+    delay: u32,
+    f: F,
+) {
+    let new = gtk::timeout_add(
+        delay,
+        clone!(source_id => move || {
+            // The timeout is used at this point, so make sure we don't try
+            // to remove it later on.
+            source_id.borrow_mut().take();
+
+            f();
+
+            Continue(false)
+        }),
+    );
+
+    let mut source_id = source_id.borrow_mut();
+    if let Some(old) = source_id.take() {
+        glib::source::source_remove(old);
+    }
+
+    *source_id = Some(new);
+}
+
+/// Rate limiter for calls that should happen right away, but not more often
+/// than once per interval. Calls made during the interval are dropped,
+/// except for the latest one, which is made once the interval is over.
+///
+/// Cloning returns a handle to the same limiter.
+#[derive(Clone, Default)]
+struct Throttle {
+    /// Source id of the ongoing interval.
+    source_id: Rc<RefCell<Option<glib::SourceId>>>,
+    /// Latest call made during the ongoing interval.
+    pending: Rc<RefCell<Option<Box<dyn Fn()>>>>,
+}
+
+impl Throttle {
+    fn call<F: Fn() + 'static>(&self, interval: u32, f: F) {
+        if self.source_id.borrow().is_some() {
+            self.pending.replace(Some(Box::new(f)));
+            return;
+        }
+
+        f();
+
+        let this = self.clone();
+        let id = gtk::timeout_add(interval, move || {
+            let pending = this.pending.borrow_mut().take();
+            if let Some(f) = pending {
+                // Keep the interval going, so calls made right after this
+                // one are throttled too.
+                f();
+                return Continue(true);
+            }
+
+            // The source is removed once we return, so forget about it.
+            this.source_id.borrow_mut().take();
+            Continue(false)
+        });
+
+        self.source_id.replace(Some(id));
+    }
+}
+
+/// Configurable speed for mouse wheel scrolling. By default (with zero
+/// lines), wheel events are sent to nvim, which scrolls by 'mousescroll'.
+///
+/// Cloning returns a handle to the same settings.
+#[derive(Clone)]
+pub struct ScrollSpeed {
+    /// Lines to scroll per wheel step.
+    lines: Rc<Cell<u64>>,
+    /// Multiplier for `lines`, when shift is held.
+    shift_multiplier: Rc<Cell<f64>>,
+}
+
+impl Default for ScrollSpeed {
+    fn default() -> Self {
+        ScrollSpeed {
+            lines: Rc::new(Cell::new(0)),
+            shift_multiplier: Rc::new(Cell::new(1.0)),
+        }
+    }
+}
+
+impl ScrollSpeed {
+    pub fn set_lines(&self, lines: u64) {
+        self.lines.set(lines);
+    }
+
+    pub fn set_shift_multiplier(&self, multiplier: f64) {
+        self.shift_multiplier.set(multiplier);
+    }
+
+    /// Returns the lines to scroll per wheel step, or `None` if the scrolling
+    /// is left to nvim.
+    pub fn lines(&self, shift: bool) -> Option<u64> {
+        scroll_lines(self.lines.get(), self.shift_multiplier.get(), shift)
+    }
+}
+
+/// Nvim's 'mouse' option and current mode, telling if mouse input should be
+/// sent to nvim. Until nvim tells us otherwise, the mouse is enabled in all
+/// modes.
+///
+/// Cloning returns a handle to the same settings.
+#[derive(Clone)]
+pub struct MouseReporting {
+    /// Value of 'mouse'.
+    option: Rc<RefCell<String>>,
+    /// Name of nvim's current mode, from `mode_change`.
+    mode: Rc<RefCell<String>>,
+}
+
+impl Default for MouseReporting {
+    fn default() -> Self {
+        MouseReporting {
+            option: Rc::new(RefCell::new(String::from("a"))),
+            mode: Rc::new(RefCell::new(String::from("normal"))),
+        }
+    }
+}
+
+impl MouseReporting {
+    pub fn set_option(&self, mouse: &str) {
+        self.option.replace(mouse.to_string());
+    }
+
+    pub fn set_mode(&self, mode: &str) {
+        self.mode.replace(mode.to_string());
+    }
+
+    /// Returns true if 'mouse' enables the mouse in the current mode.
+    pub fn is_enabled(&self) -> bool {
+        mouse_enabled(&self.option.borrow(), &self.mode.borrow())
+    }
+}
+
+/// Arguments of a deferred `Window::set_external` call.
+struct PendingExternal {
+    parent: gtk::Window,
+    grid: Grid,
+    geometries: ExternalGeometries,
+    keys: KeyInput,
+    opts: ExternalOptions,
+}
+
+/// Options for external windows.
+#[derive(Clone, Copy, Default)]
+pub struct ExternalOptions {
+    /// If the window can be focused. Focusing it makes it nvim's current
+    /// window, and its key presses are handled like the main window's.
+    pub accept_focus: bool,
+    /// If the window can be resized by the user, which resizes its grid.
+    pub resizable: bool,
+}
+
+/// Last geometries (`(x, y, width, height)`) of external windows, by grid id.
+/// Used to restore the window's position when the grid is externalized
+/// again.
+///
+/// Cloning returns a handle to the same map.
+#[derive(Clone, Default)]
+pub struct ExternalGeometries {
+    inner: Rc<RefCell<HashMap<i64, (i32, i32, i32, i32)>>>,
+}
+
+impl ExternalGeometries {
+    pub fn get(&self, grid_id: i64) -> Option<(i32, i32, i32, i32)> {
+        self.inner.borrow().get(&grid_id).copied()
+    }
+
+    pub fn set(&self, grid_id: i64, geometry: (i32, i32, i32, i32)) {
+        self.inner.borrow_mut().insert(grid_id, geometry);
+    }
+}
+
+/// Scrolls `win` by `delta` lines, like the scrollbar does.
+pub fn scroll_window(nvim: GioNeovim, win: Value, delta: i64) {
+    if let Some(cmd) = scroll_command(delta) {
+        win_execute(nvim, win, vec![cmd.into()]);
+    }
+}
+
+/// Returns the lines to scroll per wheel step, when configured to scroll
+/// `lines`, and `multiplier` times more with shift.
+fn scroll_lines(lines: u64, multiplier: f64, shift: bool) -> Option<u64> {
+    if lines == 0 {
+        return None;
+    }
+
+    if shift {
+        Some(((lines as f64 * multiplier).round() as u64).max(1))
+    } else {
+        Some(lines)
+    }
+}
+
+/// Returns true if the 'mouse' option value `mouse` enables the mouse in
+/// `mode` (a mode name from `mode_change`, e.g. `cmdline_normal`).
+fn mouse_enabled(mouse: &str, mode: &str) -> bool {
+    let flag = match mode {
+        "visual" | "visual_select" => 'v',
+        "insert" | "replace" | "showmatch" => 'i',
+        "more" | "more_lastline" => 'r',
+        _ if mode.starts_with("cmdline") => 'c',
+        _ => 'n',
+    };
+
+    mouse.contains(flag) || (flag != 'r' && mouse.contains('a'))
+}
+
+/// Returns the geometry of the monitor `win` is (mostly) on.
+fn monitor_geometry(win: &gtk::Window) -> Option<gdk::Rectangle> {
+    let gdk_win = win.get_window()?;
+    let screen = gdk_win.get_screen();
+    let monitor = screen.get_monitor_at_window(&gdk_win);
+    Some(screen.get_monitor_geometry(monitor))
+}
+
+/// Returns the position for centering something of `size` in `area`
+/// (`(x, y, width, height)`). Things bigger than `area` are aligned to its
+/// top left corner.
+fn center_in(area: (i32, i32, i32, i32), size: (i32, i32)) -> (i32, i32) {
+    let (x, y, w, h) = area;
+    (x + ((w - size.0) / 2).max(0), y + ((h - size.1) / 2).max(0))
+}
+
+/// Returns the size (in pixels) for an external window showing a grid of
+/// `width` and `height`, or `None` if the grid is too small to be shown.
+fn external_size(width: f64, height: f64) -> Option<(i32, i32)> {
+    if width < 1.0 || height < 1.0 {
+        return None;
+    }
+
+    Some((width.ceil() as i32, height.ceil() as i32))
+}
+
+/// Returns the minimum size (in pixels) of a resizable external window,
+/// which fits one cell of `cell_size`.
+fn min_external_size(cell_size: (f64, f64)) -> (i32, i32) {
+    (
+        (cell_size.0.ceil() as i32).max(1),
+        (cell_size.1.ceil() as i32).max(1),
+    )
+}
+
+/// Returns the title for an external window showing the buffer `name`.
+fn external_title(name: &str) -> String {
+    if name.is_empty() {
+        String::from("[No Name]")
+    } else {
+        name.to_string()
+    }
+}
+
+/// Returns the size of a grid (`(cols, rows)`) that fits into `size` (in
+/// pixels), with cells of `cell_size`. The grid has at least one cell.
+fn grid_size_for(size: (f64, f64), cell_size: (f64, f64)) -> (i64, i64) {
+    let cols = (size.0 / cell_size.0).floor() as i64;
+    let rows = (size.1 / cell_size.1).floor() as i64;
+    (cols.max(1), rows.max(1))
+}
+
+/// Executes `cmds` in the nvim window `win`.
+fn win_execute(nvim: GioNeovim, win: Value, cmds: Vec<Value>) {
+    spawn_local(async move {
+        if let Err(err) = nvim
+            .call_function("win_execute", vec![win, cmds.into()])
+            .await
+        {
+            report_error("Failed to scroll window", err);
+        }
+    });
+}
+
+/// Returns the scrollbar's opacity at `step` of a fade out that takes
+/// `steps` steps.
+fn fade_opacity(step: u32, steps: u32) -> f64 {
+    if step >= steps {
+        return 0.0;
+    }
+
+    1.0 - f64::from(step) / f64::from(steps)
+}
+
+/// Returns a normal mode command that scrolls a window by `delta` lines, or
+/// `None` if there is nothing to scroll.
+fn scroll_command(delta: i64) -> Option<String> {
+    match delta {
+        0 => None,
+        // <C-e>
+        d if d > 0 => Some(format!("normal! {}\x05", d)),
+        // <C-y>
+        d => Some(format!("normal! {}\x19", -d)),
+    }
+}
+
+/// Returns an ex command that scrolls a window so that (zero based) line
+/// `topline` is at its top, and moves the cursor to line `line`.
+fn scroll_to_command(topline: u64, line: u64) -> String {
+    format!(
+        "call winrestview({{'topline': {}, 'lnum': {}}})",
+        topline + 1,
+        line + 1
+    )
+}
+
+/// Returns a normal mode command that scrolls a window horizontally by
+/// `delta` columns, or `None` if there is nothing to scroll.
+fn hscroll_command(delta: i64) -> Option<String> {
+    match delta {
+        0 => None,
+        d if d > 0 => Some(format!("normal! {}zl", d)),
+        d => Some(format!("normal! {}zh", -d)),
+    }
+}
+
+/// Returns the (zero based) line where the cursor should be after scrolling
+/// the viewport `vp` by `delta` lines. The cursor moves with the viewport,
+/// but is kept `scrolloff` lines away from the window's edges.
+fn cursor_line_after_scroll(vp: &Viewport, delta: i64, scrolloff: u64) -> u64 {
+    let last = vp.line_count.saturating_sub(1) as i64;
+    let height = vp.botline.saturating_sub(vp.topline) as i64;
+    // Like in nvim, scrolloff can't be more than half of the window.
+    let so = (scrolloff as i64).min((height - 1).max(0) / 2);
+
+    let top = (vp.topline as i64 + delta).max(0).min(last);
+    let bot = top + height - 1;
+
+    // At the start (and end) of the buffer, the cursor is allowed to go
+    // all the way to the window's edge.
+    let min = if top == 0 { 0 } else { (top + so).min(last) };
+    let max = if bot >= last { last } else { bot - so };
+
+    (vp.curline as i64 + delta).max(min).min(max.max(min)) as u64
+}
+
+/// Returns if the scrollbar info needs to be queried again after the
+/// viewport changed from `prev` to `vp`. The info only depends on what's
+/// visible in the window and how it's scrolled horizontally, so e.g. just
+/// moving the cursor up or down doesn't change it.
+fn needs_scrollbar_info(prev: &Viewport, vp: &Viewport) -> bool {
+    prev.topline != vp.topline
+        || prev.botline != vp.botline
+        || prev.curcol != vp.curcol
+        || prev.line_count != vp.line_count
+}
+
+/// Returns the value, upper and page size for a scrollbar's adjustment.
+///
+/// Everything is in buffer lines. The page size is the number of buffer
+/// lines between `topline` and `botline`, so lines hidden in closed folds
+/// count towards the visible part - this way the slider's size matches the
+/// portion of the buffer that is visible.
+fn adjustment_values(
+    topline: u64,
+    botline: u64,
+    line_count: u64,
+) -> (f64, f64, f64) {
+    let upper = line_count.max(1) as f64;
+    let page_size =
+        (botline.saturating_sub(topline) as f64).max(1.0).min(upper);
+    let value = (topline as f64).min(upper - page_size);
+
+    (value, upper, page_size)
+}
+
+/// Returns the adjustment value that centers the scrollbar's slider on
+/// position `y` of a trough that is `height` pixels tall.
+fn trough_click_value(y: f64, height: f64, upper: f64, page_size: f64) -> f64 {
+    if height <= 0.0 {
+        return 0.0;
+    }
+
+    let fraction = (y / height).max(0.0).min(1.0);
+    (fraction * upper - page_size / 2.0)
+        .max(0.0)
+        .min((upper - page_size).max(0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reparent() {
+        // Needs a display.
+        if gtk::init().is_err() {
+            return;
+        }
+
+        let widget = gtk::DrawingArea::new().upcast::<gtk::Widget>();
+        let overlay = gtk::Overlay::new();
+        let viewport =
+            gtk::Viewport::new::<gtk::Adjustment, gtk::Adjustment>(None, None);
+
+        for _ in 0..3 {
+            reparent(&widget, &overlay);
+            assert_eq!(Some(overlay.clone().upcast()), widget.get_parent());
+            assert_eq!(Some(widget.clone()), overlay.get_child());
+            assert_eq!(None, viewport.get_child());
+
+            reparent(&widget, &viewport);
+            assert_eq!(Some(viewport.clone().upcast()), widget.get_parent());
+            assert_eq!(Some(widget.clone()), viewport.get_child());
+            assert_eq!(None, overlay.get_child());
+        }
+
+        // Reparenting to the current parent is a no-op.
+        reparent(&widget, &viewport);
+        assert_eq!(Some(widget.clone()), viewport.get_child());
+    }
+
+    #[test]
+    fn test_scroll_lines() {
+        assert_eq!(None, scroll_lines(0, 3.0, false));
+        assert_eq!(None, scroll_lines(0, 3.0, true));
+        assert_eq!(Some(2), scroll_lines(2, 3.0, false));
+        assert_eq!(Some(6), scroll_lines(2, 3.0, true));
+        assert_eq!(Some(1), scroll_lines(2, 0.1, true));
+    }
+
+    #[test]
+    fn test_mouse_enabled() {
+        assert!(mouse_enabled("a", "normal"));
+        assert!(mouse_enabled("a", "cmdline_insert"));
+        assert!(!mouse_enabled("a", "more"));
+        assert!(mouse_enabled("nv", "visual_select"));
+        assert!(!mouse_enabled("nv", "insert"));
+        assert!(mouse_enabled("c", "cmdline_normal"));
+        assert!(!mouse_enabled("", "normal"));
+    }
+
+    #[test]
+    fn test_scroll_command() {
+        assert_eq!(None, scroll_command(0));
+        assert_eq!(Some("normal! 1\x05".to_string()), scroll_command(1));
+        assert_eq!(
+            Some("normal! 100000\x05".to_string()),
+            scroll_command(100000)
+        );
+        assert_eq!(Some("normal! 42\x19".to_string()), scroll_command(-42));
+    }
+
+    #[test]
+    fn test_scroll_to_command() {
+        assert_eq!(
+            "call winrestview({'topline': 1, 'lnum': 3})",
+            scroll_to_command(0, 2)
+        );
+        assert_eq!(
+            "call winrestview({'topline': 43, 'lnum': 50})",
+            scroll_to_command(42, 49)
+        );
+    }
+
+    #[test]
+    fn test_slide_position() {
+        assert_eq!((100.0, false), slide_position(100.0, 0.0, 0.0));
+        let (pos, done) = slide_position(100.0, 0.0, 60.0);
+        assert!(pos > 0.0 && pos < 50.0);
+        assert!(!done);
+        assert_eq!((0.0, true), slide_position(100.0, 0.0, 120.0));
+        assert_eq!((0.0, true), slide_position(100.0, 0.0, 500.0));
+    }
+
+    #[test]
+    fn test_ease_position() {
+        assert_eq!((10.0, false), ease_position(10.0, 50.0, 0.0, 100));
+        let (pos, done) = ease_position(10.0, 50.0, 50.0, 100);
+        assert!(pos > 30.0 && pos < 50.0);
+        assert!(!done);
+        assert_eq!((50.0, true), ease_position(10.0, 50.0, 100.0, 100));
+        assert_eq!((50.0, true), ease_position(10.0, 50.0, 250.0, 100));
+    }
+
+    #[test]
+    fn test_external_geometries() {
+        let geometries = ExternalGeometries::default();
+        assert_eq!(None, geometries.get(2));
+
+        // Clones share the geometries.
+        geometries.clone().set(2, (10, 20, 300, 400));
+        assert_eq!(Some((10, 20, 300, 400)), geometries.get(2));
+        assert_eq!(None, geometries.get(3));
+    }
+
+    #[test]
+    fn test_grid_size_for() {
+        assert_eq!((80, 24), grid_size_for((800.0, 480.0), (10.0, 20.0)));
+        assert_eq!((80, 24), grid_size_for((809.0, 499.0), (10.0, 20.0)));
+        assert_eq!((1, 1), grid_size_for((5.0, 0.0), (10.0, 20.0)));
+    }
+
+    #[test]
+    fn test_center_in() {
+        assert_eq!((400, 200), center_in((0, 0, 1000, 600), (200, 200)));
+        // Second monitor.
+        assert_eq!((2320, 200), center_in((1920, 0, 1000, 600), (200, 200)));
+        assert_eq!((1920, 0), center_in((1920, 0, 1000, 600), (1200, 800)));
+    }
+
+    #[test]
+    fn test_external_size() {
+        assert_eq!(None, external_size(0.0, 0.0));
+        assert_eq!(None, external_size(100.0, 0.0));
+        assert_eq!(None, external_size(0.5, 100.0));
+        assert_eq!(Some((101, 50)), external_size(100.2, 50.0));
+    }
+
+    #[test]
+    fn test_min_external_size() {
+        assert_eq!((8, 17), min_external_size((7.4, 17.0)));
+        assert_eq!((1, 1), min_external_size((0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_external_title() {
+        assert_eq!("[No Name]", external_title(""));
+        assert_eq!("/tmp/foo.rs", external_title("/tmp/foo.rs"));
+    }
+
+    #[test]
+    fn test_fade_opacity() {
+        assert_eq!(1.0, fade_opacity(0, 4));
+        assert_eq!(0.75, fade_opacity(1, 4));
+        assert_eq!(0.25, fade_opacity(3, 4));
+        assert_eq!(0.0, fade_opacity(4, 4));
+        assert_eq!(0.0, fade_opacity(5, 4));
+        assert_eq!(0.0, fade_opacity(0, 0));
+    }
+
+    #[test]
+    fn test_hscroll_command() {
+        assert_eq!(None, hscroll_command(0));
+        assert_eq!(Some("normal! 3zl".to_string()), hscroll_command(3));
+        assert_eq!(Some("normal! 7zh".to_string()), hscroll_command(-7));
+    }
+
+    #[test]
+    fn test_cursor_line_after_scroll() {
+        let vp = Viewport {
+            topline: 10,
+            botline: 30,
+            curline: 15,
+            curcol: 0,
+            line_count: 100,
+        };
+
+        // Cursor moves with the viewport.
+        assert_eq!(20, cursor_line_after_scroll(&vp, 5, 0));
+        assert_eq!(5, cursor_line_after_scroll(&vp, -10, 0));
+
+        // Scrolloff keeps the cursor away from the top...
+        let vp = Viewport { curline: 10, ..vp };
+        assert_eq!(13, cursor_line_after_scroll(&vp, 0, 3));
+        // ...and the bottom.
+        let vp = Viewport { curline: 29, ..vp };
+        assert_eq!(31, cursor_line_after_scroll(&vp, 5, 3));
+
+        // Scrolloff doesn't apply at the start or the end of the buffer.
+        let vp = Viewport { curline: 12, ..vp };
+        assert_eq!(2, cursor_line_after_scroll(&vp, -10, 3));
+        assert_eq!(99, cursor_line_after_scroll(&vp, 100, 3));
+    }
+
+    #[test]
+    fn test_needs_scrollbar_info() {
+        let vp = Viewport {
+            topline: 10,
+            botline: 30,
+            curline: 15,
+            curcol: 4,
+            line_count: 100,
+        };
+
+        assert!(!needs_scrollbar_info(&vp, &vp));
+        assert!(!needs_scrollbar_info(&vp, &Viewport { curline: 16, ..vp }));
+        assert!(needs_scrollbar_info(&vp, &Viewport { curcol: 5, ..vp }));
+        assert!(needs_scrollbar_info(&vp, &Viewport { topline: 11, ..vp }));
+        assert!(needs_scrollbar_info(&vp, &Viewport { botline: 31, ..vp }));
+        assert!(needs_scrollbar_info(
+            &vp,
+            &Viewport {
+                line_count: 101,
+                ..vp
+            }
+        ));
+    }
+
+    #[test]
+    fn test_trough_click_value() {
+        // Middle of the trough centers the viewport on the middle of the
+        // buffer.
+        assert_eq!(450.0, trough_click_value(50.0, 100.0, 1000.0, 100.0));
+        // Clamped to the start...
+        assert_eq!(0.0, trough_click_value(1.0, 100.0, 1000.0, 100.0));
+        // ...and to the end.
+        assert_eq!(900.0, trough_click_value(100.0, 100.0, 1000.0, 100.0));
+        assert_eq!(0.0, trough_click_value(50.0, 0.0, 1000.0, 100.0));
+    }
+
+    #[test]
+    fn test_adjustment_values() {
+        let data = vec![
+            // (topline, botline, line_count), expected visible / total
+            ((0, 50, 1000), 50.0 / 1000.0),
+            // Closed fold of 100 lines in the middle of the window.
+            ((200, 350, 1000), 150.0 / 1000.0),
+            ((990, 1000, 1000), 10.0 / 1000.0),
+            // Everything fits on the screen.
+            ((0, 10, 10), 1.0),
+        ];
+
+        for ((topline, botline, line_count), expected) in data.into_iter() {
+            let (value, upper, page_size) =
+                adjustment_values(topline, botline, line_count);
+
+            assert!((page_size / upper - expected).abs() < f64::EPSILON);
+            assert!((value - topline as f64).abs() < f64::EPSILON);
+        }
+
+        // Bogus values from nvim don't end up with a unusable adjustment.
+        assert_eq!((0.0, 1.0, 1.0), adjustment_values(0, 0, 0));
+    }
+}
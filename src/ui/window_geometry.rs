@@ -0,0 +1,120 @@
+//! Persisting the main window's size between restarts.
+
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+use log::error;
+
+/// Size and maximized state of the main window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WindowGeometry {
+    pub width: i32,
+    pub height: i32,
+    pub maximized: bool,
+}
+
+impl WindowGeometry {
+    /// Loads the geometry saved on the previous exit, if any.
+    pub fn load() -> Option<Self> {
+        let contents = fs::read_to_string(state_file()?).ok()?;
+        Self::parse(&contents)
+    }
+
+    /// Saves the geometry, to be restored on the next start.
+    pub fn save(&self) {
+        let path = match state_file() {
+            Some(path) => path,
+            None => return,
+        };
+
+        let res = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, self.to_string()));
+        if let Err(err) = res {
+            error!("Failed to save window geometry: {}", err);
+        }
+    }
+
+    /// Parses the geometry from the format written by `save`, e.g.
+    /// `1280x720 maximized`.
+    fn parse(s: &str) -> Option<Self> {
+        let mut parts = s.split_whitespace();
+        let mut size = parts.next()?.splitn(2, 'x');
+        let width = size.next()?.parse().ok()?;
+        let height = size.next()?.parse().ok()?;
+        let maximized = parts.next() == Some("maximized");
+
+        if width <= 0 || height <= 0 {
+            return None;
+        }
+
+        Some(Self {
+            width,
+            height,
+            maximized,
+        })
+    }
+}
+
+impl fmt::Display for WindowGeometry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)?;
+        if self.maximized {
+            write!(f, " maximized")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the path of the file where the geometry is saved.
+fn state_file() -> Option<PathBuf> {
+    Some(
+        glib::get_user_cache_dir()?
+            .join("gnvim")
+            .join("window-geometry"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let geometry = WindowGeometry {
+            width: 1280,
+            height: 720,
+            maximized: false,
+        };
+        assert_eq!(Some(geometry), WindowGeometry::parse("1280x720\n"));
+        assert_eq!(
+            Some(WindowGeometry {
+                maximized: true,
+                ..geometry
+            }),
+            WindowGeometry::parse("1280x720 maximized")
+        );
+        assert_eq!(None, WindowGeometry::parse(""));
+        assert_eq!(None, WindowGeometry::parse("1280"));
+        assert_eq!(None, WindowGeometry::parse("0x720"));
+        assert_eq!(None, WindowGeometry::parse("foox720"));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for maximized in &[false, true] {
+            let geometry = WindowGeometry {
+                width: 800,
+                height: 600,
+                maximized: *maximized,
+            };
+            assert_eq!(
+                Some(geometry),
+                WindowGeometry::parse(&geometry.to_string())
+            );
+        }
+    }
+}